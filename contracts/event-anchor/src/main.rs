@@ -9,7 +9,7 @@
 //!   - Enabling trustless event discovery
 //!
 //! Cell data should contain JSON metadata:
-//!   { event_id, creator_address, metadata_hash, created_at_block }
+//!   { event_id, creator_address, metadata_hash, allowlist_root?, created_at_block }
 //!
 //! Args format (64 bytes):
 //!   - bytes 0-31:  SHA256(event_id)
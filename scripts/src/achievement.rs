@@ -0,0 +1,213 @@
+use std::collections::HashSet;
+
+use chrono::Utc;
+use serde::Serialize;
+
+use crate::cache::Cache;
+use crate::crypto::signatures;
+use crate::observe;
+use crate::rpc::{CkbRpcClient, RpcError};
+use crate::types::{Achievement, AchievementDefinition};
+
+/// Most events one achievement may draw on.
+const MAX_ACHIEVEMENT_EVENTS: usize = 500;
+
+/// Whether an address currently qualifies for an achievement.
+#[derive(Debug, Serialize)]
+pub struct AchievementEligibility {
+    pub achievement_id: String,
+    pub address: String,
+    /// Qualifying events the address holds a confirmed badge for; with chain
+    /// checks, only those whose badge cell is still live.
+    pub held_event_ids: Vec<String>,
+    pub min_count: u32,
+    pub eligible: bool,
+    /// Tip block at which badges were checked on chain, when they were.
+    pub verified_at_block: Option<u64>,
+}
+
+/// Record a creator-signed achievement definition.
+pub async fn define_achievement(
+    cache: &Cache,
+    creator_address: &str,
+    definition: AchievementDefinition,
+    creator_signature: &str,
+) -> Result<Achievement, AchievementError> {
+    let unique: HashSet<&String> = definition.event_ids.iter().collect();
+    if definition.event_ids.is_empty()
+        || definition.event_ids.len() > MAX_ACHIEVEMENT_EVENTS
+        || unique.len() != definition.event_ids.len()
+        || definition.min_count == 0
+        || definition.min_count as usize > definition.event_ids.len()
+    {
+        return Err(AchievementError::InvalidDefinition);
+    }
+
+    let achievement = Achievement {
+        achievement_id: Achievement::compute_id(creator_address, &definition.definition_hash()),
+        creator_address: creator_address.to_string(),
+        definition,
+        creator_signature: creator_signature.to_string(),
+        created_at: Utc::now(),
+    };
+    signatures::verify_ckb_address_signature(&achievement.signed_message(), creator_signature, creator_address)
+        .map_err(|_| AchievementError::InvalidSignature)?;
+
+    cache.store_achievement(&achievement).await.map_err(AchievementError::Cache)?;
+    Ok(achievement)
+}
+
+pub async fn get_achievement(cache: &Cache, achievement_id: &str) -> Result<Achievement, AchievementError> {
+    cache
+        .get_achievement(achievement_id)
+        .await
+        .map_err(AchievementError::Cache)?
+        .ok_or(AchievementError::NotFound)
+}
+
+/// Count the address's badges from the achievement's events. Only confirmed,
+/// unflagged badges count. With `chain_config`, the address's badges are
+/// first synced from the indexer and a badge only counts while the address
+/// still holds its live, unspent cell.
+pub async fn check_eligibility(
+    cache: &Cache,
+    rpc: &CkbRpcClient,
+    achievement: &Achievement,
+    address: &str,
+    chain_config: Option<(&str, &str)>,
+) -> Result<AchievementEligibility, AchievementError> {
+    let lock_hash = signatures::address_to_lock_hash(address).map_err(|_| AchievementError::InvalidAddress)?;
+
+    let mut live_event_ids = None;
+    if let Some((code_hash, address_hrp)) = chain_config {
+        if let Err(e) = observe::sync_from_chain_for_address(cache, rpc, address, code_hash, address_hrp).await {
+            tracing::warn!("Chain sync for address {address} failed: {e}");
+        }
+        let lock = signatures::parse_ckb_address(address).map_err(|_| AchievementError::InvalidAddress)?;
+        let live = observe::live_badge_event_ids(rpc, &lock, code_hash, &achievement.definition.event_ids)
+            .await
+            .map_err(AchievementError::Chain)?;
+        live_event_ids = Some(live);
+    }
+
+    let wanted: HashSet<&String> = achievement.definition.event_ids.iter().collect();
+    let mut held_event_ids = Vec::new();
+    for badge in cache.get_badges_by_lock_hash(&lock_hash).await.map_err(AchievementError::Cache)? {
+        if !wanted.contains(&badge.event_id)
+            || held_event_ids.contains(&badge.event_id)
+            || badge.flagged_at.is_some()
+            || badge.mint_block_number == 0
+            || live_event_ids.as_ref().is_some_and(|live| !live.contains(&badge.event_id))
+        {
+            continue;
+        }
+        held_event_ids.push(badge.event_id);
+    }
+
+    let verified_at_block = match chain_config {
+        Some(_) => rpc.get_tip_block_number().await.ok(),
+        None => None,
+    };
+
+    Ok(AchievementEligibility {
+        achievement_id: achievement.achievement_id.clone(),
+        address: address.to_string(),
+        eligible: held_event_ids.len() >= achievement.definition.min_count as usize,
+        held_event_ids,
+        min_count: achievement.definition.min_count,
+        verified_at_block,
+    })
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum AchievementError {
+    #[error("cache error: {0}")]
+    Cache(#[from] sqlx::Error),
+    #[error("chain error: {0}")]
+    Chain(RpcError),
+    #[error("invalid achievement definition")]
+    InvalidDefinition,
+    #[error("invalid creator signature")]
+    InvalidSignature,
+    #[error("invalid CKB address")]
+    InvalidAddress,
+    #[error("achievement not found")]
+    NotFound,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::crypto::signatures::test_wallet::TestWallet;
+    use crate::types::{BadgeObservation, BadgeRole};
+
+    fn definition() -> AchievementDefinition {
+        AchievementDefinition {
+            name: "Regular".to_string(),
+            description: None,
+            event_ids: vec!["w1".to_string(), "w2".to_string(), "w3".to_string()],
+            min_count: 2,
+        }
+    }
+
+    fn badge(event_id: &str, holder: &TestWallet, block: u64) -> BadgeObservation {
+        BadgeObservation {
+            event_id: event_id.to_string(),
+            holder_address: holder.address.clone(),
+            holder_lock_hash: signatures::address_to_lock_hash(&holder.address).unwrap(),
+            mint_tx_hash: format!("0xtx_{event_id}"),
+            mint_block_number: block,
+            verified_at_block: block,
+            observed_at: Utc::now(),
+            flagged_at: None,
+            flag_reason: None,
+            role: BadgeRole::Attendee,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_define_achievement_validates_and_verifies() {
+        let cache = Cache::new("sqlite::memory:").await.unwrap();
+        let creator = TestWallet::new(1, "ckt");
+        let signature = creator.sign(&Achievement::message_to_sign(&definition().definition_hash()));
+
+        let too_strict = AchievementDefinition { min_count: 4, ..definition() };
+        let result = define_achievement(&cache, &creator.address, too_strict, &signature).await;
+        assert!(matches!(result, Err(AchievementError::InvalidDefinition)));
+
+        let renamed = AchievementDefinition { name: "Other".to_string(), ..definition() };
+        let result = define_achievement(&cache, &creator.address, renamed, &signature).await;
+        assert!(matches!(result, Err(AchievementError::InvalidSignature)));
+
+        let achievement = define_achievement(&cache, &creator.address, definition(), &signature).await.unwrap();
+        let stored = get_achievement(&cache, &achievement.achievement_id).await.unwrap();
+        assert_eq!(stored.definition.min_count, 2);
+    }
+
+    #[tokio::test]
+    async fn test_check_eligibility_counts_confirmed_badges() {
+        let cache = Cache::new("sqlite::memory:").await.unwrap();
+        let rpc = CkbRpcClient::new("http://localhost:1");
+        let creator = TestWallet::new(1, "ckt");
+        let holder = TestWallet::new(2, "ckt");
+        let signature = creator.sign(&Achievement::message_to_sign(&definition().definition_hash()));
+        let achievement = define_achievement(&cache, &creator.address, definition(), &signature).await.unwrap();
+
+        cache.store_badge_observation(&badge("w1", &holder, 10)).await.unwrap();
+        cache.store_badge_observation(&badge("w2", &holder, 0)).await.unwrap();
+        cache.store_badge_observation(&badge("other", &holder, 12)).await.unwrap();
+        let result = check_eligibility(&cache, &rpc, &achievement, &holder.address, None).await.unwrap();
+        assert_eq!(result.held_event_ids, vec!["w1".to_string()]);
+        assert!(!result.eligible);
+
+        cache.store_badge_observation(&badge("w3", &holder, 20)).await.unwrap();
+        let result = check_eligibility(&cache, &rpc, &achievement, &holder.address, None).await.unwrap();
+        assert!(result.eligible);
+        assert_eq!(result.held_event_ids.len(), 2);
+
+        // Cached badges are not enough once live cells are checked on chain.
+        let chain_config = Some(("0xdob", "ckt"));
+        let result = check_eligibility(&cache, &rpc, &achievement, &holder.address, chain_config).await;
+        assert!(matches!(result, Err(AchievementError::Chain(_))));
+    }
+}
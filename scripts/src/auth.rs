@@ -0,0 +1,236 @@
+use axum::{
+    extract::{Request, State},
+    http::{header, StatusCode},
+    middleware::Next,
+    response::{IntoResponse, Response},
+    Json,
+};
+use chrono::{Duration, Utc};
+use sha2::{Digest, Sha256};
+
+use crate::cache::Cache;
+use crate::crypto::signatures;
+use crate::state::AppState;
+use crate::types::{AuthSession, SessionChallenge};
+
+/// How long a wallet has to sign a challenge.
+const CHALLENGE_TTL_MINUTES: i64 = 5;
+
+/// How long a session token stays valid after sign-in.
+const SESSION_TTL_MINUTES: i64 = 60;
+
+/// Issue a single-use nonce for `address` to sign.
+pub async fn issue_challenge(cache: &Cache, address: &str) -> Result<SessionChallenge, AuthError> {
+    let now = Utc::now();
+    let challenge = SessionChallenge {
+        challenge_id: hex::encode(rand::random::<[u8; 16]>()),
+        address: address.to_string(),
+        nonce: hex::encode(rand::random::<[u8; 32]>()),
+        created_at: now,
+        expires_at: now + Duration::minutes(CHALLENGE_TTL_MINUTES),
+    };
+
+    cache.store_challenge(&challenge).await.map_err(AuthError::Cache)?;
+    Ok(challenge)
+}
+
+/// Exchange a signed challenge for a session. Returns the bearer token,
+/// which is only ever held by the client; the cache stores its hash.
+pub async fn open_session(
+    cache: &Cache,
+    challenge_id: &str,
+    signature: &str,
+) -> Result<(String, AuthSession), AuthError> {
+    let challenge = cache
+        .take_challenge(challenge_id)
+        .await
+        .map_err(AuthError::Cache)?
+        .ok_or(AuthError::ChallengeNotFound)?;
+
+    let now = Utc::now();
+    if challenge.expires_at < now {
+        return Err(AuthError::ChallengeExpired);
+    }
+
+    signatures::verify_ckb_address_signature(&challenge.signed_message(), signature, &challenge.address)
+        .map_err(|_| AuthError::InvalidSignature)?;
+
+    let lock_hash = signatures::address_to_lock_hash(&challenge.address)
+        .map_err(|_| AuthError::InvalidSignature)?;
+
+    let token = hex::encode(rand::random::<[u8; 32]>());
+    let session = AuthSession {
+        address: challenge.address,
+        lock_hash,
+        created_at: now,
+        expires_at: now + Duration::minutes(SESSION_TTL_MINUTES),
+    };
+
+    cache
+        .store_session(&token_hash(&token), &session)
+        .await
+        .map_err(AuthError::Cache)?;
+
+    Ok((token, session))
+}
+
+pub async fn close_session(cache: &Cache, token: &str) -> Result<(), AuthError> {
+    cache.delete_session(&token_hash(token)).await.map_err(AuthError::Cache)
+}
+
+/// Whether the session belongs to the same lock as `address`.
+pub fn session_is_for(session: &AuthSession, address: &str) -> bool {
+    signatures::address_to_lock_hash(address).is_ok_and(|hash| hash == session.lock_hash)
+}
+
+/// Extract the bearer token from an `Authorization` header, if any.
+pub fn bearer_token(req: &Request) -> Option<&str> {
+    req.headers()
+        .get(header::AUTHORIZATION)?
+        .to_str()
+        .ok()?
+        .strip_prefix("Bearer ")
+        .map(str::trim)
+}
+
+/// Resolve a bearer token into an `AuthSession` request extension.
+///
+/// Requests without a token pass through unauthenticated; handlers decide
+/// whether they need a session. A token that is unknown or expired is
+/// rejected outright rather than silently downgraded.
+pub async fn resolve_session(State(state): State<AppState>, mut req: Request, next: Next) -> Response {
+    let Some(token) = bearer_token(&req) else {
+        return next.run(req).await;
+    };
+
+    let session = match state.cache.get_session(&token_hash(token)).await {
+        Ok(Some(session)) if session.expires_at > Utc::now() => session,
+        Ok(_) => return unauthorized(),
+        Err(e) => {
+            tracing::error!("Session lookup failed: {e}");
+            let body = serde_json::json!({ "error": "internal error" });
+            return (StatusCode::INTERNAL_SERVER_ERROR, Json(body)).into_response();
+        }
+    };
+
+    req.extensions_mut().insert(session);
+    next.run(req).await
+}
+
+fn unauthorized() -> Response {
+    let body = serde_json::json!({ "error": "invalid or expired session" });
+    (StatusCode::UNAUTHORIZED, Json(body)).into_response()
+}
+
+fn token_hash(token: &str) -> String {
+    hex::encode(Sha256::digest(token.as_bytes()))
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum AuthError {
+    #[error("cache error: {0}")]
+    Cache(#[from] sqlx::Error),
+    #[error("challenge not found")]
+    ChallengeNotFound,
+    #[error("challenge expired")]
+    ChallengeExpired,
+    #[error("invalid signature")]
+    InvalidSignature,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::crypto::signatures::test_wallet::TestWallet;
+
+    async fn test_cache() -> Cache {
+        Cache::new("sqlite::memory:").await.unwrap()
+    }
+
+    const ADDRESS: &str = "ckb1qzda0cr08m85hc8jlnfp3zer7xulejywt49kt2rr0vthywaa50xwsqdnnw7qkdnnclfkg59uzn8umtfd2kwxceqxwquc4";
+
+    #[tokio::test]
+    async fn test_issue_challenge_stores_nonce() {
+        let cache = test_cache().await;
+        let challenge = issue_challenge(&cache, ADDRESS).await.unwrap();
+        assert_eq!(challenge.nonce.len(), 64);
+        assert!(challenge.expires_at > challenge.created_at);
+
+        let stored = cache.take_challenge(&challenge.challenge_id).await.unwrap().unwrap();
+        assert_eq!(stored.nonce, challenge.nonce);
+    }
+
+    #[tokio::test]
+    async fn test_open_session_with_valid_signature() {
+        let cache = test_cache().await;
+        let wallet = TestWallet::new(1, "ckt");
+        let challenge = issue_challenge(&cache, &wallet.address).await.unwrap();
+
+        let signature = wallet.sign(&challenge.signed_message());
+        let (token, session) = open_session(&cache, &challenge.challenge_id, &signature).await.unwrap();
+        assert_eq!(session.address, wallet.address);
+        assert!(session_is_for(&session, &wallet.address));
+
+        let stored = cache.get_session(&token_hash(&token)).await.unwrap().unwrap();
+        assert_eq!(stored.lock_hash, session.lock_hash);
+
+        close_session(&cache, &token).await.unwrap();
+        assert!(cache.get_session(&token_hash(&token)).await.unwrap().is_none());
+    }
+
+    #[tokio::test]
+    async fn test_open_session_rejects_bad_signature_and_consumes_challenge() {
+        let cache = test_cache().await;
+        let challenge = issue_challenge(&cache, ADDRESS).await.unwrap();
+
+        let result = open_session(&cache, &challenge.challenge_id, "0xdeadbeef").await;
+        assert!(matches!(result, Err(AuthError::InvalidSignature)));
+
+        // A failed attempt still burns the challenge.
+        let result = open_session(&cache, &challenge.challenge_id, "0xdeadbeef").await;
+        assert!(matches!(result, Err(AuthError::ChallengeNotFound)));
+    }
+
+    #[tokio::test]
+    async fn test_open_session_rejects_expired_challenge() {
+        let cache = test_cache().await;
+        let challenge = SessionChallenge {
+            challenge_id: "old".to_string(),
+            address: ADDRESS.to_string(),
+            nonce: "n".to_string(),
+            created_at: Utc::now() - Duration::minutes(10),
+            expires_at: Utc::now() - Duration::minutes(5),
+        };
+        cache.store_challenge(&challenge).await.unwrap();
+
+        let result = open_session(&cache, "old", "0xsig").await;
+        assert!(matches!(result, Err(AuthError::ChallengeExpired)));
+    }
+
+    #[test]
+    fn test_session_is_for_compares_lock_hash() {
+        let session = AuthSession {
+            address: ADDRESS.to_string(),
+            lock_hash: signatures::address_to_lock_hash(ADDRESS).unwrap(),
+            created_at: Utc::now(),
+            expires_at: Utc::now() + Duration::hours(1),
+        };
+        assert!(session_is_for(&session, ADDRESS));
+        assert!(!session_is_for(&session, "ckt1qsomeoneelse"));
+    }
+
+    #[test]
+    fn test_bearer_token() {
+        let req = Request::builder()
+            .header(header::AUTHORIZATION, "Bearer abc123")
+            .body(axum::body::Body::empty())
+            .unwrap();
+        assert_eq!(bearer_token(&req), Some("abc123"));
+
+        let req = Request::builder()
+            .header(header::AUTHORIZATION, "Basic abc123")
+            .body(axum::body::Body::empty())
+            .unwrap();
+        assert_eq!(bearer_token(&req), None);
+    }
+}
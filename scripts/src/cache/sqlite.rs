@@ -1,12 +1,99 @@
+use std::collections::HashMap;
+
 use chrono::{DateTime, Utc};
-use sqlx::{sqlite::SqlitePoolOptions, Pool, Sqlite};
+use sqlx::{sqlite::SqlitePoolOptions, Pool, Sqlite, SqliteConnection};
+
+use crate::crypto::signatures;
 
 use crate::types::{
-    ActiveEvent, BadgeObservation, PaymentIntent, PaymentObservation, WindowProof,
+    Achievement, ActiveEvent, AirdropInclusion, Allowlist, AttendanceTier, AuthSession, BadgeObservation, BadgeRole,
+    CheckIn, CheckoutPolicy, ClaimPolicy, DelegateRole, Delegation, DurationProof, EventMetadata, EventSeries,
+    EventState, MetadataRevision, OfflinePolicy, PaymentIntent, PaymentObservation, RoleAssignment, Rsvp, RsvpPolicy,
+    RsvpStatus, SessionChallenge, SponsorDeposit, SponsorDraw, StateTransition, VoucherRecord, WindowProof,
 };
 
 /// Row type returned by active_events queries.
-type EventRow = (String, String, String, String, i64, String, Option<String>);
+type EventRow = (
+    String,
+    String,
+    String,
+    String,
+    i64,
+    String,
+    i64,
+    Option<String>,
+    String,
+    String,
+);
+
+const EVENT_COLUMNS: &str = "event_id, metadata_json, creator_address, payment_tx_hash, payment_block_number, activated_at, metadata_version, cancelled_at, state, state_changed_at";
+
+/// Row type returned by checkins queries.
+type CheckInRow = (String, String, String, String, i64, String);
+
+const CHECKIN_COLUMNS: &str =
+    "event_id, session_id, attendee_address, attendee_lock_hash, qr_timestamp, checked_in_at";
+
+/// Row type returned by delegations queries.
+type DelegationRow = (String, String, String, String, i64, i64, String, String, Option<String>);
+
+const DELEGATION_COLUMNS: &str = "event_id, delegate_address, delegate_lock_hash, role, expires_at, version, \
+     creator_signature, created_at, revoked_at";
+
+/// Row type returned by role_assignments queries.
+type RoleAssignmentRow = (String, String, String, String, String, String);
+
+const ROLE_ASSIGNMENT_COLUMNS: &str =
+    "event_id, holder_address, holder_lock_hash, role, creator_signature, assigned_at";
+
+/// Row type returned by rsvps queries.
+type RsvpRow = (String, String, String, String, String, String);
+
+const RSVP_COLUMNS: &str = "event_id, attendee_address, attendee_lock_hash, status, attendee_signature, created_at";
+
+/// Row type returned by claim_vouchers queries.
+type VoucherRow = (String, String, String, i64, i64, String, String, Option<String>, Option<String>);
+
+const VOUCHER_COLUMNS: &str = "voucher_hash, event_id, session_id, qr_timestamp, expires_at, signature, issued_at, \
+     redeemed_by, redeemed_at";
+
+/// Row type returned by sponsor_deposits queries.
+type SponsorDepositRow = (String, String, i64, i64, String);
+
+const SPONSOR_DEPOSIT_COLUMNS: &str = "event_id, tx_hash, capacity, block_number, recorded_at";
+
+/// Row type returned by sponsor_draws queries.
+type SponsorDrawRow = (String, String, String, String, i64, i64, String);
+
+const SPONSOR_DRAW_COLUMNS: &str = "event_id, holder_lock_hash, holder_address, tx_hash, capacity, fee, drawn_at";
+
+/// Row type returned by airdrop_inclusions queries.
+type AirdropInclusionRow = (String, String, String, String, String, String, String, Option<i64>);
+
+const AIRDROP_INCLUSION_COLUMNS: &str =
+    "event_id, holder_lock_hash, holder_address, batch_id, tx_hash, funder_address, included_at, confirmed_block";
+
+/// Row type returned by duration_proofs queries.
+type DurationProofRow = (String, String, String, String, i64, String, i64, String, String);
+
+const DURATION_PROOF_COLUMNS: &str = "event_id, attendee_lock_hash, attendee_address, checkin_session_id, \
+     checkin_timestamp, checkout_session_id, checkout_timestamp, tier, recorded_at";
+
+/// Row type returned by badge_observations queries.
+type BadgeRow = (String, String, String, String, i64, i64, String, Option<String>, Option<String>, String);
+
+const BADGE_COLUMNS: &str = "event_id, holder_address, holder_lock_hash, mint_tx_hash, mint_block_number, \
+     verified_at_block, observed_at, flagged_at, flag_reason, role";
+
+/// Result of `Cache::record_checkin`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum CheckInOutcome {
+    Recorded,
+    /// The attendee already checked in to this session; nothing changed.
+    AlreadyCheckedIn,
+    /// The event's attendee cap is reached; nothing changed.
+    AtCapacity,
+}
 
 pub struct Cache {
     pool: Pool<Sqlite>,
@@ -51,33 +138,406 @@ impl Cache {
                 payment_tx_hash TEXT NOT NULL,
                 payment_block_number INTEGER NOT NULL,
                 activated_at TEXT NOT NULL,
-                window_json TEXT
+                metadata_version INTEGER NOT NULL DEFAULT 1,
+                cancelled_at TEXT,
+                state TEXT NOT NULL DEFAULT 'active',
+                state_changed_at TEXT NOT NULL DEFAULT ''
+            );
+
+            CREATE TABLE IF NOT EXISTS windows (
+                event_id TEXT NOT NULL,
+                session_id TEXT NOT NULL,
+                window_start INTEGER NOT NULL,
+                window_json TEXT NOT NULL,
+                PRIMARY KEY (event_id, session_id)
+            );
+
+            CREATE TABLE IF NOT EXISTS checkins (
+                event_id TEXT NOT NULL,
+                session_id TEXT NOT NULL,
+                attendee_address TEXT NOT NULL,
+                attendee_lock_hash TEXT NOT NULL,
+                qr_timestamp INTEGER NOT NULL,
+                checked_in_at TEXT NOT NULL,
+                PRIMARY KEY (event_id, session_id, attendee_lock_hash)
+            );
+
+            CREATE TABLE IF NOT EXISTS delegations (
+                event_id TEXT NOT NULL,
+                delegate_lock_hash TEXT NOT NULL,
+                delegate_address TEXT NOT NULL,
+                role TEXT NOT NULL,
+                expires_at INTEGER NOT NULL,
+                version INTEGER NOT NULL DEFAULT 0,
+                creator_signature TEXT NOT NULL,
+                created_at TEXT NOT NULL,
+                revoked_at TEXT,
+                PRIMARY KEY (event_id, delegate_lock_hash)
+            );
+
+            CREATE TABLE IF NOT EXISTS role_assignments (
+                event_id TEXT NOT NULL,
+                holder_lock_hash TEXT NOT NULL,
+                holder_address TEXT NOT NULL,
+                role TEXT NOT NULL,
+                creator_signature TEXT NOT NULL,
+                assigned_at TEXT NOT NULL,
+                PRIMARY KEY (event_id, holder_lock_hash)
+            );
+
+            CREATE TABLE IF NOT EXISTS allowlists (
+                event_id TEXT PRIMARY KEY,
+                merkle_root TEXT NOT NULL,
+                version INTEGER NOT NULL DEFAULT 0,
+                creator_signature TEXT NOT NULL,
+                updated_at TEXT NOT NULL
+            );
+
+            CREATE TABLE IF NOT EXISTS allowlist_entries (
+                event_id TEXT NOT NULL,
+                lock_hash TEXT NOT NULL,
+                PRIMARY KEY (event_id, lock_hash)
+            );
+
+            CREATE TABLE IF NOT EXISTS offline_policies (
+                event_id TEXT PRIMARY KEY,
+                grace_secs INTEGER NOT NULL,
+                version INTEGER NOT NULL DEFAULT 0,
+                updated_at TEXT NOT NULL
+            );
+
+            CREATE TABLE IF NOT EXISTS checkout_policies (
+                event_id TEXT PRIMARY KEY,
+                checkout_session_id TEXT NOT NULL,
+                min_duration_secs INTEGER NOT NULL,
+                version INTEGER NOT NULL DEFAULT 0,
+                updated_at TEXT NOT NULL
+            );
+
+            CREATE TABLE IF NOT EXISTS claim_policies (
+                event_id TEXT PRIMARY KEY,
+                claim_period_secs INTEGER NOT NULL,
+                version INTEGER NOT NULL DEFAULT 0,
+                updated_at TEXT NOT NULL
+            );
+
+            CREATE TABLE IF NOT EXISTS claim_vouchers (
+                voucher_hash TEXT PRIMARY KEY,
+                event_id TEXT NOT NULL,
+                session_id TEXT NOT NULL,
+                qr_timestamp INTEGER NOT NULL,
+                expires_at INTEGER NOT NULL,
+                signature TEXT NOT NULL,
+                issued_at TEXT NOT NULL,
+                redeemed_by TEXT,
+                redeemed_at TEXT
+            );
+
+            CREATE TABLE IF NOT EXISTS rsvp_policies (
+                event_id TEXT PRIMARY KEY,
+                capacity INTEGER,
+                required INTEGER NOT NULL,
+                version INTEGER NOT NULL DEFAULT 0,
+                updated_at TEXT NOT NULL
+            );
+
+            CREATE TABLE IF NOT EXISTS rsvps (
+                event_id TEXT NOT NULL,
+                attendee_lock_hash TEXT NOT NULL,
+                attendee_address TEXT NOT NULL,
+                status TEXT NOT NULL,
+                attendee_signature TEXT NOT NULL,
+                created_at TEXT NOT NULL,
+                PRIMARY KEY (event_id, attendee_lock_hash)
+            );
+
+            CREATE TABLE IF NOT EXISTS sponsor_deposits (
+                tx_hash TEXT PRIMARY KEY,
+                event_id TEXT NOT NULL,
+                capacity INTEGER NOT NULL,
+                block_number INTEGER NOT NULL,
+                recorded_at TEXT NOT NULL
+            );
+
+            CREATE INDEX IF NOT EXISTS idx_sponsor_deposits_event ON sponsor_deposits(event_id);
+
+            CREATE TABLE IF NOT EXISTS sponsor_draws (
+                event_id TEXT NOT NULL,
+                holder_lock_hash TEXT NOT NULL,
+                holder_address TEXT NOT NULL,
+                tx_hash TEXT NOT NULL,
+                capacity INTEGER NOT NULL,
+                fee INTEGER NOT NULL,
+                drawn_at TEXT NOT NULL,
+                PRIMARY KEY (event_id, holder_lock_hash)
+            );
+
+            CREATE TABLE IF NOT EXISTS airdrop_inclusions (
+                event_id TEXT NOT NULL,
+                holder_lock_hash TEXT NOT NULL,
+                holder_address TEXT NOT NULL,
+                batch_id TEXT NOT NULL,
+                tx_hash TEXT NOT NULL,
+                funder_address TEXT NOT NULL,
+                included_at TEXT NOT NULL,
+                confirmed_block INTEGER,
+                PRIMARY KEY (event_id, holder_lock_hash)
+            );
+
+            CREATE TABLE IF NOT EXISTS duration_proofs (
+                event_id TEXT NOT NULL,
+                attendee_lock_hash TEXT NOT NULL,
+                attendee_address TEXT NOT NULL,
+                checkin_session_id TEXT NOT NULL,
+                checkin_timestamp INTEGER NOT NULL,
+                checkout_session_id TEXT NOT NULL,
+                checkout_timestamp INTEGER NOT NULL,
+                tier TEXT NOT NULL,
+                recorded_at TEXT NOT NULL,
+                PRIMARY KEY (event_id, attendee_lock_hash)
+            );
+
+            CREATE TABLE IF NOT EXISTS event_series (
+                series_id TEXT PRIMARY KEY,
+                creator_address TEXT NOT NULL,
+                nonce TEXT NOT NULL,
+                template_json TEXT NOT NULL,
+                recurrence_json TEXT NOT NULL,
+                creator_signature TEXT NOT NULL,
+                created_at TEXT NOT NULL
+            );
+
+            CREATE TABLE IF NOT EXISTS series_events (
+                event_id TEXT PRIMARY KEY,
+                series_id TEXT NOT NULL,
+                occurrence INTEGER NOT NULL
+            );
+
+            CREATE TABLE IF NOT EXISTS achievements (
+                achievement_id TEXT PRIMARY KEY,
+                creator_address TEXT NOT NULL,
+                definition_json TEXT NOT NULL,
+                creator_signature TEXT NOT NULL,
+                created_at TEXT NOT NULL
+            );
+
+            CREATE TABLE IF NOT EXISTS event_transitions (
+                event_id TEXT NOT NULL,
+                from_state TEXT,
+                to_state TEXT NOT NULL,
+                at TEXT NOT NULL
+            );
+
+            CREATE TABLE IF NOT EXISTS event_metadata_history (
+                event_id TEXT NOT NULL,
+                version INTEGER NOT NULL,
+                metadata_json TEXT NOT NULL,
+                superseded_at TEXT NOT NULL,
+                superseded_by_signature TEXT NOT NULL,
+                PRIMARY KEY (event_id, version)
             );
 
             CREATE TABLE IF NOT EXISTS badge_observations (
                 event_id TEXT NOT NULL,
                 holder_address TEXT NOT NULL,
+                holder_lock_hash TEXT NOT NULL,
                 mint_tx_hash TEXT NOT NULL,
                 mint_block_number INTEGER NOT NULL,
                 verified_at_block INTEGER NOT NULL,
                 observed_at TEXT NOT NULL,
-                PRIMARY KEY (event_id, holder_address)
+                flagged_at TEXT,
+                flag_reason TEXT,
+                role TEXT NOT NULL DEFAULT 'attendee',
+                PRIMARY KEY (event_id, holder_lock_hash)
             );
 
             CREATE TABLE IF NOT EXISTS qr_replay_log (
                 event_id TEXT NOT NULL,
+                session_id TEXT NOT NULL,
                 timestamp INTEGER NOT NULL,
                 used_at TEXT NOT NULL,
-                PRIMARY KEY (event_id, timestamp)
+                PRIMARY KEY (event_id, session_id, timestamp)
             );
 
             CREATE TABLE IF NOT EXISTS challenge_cache (
                 challenge_id TEXT PRIMARY KEY,
-                event_id TEXT NOT NULL,
+                address TEXT NOT NULL,
                 nonce TEXT NOT NULL,
                 created_at TEXT NOT NULL,
                 expires_at TEXT NOT NULL
             );
+
+            CREATE TABLE IF NOT EXISTS sessions (
+                token_hash TEXT PRIMARY KEY,
+                address TEXT NOT NULL,
+                lock_hash TEXT NOT NULL,
+                created_at TEXT NOT NULL,
+                expires_at TEXT NOT NULL
+            );
+            "#,
+        )
+        .execute(&self.pool)
+        .await?;
+
+        self.migrate_badge_lock_hashes().await?;
+        self.add_column_if_missing("badge_observations", "flagged_at", "TEXT").await?;
+        self.add_column_if_missing("badge_observations", "flag_reason", "TEXT").await?;
+        self.add_column_if_missing("badge_observations", "role", "TEXT NOT NULL DEFAULT 'attendee'").await?;
+        self.migrate_challenge_cache().await?;
+        self.add_column_if_missing("active_events", "metadata_version", "INTEGER NOT NULL DEFAULT 1").await?;
+        self.add_column_if_missing("active_events", "cancelled_at", "TEXT").await?;
+        self.migrate_event_states().await?;
+        self.migrate_window_sessions().await?;
+        self.migrate_qr_replay_sessions().await?;
+        self.add_column_if_missing("offline_policies", "version", "INTEGER NOT NULL DEFAULT 0").await?;
+        self.add_column_if_missing("allowlists", "version", "INTEGER NOT NULL DEFAULT 0").await?;
+        self.add_column_if_missing("delegations", "version", "INTEGER NOT NULL DEFAULT 0").await?;
+        self.add_column_if_missing("delegations", "revoked_at", "TEXT").await?;
+        self.add_column_if_missing("rsvp_policies", "version", "INTEGER NOT NULL DEFAULT 0").await?;
+        self.add_column_if_missing("checkout_policies", "version", "INTEGER NOT NULL DEFAULT 0").await?;
+        self.add_column_if_missing("claim_policies", "version", "INTEGER NOT NULL DEFAULT 0").await?;
+
+        sqlx::query(
+            "CREATE INDEX IF NOT EXISTS idx_badge_observations_lock ON badge_observations (holder_lock_hash)",
+        )
+        .execute(&self.pool)
+        .await?;
+        sqlx::query("CREATE INDEX IF NOT EXISTS idx_event_transitions_event ON event_transitions (event_id)")
+            .execute(&self.pool)
+            .await?;
+        Ok(())
+    }
+
+    /// Whether `table` currently has a column named `column`.
+    async fn has_column(&self, table: &str, column: &str) -> Result<bool, sqlx::Error> {
+        let rows: Vec<(String,)> =
+            sqlx::query_as(&format!("SELECT name FROM pragma_table_info('{}')", table))
+                .fetch_all(&self.pool)
+                .await?;
+        Ok(rows.iter().any(|(name,)| name == column))
+    }
+
+    async fn add_column_if_missing(&self, table: &str, column: &str, definition: &str) -> Result<(), sqlx::Error> {
+        if self.has_column(table, column).await? {
+            return Ok(());
+        }
+        sqlx::query(&format!("ALTER TABLE {} ADD COLUMN {} {}", table, column, definition))
+            .execute(&self.pool)
+            .await?;
+        Ok(())
+    }
+
+    /// Rebuild a legacy badge_observations table (keyed by raw address string)
+    /// so it is keyed by the holder's lock script hash instead.
+    ///
+    /// Rows whose address cannot be parsed keep their raw address as the lock
+    /// hash so no data is lost; they will never collide with a real hash.
+    async fn migrate_badge_lock_hashes(&self) -> Result<(), sqlx::Error> {
+        if self.has_column("badge_observations", "holder_lock_hash").await? {
+            return Ok(());
+        }
+
+        tracing::info!("Migrating badge_observations to lock-hash keys");
+
+        let mut tx = self.pool.begin().await?;
+
+        let rows: Vec<(String, String, String, i64, i64, String)> = sqlx::query_as(
+            "SELECT event_id, holder_address, mint_tx_hash, mint_block_number, verified_at_block, observed_at FROM badge_observations",
+        )
+        .fetch_all(&mut *tx)
+        .await?;
+
+        sqlx::query(
+            r#"
+            CREATE TABLE badge_observations_v2 (
+                event_id TEXT NOT NULL,
+                holder_address TEXT NOT NULL,
+                holder_lock_hash TEXT NOT NULL,
+                mint_tx_hash TEXT NOT NULL,
+                mint_block_number INTEGER NOT NULL,
+                verified_at_block INTEGER NOT NULL,
+                observed_at TEXT NOT NULL,
+                PRIMARY KEY (event_id, holder_lock_hash)
+            )
+            "#,
+        )
+        .execute(&mut *tx)
+        .await?;
+
+        for (event_id, holder_address, mint_tx_hash, mint_block_number, verified_at_block, observed_at) in rows {
+            let lock_hash = match signatures::address_to_lock_hash(&holder_address) {
+                Ok(hash) => hash,
+                Err(e) => {
+                    tracing::warn!("Keeping unparseable badge holder {holder_address} as-is: {e}");
+                    holder_address.clone()
+                }
+            };
+            sqlx::query(
+                "INSERT OR REPLACE INTO badge_observations_v2 (event_id, holder_address, holder_lock_hash, mint_tx_hash, \
+                 mint_block_number, verified_at_block, observed_at) VALUES (?, ?, ?, ?, ?, ?, ?)",
+            )
+            .bind(event_id)
+            .bind(holder_address)
+            .bind(lock_hash)
+            .bind(mint_tx_hash)
+            .bind(mint_block_number)
+            .bind(verified_at_block)
+            .bind(observed_at)
+            .execute(&mut *tx)
+            .await?;
+        }
+
+        sqlx::query("DROP TABLE badge_observations").execute(&mut *tx).await?;
+        sqlx::query("ALTER TABLE badge_observations_v2 RENAME TO badge_observations")
+            .execute(&mut *tx)
+            .await?;
+
+        tx.commit().await
+    }
+
+    /// The original challenge_cache was keyed by event and never written to;
+    /// recreate it in the shape sign-in challenges use.
+    async fn migrate_challenge_cache(&self) -> Result<(), sqlx::Error> {
+        if self.has_column("challenge_cache", "address").await? {
+            return Ok(());
+        }
+
+        sqlx::query("DROP TABLE challenge_cache").execute(&self.pool).await?;
+        sqlx::query(
+            r#"
+            CREATE TABLE challenge_cache (
+                challenge_id TEXT PRIMARY KEY,
+                address TEXT NOT NULL,
+                nonce TEXT NOT NULL,
+                created_at TEXT NOT NULL,
+                expires_at TEXT NOT NULL
+            )
+            "#,
+        )
+        .execute(&self.pool)
+        .await?;
+        Ok(())
+    }
+
+    /// Add lifecycle columns to active_events and derive each existing
+    /// event's state from the fields that implied it before.
+    async fn migrate_event_states(&self) -> Result<(), sqlx::Error> {
+        if self.has_column("active_events", "state").await? {
+            return Ok(());
+        }
+
+        self.add_column_if_missing("active_events", "state", "TEXT NOT NULL DEFAULT 'active'").await?;
+        self.add_column_if_missing("active_events", "state_changed_at", "TEXT NOT NULL DEFAULT ''").await?;
+        sqlx::query(
+            r#"
+            UPDATE active_events SET
+                state = CASE
+                    WHEN cancelled_at IS NOT NULL THEN 'cancelled'
+                    WHEN payment_tx_hash = '' THEN 'pending_payment'
+                    WHEN window_json IS NOT NULL THEN 'window_open'
+                    ELSE 'active'
+                END,
+                state_changed_at = COALESCE(cancelled_at, activated_at)
             "#,
         )
         .execute(&self.pool)
@@ -85,6 +545,63 @@ impl Cache {
         Ok(())
     }
 
+    /// Move each event's single `window_json` into the windows table as its
+    /// default session, then drop the column.
+    async fn migrate_window_sessions(&self) -> Result<(), sqlx::Error> {
+        if !self.has_column("active_events", "window_json").await? {
+            return Ok(());
+        }
+
+        tracing::info!("Migrating event windows to per-session rows");
+
+        let mut tx = self.pool.begin().await?;
+        sqlx::query(
+            r#"
+            INSERT OR IGNORE INTO windows (event_id, session_id, window_start, window_json)
+            SELECT event_id, 'default', json_extract(window_json, '$.window_start'), window_json
+            FROM active_events WHERE window_json IS NOT NULL
+            "#,
+        )
+        .execute(&mut *tx)
+        .await?;
+        sqlx::query("ALTER TABLE active_events DROP COLUMN window_json")
+            .execute(&mut *tx)
+            .await?;
+        tx.commit().await
+    }
+
+    /// Key replay entries by window session as well as event.
+    async fn migrate_qr_replay_sessions(&self) -> Result<(), sqlx::Error> {
+        if self.has_column("qr_replay_log", "session_id").await? {
+            return Ok(());
+        }
+
+        let mut tx = self.pool.begin().await?;
+        sqlx::query(
+            r#"
+            CREATE TABLE qr_replay_log_v2 (
+                event_id TEXT NOT NULL,
+                session_id TEXT NOT NULL,
+                timestamp INTEGER NOT NULL,
+                used_at TEXT NOT NULL,
+                PRIMARY KEY (event_id, session_id, timestamp)
+            )
+            "#,
+        )
+        .execute(&mut *tx)
+        .await?;
+        sqlx::query(
+            "INSERT INTO qr_replay_log_v2 SELECT event_id, 'default', timestamp, used_at FROM qr_replay_log",
+        )
+        .execute(&mut *tx)
+        .await?;
+        sqlx::query("DROP TABLE qr_replay_log").execute(&mut *tx).await?;
+        sqlx::query("ALTER TABLE qr_replay_log_v2 RENAME TO qr_replay_log")
+            .execute(&mut *tx)
+            .await?;
+        tx.commit().await
+    }
+
     pub async fn store_payment_intent(&self, intent: &PaymentIntent) -> Result<(), sqlx::Error> {
         let event_id = intent.event_id_preimage.compute_event_id();
         sqlx::query(
@@ -176,41 +693,48 @@ impl Cache {
     }
 
     pub async fn store_active_event(&self, event: &ActiveEvent) -> Result<(), sqlx::Error> {
-        let window_json = event.window.as_ref().map(|w| serde_json::to_string(w).unwrap());
-        sqlx::query(
-            "INSERT OR REPLACE INTO active_events (event_id, metadata_json, creator_address, payment_tx_hash, payment_block_number, activated_at, window_json) VALUES (?, ?, ?, ?, ?, ?, ?)",
-        )
+        sqlx::query(&format!(
+            "INSERT OR REPLACE INTO active_events ({}) VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?)",
+            EVENT_COLUMNS
+        ))
         .bind(&event.event_id)
         .bind(serde_json::to_string(&event.metadata).unwrap())
         .bind(&event.creator_address)
         .bind(&event.payment_tx_hash)
         .bind(event.payment_block_number as i64)
         .bind(event.activated_at.to_rfc3339())
-        .bind(window_json)
+        .bind(event.metadata_version as i64)
+        .bind(event.cancelled_at.map(|t| t.to_rfc3339()))
+        .bind(event.state.as_str())
+        .bind(event.state_changed_at.to_rfc3339())
         .execute(&self.pool)
         .await?;
+
+        for window in &event.windows {
+            self.store_window(window).await?;
+        }
         Ok(())
     }
 
     pub async fn get_active_event(&self, event_id: &str) -> Result<Option<ActiveEvent>, sqlx::Error> {
-        let row: Option<EventRow> = sqlx::query_as(
-            "SELECT event_id, metadata_json, creator_address, payment_tx_hash, payment_block_number, activated_at, window_json FROM active_events WHERE event_id = ?",
-        )
+        let row: Option<EventRow> = sqlx::query_as(&format!(
+            "SELECT {} FROM active_events WHERE event_id = ?",
+            EVENT_COLUMNS
+        ))
         .bind(event_id)
         .fetch_optional(&self.pool)
         .await?;
 
-        Ok(row.map(|(event_id, metadata_json, creator_address, payment_tx_hash, payment_block_number, activated_at, window_json)| {
-            ActiveEvent {
-                event_id,
-                metadata: serde_json::from_str(&metadata_json).unwrap(),
-                creator_address,
-                payment_tx_hash,
-                payment_block_number: payment_block_number as u64,
-                activated_at: DateTime::parse_from_rfc3339(&activated_at).unwrap().with_timezone(&Utc),
-                window: window_json.map(|w| serde_json::from_str(&w).unwrap()),
-            }
-        }))
+        self.with_windows(row.map(event_from_row)).await
+    }
+
+    /// Fill in an event's windows from the windows table.
+    async fn with_windows(&self, event: Option<ActiveEvent>) -> Result<Option<ActiveEvent>, sqlx::Error> {
+        let Some(mut event) = event else {
+            return Ok(None);
+        };
+        event.windows = self.get_event_windows(&event.event_id).await?;
+        Ok(Some(event))
     }
 
     /// Look up an active event by prefix of its event_id.
@@ -218,9 +742,10 @@ impl Cache {
     /// Returns an error if multiple events match (ambiguous prefix).
     pub async fn get_active_event_by_prefix(&self, prefix: &str) -> Result<Option<ActiveEvent>, sqlx::Error> {
         let pattern = format!("{}%", prefix);
-        let rows: Vec<EventRow> = sqlx::query_as(
-            "SELECT event_id, metadata_json, creator_address, payment_tx_hash, payment_block_number, activated_at, window_json FROM active_events WHERE event_id LIKE ? LIMIT 2",
-        )
+        let rows: Vec<EventRow> = sqlx::query_as(&format!(
+            "SELECT {} FROM active_events WHERE event_id LIKE ? LIMIT 2",
+            EVENT_COLUMNS
+        ))
         .bind(&pattern)
         .fetch_all(&self.pool)
         .await?;
@@ -230,64 +755,1102 @@ impl Cache {
             return Ok(None);
         }
 
-        Ok(rows.into_iter().next().map(|(event_id, metadata_json, creator_address, payment_tx_hash, payment_block_number, activated_at, window_json)| {
-            ActiveEvent {
-                event_id,
-                metadata: serde_json::from_str(&metadata_json).unwrap(),
-                creator_address,
-                payment_tx_hash,
-                payment_block_number: payment_block_number as u64,
-                activated_at: DateTime::parse_from_rfc3339(&activated_at).unwrap().with_timezone(&Utc),
-                window: window_json.map(|w| serde_json::from_str(&w).unwrap()),
-            }
-        }))
+        self.with_windows(rows.into_iter().next().map(event_from_row)).await
     }
 
     pub async fn list_active_events(&self) -> Result<Vec<ActiveEvent>, sqlx::Error> {
-        let rows: Vec<EventRow> = sqlx::query_as(
-            "SELECT event_id, metadata_json, creator_address, payment_tx_hash, payment_block_number, activated_at, window_json FROM active_events",
+        let rows: Vec<EventRow> = sqlx::query_as(&format!("SELECT {} FROM active_events", EVENT_COLUMNS))
+            .fetch_all(&self.pool)
+            .await?;
+
+        let windows: Vec<(String, String)> =
+            sqlx::query_as("SELECT event_id, window_json FROM windows ORDER BY window_start, session_id")
+                .fetch_all(&self.pool)
+                .await?;
+        let mut by_event: HashMap<String, Vec<WindowProof>> = HashMap::new();
+        for (event_id, window_json) in windows {
+            by_event.entry(event_id).or_default().push(serde_json::from_str(&window_json).unwrap());
+        }
+
+        Ok(rows
+            .into_iter()
+            .map(|row| {
+                let mut event = event_from_row(row);
+                event.windows = by_event.remove(&event.event_id).unwrap_or_default();
+                event
+            })
+            .collect())
+    }
+
+    /// Replace an event's metadata if it is still at `expected_version`,
+    /// archiving the previous metadata in event_metadata_history.
+    /// Returns false when the version no longer matches.
+    pub async fn update_event_metadata(
+        &self,
+        event_id: &str,
+        expected_version: u32,
+        metadata: &EventMetadata,
+        signature: &str,
+    ) -> Result<bool, sqlx::Error> {
+        let mut tx = self.pool.begin().await?;
+
+        let result = sqlx::query(
+            r#"
+            INSERT INTO event_metadata_history (event_id, version, metadata_json, superseded_at, superseded_by_signature)
+            SELECT event_id, metadata_version, metadata_json, ?, ?
+            FROM active_events WHERE event_id = ? AND metadata_version = ?
+            "#,
+        )
+        .bind(Utc::now().to_rfc3339())
+        .bind(signature)
+        .bind(event_id)
+        .bind(expected_version as i64)
+        .execute(&mut *tx)
+        .await?;
+
+        if result.rows_affected() == 0 {
+            return Ok(false);
+        }
+
+        sqlx::query(
+            "UPDATE active_events SET metadata_json = ?, metadata_version = metadata_version + 1 WHERE event_id = ?",
+        )
+        .bind(serde_json::to_string(metadata).unwrap())
+        .bind(event_id)
+        .execute(&mut *tx)
+        .await?;
+
+        tx.commit().await?;
+        Ok(true)
+    }
+
+    pub async fn get_event_metadata_history(&self, event_id: &str) -> Result<Vec<MetadataRevision>, sqlx::Error> {
+        let rows: Vec<(String, i64, String, String, String)> = sqlx::query_as(
+            "SELECT event_id, version, metadata_json, superseded_at, superseded_by_signature FROM event_metadata_history WHERE event_id = ? ORDER BY version",
         )
+        .bind(event_id)
         .fetch_all(&self.pool)
         .await?;
 
-        Ok(rows.into_iter().map(|(event_id, metadata_json, creator_address, payment_tx_hash, payment_block_number, activated_at, window_json)| {
-            ActiveEvent {
-                event_id,
-                metadata: serde_json::from_str(&metadata_json).unwrap(),
-                creator_address,
-                payment_tx_hash,
-                payment_block_number: payment_block_number as u64,
-                activated_at: DateTime::parse_from_rfc3339(&activated_at).unwrap().with_timezone(&Utc),
-                window: window_json.map(|w| serde_json::from_str(&w).unwrap()),
-            }
-        }).collect())
+        Ok(rows.into_iter().map(|(event_id, version, metadata_json, superseded_at, superseded_by_signature)| {
+            MetadataRevision {
+                event_id,
+                version: version as u32,
+                metadata: serde_json::from_str(&metadata_json).unwrap(),
+                superseded_at: DateTime::parse_from_rfc3339(&superseded_at).unwrap().with_timezone(&Utc),
+                superseded_by_signature,
+            }
+        }).collect())
+    }
+
+    /// Move an event from `from` to `to` and log the transition. Returns
+    /// false if the event is no longer in `from`. Entering `Cancelled` also
+    /// stamps `cancelled_at`.
+    pub async fn transition_event_state(
+        &self,
+        event_id: &str,
+        from: EventState,
+        to: EventState,
+        at: DateTime<Utc>,
+    ) -> Result<bool, sqlx::Error> {
+        let mut tx = self.pool.begin().await?;
+
+        let result = sqlx::query(
+            r#"
+            UPDATE active_events SET
+                state = ?1,
+                state_changed_at = ?2,
+                cancelled_at = CASE WHEN ?1 = 'cancelled' THEN ?2 ELSE cancelled_at END
+            WHERE event_id = ?3 AND state = ?4
+            "#,
+        )
+        .bind(to.as_str())
+        .bind(at.to_rfc3339())
+        .bind(event_id)
+        .bind(from.as_str())
+        .execute(&mut *tx)
+        .await?;
+
+        if result.rows_affected() == 0 {
+            return Ok(false);
+        }
+
+        sqlx::query("INSERT INTO event_transitions (event_id, from_state, to_state, at) VALUES (?, ?, ?, ?)")
+            .bind(event_id)
+            .bind(from.as_str())
+            .bind(to.as_str())
+            .bind(at.to_rfc3339())
+            .execute(&mut *tx)
+            .await?;
+
+        tx.commit().await?;
+        Ok(true)
+    }
+
+    /// Log a transition without touching active_events, for states an event
+    /// passes through before or as its record is written.
+    pub async fn record_event_transition(&self, transition: &StateTransition) -> Result<(), sqlx::Error> {
+        sqlx::query("INSERT INTO event_transitions (event_id, from_state, to_state, at) VALUES (?, ?, ?, ?)")
+            .bind(&transition.event_id)
+            .bind(transition.from_state.map(EventState::as_str))
+            .bind(transition.to_state.as_str())
+            .bind(transition.at.to_rfc3339())
+            .execute(&self.pool)
+            .await?;
+        Ok(())
+    }
+
+    pub async fn get_event_transitions(&self, event_id: &str) -> Result<Vec<StateTransition>, sqlx::Error> {
+        let rows: Vec<(String, Option<String>, String, String)> = sqlx::query_as(
+            "SELECT event_id, from_state, to_state, at FROM event_transitions WHERE event_id = ? ORDER BY rowid",
+        )
+        .bind(event_id)
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(rows.into_iter().map(|(event_id, from_state, to_state, at)| StateTransition {
+            event_id,
+            from_state: from_state.as_deref().and_then(EventState::parse),
+            to_state: EventState::parse(&to_state).unwrap(),
+            at: DateTime::parse_from_rfc3339(&at).unwrap().with_timezone(&Utc),
+        }).collect())
+    }
+
+    /// Insert or replace one window session of an event.
+    pub async fn store_window(&self, window: &WindowProof) -> Result<(), sqlx::Error> {
+        sqlx::query(
+            "INSERT OR REPLACE INTO windows (event_id, session_id, window_start, window_json) VALUES (?, ?, ?, ?)",
+        )
+        .bind(&window.event_id)
+        .bind(&window.session_id)
+        .bind(window.window_start)
+        .bind(serde_json::to_string(window).unwrap())
+        .execute(&self.pool)
+        .await?;
+        Ok(())
+    }
+
+    pub async fn get_event_windows(&self, event_id: &str) -> Result<Vec<WindowProof>, sqlx::Error> {
+        let rows: Vec<(String,)> = sqlx::query_as(
+            "SELECT window_json FROM windows WHERE event_id = ? ORDER BY window_start, session_id",
+        )
+        .bind(event_id)
+        .fetch_all(&self.pool)
+        .await?;
+        Ok(rows.into_iter().map(|(w,)| serde_json::from_str(&w).unwrap()).collect())
+    }
+
+    /// Record a check-in. Returns false if the attendee had already checked
+    /// in to this session.
+    /// Record a check-in unless the attendee already has one for this
+    /// session. With `max_attendees`, a check-in that would add a new
+    /// distinct attendee beyond the cap is refused; the count and insert run
+    /// as one statement so concurrent check-ins cannot overshoot it.
+    pub async fn record_checkin(
+        &self,
+        checkin: &CheckIn,
+        max_attendees: Option<u32>,
+    ) -> Result<CheckInOutcome, sqlx::Error> {
+        let mut conn = self.pool.acquire().await?;
+        Self::insert_checkin(&mut conn, checkin, max_attendees).await
+    }
+
+    async fn insert_checkin(
+        conn: &mut SqliteConnection,
+        checkin: &CheckIn,
+        max_attendees: Option<u32>,
+    ) -> Result<CheckInOutcome, sqlx::Error> {
+        let result = sqlx::query(&format!(
+            r#"
+            INSERT OR IGNORE INTO checkins ({})
+            SELECT ?1, ?2, ?3, ?4, ?5, ?6
+            WHERE ?7 IS NULL
+                OR EXISTS (SELECT 1 FROM checkins WHERE event_id = ?1 AND attendee_lock_hash = ?4)
+                OR (SELECT COUNT(DISTINCT attendee_lock_hash) FROM checkins WHERE event_id = ?1) < ?7
+            "#,
+            CHECKIN_COLUMNS
+        ))
+        .bind(&checkin.event_id)
+        .bind(&checkin.session_id)
+        .bind(&checkin.attendee_address)
+        .bind(&checkin.attendee_lock_hash)
+        .bind(checkin.qr_timestamp)
+        .bind(checkin.checked_in_at.to_rfc3339())
+        .bind(max_attendees)
+        .execute(&mut *conn)
+        .await?;
+        if result.rows_affected() > 0 {
+            return Ok(CheckInOutcome::Recorded);
+        }
+
+        let (existing,): (bool,) = sqlx::query_as(
+            "SELECT EXISTS (SELECT 1 FROM checkins WHERE event_id = ? AND session_id = ? AND attendee_lock_hash = ?)",
+        )
+        .bind(&checkin.event_id)
+        .bind(&checkin.session_id)
+        .bind(&checkin.attendee_lock_hash)
+        .fetch_one(&mut *conn)
+        .await?;
+        Ok(if existing { CheckInOutcome::AlreadyCheckedIn } else { CheckInOutcome::AtCapacity })
+    }
+
+    /// Number of distinct attendees checked in to any session of the event.
+    pub async fn count_attendees(&self, event_id: &str) -> Result<u32, sqlx::Error> {
+        let (count,): (i64,) =
+            sqlx::query_as("SELECT COUNT(DISTINCT attendee_lock_hash) FROM checkins WHERE event_id = ?")
+                .bind(event_id)
+                .fetch_one(&self.pool)
+                .await?;
+        Ok(count as u32)
+    }
+
+    pub async fn get_checkins(&self, event_id: &str) -> Result<Vec<CheckIn>, sqlx::Error> {
+        let rows: Vec<CheckInRow> = sqlx::query_as(&format!(
+            "SELECT {} FROM checkins WHERE event_id = ? ORDER BY session_id, checked_in_at",
+            CHECKIN_COLUMNS
+        ))
+        .bind(event_id)
+        .fetch_all(&self.pool)
+        .await?;
+        Ok(rows.into_iter().map(checkin_from_row).collect())
+    }
+
+    /// Store a delegation, replacing any earlier one for the same delegate.
+    /// Store a delegation, replacing the delegate's earlier grant unless it
+    /// has the same or a newer version, revoked or not. Returns false when
+    /// it was superseded.
+    pub async fn store_delegation(&self, delegation: &Delegation) -> Result<bool, sqlx::Error> {
+        let result = sqlx::query(&format!(
+            r#"
+            INSERT INTO delegations ({}) VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?)
+            ON CONFLICT (event_id, delegate_lock_hash) DO UPDATE SET
+                delegate_address = excluded.delegate_address,
+                role = excluded.role,
+                expires_at = excluded.expires_at,
+                version = excluded.version,
+                creator_signature = excluded.creator_signature,
+                created_at = excluded.created_at,
+                revoked_at = excluded.revoked_at
+            WHERE excluded.version > delegations.version
+            "#,
+            DELEGATION_COLUMNS
+        ))
+        .bind(&delegation.event_id)
+        .bind(&delegation.delegate_address)
+        .bind(&delegation.delegate_lock_hash)
+        .bind(delegation.role.as_str())
+        .bind(delegation.expires_at)
+        .bind(delegation.version as i64)
+        .bind(&delegation.creator_signature)
+        .bind(delegation.created_at.to_rfc3339())
+        .bind(delegation.revoked_at.map(|t| t.to_rfc3339()))
+        .execute(&self.pool)
+        .await?;
+        Ok(result.rows_affected() > 0)
+    }
+
+    pub async fn get_delegations(&self, event_id: &str) -> Result<Vec<Delegation>, sqlx::Error> {
+        let rows: Vec<DelegationRow> = sqlx::query_as(&format!(
+            "SELECT {} FROM delegations WHERE event_id = ? AND revoked_at IS NULL ORDER BY created_at",
+            DELEGATION_COLUMNS
+        ))
+        .bind(event_id)
+        .fetch_all(&self.pool)
+        .await?;
+        Ok(rows.into_iter().filter_map(delegation_from_row).collect())
+    }
+
+    pub async fn get_delegation(
+        &self,
+        event_id: &str,
+        delegate_lock_hash: &str,
+    ) -> Result<Option<Delegation>, sqlx::Error> {
+        let row: Option<DelegationRow> = sqlx::query_as(&format!(
+            "SELECT {} FROM delegations WHERE event_id = ? AND delegate_lock_hash = ?",
+            DELEGATION_COLUMNS
+        ))
+        .bind(event_id)
+        .bind(delegate_lock_hash)
+        .fetch_optional(&self.pool)
+        .await?;
+        Ok(row.and_then(delegation_from_row))
+    }
+
+    /// Store a role assignment, replacing any earlier one for the same holder.
+    pub async fn store_role_assignment(&self, assignment: &RoleAssignment) -> Result<(), sqlx::Error> {
+        sqlx::query(&format!(
+            "INSERT OR REPLACE INTO role_assignments ({}) VALUES (?, ?, ?, ?, ?, ?)",
+            ROLE_ASSIGNMENT_COLUMNS
+        ))
+        .bind(&assignment.event_id)
+        .bind(&assignment.holder_address)
+        .bind(&assignment.holder_lock_hash)
+        .bind(assignment.role.as_str())
+        .bind(&assignment.creator_signature)
+        .bind(assignment.assigned_at.to_rfc3339())
+        .execute(&self.pool)
+        .await?;
+        Ok(())
+    }
+
+    pub async fn get_role_assignments(&self, event_id: &str) -> Result<Vec<RoleAssignment>, sqlx::Error> {
+        let rows: Vec<RoleAssignmentRow> = sqlx::query_as(&format!(
+            "SELECT {} FROM role_assignments WHERE event_id = ? ORDER BY assigned_at",
+            ROLE_ASSIGNMENT_COLUMNS
+        ))
+        .bind(event_id)
+        .fetch_all(&self.pool)
+        .await?;
+        Ok(rows.into_iter().filter_map(role_assignment_from_row).collect())
+    }
+
+    pub async fn get_role_assignment(
+        &self,
+        event_id: &str,
+        holder_lock_hash: &str,
+    ) -> Result<Option<RoleAssignment>, sqlx::Error> {
+        let row: Option<RoleAssignmentRow> = sqlx::query_as(&format!(
+            "SELECT {} FROM role_assignments WHERE event_id = ? AND holder_lock_hash = ?",
+            ROLE_ASSIGNMENT_COLUMNS
+        ))
+        .bind(event_id)
+        .bind(holder_lock_hash)
+        .fetch_optional(&self.pool)
+        .await?;
+        Ok(row.and_then(role_assignment_from_row))
+    }
+
+    /// Mark a delegation revoked, keeping the row so its version cannot be
+    /// granted again. Returns whether an unrevoked delegation was found.
+    pub async fn revoke_delegation(
+        &self,
+        event_id: &str,
+        delegate_lock_hash: &str,
+        revoked_at: DateTime<Utc>,
+    ) -> Result<bool, sqlx::Error> {
+        let result = sqlx::query(
+            "UPDATE delegations SET revoked_at = ? WHERE event_id = ? AND delegate_lock_hash = ? AND revoked_at IS NULL",
+        )
+        .bind(revoked_at.to_rfc3339())
+        .bind(event_id)
+        .bind(delegate_lock_hash)
+        .execute(&self.pool)
+        .await?;
+        Ok(result.rows_affected() > 0)
+    }
+
+    /// Replace an event's allowlist and its entries in one transaction,
+    /// unless a list with the same or a newer version is already stored.
+    /// Returns false when it was superseded.
+    pub async fn store_allowlist(&self, allowlist: &Allowlist, lock_hashes: &[String]) -> Result<bool, sqlx::Error> {
+        let mut tx = self.pool.begin().await?;
+
+        let stored = sqlx::query(
+            r#"
+            INSERT INTO allowlists (event_id, merkle_root, version, creator_signature, updated_at) VALUES (?, ?, ?, ?, ?)
+            ON CONFLICT (event_id) DO UPDATE SET
+                merkle_root = excluded.merkle_root,
+                version = excluded.version,
+                creator_signature = excluded.creator_signature,
+                updated_at = excluded.updated_at
+            WHERE excluded.version > allowlists.version
+            "#,
+        )
+        .bind(&allowlist.event_id)
+        .bind(&allowlist.merkle_root)
+        .bind(allowlist.version as i64)
+        .bind(&allowlist.creator_signature)
+        .bind(allowlist.updated_at.to_rfc3339())
+        .execute(&mut *tx)
+        .await?;
+        if stored.rows_affected() == 0 {
+            return Ok(false);
+        }
+
+        sqlx::query("DELETE FROM allowlist_entries WHERE event_id = ?")
+            .bind(&allowlist.event_id)
+            .execute(&mut *tx)
+            .await?;
+        for lock_hash in lock_hashes {
+            sqlx::query("INSERT OR IGNORE INTO allowlist_entries (event_id, lock_hash) VALUES (?, ?)")
+                .bind(&allowlist.event_id)
+                .bind(lock_hash)
+                .execute(&mut *tx)
+                .await?;
+        }
+
+        tx.commit().await?;
+        Ok(true)
+    }
+
+    pub async fn get_allowlist(&self, event_id: &str) -> Result<Option<Allowlist>, sqlx::Error> {
+        let row: Option<(String, String, i64, String, String, i64)> = sqlx::query_as(
+            r#"
+            SELECT a.event_id, a.merkle_root, a.version, a.creator_signature, a.updated_at,
+                (SELECT COUNT(*) FROM allowlist_entries e WHERE e.event_id = a.event_id)
+            FROM allowlists a WHERE a.event_id = ?
+            "#,
+        )
+        .bind(event_id)
+        .fetch_optional(&self.pool)
+        .await?;
+
+        Ok(row.map(|(event_id, merkle_root, version, creator_signature, updated_at, entry_count)| Allowlist {
+            event_id,
+            merkle_root,
+            entry_count: entry_count as usize,
+            version: version as u32,
+            creator_signature,
+            updated_at: DateTime::parse_from_rfc3339(&updated_at).unwrap().with_timezone(&Utc),
+        }))
+    }
+
+    /// Whether the lock may check in: true when the event has no allowlist
+    /// or the lock is on it.
+    pub async fn allowlist_permits(&self, event_id: &str, lock_hash: &str) -> Result<bool, sqlx::Error> {
+        let (permitted,): (bool,) = sqlx::query_as(
+            r#"
+            SELECT NOT EXISTS (SELECT 1 FROM allowlists WHERE event_id = ?1)
+                OR EXISTS (SELECT 1 FROM allowlist_entries WHERE event_id = ?1 AND lock_hash = ?2)
+            "#,
+        )
+        .bind(event_id)
+        .bind(lock_hash)
+        .fetch_one(&self.pool)
+        .await?;
+        Ok(permitted)
+    }
+
+    /// Store the policy unless one with the same or a newer version is
+    /// already stored. Returns false when it was superseded.
+    pub async fn store_offline_policy(&self, policy: &OfflinePolicy) -> Result<bool, sqlx::Error> {
+        let result = sqlx::query(
+            r#"
+            INSERT INTO offline_policies (event_id, grace_secs, version, updated_at) VALUES (?, ?, ?, ?)
+            ON CONFLICT (event_id) DO UPDATE SET
+                grace_secs = excluded.grace_secs,
+                version = excluded.version,
+                updated_at = excluded.updated_at
+            WHERE excluded.version > offline_policies.version
+            "#,
+        )
+        .bind(&policy.event_id)
+        .bind(policy.grace_secs)
+        .bind(policy.version as i64)
+        .bind(policy.updated_at.to_rfc3339())
+        .execute(&self.pool)
+        .await?;
+        Ok(result.rows_affected() > 0)
+    }
+
+    pub async fn get_offline_policy(&self, event_id: &str) -> Result<Option<OfflinePolicy>, sqlx::Error> {
+        let row: Option<(String, i64, i64, String)> = sqlx::query_as(
+            "SELECT event_id, grace_secs, version, updated_at FROM offline_policies WHERE event_id = ?",
+        )
+        .bind(event_id)
+        .fetch_optional(&self.pool)
+        .await?;
+
+        Ok(row.map(|(event_id, grace_secs, version, updated_at)| OfflinePolicy {
+            event_id,
+            grace_secs,
+            version: version as u32,
+            updated_at: DateTime::parse_from_rfc3339(&updated_at).unwrap().with_timezone(&Utc),
+        }))
+    }
+
+    /// Store the policy unless one with the same or a newer version is
+    /// already stored. Returns false when it was superseded.
+    pub async fn store_checkout_policy(&self, policy: &CheckoutPolicy) -> Result<bool, sqlx::Error> {
+        let result = sqlx::query(
+            r#"
+            INSERT INTO checkout_policies (event_id, checkout_session_id, min_duration_secs, version, updated_at)
+            VALUES (?, ?, ?, ?, ?)
+            ON CONFLICT (event_id) DO UPDATE SET
+                checkout_session_id = excluded.checkout_session_id,
+                min_duration_secs = excluded.min_duration_secs,
+                version = excluded.version,
+                updated_at = excluded.updated_at
+            WHERE excluded.version > checkout_policies.version
+            "#,
+        )
+        .bind(&policy.event_id)
+        .bind(&policy.checkout_session_id)
+        .bind(policy.min_duration_secs)
+        .bind(policy.version as i64)
+        .bind(policy.updated_at.to_rfc3339())
+        .execute(&self.pool)
+        .await?;
+        Ok(result.rows_affected() > 0)
+    }
+
+    pub async fn get_checkout_policy(&self, event_id: &str) -> Result<Option<CheckoutPolicy>, sqlx::Error> {
+        let row: Option<(String, String, i64, i64, String)> = sqlx::query_as(
+            "SELECT event_id, checkout_session_id, min_duration_secs, version, updated_at FROM checkout_policies WHERE event_id = ?",
+        )
+        .bind(event_id)
+        .fetch_optional(&self.pool)
+        .await?;
+
+        Ok(row.map(|(event_id, checkout_session_id, min_duration_secs, version, updated_at)| CheckoutPolicy {
+            event_id,
+            checkout_session_id,
+            min_duration_secs,
+            version: version as u32,
+            updated_at: DateTime::parse_from_rfc3339(&updated_at).unwrap().with_timezone(&Utc),
+        }))
+    }
+
+    /// Store the policy unless one with the same or a newer version is
+    /// already stored. Returns false when it was superseded.
+    pub async fn store_claim_policy(&self, policy: &ClaimPolicy) -> Result<bool, sqlx::Error> {
+        let result = sqlx::query(
+            r#"
+            INSERT INTO claim_policies (event_id, claim_period_secs, version, updated_at) VALUES (?, ?, ?, ?)
+            ON CONFLICT (event_id) DO UPDATE SET
+                claim_period_secs = excluded.claim_period_secs,
+                version = excluded.version,
+                updated_at = excluded.updated_at
+            WHERE excluded.version > claim_policies.version
+            "#,
+        )
+        .bind(&policy.event_id)
+        .bind(policy.claim_period_secs)
+        .bind(policy.version as i64)
+        .bind(policy.updated_at.to_rfc3339())
+        .execute(&self.pool)
+        .await?;
+        Ok(result.rows_affected() > 0)
+    }
+
+    pub async fn get_claim_policy(&self, event_id: &str) -> Result<Option<ClaimPolicy>, sqlx::Error> {
+        let row: Option<(String, i64, i64, String)> = sqlx::query_as(
+            "SELECT event_id, claim_period_secs, version, updated_at FROM claim_policies WHERE event_id = ?",
+        )
+        .bind(event_id)
+        .fetch_optional(&self.pool)
+        .await?;
+
+        Ok(row.map(|(event_id, claim_period_secs, version, updated_at)| ClaimPolicy {
+            event_id,
+            claim_period_secs,
+            version: version as u32,
+            updated_at: DateTime::parse_from_rfc3339(&updated_at).unwrap().with_timezone(&Utc),
+        }))
+    }
+
+    pub async fn store_voucher(&self, voucher: &VoucherRecord) -> Result<(), sqlx::Error> {
+        sqlx::query(&format!(
+            "INSERT INTO claim_vouchers ({}) VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?)",
+            VOUCHER_COLUMNS
+        ))
+        .bind(&voucher.voucher_hash)
+        .bind(&voucher.event_id)
+        .bind(&voucher.session_id)
+        .bind(voucher.qr_timestamp)
+        .bind(voucher.expires_at)
+        .bind(&voucher.signature)
+        .bind(voucher.issued_at.to_rfc3339())
+        .bind(&voucher.redeemed_by)
+        .bind(voucher.redeemed_at.map(|t| t.to_rfc3339()))
+        .execute(&self.pool)
+        .await?;
+        Ok(())
+    }
+
+    pub async fn get_voucher(&self, voucher_hash: &str) -> Result<Option<VoucherRecord>, sqlx::Error> {
+        let row: Option<VoucherRow> = sqlx::query_as(&format!(
+            "SELECT {} FROM claim_vouchers WHERE voucher_hash = ?",
+            VOUCHER_COLUMNS
+        ))
+        .bind(voucher_hash)
+        .fetch_optional(&self.pool)
+        .await?;
+        Ok(row.map(voucher_from_row))
+    }
+
+    /// Mark a voucher redeemed by `address`. Returns false if it was already
+    /// redeemed, so concurrent claims cannot both succeed.
+    /// Redeem a voucher and record the redeemer's check-in in one
+    /// transaction. Returns `None` when the voucher was already redeemed.
+    /// At capacity nothing is written, leaving the voucher redeemable.
+    pub async fn redeem_voucher(
+        &self,
+        voucher_hash: &str,
+        checkin: &CheckIn,
+        max_attendees: Option<u32>,
+    ) -> Result<Option<CheckInOutcome>, sqlx::Error> {
+        let mut tx = self.pool.begin().await?;
+
+        let result = sqlx::query(
+            "UPDATE claim_vouchers SET redeemed_by = ?, redeemed_at = ? WHERE voucher_hash = ? AND redeemed_at IS NULL",
+        )
+        .bind(&checkin.attendee_address)
+        .bind(checkin.checked_in_at.to_rfc3339())
+        .bind(voucher_hash)
+        .execute(&mut *tx)
+        .await?;
+        if result.rows_affected() == 0 {
+            return Ok(None);
+        }
+
+        let outcome = Self::insert_checkin(&mut tx, checkin, max_attendees).await?;
+        if outcome != CheckInOutcome::AtCapacity {
+            tx.commit().await?;
+        }
+        Ok(Some(outcome))
+    }
+
+    /// Store the policy unless one with the same or a newer version is
+    /// already stored. Returns false when it was superseded.
+    pub async fn store_rsvp_policy(&self, policy: &RsvpPolicy) -> Result<bool, sqlx::Error> {
+        let result = sqlx::query(
+            r#"
+            INSERT INTO rsvp_policies (event_id, capacity, required, version, updated_at) VALUES (?, ?, ?, ?, ?)
+            ON CONFLICT (event_id) DO UPDATE SET
+                capacity = excluded.capacity,
+                required = excluded.required,
+                version = excluded.version,
+                updated_at = excluded.updated_at
+            WHERE excluded.version > rsvp_policies.version
+            "#,
+        )
+        .bind(&policy.event_id)
+        .bind(policy.capacity)
+        .bind(policy.required)
+        .bind(policy.version as i64)
+        .bind(policy.updated_at.to_rfc3339())
+        .execute(&self.pool)
+        .await?;
+        Ok(result.rows_affected() > 0)
+    }
+
+    pub async fn get_rsvp_policy(&self, event_id: &str) -> Result<Option<RsvpPolicy>, sqlx::Error> {
+        let row: Option<(String, Option<i64>, bool, i64, String)> = sqlx::query_as(
+            "SELECT event_id, capacity, required, version, updated_at FROM rsvp_policies WHERE event_id = ?",
+        )
+        .bind(event_id)
+        .fetch_optional(&self.pool)
+        .await?;
+
+        Ok(row.map(|(event_id, capacity, required, version, updated_at)| RsvpPolicy {
+            event_id,
+            capacity: capacity.map(|c| c as u32),
+            required,
+            version: version as u32,
+            updated_at: DateTime::parse_from_rfc3339(&updated_at).unwrap().with_timezone(&Utc),
+        }))
+    }
+
+    /// Record an RSVP, confirmed while fewer than `capacity` are confirmed
+    /// and waitlisted otherwise; `rsvp.status` is ignored. An attendee who
+    /// already RSVP'd keeps their original place. Returns the stored RSVP.
+    pub async fn record_rsvp(&self, rsvp: &Rsvp, capacity: Option<u32>) -> Result<Rsvp, sqlx::Error> {
+        sqlx::query(&format!(
+            r#"
+            INSERT OR IGNORE INTO rsvps ({})
+            SELECT ?1, ?2, ?3,
+                CASE WHEN ?4 IS NULL
+                    OR (SELECT COUNT(*) FROM rsvps WHERE event_id = ?1 AND status = 'confirmed') < ?4
+                THEN 'confirmed' ELSE 'waitlisted' END,
+                ?5, ?6
+            "#,
+            RSVP_COLUMNS
+        ))
+        .bind(&rsvp.event_id)
+        .bind(&rsvp.attendee_address)
+        .bind(&rsvp.attendee_lock_hash)
+        .bind(capacity)
+        .bind(&rsvp.attendee_signature)
+        .bind(rsvp.created_at.to_rfc3339())
+        .execute(&self.pool)
+        .await?;
+
+        let stored = self.get_rsvp(&rsvp.event_id, &rsvp.attendee_lock_hash).await?;
+        stored.ok_or(sqlx::Error::RowNotFound)
+    }
+
+    pub async fn get_rsvp(&self, event_id: &str, attendee_lock_hash: &str) -> Result<Option<Rsvp>, sqlx::Error> {
+        let row: Option<RsvpRow> = sqlx::query_as(&format!(
+            "SELECT {} FROM rsvps WHERE event_id = ? AND attendee_lock_hash = ?",
+            RSVP_COLUMNS
+        ))
+        .bind(event_id)
+        .bind(attendee_lock_hash)
+        .fetch_optional(&self.pool)
+        .await?;
+        Ok(row.and_then(rsvp_from_row))
+    }
+
+    /// RSVPs for an event in the order they were made.
+    pub async fn get_rsvps(&self, event_id: &str) -> Result<Vec<Rsvp>, sqlx::Error> {
+        let rows: Vec<RsvpRow> = sqlx::query_as(&format!(
+            "SELECT {} FROM rsvps WHERE event_id = ? ORDER BY created_at, rowid",
+            RSVP_COLUMNS
+        ))
+        .bind(event_id)
+        .fetch_all(&self.pool)
+        .await?;
+        Ok(rows.into_iter().filter_map(rsvp_from_row).collect())
+    }
+
+    /// Returns whether an RSVP was removed.
+    pub async fn delete_rsvp(&self, event_id: &str, attendee_lock_hash: &str) -> Result<bool, sqlx::Error> {
+        let result = sqlx::query("DELETE FROM rsvps WHERE event_id = ? AND attendee_lock_hash = ?")
+            .bind(event_id)
+            .bind(attendee_lock_hash)
+            .execute(&self.pool)
+            .await?;
+        Ok(result.rows_affected() > 0)
+    }
+
+    /// Confirm waitlisted RSVPs, earliest first, until `capacity` are
+    /// confirmed (all of them when there is no capacity). Returns the number
+    /// promoted.
+    pub async fn promote_waitlist(&self, event_id: &str, capacity: Option<u32>) -> Result<u64, sqlx::Error> {
+        let result = sqlx::query(
+            r#"
+            UPDATE rsvps SET status = 'confirmed'
+            WHERE rowid IN (
+                SELECT rowid FROM rsvps WHERE event_id = ?1 AND status = 'waitlisted'
+                ORDER BY created_at, rowid
+                LIMIT CASE WHEN ?2 IS NULL THEN -1 ELSE MAX(?2 - (
+                    SELECT COUNT(*) FROM rsvps WHERE event_id = ?1 AND status = 'confirmed'
+                ), 0) END
+            )
+            "#,
+        )
+        .bind(event_id)
+        .bind(capacity)
+        .execute(&self.pool)
+        .await?;
+        Ok(result.rows_affected())
+    }
+
+    /// Credit a sponsor deposit. Returns false if the transaction was
+    /// already credited, to this or any other event.
+    pub async fn record_sponsor_deposit(&self, deposit: &SponsorDeposit) -> Result<bool, sqlx::Error> {
+        let result = sqlx::query(&format!(
+            "INSERT OR IGNORE INTO sponsor_deposits ({}) VALUES (?, ?, ?, ?, ?)",
+            SPONSOR_DEPOSIT_COLUMNS
+        ))
+        .bind(&deposit.event_id)
+        .bind(&deposit.tx_hash)
+        .bind(deposit.capacity as i64)
+        .bind(deposit.block_number as i64)
+        .bind(deposit.recorded_at.to_rfc3339())
+        .execute(&self.pool)
+        .await?;
+        Ok(result.rows_affected() > 0)
+    }
+
+    pub async fn get_sponsor_deposits(&self, event_id: &str) -> Result<Vec<SponsorDeposit>, sqlx::Error> {
+        let rows: Vec<SponsorDepositRow> = sqlx::query_as(&format!(
+            "SELECT {} FROM sponsor_deposits WHERE event_id = ? ORDER BY recorded_at",
+            SPONSOR_DEPOSIT_COLUMNS
+        ))
+        .bind(event_id)
+        .fetch_all(&self.pool)
+        .await?;
+        Ok(rows.into_iter().map(sponsor_deposit_from_row).collect())
+    }
+
+    /// Draw a badge's capacity and fee from the event's sponsor budget.
+    /// Returns false, drawing nothing, if the remaining budget cannot cover
+    /// it or the holder already has a draw for this event.
+    pub async fn draw_sponsor_budget(&self, draw: &SponsorDraw) -> Result<bool, sqlx::Error> {
+        let result = sqlx::query(&format!(
+            r#"
+            INSERT OR IGNORE INTO sponsor_draws ({})
+            SELECT ?1, ?2, ?3, ?4, ?5, ?6, ?7
+            WHERE (SELECT COALESCE(SUM(capacity), 0) FROM sponsor_deposits WHERE event_id = ?1)
+                - (SELECT COALESCE(SUM(capacity + fee), 0) FROM sponsor_draws WHERE event_id = ?1) >= ?5 + ?6
+            "#,
+            SPONSOR_DRAW_COLUMNS
+        ))
+        .bind(&draw.event_id)
+        .bind(&draw.holder_lock_hash)
+        .bind(&draw.holder_address)
+        .bind(&draw.tx_hash)
+        .bind(draw.capacity as i64)
+        .bind(draw.fee as i64)
+        .bind(draw.drawn_at.to_rfc3339())
+        .execute(&self.pool)
+        .await?;
+        Ok(result.rows_affected() > 0)
+    }
+
+    pub async fn get_sponsor_draw(
+        &self,
+        event_id: &str,
+        holder_lock_hash: &str,
+    ) -> Result<Option<SponsorDraw>, sqlx::Error> {
+        let row: Option<SponsorDrawRow> = sqlx::query_as(&format!(
+            "SELECT {} FROM sponsor_draws WHERE event_id = ? AND holder_lock_hash = ?",
+            SPONSOR_DRAW_COLUMNS
+        ))
+        .bind(event_id)
+        .bind(holder_lock_hash)
+        .fetch_optional(&self.pool)
+        .await?;
+        Ok(row.map(sponsor_draw_from_row))
+    }
+
+    /// Record the transaction the sponsor wallet co-signs for a draw.
+    /// Returns false if the draw was already signed for another one.
+    pub async fn bind_sponsor_draw(
+        &self,
+        event_id: &str,
+        holder_lock_hash: &str,
+        tx_hash: &str,
+    ) -> Result<bool, sqlx::Error> {
+        let result = sqlx::query(
+            "UPDATE sponsor_draws SET tx_hash = ?3 WHERE event_id = ?1 AND holder_lock_hash = ?2 AND (tx_hash = '' OR tx_hash = ?3)",
+        )
+        .bind(event_id)
+        .bind(holder_lock_hash)
+        .bind(tx_hash)
+        .execute(&self.pool)
+        .await?;
+        Ok(result.rows_affected() > 0)
+    }
+
+    pub async fn get_sponsor_draws(&self, event_id: &str) -> Result<Vec<SponsorDraw>, sqlx::Error> {
+        let rows: Vec<SponsorDrawRow> = sqlx::query_as(&format!(
+            "SELECT {} FROM sponsor_draws WHERE event_id = ? ORDER BY drawn_at",
+            SPONSOR_DRAW_COLUMNS
+        ))
+        .bind(event_id)
+        .fetch_all(&self.pool)
+        .await?;
+        Ok(rows.into_iter().map(sponsor_draw_from_row).collect())
+    }
+
+    /// Include an attendee in an airdrop batch. Returns false if an earlier
+    /// airdrop already included them.
+    pub async fn record_airdrop_inclusion(&self, inclusion: &AirdropInclusion) -> Result<bool, sqlx::Error> {
+        let result = sqlx::query(&format!(
+            "INSERT OR IGNORE INTO airdrop_inclusions ({}) VALUES (?, ?, ?, ?, ?, ?, ?, ?)",
+            AIRDROP_INCLUSION_COLUMNS
+        ))
+        .bind(&inclusion.event_id)
+        .bind(&inclusion.holder_lock_hash)
+        .bind(&inclusion.holder_address)
+        .bind(&inclusion.batch_id)
+        .bind(&inclusion.tx_hash)
+        .bind(&inclusion.funder_address)
+        .bind(inclusion.included_at.to_rfc3339())
+        .bind(inclusion.confirmed_block.map(|b| b as i64))
+        .execute(&self.pool)
+        .await?;
+        Ok(result.rows_affected() > 0)
+    }
+
+    /// Record the transaction a batch was broadcast in. Only a batch with no
+    /// reported transaction is updated.
+    pub async fn report_airdrop_tx(&self, event_id: &str, batch_id: &str, tx_hash: &str) -> Result<(), sqlx::Error> {
+        sqlx::query("UPDATE airdrop_inclusions SET tx_hash = ? WHERE event_id = ? AND batch_id = ? AND tx_hash = ''")
+            .bind(tx_hash)
+            .bind(event_id)
+            .bind(batch_id)
+            .execute(&self.pool)
+            .await?;
+        Ok(())
+    }
+
+    /// Mark the inclusions in a broadcast batch as committed.
+    pub async fn confirm_airdrop_inclusions(&self, tx_hash: &str, block_number: u64) -> Result<(), sqlx::Error> {
+        sqlx::query("UPDATE airdrop_inclusions SET confirmed_block = ? WHERE tx_hash = ? AND confirmed_block IS NULL")
+            .bind(block_number as i64)
+            .bind(tx_hash)
+            .execute(&self.pool)
+            .await?;
+        Ok(())
+    }
+
+    /// Drop a batch that was never reported as broadcast, so its attendees
+    /// can be airdropped again. Returns how many inclusions were released.
+    pub async fn release_airdrop_batch(&self, event_id: &str, batch_id: &str) -> Result<u64, sqlx::Error> {
+        let result =
+            sqlx::query("DELETE FROM airdrop_inclusions WHERE event_id = ? AND batch_id = ? AND tx_hash = ''")
+                .bind(event_id)
+                .bind(batch_id)
+                .execute(&self.pool)
+                .await?;
+        Ok(result.rows_affected())
+    }
+
+    pub async fn get_airdrop_batch(&self, event_id: &str, batch_id: &str) -> Result<Vec<AirdropInclusion>, sqlx::Error> {
+        let rows: Vec<AirdropInclusionRow> = sqlx::query_as(&format!(
+            "SELECT {} FROM airdrop_inclusions WHERE event_id = ? AND batch_id = ? ORDER BY rowid",
+            AIRDROP_INCLUSION_COLUMNS
+        ))
+        .bind(event_id)
+        .bind(batch_id)
+        .fetch_all(&self.pool)
+        .await?;
+        Ok(rows.into_iter().map(airdrop_inclusion_from_row).collect())
+    }
+
+    pub async fn get_airdrop_inclusions(&self, event_id: &str) -> Result<Vec<AirdropInclusion>, sqlx::Error> {
+        let rows: Vec<AirdropInclusionRow> = sqlx::query_as(&format!(
+            "SELECT {} FROM airdrop_inclusions WHERE event_id = ? ORDER BY included_at, rowid",
+            AIRDROP_INCLUSION_COLUMNS
+        ))
+        .bind(event_id)
+        .fetch_all(&self.pool)
+        .await?;
+        Ok(rows.into_iter().map(airdrop_inclusion_from_row).collect())
+    }
+
+    /// Record an attendee's check-out. Returns false if they already checked
+    /// out of this event, leaving the earlier proof in place.
+    pub async fn record_duration_proof(&self, proof: &DurationProof) -> Result<bool, sqlx::Error> {
+        let result = sqlx::query(&format!(
+            "INSERT OR IGNORE INTO duration_proofs ({}) VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?)",
+            DURATION_PROOF_COLUMNS
+        ))
+        .bind(&proof.event_id)
+        .bind(&proof.attendee_lock_hash)
+        .bind(&proof.attendee_address)
+        .bind(&proof.checkin_session_id)
+        .bind(proof.checkin_timestamp)
+        .bind(&proof.checkout_session_id)
+        .bind(proof.checkout_timestamp)
+        .bind(proof.tier.as_str())
+        .bind(proof.recorded_at.to_rfc3339())
+        .execute(&self.pool)
+        .await?;
+        Ok(result.rows_affected() > 0)
+    }
+
+    pub async fn get_duration_proofs(&self, event_id: &str) -> Result<Vec<DurationProof>, sqlx::Error> {
+        let rows: Vec<DurationProofRow> = sqlx::query_as(&format!(
+            "SELECT {} FROM duration_proofs WHERE event_id = ? ORDER BY recorded_at",
+            DURATION_PROOF_COLUMNS
+        ))
+        .bind(event_id)
+        .fetch_all(&self.pool)
+        .await?;
+        Ok(rows.into_iter().filter_map(duration_proof_from_row).collect())
+    }
+
+    pub async fn store_series(&self, series: &EventSeries) -> Result<(), sqlx::Error> {
+        sqlx::query(
+            r#"
+            INSERT OR REPLACE INTO event_series
+            (series_id, creator_address, nonce, template_json, recurrence_json, creator_signature, created_at)
+            VALUES (?, ?, ?, ?, ?, ?, ?)
+            "#,
+        )
+        .bind(&series.series_id)
+        .bind(&series.creator_address)
+        .bind(&series.nonce)
+        .bind(serde_json::to_string(&series.template).unwrap())
+        .bind(serde_json::to_string(&series.recurrence).unwrap())
+        .bind(&series.creator_signature)
+        .bind(series.created_at.to_rfc3339())
+        .execute(&self.pool)
+        .await?;
+        Ok(())
+    }
+
+    pub async fn get_series(&self, series_id: &str) -> Result<Option<EventSeries>, sqlx::Error> {
+        let row: Option<(String, String, String, String, String, String, String)> = sqlx::query_as(
+            "SELECT series_id, creator_address, nonce, template_json, recurrence_json, creator_signature, created_at FROM event_series WHERE series_id = ?",
+        )
+        .bind(series_id)
+        .fetch_optional(&self.pool)
+        .await?;
+
+        Ok(row.map(
+            |(series_id, creator_address, nonce, template_json, recurrence_json, creator_signature, created_at)| {
+                EventSeries {
+                    series_id,
+                    creator_address,
+                    nonce,
+                    template: serde_json::from_str(&template_json).unwrap(),
+                    recurrence: serde_json::from_str(&recurrence_json).unwrap(),
+                    creator_signature,
+                    created_at: DateTime::parse_from_rfc3339(&created_at).unwrap().with_timezone(&Utc),
+                }
+            },
+        ))
     }
 
-    pub async fn update_event_window(&self, event_id: &str, window: &WindowProof) -> Result<(), sqlx::Error> {
-        sqlx::query("UPDATE active_events SET window_json = ? WHERE event_id = ?")
-            .bind(serde_json::to_string(window).unwrap())
+    pub async fn link_series_event(&self, series_id: &str, event_id: &str, occurrence: u32) -> Result<(), sqlx::Error> {
+        sqlx::query("INSERT OR REPLACE INTO series_events (event_id, series_id, occurrence) VALUES (?, ?, ?)")
             .bind(event_id)
+            .bind(series_id)
+            .bind(occurrence)
             .execute(&self.pool)
             .await?;
         Ok(())
     }
 
-    pub async fn check_qr_replay(&self, event_id: &str, timestamp: i64) -> Result<bool, sqlx::Error> {
+    /// Ids of the series' events, in occurrence order.
+    pub async fn get_series_event_ids(&self, series_id: &str) -> Result<Vec<String>, sqlx::Error> {
+        let rows: Vec<(String,)> =
+            sqlx::query_as("SELECT event_id FROM series_events WHERE series_id = ? ORDER BY occurrence")
+                .bind(series_id)
+                .fetch_all(&self.pool)
+                .await?;
+        Ok(rows.into_iter().map(|(event_id,)| event_id).collect())
+    }
+
+    pub async fn store_achievement(&self, achievement: &Achievement) -> Result<(), sqlx::Error> {
+        sqlx::query(
+            r#"
+            INSERT OR REPLACE INTO achievements
+            (achievement_id, creator_address, definition_json, creator_signature, created_at)
+            VALUES (?, ?, ?, ?, ?)
+            "#,
+        )
+        .bind(&achievement.achievement_id)
+        .bind(&achievement.creator_address)
+        .bind(serde_json::to_string(&achievement.definition).unwrap())
+        .bind(&achievement.creator_signature)
+        .bind(achievement.created_at.to_rfc3339())
+        .execute(&self.pool)
+        .await?;
+        Ok(())
+    }
+
+    pub async fn get_achievement(&self, achievement_id: &str) -> Result<Option<Achievement>, sqlx::Error> {
+        let row: Option<(String, String, String, String, String)> = sqlx::query_as(
+            "SELECT achievement_id, creator_address, definition_json, creator_signature, created_at FROM achievements WHERE achievement_id = ?",
+        )
+        .bind(achievement_id)
+        .fetch_optional(&self.pool)
+        .await?;
+
+        Ok(row.map(|(achievement_id, creator_address, definition_json, creator_signature, created_at)| Achievement {
+            achievement_id,
+            creator_address,
+            definition: serde_json::from_str(&definition_json).unwrap(),
+            creator_signature,
+            created_at: DateTime::parse_from_rfc3339(&created_at).unwrap().with_timezone(&Utc),
+        }))
+    }
+
+    pub async fn check_qr_replay(&self, event_id: &str, session_id: &str, timestamp: i64) -> Result<bool, sqlx::Error> {
         let count: (i64,) = sqlx::query_as(
-            "SELECT COUNT(*) FROM qr_replay_log WHERE event_id = ? AND timestamp = ?",
+            "SELECT COUNT(*) FROM qr_replay_log WHERE event_id = ? AND session_id = ? AND timestamp = ?",
         )
         .bind(event_id)
+        .bind(session_id)
         .bind(timestamp)
         .fetch_one(&self.pool)
         .await?;
         Ok(count.0 > 0)
     }
 
-    pub async fn record_qr_usage(&self, event_id: &str, timestamp: i64) -> Result<(), sqlx::Error> {
+    pub async fn record_qr_usage(&self, event_id: &str, session_id: &str, timestamp: i64) -> Result<(), sqlx::Error> {
         sqlx::query(
-            "INSERT OR IGNORE INTO qr_replay_log (event_id, timestamp, used_at) VALUES (?, ?, ?)",
+            "INSERT OR IGNORE INTO qr_replay_log (event_id, session_id, timestamp, used_at) VALUES (?, ?, ?, ?)",
         )
         .bind(event_id)
+        .bind(session_id)
         .bind(timestamp)
         .bind(Utc::now().to_rfc3339())
         .execute(&self.pool)
@@ -295,87 +1858,116 @@ impl Cache {
         Ok(())
     }
 
-    pub async fn cleanup_expired_replay_log(&self, before: DateTime<Utc>) -> Result<u64, sqlx::Error> {
-        let result = sqlx::query("DELETE FROM qr_replay_log WHERE used_at < ?")
-            .bind(before.to_rfc3339())
+    /// Forget frames whose QR timestamp is before `before`. Callers keep
+    /// frames for as long as any proof carrying them could still be valid.
+    pub async fn cleanup_expired_replay_log(&self, before: i64) -> Result<u64, sqlx::Error> {
+        let result = sqlx::query("DELETE FROM qr_replay_log WHERE timestamp < ?")
+            .bind(before)
             .execute(&self.pool)
             .await?;
         Ok(result.rows_affected())
     }
 
+    /// Insert or refresh a badge observation. A review flag already on the
+    /// row is kept; re-observing a badge does not clear it.
     pub async fn store_badge_observation(&self, badge: &BadgeObservation) -> Result<(), sqlx::Error> {
-        sqlx::query(
-            "INSERT OR REPLACE INTO badge_observations (event_id, holder_address, mint_tx_hash, mint_block_number, verified_at_block, observed_at) VALUES (?, ?, ?, ?, ?, ?)",
-        )
+        sqlx::query(&format!(
+            r#"
+            INSERT INTO badge_observations ({}) VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?)
+            ON CONFLICT (event_id, holder_lock_hash) DO UPDATE SET
+                holder_address = excluded.holder_address,
+                mint_tx_hash = excluded.mint_tx_hash,
+                mint_block_number = excluded.mint_block_number,
+                verified_at_block = excluded.verified_at_block,
+                observed_at = excluded.observed_at,
+                flagged_at = COALESCE(badge_observations.flagged_at, excluded.flagged_at),
+                flag_reason = COALESCE(badge_observations.flag_reason, excluded.flag_reason),
+                role = excluded.role
+            "#,
+            BADGE_COLUMNS
+        ))
         .bind(&badge.event_id)
         .bind(&badge.holder_address)
+        .bind(&badge.holder_lock_hash)
         .bind(&badge.mint_tx_hash)
         .bind(badge.mint_block_number as i64)
         .bind(badge.verified_at_block as i64)
         .bind(badge.observed_at.to_rfc3339())
+        .bind(badge.flagged_at.map(|t| t.to_rfc3339()))
+        .bind(&badge.flag_reason)
+        .bind(badge.role.as_str())
         .execute(&self.pool)
         .await?;
         Ok(())
     }
 
-    pub async fn get_badges_by_address(&self, address: &str) -> Result<Vec<BadgeObservation>, sqlx::Error> {
-        let rows: Vec<(String, String, String, i64, i64, String)> = sqlx::query_as(
-            "SELECT event_id, holder_address, mint_tx_hash, mint_block_number, verified_at_block, observed_at FROM badge_observations WHERE holder_address = ?",
+    /// Flag for review every badge in `event_id` whose holder checked in to
+    /// `session_id` at a QR timestamp within `[from, to]`. Badges already
+    /// flagged keep their original reason. Returns the number newly flagged.
+    pub async fn flag_badges_for_session(
+        &self,
+        event_id: &str,
+        session_id: &str,
+        from: i64,
+        to: i64,
+        reason: &str,
+    ) -> Result<u64, sqlx::Error> {
+        let result = sqlx::query(
+            r#"
+            UPDATE badge_observations SET flagged_at = ?, flag_reason = ?
+            WHERE event_id = ? AND flagged_at IS NULL AND holder_lock_hash IN (
+                SELECT attendee_lock_hash FROM checkins
+                WHERE event_id = ? AND session_id = ? AND qr_timestamp BETWEEN ? AND ?
+            )
+            "#,
         )
-        .bind(address)
+        .bind(Utc::now().to_rfc3339())
+        .bind(reason)
+        .bind(event_id)
+        .bind(event_id)
+        .bind(session_id)
+        .bind(from)
+        .bind(to)
+        .execute(&self.pool)
+        .await?;
+        Ok(result.rows_affected())
+    }
+
+    /// Return all badges held by the lock with the given (normalized) hash.
+    pub async fn get_badges_by_lock_hash(&self, lock_hash: &str) -> Result<Vec<BadgeObservation>, sqlx::Error> {
+        let rows: Vec<BadgeRow> = sqlx::query_as(&format!(
+            "SELECT {} FROM badge_observations WHERE holder_lock_hash = ?",
+            BADGE_COLUMNS
+        ))
+        .bind(lock_hash)
         .fetch_all(&self.pool)
         .await?;
 
-        Ok(rows.into_iter().map(|(event_id, holder_address, mint_tx_hash, mint_block_number, verified_at_block, observed_at)| {
-            BadgeObservation {
-                event_id,
-                holder_address,
-                mint_tx_hash,
-                mint_block_number: mint_block_number as u64,
-                verified_at_block: verified_at_block as u64,
-                observed_at: DateTime::parse_from_rfc3339(&observed_at).unwrap().with_timezone(&Utc),
-            }
-        }).collect())
+        Ok(rows.into_iter().map(badge_from_row).collect())
     }
 
     pub async fn get_badges_by_event(&self, event_id: &str) -> Result<Vec<BadgeObservation>, sqlx::Error> {
-        let rows: Vec<(String, String, String, i64, i64, String)> = sqlx::query_as(
-            "SELECT event_id, holder_address, mint_tx_hash, mint_block_number, verified_at_block, observed_at FROM badge_observations WHERE event_id = ?",
-        )
+        let rows: Vec<BadgeRow> = sqlx::query_as(&format!(
+            "SELECT {} FROM badge_observations WHERE event_id = ?",
+            BADGE_COLUMNS
+        ))
         .bind(event_id)
         .fetch_all(&self.pool)
         .await?;
 
-        Ok(rows.into_iter().map(|(event_id, holder_address, mint_tx_hash, mint_block_number, verified_at_block, observed_at)| {
-            BadgeObservation {
-                event_id,
-                holder_address,
-                mint_tx_hash,
-                mint_block_number: mint_block_number as u64,
-                verified_at_block: verified_at_block as u64,
-                observed_at: DateTime::parse_from_rfc3339(&observed_at).unwrap().with_timezone(&Utc),
-            }
-        }).collect())
+        Ok(rows.into_iter().map(badge_from_row).collect())
     }
 
     /// Return all badges with `mint_block_number = 0` (pending confirmation).
     pub async fn get_pending_badges(&self) -> Result<Vec<BadgeObservation>, sqlx::Error> {
-        let rows: Vec<(String, String, String, i64, i64, String)> = sqlx::query_as(
-            "SELECT event_id, holder_address, mint_tx_hash, mint_block_number, verified_at_block, observed_at FROM badge_observations WHERE mint_block_number = 0",
-        )
+        let rows: Vec<BadgeRow> = sqlx::query_as(&format!(
+            "SELECT {} FROM badge_observations WHERE mint_block_number = 0",
+            BADGE_COLUMNS
+        ))
         .fetch_all(&self.pool)
         .await?;
 
-        Ok(rows.into_iter().map(|(event_id, holder_address, mint_tx_hash, mint_block_number, verified_at_block, observed_at)| {
-            BadgeObservation {
-                event_id,
-                holder_address,
-                mint_tx_hash,
-                mint_block_number: mint_block_number as u64,
-                verified_at_block: verified_at_block as u64,
-                observed_at: DateTime::parse_from_rfc3339(&observed_at).unwrap().with_timezone(&Utc),
-            }
-        }).collect())
+        Ok(rows.into_iter().map(badge_from_row).collect())
     }
 
     /// Update the block number for a confirmed badge transaction.
@@ -391,6 +1983,94 @@ impl Cache {
         Ok(())
     }
 
+    pub async fn store_challenge(&self, challenge: &SessionChallenge) -> Result<(), sqlx::Error> {
+        sqlx::query(
+            "INSERT INTO challenge_cache (challenge_id, address, nonce, created_at, expires_at) VALUES (?, ?, ?, ?, ?)",
+        )
+        .bind(&challenge.challenge_id)
+        .bind(&challenge.address)
+        .bind(&challenge.nonce)
+        .bind(challenge.created_at.to_rfc3339())
+        .bind(challenge.expires_at.to_rfc3339())
+        .execute(&self.pool)
+        .await?;
+        Ok(())
+    }
+
+    /// Remove and return a challenge, so each one can be answered at most once.
+    pub async fn take_challenge(&self, challenge_id: &str) -> Result<Option<SessionChallenge>, sqlx::Error> {
+        let row: Option<(String, String, String, String, String)> = sqlx::query_as(
+            "DELETE FROM challenge_cache WHERE challenge_id = ? RETURNING challenge_id, address, nonce, created_at, expires_at",
+        )
+        .bind(challenge_id)
+        .fetch_optional(&self.pool)
+        .await?;
+
+        Ok(row.map(|(challenge_id, address, nonce, created_at, expires_at)| {
+            SessionChallenge {
+                challenge_id,
+                address,
+                nonce,
+                created_at: DateTime::parse_from_rfc3339(&created_at).unwrap().with_timezone(&Utc),
+                expires_at: DateTime::parse_from_rfc3339(&expires_at).unwrap().with_timezone(&Utc),
+            }
+        }))
+    }
+
+    /// Store a session under the hash of its bearer token; the token itself is never persisted.
+    pub async fn store_session(&self, token_hash: &str, session: &AuthSession) -> Result<(), sqlx::Error> {
+        sqlx::query(
+            "INSERT INTO sessions (token_hash, address, lock_hash, created_at, expires_at) VALUES (?, ?, ?, ?, ?)",
+        )
+        .bind(token_hash)
+        .bind(&session.address)
+        .bind(&session.lock_hash)
+        .bind(session.created_at.to_rfc3339())
+        .bind(session.expires_at.to_rfc3339())
+        .execute(&self.pool)
+        .await?;
+        Ok(())
+    }
+
+    pub async fn get_session(&self, token_hash: &str) -> Result<Option<AuthSession>, sqlx::Error> {
+        let row: Option<(String, String, String, String)> = sqlx::query_as(
+            "SELECT address, lock_hash, created_at, expires_at FROM sessions WHERE token_hash = ?",
+        )
+        .bind(token_hash)
+        .fetch_optional(&self.pool)
+        .await?;
+
+        Ok(row.map(|(address, lock_hash, created_at, expires_at)| {
+            AuthSession {
+                address,
+                lock_hash,
+                created_at: DateTime::parse_from_rfc3339(&created_at).unwrap().with_timezone(&Utc),
+                expires_at: DateTime::parse_from_rfc3339(&expires_at).unwrap().with_timezone(&Utc),
+            }
+        }))
+    }
+
+    pub async fn delete_session(&self, token_hash: &str) -> Result<(), sqlx::Error> {
+        sqlx::query("DELETE FROM sessions WHERE token_hash = ?")
+            .bind(token_hash)
+            .execute(&self.pool)
+            .await?;
+        Ok(())
+    }
+
+    /// Delete challenges and sessions that expired before `now`.
+    pub async fn cleanup_expired_sessions(&self, now: DateTime<Utc>) -> Result<u64, sqlx::Error> {
+        let challenges = sqlx::query("DELETE FROM challenge_cache WHERE expires_at < ?")
+            .bind(now.to_rfc3339())
+            .execute(&self.pool)
+            .await?;
+        let sessions = sqlx::query("DELETE FROM sessions WHERE expires_at < ?")
+            .bind(now.to_rfc3339())
+            .execute(&self.pool)
+            .await?;
+        Ok(challenges.rows_affected() + sessions.rows_affected())
+    }
+
     /// Return all event IDs from active_events.
     pub async fn list_all_event_ids(&self) -> Result<Vec<String>, sqlx::Error> {
         let rows: Vec<(String,)> = sqlx::query_as(
@@ -406,6 +2086,232 @@ impl Cache {
     }
 }
 
+fn event_from_row(
+    (
+        event_id,
+        metadata_json,
+        creator_address,
+        payment_tx_hash,
+        payment_block_number,
+        activated_at,
+        metadata_version,
+        cancelled_at,
+        state,
+        state_changed_at,
+    ): EventRow,
+) -> ActiveEvent {
+    ActiveEvent {
+        event_id,
+        metadata: serde_json::from_str(&metadata_json).unwrap(),
+        creator_address,
+        payment_tx_hash,
+        payment_block_number: payment_block_number as u64,
+        activated_at: DateTime::parse_from_rfc3339(&activated_at).unwrap().with_timezone(&Utc),
+        windows: Vec::new(),
+        metadata_version: metadata_version as u32,
+        cancelled_at: cancelled_at.map(|t| DateTime::parse_from_rfc3339(&t).unwrap().with_timezone(&Utc)),
+        state: EventState::parse(&state).unwrap(),
+        state_changed_at: DateTime::parse_from_rfc3339(&state_changed_at).unwrap().with_timezone(&Utc),
+    }
+}
+
+fn checkin_from_row(
+    (event_id, session_id, attendee_address, attendee_lock_hash, qr_timestamp, checked_in_at): CheckInRow,
+) -> CheckIn {
+    CheckIn {
+        event_id,
+        session_id,
+        attendee_address,
+        attendee_lock_hash,
+        qr_timestamp,
+        checked_in_at: DateTime::parse_from_rfc3339(&checked_in_at).unwrap().with_timezone(&Utc),
+    }
+}
+
+/// Rows with an unrecognized tier are skipped rather than failing the query.
+fn duration_proof_from_row(
+    (
+        event_id,
+        attendee_lock_hash,
+        attendee_address,
+        checkin_session_id,
+        checkin_timestamp,
+        checkout_session_id,
+        checkout_timestamp,
+        tier,
+        recorded_at,
+    ): DurationProofRow,
+) -> Option<DurationProof> {
+    Some(DurationProof {
+        event_id,
+        attendee_address,
+        attendee_lock_hash,
+        checkin_session_id,
+        checkin_timestamp,
+        checkout_session_id,
+        checkout_timestamp,
+        tier: AttendanceTier::parse(&tier)?,
+        recorded_at: DateTime::parse_from_rfc3339(&recorded_at).unwrap().with_timezone(&Utc),
+    })
+}
+
+fn airdrop_inclusion_from_row(
+    (
+        event_id,
+        holder_lock_hash,
+        holder_address,
+        batch_id,
+        tx_hash,
+        funder_address,
+        included_at,
+        confirmed_block,
+    ): AirdropInclusionRow,
+) -> AirdropInclusion {
+    AirdropInclusion {
+        event_id,
+        holder_address,
+        holder_lock_hash,
+        batch_id,
+        tx_hash,
+        funder_address,
+        included_at: DateTime::parse_from_rfc3339(&included_at).unwrap().with_timezone(&Utc),
+        confirmed_block: confirmed_block.map(|b| b as u64),
+    }
+}
+
+fn sponsor_deposit_from_row((event_id, tx_hash, capacity, block_number, recorded_at): SponsorDepositRow) -> SponsorDeposit {
+    SponsorDeposit {
+        event_id,
+        tx_hash,
+        capacity: capacity as u64,
+        block_number: block_number as u64,
+        recorded_at: DateTime::parse_from_rfc3339(&recorded_at).unwrap().with_timezone(&Utc),
+    }
+}
+
+fn sponsor_draw_from_row(
+    (event_id, holder_lock_hash, holder_address, tx_hash, capacity, fee, drawn_at): SponsorDrawRow,
+) -> SponsorDraw {
+    SponsorDraw {
+        event_id,
+        holder_address,
+        holder_lock_hash,
+        tx_hash,
+        capacity: capacity as u64,
+        fee: fee as u64,
+        drawn_at: DateTime::parse_from_rfc3339(&drawn_at).unwrap().with_timezone(&Utc),
+    }
+}
+
+/// Rows with an unrecognized role are skipped rather than failing the query.
+fn delegation_from_row(
+    (
+        event_id,
+        delegate_address,
+        delegate_lock_hash,
+        role,
+        expires_at,
+        version,
+        creator_signature,
+        created_at,
+        revoked_at,
+    ): DelegationRow,
+) -> Option<Delegation> {
+    Some(Delegation {
+        event_id,
+        delegate_address,
+        delegate_lock_hash,
+        role: DelegateRole::parse(&role)?,
+        expires_at,
+        version: version as u32,
+        creator_signature,
+        created_at: DateTime::parse_from_rfc3339(&created_at).unwrap().with_timezone(&Utc),
+        revoked_at: revoked_at.map(|t| DateTime::parse_from_rfc3339(&t).unwrap().with_timezone(&Utc)),
+    })
+}
+
+fn voucher_from_row(
+    (
+        voucher_hash,
+        event_id,
+        session_id,
+        qr_timestamp,
+        expires_at,
+        signature,
+        issued_at,
+        redeemed_by,
+        redeemed_at,
+    ): VoucherRow,
+) -> VoucherRecord {
+    VoucherRecord {
+        voucher_hash,
+        event_id,
+        session_id,
+        qr_timestamp,
+        expires_at,
+        signature,
+        issued_at: DateTime::parse_from_rfc3339(&issued_at).unwrap().with_timezone(&Utc),
+        redeemed_by,
+        redeemed_at: redeemed_at.map(|t| DateTime::parse_from_rfc3339(&t).unwrap().with_timezone(&Utc)),
+    }
+}
+
+/// Rows with an unrecognized status are skipped rather than failing the query.
+fn rsvp_from_row(
+    (event_id, attendee_address, attendee_lock_hash, status, attendee_signature, created_at): RsvpRow,
+) -> Option<Rsvp> {
+    Some(Rsvp {
+        event_id,
+        attendee_address,
+        attendee_lock_hash,
+        status: RsvpStatus::parse(&status)?,
+        attendee_signature,
+        created_at: DateTime::parse_from_rfc3339(&created_at).unwrap().with_timezone(&Utc),
+    })
+}
+
+/// Rows with an unrecognized role are skipped rather than failing the query.
+fn role_assignment_from_row(
+    (event_id, holder_address, holder_lock_hash, role, creator_signature, assigned_at): RoleAssignmentRow,
+) -> Option<RoleAssignment> {
+    Some(RoleAssignment {
+        event_id,
+        holder_address,
+        holder_lock_hash,
+        role: BadgeRole::parse(&role)?,
+        creator_signature,
+        assigned_at: DateTime::parse_from_rfc3339(&assigned_at).unwrap().with_timezone(&Utc),
+    })
+}
+
+fn badge_from_row(
+    (
+        event_id,
+        holder_address,
+        holder_lock_hash,
+        mint_tx_hash,
+        mint_block_number,
+        verified_at_block,
+        observed_at,
+        flagged_at,
+        flag_reason,
+        role,
+    ): BadgeRow,
+) -> BadgeObservation {
+    BadgeObservation {
+        event_id,
+        holder_address,
+        holder_lock_hash,
+        mint_tx_hash,
+        mint_block_number: mint_block_number as u64,
+        verified_at_block: verified_at_block as u64,
+        observed_at: DateTime::parse_from_rfc3339(&observed_at).unwrap().with_timezone(&Utc),
+        flagged_at: flagged_at.map(|t| DateTime::parse_from_rfc3339(&t).unwrap().with_timezone(&Utc)),
+        flag_reason,
+        role: BadgeRole::parse(&role).unwrap_or_default(),
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -423,6 +2329,9 @@ mod tests {
             location: Some("NYC".to_string()),
             start_time: None,
             end_time: None,
+            max_attendees: None,
+            prerequisite_events: vec![],
+            min_prerequisites: None,
         }
     }
 
@@ -507,7 +2416,11 @@ mod tests {
             payment_tx_hash: "0xtx".to_string(),
             payment_block_number: 200,
             activated_at: Utc::now(),
-            window: None,
+            metadata_version: 1,
+            cancelled_at: None,
+            state: EventState::Active,
+            state_changed_at: Utc::now(),
+            windows: vec![],
         };
 
         cache.store_active_event(&event).await.unwrap();
@@ -515,7 +2428,7 @@ mod tests {
         assert!(loaded.is_some());
         let loaded = loaded.unwrap();
         assert_eq!(loaded.metadata.name, "Test Event");
-        assert!(loaded.window.is_none());
+        assert!(loaded.windows.is_empty());
     }
 
     #[tokio::test]
@@ -530,7 +2443,11 @@ mod tests {
                 payment_tx_hash: format!("0xtx{}", i),
                 payment_block_number: 100 + i as u64,
                 activated_at: Utc::now(),
-                window: None,
+                metadata_version: 1,
+                cancelled_at: None,
+                state: EventState::Active,
+                state_changed_at: Utc::now(),
+                windows: vec![],
             };
             cache.store_active_event(&event).await.unwrap();
         }
@@ -540,7 +2457,7 @@ mod tests {
     }
 
     #[tokio::test]
-    async fn test_update_event_window() {
+    async fn test_store_windows_per_session() {
         let cache = test_cache().await;
         let event = ActiveEvent {
             event_id: "evt1".to_string(),
@@ -549,7 +2466,11 @@ mod tests {
             payment_tx_hash: "0xtx".to_string(),
             payment_block_number: 200,
             activated_at: Utc::now(),
-            window: None,
+            metadata_version: 1,
+            cancelled_at: None,
+            state: EventState::Active,
+            state_changed_at: Utc::now(),
+            windows: vec![],
         };
         cache.store_active_event(&event).await.unwrap();
 
@@ -559,30 +2480,292 @@ mod tests {
             window_end: None,
             creator_signature: "0xsig".to_string(),
             window_secret_commitment: "commit".to_string(),
+            secret_salt: None,
+            rotated_at: None,
+            closed_at: None,
+            authorized_by: None,
+            session_id: DEFAULT_SESSION_ID.to_string(),
+            label: None,
         };
-        cache.update_event_window("evt1", &window).await.unwrap();
+        cache.store_window(&window).await.unwrap();
+        let day2 = WindowProof {
+            session_id: "day2".to_string(),
+            label: Some("Day 2".to_string()),
+            window_start: window.window_start + 86400,
+            ..window.clone()
+        };
+        cache.store_window(&day2).await.unwrap();
+
+        let loaded = cache.get_active_event("evt1").await.unwrap().unwrap();
+        assert_eq!(loaded.windows.len(), 2);
+        assert_eq!(loaded.windows[0].creator_signature, "0xsig");
+        assert_eq!(loaded.window("day2").unwrap().label.as_deref(), Some("Day 2"));
+
+        // Re-submitting a session replaces only that session.
+        cache.store_window(&WindowProof { creator_signature: "0xnew".to_string(), ..window }).await.unwrap();
+        let listed = cache.list_active_events().await.unwrap();
+        assert_eq!(listed[0].windows.len(), 2);
+        assert_eq!(listed[0].window(DEFAULT_SESSION_ID).unwrap().creator_signature, "0xnew");
+    }
+
+    #[tokio::test]
+    async fn test_record_checkin_once_per_session() {
+        let cache = test_cache().await;
+        let checkin = CheckIn {
+            event_id: "evt1".to_string(),
+            session_id: "day1".to_string(),
+            attendee_address: "ckt1qattendee".to_string(),
+            attendee_lock_hash: "0xlock".to_string(),
+            qr_timestamp: 1000,
+            checked_in_at: Utc::now(),
+        };
+        assert_eq!(cache.record_checkin(&checkin, None).await.unwrap(), CheckInOutcome::Recorded);
+        assert_eq!(cache.record_checkin(&checkin, None).await.unwrap(), CheckInOutcome::AlreadyCheckedIn);
+        let day2 = CheckIn { session_id: "day2".to_string(), ..checkin };
+        assert_eq!(cache.record_checkin(&day2, None).await.unwrap(), CheckInOutcome::Recorded);
+
+        let checkins = cache.get_checkins("evt1").await.unwrap();
+        assert_eq!(checkins.len(), 2);
+        assert_eq!(checkins[1].session_id, "day2");
+    }
+
+    #[tokio::test]
+    async fn test_record_checkin_respects_capacity() {
+        let cache = test_cache().await;
+        let checkin = |holder: &str, session: &str| CheckIn {
+            event_id: "evt1".to_string(),
+            session_id: session.to_string(),
+            attendee_address: holder.to_string(),
+            attendee_lock_hash: format!("0xlock_{holder}"),
+            qr_timestamp: 1000,
+            checked_in_at: Utc::now(),
+        };
+        assert_eq!(cache.record_checkin(&checkin("a", "day1"), Some(2)).await.unwrap(), CheckInOutcome::Recorded);
+        assert_eq!(cache.record_checkin(&checkin("b", "day1"), Some(2)).await.unwrap(), CheckInOutcome::Recorded);
+        assert_eq!(cache.record_checkin(&checkin("c", "day1"), Some(2)).await.unwrap(), CheckInOutcome::AtCapacity);
+
+        // Attendees already counted may still check in to other sessions.
+        assert_eq!(cache.record_checkin(&checkin("a", "day2"), Some(2)).await.unwrap(), CheckInOutcome::Recorded);
+        assert_eq!(cache.record_checkin(&checkin("b", "day1"), Some(2)).await.unwrap(), CheckInOutcome::AlreadyCheckedIn);
+        assert_eq!(cache.count_attendees("evt1").await.unwrap(), 2);
+    }
+
+    #[tokio::test]
+    async fn test_redeem_voucher_checks_in_only_the_redeemer() {
+        let cache = test_cache().await;
+        let voucher = |hash: &str| VoucherRecord {
+            voucher_hash: hash.to_string(),
+            event_id: "evt1".to_string(),
+            session_id: "day1".to_string(),
+            qr_timestamp: 1000,
+            expires_at: 2000,
+            signature: "sig".to_string(),
+            issued_at: Utc::now(),
+            redeemed_by: None,
+            redeemed_at: None,
+        };
+        let checkin = |holder: &str| CheckIn {
+            event_id: "evt1".to_string(),
+            session_id: "day1".to_string(),
+            attendee_address: holder.to_string(),
+            attendee_lock_hash: format!("0xlock_{holder}"),
+            qr_timestamp: 1000,
+            checked_in_at: Utc::now(),
+        };
+        cache.store_voucher(&voucher("0xv1")).await.unwrap();
+        cache.store_voucher(&voucher("0xv2")).await.unwrap();
+
+        let outcome = cache.redeem_voucher("0xv1", &checkin("a"), Some(1)).await.unwrap();
+        assert_eq!(outcome, Some(CheckInOutcome::Recorded));
+        // A second redeemer of the same voucher takes no capacity slot.
+        assert_eq!(cache.redeem_voucher("0xv1", &checkin("b"), None).await.unwrap(), None);
+        assert_eq!(cache.count_attendees("evt1").await.unwrap(), 1);
+
+        // At capacity the voucher stays unredeemed.
+        let outcome = cache.redeem_voucher("0xv2", &checkin("c"), Some(1)).await.unwrap();
+        assert_eq!(outcome, Some(CheckInOutcome::AtCapacity));
+        assert!(cache.get_voucher("0xv2").await.unwrap().unwrap().redeemed_at.is_none());
+    }
+
+    #[tokio::test]
+    async fn test_migrate_window_json_to_default_session() {
+        let cache = test_cache().await;
+        sqlx::query("ALTER TABLE active_events ADD COLUMN window_json TEXT").execute(&cache.pool).await.unwrap();
+        let legacy_window = r#"{"event_id":"evt1","window_start":1000,"window_end":null,"creator_signature":"0xsig","window_secret_commitment":"c"}"#;
+        sqlx::query("INSERT INTO active_events (event_id, metadata_json, creator_address, payment_tx_hash, payment_block_number, activated_at, window_json, state_changed_at) VALUES ('evt1', ?, 'ckt1q', '0xtx', 1, ?, ?, ?)")
+            .bind(serde_json::to_string(&test_metadata()).unwrap())
+            .bind(Utc::now().to_rfc3339())
+            .bind(legacy_window)
+            .bind(Utc::now().to_rfc3339())
+            .execute(&cache.pool)
+            .await
+            .unwrap();
+
+        cache.init_schema().await.unwrap();
+
+        assert!(!cache.has_column("active_events", "window_json").await.unwrap());
+        let event = cache.get_active_event("evt1").await.unwrap().unwrap();
+        assert_eq!(event.windows.len(), 1);
+        assert_eq!(event.windows[0].session_id, DEFAULT_SESSION_ID);
+        assert_eq!(event.windows[0].window_start, 1000);
+    }
+
+    #[tokio::test]
+    async fn test_update_event_metadata_archives_previous_version() {
+        let cache = test_cache().await;
+        let event = ActiveEvent {
+            event_id: "evt1".to_string(),
+            metadata: test_metadata(),
+            creator_address: "ckt1q".to_string(),
+            payment_tx_hash: "0xtx".to_string(),
+            payment_block_number: 200,
+            activated_at: Utc::now(),
+            windows: vec![],
+            metadata_version: 1,
+            cancelled_at: None,
+            state: EventState::Active,
+            state_changed_at: Utc::now(),
+        };
+        cache.store_active_event(&event).await.unwrap();
+
+        let mut updated = test_metadata();
+        updated.name = "Renamed".to_string();
+        assert!(cache.update_event_metadata("evt1", 1, &updated, "0xsig").await.unwrap());
+        // A stale version is rejected.
+        assert!(!cache.update_event_metadata("evt1", 1, &updated, "0xsig").await.unwrap());
+
+        let loaded = cache.get_active_event("evt1").await.unwrap().unwrap();
+        assert_eq!(loaded.metadata.name, "Renamed");
+        assert_eq!(loaded.metadata_version, 2);
+
+        let history = cache.get_event_metadata_history("evt1").await.unwrap();
+        assert_eq!(history.len(), 1);
+        assert_eq!(history[0].version, 1);
+        assert_eq!(history[0].metadata.name, "Test Event");
+        assert_eq!(history[0].superseded_by_signature, "0xsig");
+    }
+
+    #[tokio::test]
+    async fn test_transition_event_state_is_conditional() {
+        let cache = test_cache().await;
+        let event = ActiveEvent {
+            event_id: "evt1".to_string(),
+            metadata: test_metadata(),
+            creator_address: "ckt1q".to_string(),
+            payment_tx_hash: "0xtx".to_string(),
+            payment_block_number: 200,
+            activated_at: Utc::now(),
+            windows: vec![],
+            metadata_version: 1,
+            cancelled_at: None,
+            state: EventState::Active,
+            state_changed_at: Utc::now(),
+        };
+        cache.store_active_event(&event).await.unwrap();
+
+        let now = Utc::now();
+        assert!(cache.transition_event_state("evt1", EventState::Active, EventState::Cancelled, now).await.unwrap());
+        // The event has left Active, so a second attempt is a no-op.
+        assert!(!cache.transition_event_state("evt1", EventState::Active, EventState::Cancelled, now).await.unwrap());
 
         let loaded = cache.get_active_event("evt1").await.unwrap().unwrap();
-        assert!(loaded.window.is_some());
-        assert_eq!(loaded.window.unwrap().creator_signature, "0xsig");
+        assert_eq!(loaded.state, EventState::Cancelled);
+        assert!(loaded.cancelled_at.is_some());
+
+        let transitions = cache.get_event_transitions("evt1").await.unwrap();
+        assert_eq!(transitions.len(), 1);
+        assert_eq!(transitions[0].from_state, Some(EventState::Active));
+        assert_eq!(transitions[0].to_state, EventState::Cancelled);
+    }
+
+    #[tokio::test]
+    async fn test_migrate_event_states_from_legacy_columns() {
+        let cache = test_cache().await;
+
+        sqlx::query("DROP TABLE active_events").execute(&cache.pool).await.unwrap();
+        sqlx::query(
+            "CREATE TABLE active_events (event_id TEXT PRIMARY KEY, metadata_json TEXT NOT NULL, creator_address TEXT NOT NULL, payment_tx_hash TEXT NOT NULL, payment_block_number INTEGER NOT NULL, activated_at TEXT NOT NULL, window_json TEXT, metadata_version INTEGER NOT NULL DEFAULT 1, cancelled_at TEXT)",
+        )
+        .execute(&cache.pool)
+        .await
+        .unwrap();
+        let metadata = serde_json::to_string(&test_metadata()).unwrap();
+        let activated = Utc::now().to_rfc3339();
+        for (id, tx, cancelled) in [("unpaid", "", None), ("paid", "0xtx", None), ("gone", "0xtx", Some(activated.clone()))] {
+            sqlx::query("INSERT INTO active_events VALUES (?, ?, 'ckt1q', ?, 0, ?, NULL, 1, ?)")
+                .bind(id)
+                .bind(&metadata)
+                .bind(tx)
+                .bind(&activated)
+                .bind(cancelled)
+                .execute(&cache.pool)
+                .await
+                .unwrap();
+        }
+
+        cache.init_schema().await.unwrap();
+
+        for (id, expected) in [
+            ("unpaid", EventState::PendingPayment),
+            ("paid", EventState::Active),
+            ("gone", EventState::Cancelled),
+        ] {
+            let event = cache.get_active_event(id).await.unwrap().unwrap();
+            assert_eq!(event.state, expected);
+            assert_eq!(event.state_changed_at.to_rfc3339(), activated);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_allowlist_permits_only_listed_locks() {
+        let cache = test_cache().await;
+        assert!(cache.allowlist_permits("evt1", "0xanyone").await.unwrap());
+
+        let allowlist = Allowlist {
+            event_id: "evt1".to_string(),
+            merkle_root: "0xroot".to_string(),
+            entry_count: 2,
+            version: 1,
+            creator_signature: "0xsig".to_string(),
+            updated_at: Utc::now(),
+        };
+        cache.store_allowlist(&allowlist, &["0xa".to_string(), "0xb".to_string()]).await.unwrap();
+        assert!(cache.allowlist_permits("evt1", "0xa").await.unwrap());
+        assert!(!cache.allowlist_permits("evt1", "0xanyone").await.unwrap());
+        assert!(cache.allowlist_permits("evt2", "0xanyone").await.unwrap());
+
+        // Replacing the list drops entries that are no longer on it.
+        let replaced = Allowlist { entry_count: 1, version: 2, ..allowlist.clone() };
+        assert!(cache.store_allowlist(&replaced, &["0xb".to_string()]).await.unwrap());
+        assert!(!cache.allowlist_permits("evt1", "0xa").await.unwrap());
+        assert_eq!(cache.get_allowlist("evt1").await.unwrap().unwrap().entry_count, 1);
+
+        // A superseded version is not restored, entries included.
+        assert!(!cache.store_allowlist(&allowlist, &["0xa".to_string(), "0xb".to_string()]).await.unwrap());
+        assert!(!cache.allowlist_permits("evt1", "0xa").await.unwrap());
+        assert_eq!(cache.get_allowlist("evt1").await.unwrap().unwrap().version, 2);
     }
 
     // --- Badge Observations ---
 
     #[tokio::test]
-    async fn test_store_and_get_badges_by_address() {
+    async fn test_store_and_get_badges_by_lock_hash() {
         let cache = test_cache().await;
         let badge = BadgeObservation {
             event_id: "evt1".to_string(),
             holder_address: "ckt1qholder".to_string(),
+            holder_lock_hash: "0xlock_ckt1qholder".to_string(),
             mint_tx_hash: "0xminttx".to_string(),
             mint_block_number: 300,
             verified_at_block: 301,
             observed_at: Utc::now(),
+            flagged_at: None,
+            flag_reason: None,
+            role: BadgeRole::Attendee,
         };
 
         cache.store_badge_observation(&badge).await.unwrap();
-        let badges = cache.get_badges_by_address("ckt1qholder").await.unwrap();
+        let badges = cache.get_badges_by_lock_hash("0xlock_ckt1qholder").await.unwrap();
         assert_eq!(badges.len(), 1);
         assert_eq!(badges[0].mint_block_number, 300);
     }
@@ -594,10 +2777,14 @@ mod tests {
             let badge = BadgeObservation {
                 event_id: "evt1".to_string(),
                 holder_address: format!("addr{}", i),
+                holder_lock_hash: format!("0xlock_addr{}", i),
                 mint_tx_hash: format!("0xtx{}", i),
                 mint_block_number: 300 + i as u64,
                 verified_at_block: 301,
                 observed_at: Utc::now(),
+                flagged_at: None,
+                flag_reason: None,
+                role: BadgeRole::Attendee,
             };
             cache.store_badge_observation(&badge).await.unwrap();
         }
@@ -612,10 +2799,14 @@ mod tests {
         let badge = BadgeObservation {
             event_id: "evt1".to_string(),
             holder_address: "addr1".to_string(),
+            holder_lock_hash: "0xlock_addr1".to_string(),
             mint_tx_hash: "0xtx1".to_string(),
             mint_block_number: 300,
             verified_at_block: 301,
             observed_at: Utc::now(),
+            flagged_at: None,
+            flag_reason: None,
+            role: BadgeRole::Attendee,
         };
         cache.store_badge_observation(&badge).await.unwrap();
 
@@ -623,10 +2814,14 @@ mod tests {
         let badge2 = BadgeObservation {
             event_id: "evt1".to_string(),
             holder_address: "addr1".to_string(),
+            holder_lock_hash: "0xlock_addr1".to_string(),
             mint_tx_hash: "0xtx2_updated".to_string(),
             mint_block_number: 305,
             verified_at_block: 306,
             observed_at: Utc::now(),
+            flagged_at: None,
+            flag_reason: None,
+            role: BadgeRole::Attendee,
         };
         cache.store_badge_observation(&badge2).await.unwrap();
 
@@ -635,6 +2830,119 @@ mod tests {
         assert_eq!(badges[0].mint_tx_hash, "0xtx2_updated");
     }
 
+    #[tokio::test]
+    async fn test_badge_uniqueness_by_lock_hash_across_address_forms() {
+        let cache = test_cache().await;
+        for (addr, tx) in [("ckt1qshort", "0xtx1"), ("ckt1qfull", "0xtx2")] {
+            let badge = BadgeObservation {
+                event_id: "evt1".to_string(),
+                holder_address: addr.to_string(),
+                holder_lock_hash: "0xsamelock".to_string(),
+                mint_tx_hash: tx.to_string(),
+                mint_block_number: 300,
+                verified_at_block: 301,
+                observed_at: Utc::now(),
+                flagged_at: None,
+                flag_reason: None,
+                role: BadgeRole::Attendee,
+            };
+            cache.store_badge_observation(&badge).await.unwrap();
+        }
+
+        let badges = cache.get_badges_by_lock_hash("0xsamelock").await.unwrap();
+        assert_eq!(badges.len(), 1);
+        assert_eq!(badges[0].holder_address, "ckt1qfull");
+    }
+
+    #[tokio::test]
+    async fn test_flag_badges_for_session_window() {
+        let cache = test_cache().await;
+        for (holder, session, ts) in [("a", "talk", 1_000), ("b", "talk", 2_000), ("c", "other", 1_000)] {
+            let lock_hash = format!("0xlock_{holder}");
+            cache
+                .record_checkin(
+                    &CheckIn {
+                        event_id: "evt1".to_string(),
+                        session_id: session.to_string(),
+                        attendee_address: holder.to_string(),
+                        attendee_lock_hash: lock_hash.clone(),
+                        qr_timestamp: ts,
+                        checked_in_at: Utc::now(),
+                    },
+                    None,
+                )
+                .await
+                .unwrap();
+            cache
+                .store_badge_observation(&BadgeObservation {
+                    event_id: "evt1".to_string(),
+                    holder_address: holder.to_string(),
+                    holder_lock_hash: lock_hash,
+                    mint_tx_hash: format!("0xtx_{holder}"),
+                    mint_block_number: 0,
+                    verified_at_block: 0,
+                    observed_at: Utc::now(),
+                    flagged_at: None,
+                    flag_reason: None,
+                    role: BadgeRole::Attendee,
+                })
+                .await
+                .unwrap();
+        }
+
+        let flagged = cache.flag_badges_for_session("evt1", "talk", 500, 1_500, "leaked").await.unwrap();
+        assert_eq!(flagged, 1);
+        // Already-flagged badges are not counted again.
+        assert_eq!(cache.flag_badges_for_session("evt1", "talk", 500, 1_500, "again").await.unwrap(), 0);
+
+        let badges = cache.get_badges_by_lock_hash("0xlock_a").await.unwrap();
+        assert_eq!(badges[0].flag_reason.as_deref(), Some("leaked"));
+        assert!(badges[0].flagged_at.is_some());
+        assert!(cache.get_badges_by_lock_hash("0xlock_b").await.unwrap()[0].flagged_at.is_none());
+        assert!(cache.get_badges_by_lock_hash("0xlock_c").await.unwrap()[0].flagged_at.is_none());
+
+        // Confirming the badge later keeps the flag.
+        let mut confirmed = badges[0].clone();
+        confirmed.mint_block_number = 500;
+        confirmed.flagged_at = None;
+        confirmed.flag_reason = None;
+        cache.store_badge_observation(&confirmed).await.unwrap();
+        let badges = cache.get_badges_by_lock_hash("0xlock_a").await.unwrap();
+        assert_eq!(badges[0].mint_block_number, 500);
+        assert_eq!(badges[0].flag_reason.as_deref(), Some("leaked"));
+    }
+
+    #[tokio::test]
+    async fn test_migrate_legacy_badge_table() {
+        let cache = test_cache().await;
+        let address = "ckb1qzda0cr08m85hc8jlnfp3zer7xulejywt49kt2rr0vthywaa50xwsqdnnw7qkdnnclfkg59uzn8umtfd2kwxceqxwquc4";
+
+        sqlx::query("DROP TABLE badge_observations").execute(&cache.pool).await.unwrap();
+        sqlx::query(
+            "CREATE TABLE badge_observations (event_id TEXT NOT NULL, holder_address TEXT NOT NULL, mint_tx_hash TEXT NOT NULL, mint_block_number INTEGER NOT NULL, verified_at_block INTEGER NOT NULL, observed_at TEXT NOT NULL, PRIMARY KEY (event_id, holder_address))",
+        )
+        .execute(&cache.pool)
+        .await
+        .unwrap();
+        for (addr, tx) in [(address, "0xtx1"), ("legacy_garbage", "0xtx2")] {
+            sqlx::query("INSERT INTO badge_observations VALUES ('evt1', ?, ?, 10, 10, ?)")
+                .bind(addr)
+                .bind(tx)
+                .bind(Utc::now().to_rfc3339())
+                .execute(&cache.pool)
+                .await
+                .unwrap();
+        }
+
+        cache.init_schema().await.unwrap();
+
+        let lock_hash = signatures::address_to_lock_hash(address).unwrap();
+        let badges = cache.get_badges_by_lock_hash(&lock_hash).await.unwrap();
+        assert_eq!(badges.len(), 1);
+        assert_eq!(badges[0].holder_address, address);
+        assert_eq!(cache.get_badges_by_event("evt1").await.unwrap().len(), 2);
+    }
+
     // --- QR Replay ---
 
     #[tokio::test]
@@ -643,31 +2951,86 @@ mod tests {
         let event_id = "evt1";
         let timestamp = 1700000000i64;
 
-        assert!(!cache.check_qr_replay(event_id, timestamp).await.unwrap());
+        assert!(!cache.check_qr_replay(event_id, "s1", timestamp).await.unwrap());
 
-        cache.record_qr_usage(event_id, timestamp).await.unwrap();
+        cache.record_qr_usage(event_id, "s1", timestamp).await.unwrap();
 
-        assert!(cache.check_qr_replay(event_id, timestamp).await.unwrap());
+        assert!(cache.check_qr_replay(event_id, "s1", timestamp).await.unwrap());
     }
 
     #[tokio::test]
     async fn test_qr_replay_different_timestamps_are_independent() {
         let cache = test_cache().await;
-        cache.record_qr_usage("evt1", 1000).await.unwrap();
-        assert!(cache.check_qr_replay("evt1", 1000).await.unwrap());
-        assert!(!cache.check_qr_replay("evt1", 1001).await.unwrap());
+        cache.record_qr_usage("evt1", "s1", 1000).await.unwrap();
+        assert!(cache.check_qr_replay("evt1", "s1", 1000).await.unwrap());
+        assert!(!cache.check_qr_replay("evt1", "s1", 1001).await.unwrap());
+        assert!(!cache.check_qr_replay("evt1", "s2", 1000).await.unwrap());
     }
 
     #[tokio::test]
     async fn test_cleanup_expired_replay_log() {
         let cache = test_cache().await;
-        cache.record_qr_usage("evt1", 1000).await.unwrap();
-        cache.record_qr_usage("evt1", 2000).await.unwrap();
+        cache.record_qr_usage("evt1", "s1", 1000).await.unwrap();
+        cache.record_qr_usage("evt1", "s1", 2000).await.unwrap();
+
+        let deleted = cache.cleanup_expired_replay_log(1500).await.unwrap();
+        assert_eq!(deleted, 1);
+
+        assert!(!cache.check_qr_replay("evt1", "s1", 1000).await.unwrap());
+        assert!(cache.check_qr_replay("evt1", "s1", 2000).await.unwrap());
+    }
+
+    // --- Sessions ---
+
+    #[tokio::test]
+    async fn test_take_challenge_is_single_use() {
+        let cache = test_cache().await;
+        let challenge = SessionChallenge {
+            challenge_id: "c1".to_string(),
+            address: "ckt1qaddr".to_string(),
+            nonce: "n1".to_string(),
+            created_at: Utc::now(),
+            expires_at: Utc::now() + chrono::Duration::minutes(5),
+        };
+        cache.store_challenge(&challenge).await.unwrap();
+
+        let taken = cache.take_challenge("c1").await.unwrap().unwrap();
+        assert_eq!(taken.nonce, "n1");
+        assert!(cache.take_challenge("c1").await.unwrap().is_none());
+    }
+
+    #[tokio::test]
+    async fn test_store_get_delete_session() {
+        let cache = test_cache().await;
+        let session = AuthSession {
+            address: "ckt1qaddr".to_string(),
+            lock_hash: "0xlock".to_string(),
+            created_at: Utc::now(),
+            expires_at: Utc::now() + chrono::Duration::hours(1),
+        };
+        cache.store_session("tokenhash", &session).await.unwrap();
+
+        let loaded = cache.get_session("tokenhash").await.unwrap().unwrap();
+        assert_eq!(loaded.lock_hash, "0xlock");
+
+        cache.delete_session("tokenhash").await.unwrap();
+        assert!(cache.get_session("tokenhash").await.unwrap().is_none());
+    }
 
-        let deleted = cache.cleanup_expired_replay_log(Utc::now() + chrono::Duration::hours(1)).await.unwrap();
-        assert_eq!(deleted, 2);
+    #[tokio::test]
+    async fn test_cleanup_expired_sessions() {
+        let cache = test_cache().await;
+        let expired = AuthSession {
+            address: "ckt1qaddr".to_string(),
+            lock_hash: "0xlock".to_string(),
+            created_at: Utc::now() - chrono::Duration::hours(2),
+            expires_at: Utc::now() - chrono::Duration::hours(1),
+        };
+        cache.store_session("old", &expired).await.unwrap();
 
-        assert!(!cache.check_qr_replay("evt1", 1000).await.unwrap());
+        let deleted = cache.cleanup_expired_sessions(Utc::now()).await.unwrap();
+        assert_eq!(deleted, 1);
+        assert!(cache.get_session("old").await.unwrap().is_none());
     }
 
     // --- Prefix Lookup ---
@@ -682,7 +3045,11 @@ mod tests {
             payment_tx_hash: "0xtx".to_string(),
             payment_block_number: 200,
             activated_at: Utc::now(),
-            window: None,
+            metadata_version: 1,
+            cancelled_at: None,
+            state: EventState::Active,
+            state_changed_at: Utc::now(),
+            windows: vec![],
         };
         cache.store_active_event(&event).await.unwrap();
 
@@ -701,7 +3068,11 @@ mod tests {
             payment_tx_hash: "0xtx".to_string(),
             payment_block_number: 200,
             activated_at: Utc::now(),
-            window: None,
+            metadata_version: 1,
+            cancelled_at: None,
+            state: EventState::Active,
+            state_changed_at: Utc::now(),
+            windows: vec![],
         };
         cache.store_active_event(&event).await.unwrap();
 
@@ -718,19 +3089,27 @@ mod tests {
         let pending = BadgeObservation {
             event_id: "evt1".to_string(),
             holder_address: "addr_pending".to_string(),
+            holder_lock_hash: "0xlock_addr_pending".to_string(),
             mint_tx_hash: "0xtx_pending".to_string(),
             mint_block_number: 0,
             verified_at_block: 0,
             observed_at: Utc::now(),
+            flagged_at: None,
+            flag_reason: None,
+            role: BadgeRole::Attendee,
         };
         // Confirmed badge (block_number > 0)
         let confirmed = BadgeObservation {
             event_id: "evt1".to_string(),
             holder_address: "addr_confirmed".to_string(),
+            holder_lock_hash: "0xlock_addr_confirmed".to_string(),
             mint_tx_hash: "0xtx_confirmed".to_string(),
             mint_block_number: 500,
             verified_at_block: 501,
             observed_at: Utc::now(),
+            flagged_at: None,
+            flag_reason: None,
+            role: BadgeRole::Attendee,
         };
         cache.store_badge_observation(&pending).await.unwrap();
         cache.store_badge_observation(&confirmed).await.unwrap();
@@ -746,10 +3125,14 @@ mod tests {
         let badge = BadgeObservation {
             event_id: "evt1".to_string(),
             holder_address: "addr1".to_string(),
+            holder_lock_hash: "0xlock_addr1".to_string(),
             mint_tx_hash: "0xtx1".to_string(),
             mint_block_number: 0,
             verified_at_block: 0,
             observed_at: Utc::now(),
+            flagged_at: None,
+            flag_reason: None,
+            role: BadgeRole::Attendee,
         };
         cache.store_badge_observation(&badge).await.unwrap();
 
@@ -776,7 +3159,11 @@ mod tests {
                 payment_tx_hash: "0xtx".to_string(),
                 payment_block_number: 200,
                 activated_at: Utc::now(),
-                window: None,
+                metadata_version: 1,
+                cancelled_at: None,
+                state: EventState::Active,
+                state_changed_at: Utc::now(),
+                windows: vec![],
             };
             cache.store_active_event(&event).await.unwrap();
         }
@@ -0,0 +1,71 @@
+use sha2::{Digest, Sha256};
+
+/// Merkle root over a set of lock hashes, `0x`-prefixed.
+///
+/// Leaves are `SHA256(lock_hash bytes)`, sorted and deduplicated so the root
+/// depends only on the set. Each parent is `SHA256(min || max)` of its two
+/// children, so membership proofs need no left/right flags; an odd node at
+/// the end of a level is carried up unchanged. Returns `None` for an empty
+/// set or for entries that are not 32-byte hex hashes.
+pub fn allowlist_root(lock_hashes: &[String]) -> Option<String> {
+    let mut level: Vec<[u8; 32]> = lock_hashes
+        .iter()
+        .map(|hash| {
+            let bytes = hex::decode(hash.strip_prefix("0x").unwrap_or(hash)).ok()?;
+            (bytes.len() == 32).then(|| Sha256::digest(&bytes).into())
+        })
+        .collect::<Option<_>>()?;
+    level.sort_unstable();
+    level.dedup();
+
+    while level.len() > 1 {
+        level = level
+            .chunks(2)
+            .map(|pair| match pair {
+                [a, b] => hash_pair(a, b),
+                [single] => *single,
+                _ => unreachable!("chunks(2) yields one or two items"),
+            })
+            .collect();
+    }
+
+    level.first().map(|root| format!("0x{}", hex::encode(root)))
+}
+
+fn hash_pair(a: &[u8; 32], b: &[u8; 32]) -> [u8; 32] {
+    let (lo, hi) = if a <= b { (a, b) } else { (b, a) };
+    let mut hasher = Sha256::new();
+    hasher.update(lo);
+    hasher.update(hi);
+    hasher.finalize().into()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn lock_hash(byte: u8) -> String {
+        format!("0x{}", hex::encode([byte; 32]))
+    }
+
+    #[test]
+    fn test_allowlist_root_ignores_order_and_duplicates() {
+        let a = vec![lock_hash(1), lock_hash(2), lock_hash(3)];
+        let b = vec![lock_hash(3), lock_hash(1), lock_hash(2), lock_hash(1)];
+        assert_eq!(allowlist_root(&a), allowlist_root(&b));
+        assert_ne!(allowlist_root(&a), allowlist_root(&a[..2]));
+    }
+
+    #[test]
+    fn test_allowlist_root_single_leaf() {
+        let root = allowlist_root(&[lock_hash(7)]).unwrap();
+        assert_eq!(root, format!("0x{}", hex::encode(Sha256::digest([7u8; 32]))));
+    }
+
+    #[test]
+    fn test_allowlist_root_rejects_empty_and_malformed() {
+        assert!(allowlist_root(&[]).is_none());
+        assert!(allowlist_root(&["0x1234".to_string()]).is_none());
+        assert!(allowlist_root(&["not hex".to_string()]).is_none());
+    }
+}
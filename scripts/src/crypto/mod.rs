@@ -1,2 +1,4 @@
+pub mod merkle;
 pub mod qr;
 pub mod signatures;
+pub mod sponsor;
@@ -2,12 +2,19 @@ use chrono::Utc;
 use hmac::{Hmac, Mac};
 use sha2::Sha256;
 
-use crate::types::QrPayload;
+use crate::types::{QrPayload, WindowProof, DEFAULT_SESSION_ID};
 
 type HmacSha256 = Hmac<Sha256>;
 
 const QR_TTL_SECONDS: i64 = 30;
 
+/// Longest offline grace period an organizer may set.
+pub const MAX_OFFLINE_GRACE_SECS: i64 = 7 * 24 * 3600;
+
+/// How long after its QR timestamp a used frame must stay in the replay log.
+/// Past this no proof carrying the frame, online or offline, is accepted.
+pub const REPLAY_LOG_RETENTION_SECS: i64 = MAX_OFFLINE_GRACE_SECS + QR_TTL_SECONDS * 2;
+
 pub fn derive_window_secret(event_id: &str, window_start: i64, creator_sig: &str) -> [u8; 32] {
     use sha2::Digest;
     let mut hasher = Sha256::new();
@@ -17,6 +24,35 @@ pub fn derive_window_secret(event_id: &str, window_start: i64, creator_sig: &str
     hasher.finalize().into()
 }
 
+/// Secret for one window session. Default-session windows keep the original
+/// derivation so QR codes issued before sessions existed still verify. A
+/// rotation salt, once set, changes the secret without moving the window.
+pub fn window_secret(window: &WindowProof) -> [u8; 32] {
+    let seed = match &window.secret_salt {
+        Some(salt) => format!("{}|{}", window.creator_signature, salt),
+        None => window.creator_signature.clone(),
+    };
+    if window.session_id == DEFAULT_SESSION_ID {
+        return derive_window_secret(&window.event_id, window.window_start, &seed);
+    }
+    let scoped_id = format!("{}|{}", window.event_id, window.session_id);
+    derive_window_secret(&scoped_id, window.window_start, &seed)
+}
+
+/// Replace the window's secret with a fresh random one effective at `now`,
+/// updating the public commitment to match.
+pub fn rotate_window_secret(window: &mut WindowProof, now: i64) {
+    window.secret_salt = Some(hex::encode(rand::random::<[u8; 16]>()));
+    window.rotated_at = Some(now);
+    window.window_secret_commitment = secret_commitment(&window_secret(window));
+}
+
+/// Public commitment to a window secret.
+pub fn secret_commitment(secret: &[u8; 32]) -> String {
+    use sha2::Digest;
+    hex::encode(Sha256::digest(secret))
+}
+
 pub fn generate_qr_hmac(window_secret: &[u8; 32], timestamp: i64) -> String {
     let mut mac = HmacSha256::new_from_slice(window_secret).expect("HMAC accepts any key size");
     mac.update(&timestamp.to_le_bytes());
@@ -28,13 +64,22 @@ pub fn verify_qr_hmac(window_secret: &[u8; 32], timestamp: i64, hmac_value: &str
     expected == hmac_value
 }
 
-pub fn generate_qr_payload(event_id: &str, window_secret: &[u8; 32]) -> QrPayload {
+/// Sign a claim voucher with the window secret. Rotating the secret after a
+/// leak therefore also voids vouchers issued under it.
+pub fn sign_voucher(window_secret: &[u8; 32], message: &str) -> String {
+    let mut mac = HmacSha256::new_from_slice(window_secret).expect("HMAC accepts any key size");
+    mac.update(message.as_bytes());
+    hex::encode(mac.finalize().into_bytes())
+}
+
+pub fn generate_qr_payload(window: &WindowProof) -> QrPayload {
     let timestamp = Utc::now().timestamp();
-    let hmac = generate_qr_hmac(window_secret, timestamp);
+    let hmac = generate_qr_hmac(&window_secret(window), timestamp);
     QrPayload {
-        event_id: event_id.to_string(),
+        event_id: window.event_id.clone(),
         timestamp,
         hmac,
+        session_id: Some(window.session_id.clone()),
     }
 }
 
@@ -55,6 +100,30 @@ pub fn validate_qr_freshness(qr_timestamp: i64, window_start: i64, window_end: O
     (0..=QR_TTL_SECONDS * 2).contains(&age)
 }
 
+/// Validate a QR scanned offline and submitted later. The device's signed
+/// scan time stands in for the server clock: the QR must have been fresh when
+/// scanned and inside the window, and the proof must arrive within
+/// `grace_secs` of the QR timestamp.
+pub fn validate_offline_qr(
+    qr_timestamp: i64,
+    scanned_at: i64,
+    window_start: i64,
+    window_end: Option<i64>,
+    grace_secs: i64,
+) -> bool {
+    if qr_timestamp < window_start || window_end.is_some_and(|end| qr_timestamp > end) {
+        return false;
+    }
+
+    let scan_age = scanned_at - qr_timestamp;
+    if !(0..=QR_TTL_SECONDS * 2).contains(&scan_age) {
+        return false;
+    }
+
+    let now = Utc::now().timestamp();
+    scanned_at <= now && now - qr_timestamp <= grace_secs
+}
+
 pub fn qr_ttl_seconds() -> u32 {
     QR_TTL_SECONDS as u32
 }
@@ -144,14 +213,40 @@ mod tests {
         assert!(!verify_qr_hmac(&s2, 1700000050, &hmac));
     }
 
+    fn test_window(session_id: &str) -> WindowProof {
+        WindowProof {
+            event_id: "EVT001".to_string(),
+            session_id: session_id.to_string(),
+            label: None,
+            window_start: 1700000000,
+            window_end: None,
+            creator_signature: "sig".to_string(),
+            window_secret_commitment: "commit".to_string(),
+            secret_salt: None,
+            rotated_at: None,
+            closed_at: None,
+            authorized_by: None,
+        }
+    }
+
     #[test]
     fn test_generate_qr_payload_has_valid_hmac() {
-        let secret = derive_window_secret("EVT001", 1700000000, "sig");
-        let payload = generate_qr_payload("EVT001", &secret);
+        let window = test_window(DEFAULT_SESSION_ID);
+        let payload = generate_qr_payload(&window);
         assert_eq!(payload.event_id, "EVT001");
+        assert_eq!(payload.session_id.as_deref(), Some(DEFAULT_SESSION_ID));
+        let secret = derive_window_secret("EVT001", 1700000000, "sig");
         assert!(verify_qr_hmac(&secret, payload.timestamp, &payload.hmac));
     }
 
+    #[test]
+    fn test_window_secret_differs_by_session() {
+        let default = window_secret(&test_window(DEFAULT_SESSION_ID));
+        assert_eq!(default, derive_window_secret("EVT001", 1700000000, "sig"));
+        assert_ne!(default, window_secret(&test_window("day2")));
+        assert_ne!(window_secret(&test_window("day2")), window_secret(&test_window("day3")));
+    }
+
     #[test]
     fn test_validate_qr_freshness_valid() {
         let now = Utc::now().timestamp();
@@ -194,4 +289,49 @@ mod tests {
     fn test_qr_ttl_seconds_is_30() {
         assert_eq!(qr_ttl_seconds(), 30);
     }
+
+    #[test]
+    fn test_rotate_window_secret_invalidates_old_hmac() {
+        let mut window = WindowProof {
+            event_id: "EVT001".to_string(),
+            session_id: DEFAULT_SESSION_ID.to_string(),
+            label: None,
+            window_start: 1700000000,
+            window_end: None,
+            creator_signature: "sig_abc".to_string(),
+            window_secret_commitment: String::new(),
+            secret_salt: None,
+            rotated_at: None,
+            closed_at: None,
+            authorized_by: None,
+        };
+        let old_secret = window_secret(&window);
+        let old_hmac = generate_qr_hmac(&old_secret, 1700000100);
+
+        rotate_window_secret(&mut window, 1700000200);
+        let new_secret = window_secret(&window);
+        assert_ne!(old_secret, new_secret);
+        assert!(!verify_qr_hmac(&new_secret, 1700000100, &old_hmac));
+        assert_eq!(window.rotated_at, Some(1700000200));
+        assert_eq!(window.window_start, 1700000000);
+        assert_eq!(window.window_secret_commitment, secret_commitment(&new_secret));
+    }
+
+    #[test]
+    fn test_validate_offline_qr() {
+        let now = Utc::now().timestamp();
+        let qr_ts = now - 3 * 3600;
+        let window_start = qr_ts - 600;
+        let window_end = Some(qr_ts + 600);
+
+        assert!(validate_offline_qr(qr_ts, qr_ts + 10, window_start, window_end, 6 * 3600));
+        // Past the grace period.
+        assert!(!validate_offline_qr(qr_ts, qr_ts + 10, window_start, window_end, 3600));
+        // Scanned long after the QR was displayed.
+        assert!(!validate_offline_qr(qr_ts, qr_ts + 600, window_start, window_end, 6 * 3600));
+        // QR from outside the window.
+        assert!(!validate_offline_qr(qr_ts, qr_ts + 10, qr_ts + 1, None, 6 * 3600));
+        // Scan time in the future.
+        assert!(!validate_offline_qr(now - 5, now + 30, now - 60, None, 6 * 3600));
+    }
 }
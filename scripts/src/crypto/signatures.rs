@@ -1,11 +1,11 @@
-use bech32::FromBase32;
-use secp256k1::{ecdsa, Message, Secp256k1};
+use bech32::{FromBase32, ToBase32};
+use secp256k1::{ecdsa, Message, PublicKey, Secp256k1, SecretKey};
 
 const CKB_HASH_PERSONALIZATION: &[u8] = b"ckb-default-hash";
 const NERVOS_MESSAGE_PREFIX: &str = "Nervos Message:";
 
 // secp256k1-blake160-sighash-all code_hash (mainnet & testnet, hash_type: type)
-const SECP256K1_BLAKE160_CODE_HASH: [u8; 32] = [
+pub(crate) const SECP256K1_BLAKE160_CODE_HASH: [u8; 32] = [
     0x9b, 0xd7, 0xe0, 0x6f, 0x3e, 0xcf, 0x4b, 0xe0, 0xf2, 0xfc, 0xd2, 0x18, 0x8b, 0x23, 0xf1,
     0xb9, 0xfc, 0xc8, 0x8e, 0x5d, 0x4b, 0x65, 0xa8, 0x63, 0x7b, 0x17, 0x72, 0x3b, 0xbd, 0xa3,
     0xcc, 0xe8,
@@ -20,7 +20,7 @@ pub fn hash_message_ckb(message: &str) -> [u8; 32] {
 }
 
 /// CKB-standard blake2b (32-byte output, "ckb-default-hash" personalization).
-fn ckb_blake2b(data: &[u8]) -> [u8; 32] {
+pub(crate) fn ckb_blake2b(data: &[u8]) -> [u8; 32] {
     let mut hasher = blake2b_rs::Blake2bBuilder::new(32)
         .personal(CKB_HASH_PERSONALIZATION)
         .build();
@@ -31,13 +31,81 @@ fn ckb_blake2b(data: &[u8]) -> [u8; 32] {
 }
 
 /// First 20 bytes of CKB blake2b — derives lock script args from a compressed public key.
-fn blake160(data: &[u8]) -> [u8; 20] {
+pub(crate) fn blake160(data: &[u8]) -> [u8; 20] {
     let hash = ckb_blake2b(data);
     let mut out = [0u8; 20];
     out.copy_from_slice(&hash[..20]);
     out
 }
 
+/// CKB script hash: blake2b over the molecule-encoded `Script { code_hash, hash_type, args }`.
+pub fn compute_script_hash(code_hash: &[u8; 32], hash_type: u8, args: &[u8]) -> [u8; 32] {
+    // Molecule table: total_size(4) | 3 field offsets(12) | Byte32 | byte | Bytes(len(4) + data)
+    const HEADER_LEN: usize = 4 + 3 * 4;
+    let args_offset = HEADER_LEN + 32 + 1;
+    let total_size = args_offset + 4 + args.len();
+
+    let mut buf = Vec::with_capacity(total_size);
+    buf.extend_from_slice(&(total_size as u32).to_le_bytes());
+    buf.extend_from_slice(&(HEADER_LEN as u32).to_le_bytes());
+    buf.extend_from_slice(&((HEADER_LEN + 32) as u32).to_le_bytes());
+    buf.extend_from_slice(&(args_offset as u32).to_le_bytes());
+    buf.extend_from_slice(code_hash);
+    buf.push(hash_type);
+    buf.extend_from_slice(&(args.len() as u32).to_le_bytes());
+    buf.extend_from_slice(args);
+
+    ckb_blake2b(&buf)
+}
+
+/// Canonical identity of a CKB address: the `0x`-prefixed hash of its lock script.
+///
+/// Full, short, bech32 and bech32m encodings of the same lock all map to the
+/// same value, so storage and lookups should key on this rather than the
+/// address string.
+pub fn address_to_lock_hash(address: &str) -> Result<String, SignatureError> {
+    let (code_hash, hash_type, args) = parse_ckb_address(address)?;
+    Ok(format!("0x{}", hex::encode(compute_script_hash(&code_hash, hash_type, &args))))
+}
+
+/// Normalize a user-supplied lock hash to `0x`-prefixed lowercase hex.
+pub fn normalize_lock_hash(lock_hash: &str) -> Result<String, SignatureError> {
+    let hex_str = lock_hash.strip_prefix("0x").unwrap_or(lock_hash);
+    let bytes = hex::decode(hex_str).map_err(|_| SignatureError::InvalidHex)?;
+    if bytes.len() != 32 {
+        return Err(SignatureError::InvalidHex);
+    }
+    Ok(format!("0x{}", hex::encode(bytes)))
+}
+
+// --- Signing ---
+
+/// Full-format address of the secp256k1-blake160 lock owned by `pubkey`.
+pub(crate) fn secp256k1_address(pubkey: &PublicKey, hrp: &str) -> String {
+    let mut payload = vec![0x00];
+    payload.extend_from_slice(&SECP256K1_BLAKE160_CODE_HASH);
+    payload.push(0x01);
+    payload.extend_from_slice(&blake160(&pubkey.serialize()));
+    bech32::encode(hrp, payload.to_base32(), bech32::Variant::Bech32m).expect("valid hrp")
+}
+
+/// Sign `message` the way a CKB wallet does, as 0x-prefixed 65-byte hex.
+#[cfg(test)]
+pub(crate) fn sign_message_ckb(secret_key: &SecretKey, message: &str) -> String {
+    format!("0x{}", hex::encode(sign_digest(secret_key, hash_message_ckb(message))))
+}
+
+/// Recoverable signature over a 32-byte digest: compact `r || s` followed
+/// by the recovery id, as the secp256k1-blake160 lock expects.
+pub(crate) fn sign_digest(secret_key: &SecretKey, digest: [u8; 32]) -> [u8; 65] {
+    let sig = Secp256k1::new().sign_ecdsa_recoverable(&Message::from_digest(digest), secret_key);
+    let (recovery_id, compact) = sig.serialize_compact();
+    let mut bytes = [0u8; 65];
+    bytes[..64].copy_from_slice(&compact);
+    bytes[64] = recovery_id.to_i32() as u8;
+    bytes
+}
+
 // --- CKB address-based verification ---
 
 /// Verify a CKB secp256k1 recoverable signature against a CKB address.
@@ -130,6 +198,67 @@ pub fn parse_ckb_address(address: &str) -> Result<([u8; 32], u8, Vec<u8>), Signa
     }
 }
 
+/// Which addresses this backend accepts: the network's bech32 prefix plus the
+/// lock script code hashes deployed on that network.
+#[derive(Clone, Debug)]
+pub struct AddressPolicy {
+    /// CKB address human-readable prefix ("ckt" for testnet, "ckb" for mainnet).
+    pub hrp: String,
+    pub allowed_lock_code_hashes: Vec<[u8; 32]>,
+}
+
+impl AddressPolicy {
+    /// Policy accepting only secp256k1-blake160 locks on the given network.
+    pub fn new(hrp: &str) -> Self {
+        Self {
+            hrp: hrp.to_string(),
+            allowed_lock_code_hashes: vec![SECP256K1_BLAKE160_CODE_HASH],
+        }
+    }
+
+    /// Replace the allowed code hashes with a comma-separated list of hex hashes.
+    ///
+    /// Other locks are only accepted where an address is queried or receives a
+    /// badge: message signatures are verified as secp256k1-blake160, so
+    /// addresses that must sign are checked with `validate_signer`.
+    pub fn with_lock_code_hashes(mut self, list: &str) -> Result<Self, SignatureError> {
+        let mut hashes = Vec::new();
+        for item in list.split(',').map(str::trim).filter(|s| !s.is_empty()) {
+            let bytes = hex::decode(item.strip_prefix("0x").unwrap_or(item))
+                .map_err(|_| SignatureError::InvalidHex)?;
+            hashes.push(bytes.try_into().map_err(|_| SignatureError::InvalidHex)?);
+        }
+        if !hashes.is_empty() {
+            self.allowed_lock_code_hashes = hashes;
+        }
+        Ok(self)
+    }
+
+    /// Check that `address` is encoded for this network and uses an allowed lock.
+    pub fn validate(&self, address: &str) -> Result<(), SignatureError> {
+        let (hrp, _, _) = bech32::decode(address).map_err(|_| SignatureError::InvalidAddress)?;
+        if hrp != self.hrp {
+            return Err(SignatureError::WrongNetwork);
+        }
+
+        let (code_hash, _, _) = parse_ckb_address(address)?;
+        if !self.allowed_lock_code_hashes.contains(&code_hash) {
+            return Err(SignatureError::UnsupportedLockScript);
+        }
+        Ok(())
+    }
+
+    /// `validate`, and check that the address's lock can sign messages.
+    pub fn validate_signer(&self, address: &str) -> Result<(), SignatureError> {
+        self.validate(address)?;
+        let (code_hash, _, _) = parse_ckb_address(address)?;
+        if code_hash != SECP256K1_BLAKE160_CODE_HASH {
+            return Err(SignatureError::LockCannotSign);
+        }
+        Ok(())
+    }
+}
+
 #[derive(Debug, thiserror::Error)]
 pub enum SignatureError {
     #[error("invalid hex encoding")]
@@ -146,6 +275,37 @@ pub enum SignatureError {
     InvalidAddress,
     #[error("unsupported lock script; only secp256k1-blake160 is supported")]
     UnsupportedLockScript,
+    #[error("address belongs to a different CKB network")]
+    WrongNetwork,
+    #[error("lock script cannot sign messages; only secp256k1-blake160 is supported")]
+    LockCannotSign,
+}
+
+/// Deterministic test wallets that produce real CKB signatures.
+#[cfg(test)]
+pub(crate) mod test_wallet {
+    use secp256k1::{Secp256k1, SecretKey};
+
+    use super::{secp256k1_address, sign_message_ckb};
+
+    pub struct TestWallet {
+        secret_key: SecretKey,
+        pub address: String,
+    }
+
+    impl TestWallet {
+        /// Wallet derived from a one-byte seed, with a full-format address for `hrp`.
+        pub fn new(seed: u8, hrp: &str) -> Self {
+            let secret_key = SecretKey::from_slice(&[seed.max(1); 32]).unwrap();
+            let address = secp256k1_address(&secret_key.public_key(&Secp256k1::new()), hrp);
+            Self { secret_key, address }
+        }
+
+        /// Sign `message` the way a CKB wallet does, as 0x-prefixed 65-byte hex.
+        pub fn sign(&self, message: &str) -> String {
+            sign_message_ckb(&self.secret_key, message)
+        }
+    }
 }
 
 #[cfg(test)]
@@ -178,12 +338,94 @@ mod tests {
         assert_eq!(hex::encode(&args), "b39bbc0b3673c7d36450bc14cfcdad2d559c6c64");
     }
 
+    #[test]
+    fn test_lock_hash_same_for_short_and_full_address() {
+        use bech32::ToBase32;
+
+        let full = "ckb1qzda0cr08m85hc8jlnfp3zer7xulejywt49kt2rr0vthywaa50xwsqdnnw7qkdnnclfkg59uzn8umtfd2kwxceqxwquc4";
+        let args = hex::decode("b39bbc0b3673c7d36450bc14cfcdad2d559c6c64").unwrap();
+        let mut payload = vec![0x01, 0x00];
+        payload.extend_from_slice(&args);
+        let short = bech32::encode("ckb", payload.to_base32(), bech32::Variant::Bech32).unwrap();
+
+        let full_hash = address_to_lock_hash(full).unwrap();
+        assert_eq!(full_hash.len(), 66);
+        assert_eq!(full_hash, address_to_lock_hash(&short).unwrap());
+    }
+
+    #[test]
+    fn test_lock_hash_differs_by_args() {
+        let a = compute_script_hash(&SECP256K1_BLAKE160_CODE_HASH, 0x01, &[0u8; 20]);
+        let b = compute_script_hash(&SECP256K1_BLAKE160_CODE_HASH, 0x01, &[1u8; 20]);
+        assert_ne!(a, b);
+    }
+
+    #[test]
+    fn test_normalize_lock_hash() {
+        let upper = format!("0x{}", "AB".repeat(32));
+        assert_eq!(normalize_lock_hash(&upper).unwrap(), format!("0x{}", "ab".repeat(32)));
+        assert_eq!(normalize_lock_hash(&"ab".repeat(32)).unwrap(), format!("0x{}", "ab".repeat(32)));
+        assert!(normalize_lock_hash("0xabcd").is_err());
+    }
+
+    #[test]
+    fn test_address_policy_rejects_wrong_network() {
+        let mainnet = "ckb1qzda0cr08m85hc8jlnfp3zer7xulejywt49kt2rr0vthywaa50xwsqdnnw7qkdnnclfkg59uzn8umtfd2kwxceqxwquc4";
+        assert!(AddressPolicy::new("ckb").validate(mainnet).is_ok());
+        assert!(matches!(
+            AddressPolicy::new("ckt").validate(mainnet),
+            Err(SignatureError::WrongNetwork)
+        ));
+    }
+
+    #[test]
+    fn test_address_policy_configurable_code_hashes() {
+        let mainnet = "ckb1qzda0cr08m85hc8jlnfp3zer7xulejywt49kt2rr0vthywaa50xwsqdnnw7qkdnnclfkg59uzn8umtfd2kwxceqxwquc4";
+        let other = format!("0x{}", "11".repeat(32));
+        let policy = AddressPolicy::new("ckb").with_lock_code_hashes(&other).unwrap();
+        assert!(matches!(policy.validate(mainnet), Err(SignatureError::UnsupportedLockScript)));
+
+        let both = format!("{}, 0x{}", other, hex::encode(SECP256K1_BLAKE160_CODE_HASH));
+        let policy = AddressPolicy::new("ckb").with_lock_code_hashes(&both).unwrap();
+        assert_eq!(policy.allowed_lock_code_hashes.len(), 2);
+        assert!(policy.validate(mainnet).is_ok());
+
+        assert!(AddressPolicy::new("ckb").with_lock_code_hashes("0xnothex").is_err());
+    }
+
+    #[test]
+    fn test_extra_code_hashes_cannot_sign() {
+        let mut payload = vec![0x00];
+        payload.extend_from_slice(&[0x11; 32]);
+        payload.push(0x01);
+        payload.extend_from_slice(&[0x22; 20]);
+        let other = bech32::encode("ckb", payload.to_base32(), bech32::Variant::Bech32m).unwrap();
+        let mainnet = "ckb1qzda0cr08m85hc8jlnfp3zer7xulejywt49kt2rr0vthywaa50xwsqdnnw7qkdnnclfkg59uzn8umtfd2kwxceqxwquc4";
+
+        let both = format!("0x{}, 0x{}", "11".repeat(32), hex::encode(SECP256K1_BLAKE160_CODE_HASH));
+        let policy = AddressPolicy::new("ckb").with_lock_code_hashes(&both).unwrap();
+        assert!(policy.validate(&other).is_ok());
+        assert!(matches!(policy.validate_signer(&other), Err(SignatureError::LockCannotSign)));
+        assert!(policy.validate_signer(mainnet).is_ok());
+    }
+
     #[test]
     fn test_reject_invalid_address() {
         assert!(parse_ckb_address("not_an_address").is_err());
         assert!(parse_ckb_address("bc1qw508d6qejxtdg4y5r3zarvary0c5xw7kv8f3t4").is_err());
     }
 
+    #[test]
+    fn test_verify_signature_roundtrip() {
+        let wallet = test_wallet::TestWallet::new(7, "ckt");
+        let sig = wallet.sign("hello");
+        assert!(verify_ckb_address_signature("hello", &sig, &wallet.address).is_ok());
+        assert!(matches!(
+            verify_ckb_address_signature("other", &sig, &wallet.address),
+            Err(SignatureError::SignatureMismatch)
+        ));
+    }
+
     #[test]
     fn test_reject_wrong_length_signature() {
         let result = verify_ckb_address_signature(
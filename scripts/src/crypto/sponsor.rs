@@ -0,0 +1,71 @@
+use secp256k1::{Secp256k1, SecretKey};
+
+use super::signatures::{self, SignatureError, SECP256K1_BLAKE160_CODE_HASH};
+
+/// Backend-held key for the lock that holds sponsored events' budgets.
+/// Organizers fund an event by paying to `address`; the backend co-signs
+/// the sponsor inputs of each badge it pays for.
+pub struct SponsorWallet {
+    secret_key: SecretKey,
+    pub address: String,
+    /// `0x`-prefixed blake160 of the public key: the sponsor lock's args.
+    pub lock_args: String,
+    /// Hash of the sponsor lock script.
+    pub lock_hash: String,
+}
+
+impl SponsorWallet {
+    pub fn from_hex(secret_hex: &str, hrp: &str) -> Result<Self, SignatureError> {
+        let bytes = hex::decode(secret_hex.strip_prefix("0x").unwrap_or(secret_hex))
+            .map_err(|_| SignatureError::InvalidHex)?;
+        let secret_key = SecretKey::from_slice(&bytes).map_err(|_| SignatureError::InvalidHex)?;
+        let pubkey = secret_key.public_key(&Secp256k1::new());
+
+        let address = signatures::secp256k1_address(&pubkey, hrp);
+        Ok(Self {
+            secret_key,
+            lock_hash: signatures::address_to_lock_hash(&address)?,
+            address,
+            lock_args: format!("0x{}", hex::encode(signatures::blake160(&pubkey.serialize()))),
+        })
+    }
+
+    /// Reads `SPONSOR_PRIVATE_KEY`; sponsored minting is off without it.
+    pub fn from_env(hrp: &str) -> Option<Self> {
+        let key = std::env::var("SPONSOR_PRIVATE_KEY").ok()?;
+        Some(Self::from_hex(&key, hrp).expect("Invalid SPONSOR_PRIVATE_KEY"))
+    }
+
+    /// Whether a cell with this lock belongs to the sponsor wallet.
+    pub fn owns_lock(&self, code_hash: &str, args: &str) -> bool {
+        code_hash.eq_ignore_ascii_case(&format!("0x{}", hex::encode(SECP256K1_BLAKE160_CODE_HASH)))
+            && args.eq_ignore_ascii_case(&self.lock_args)
+    }
+
+    /// Sign a transaction's sighash-all digest for the sponsor lock.
+    pub fn sign_digest(&self, digest: [u8; 32]) -> [u8; 65] {
+        signatures::sign_digest(&self.secret_key, digest)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use secp256k1::{ecdsa, Message};
+
+    #[test]
+    fn test_sponsor_wallet_signs_for_its_lock() {
+        let wallet = SponsorWallet::from_hex(&"11".repeat(32), "ckt").unwrap();
+        let signature = wallet.sign_digest([7; 32]);
+        let recovery_id = ecdsa::RecoveryId::from_i32(signature[64] as i32).unwrap();
+        let recoverable = ecdsa::RecoverableSignature::from_compact(&signature[..64], recovery_id).unwrap();
+        let pubkey = Secp256k1::new().recover_ecdsa(&Message::from_digest([7; 32]), &recoverable).unwrap();
+        assert_eq!(format!("0x{}", hex::encode(signatures::blake160(&pubkey.serialize()))), wallet.lock_args);
+        assert_eq!(signatures::address_to_lock_hash(&wallet.address).unwrap(), wallet.lock_hash);
+
+        let code_hash = format!("0x{}", hex::encode(SECP256K1_BLAKE160_CODE_HASH));
+        assert!(wallet.owns_lock(&code_hash, &wallet.lock_args));
+        assert!(!wallet.owns_lock(&code_hash, "0x00"));
+        assert!(SponsorWallet::from_hex("0xnothex", "ckt").is_err());
+    }
+}
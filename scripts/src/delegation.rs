@@ -0,0 +1,275 @@
+use chrono::Utc;
+
+use crate::cache::Cache;
+use crate::crypto::signatures;
+use crate::types::{ActiveEvent, DelegateRole, Delegation};
+
+/// Something an organizer does that a delegate may be allowed to do too.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Capability {
+    /// Open, replace or revoke attendance windows.
+    ManageWindows,
+    /// Show QR codes and accept check-ins.
+    CheckIn,
+}
+
+impl DelegateRole {
+    pub fn allows(self, capability: Capability) -> bool {
+        match capability {
+            Capability::ManageWindows => self.can_manage_windows(),
+            Capability::CheckIn => true,
+        }
+    }
+}
+
+/// Record a creator-signed delegation for `event`. The signature is checked
+/// against the creator's address, so the stored record is verifiable by
+/// anyone with the same message format. `version` must be one more than the
+/// delegate's last grant, revoked or not.
+pub async fn create_delegation(
+    cache: &Cache,
+    event: &ActiveEvent,
+    delegate_address: &str,
+    role: DelegateRole,
+    expires_at: i64,
+    version: u32,
+    creator_signature: &str,
+) -> Result<Delegation, DelegationError> {
+    let now = Utc::now();
+    if expires_at <= now.timestamp() {
+        return Err(DelegationError::AlreadyExpired);
+    }
+
+    let delegate_lock_hash =
+        signatures::address_to_lock_hash(delegate_address).map_err(|_| DelegationError::InvalidAddress)?;
+    if is_creator(event, &delegate_lock_hash) {
+        return Err(DelegationError::SelfDelegation);
+    }
+    let current = cache
+        .get_delegation(&event.event_id, &delegate_lock_hash)
+        .await
+        .map_err(DelegationError::Cache)?;
+    if version != current.map_or(0, |d| d.version) + 1 {
+        return Err(DelegationError::VersionConflict);
+    }
+
+    let delegation = Delegation {
+        event_id: event.event_id.clone(),
+        delegate_address: delegate_address.to_string(),
+        delegate_lock_hash,
+        role,
+        expires_at,
+        version,
+        creator_signature: creator_signature.to_string(),
+        created_at: now,
+        revoked_at: None,
+    };
+    signatures::verify_ckb_address_signature(&delegation.signed_message(), creator_signature, &event.creator_address)
+        .map_err(|_| DelegationError::InvalidSignature)?;
+
+    if !cache.store_delegation(&delegation).await.map_err(DelegationError::Cache)? {
+        return Err(DelegationError::VersionConflict);
+    }
+    Ok(delegation)
+}
+
+/// Check a creator signature over the revoke message of `delegate_address`'s
+/// current grant.
+pub async fn verify_revocation(
+    cache: &Cache,
+    event: &ActiveEvent,
+    delegate_address: &str,
+    creator_signature: &str,
+) -> Result<(), DelegationError> {
+    let lock_hash =
+        signatures::address_to_lock_hash(delegate_address).map_err(|_| DelegationError::InvalidAddress)?;
+    let delegation = cache
+        .get_delegation(&event.event_id, &lock_hash)
+        .await
+        .map_err(DelegationError::Cache)?
+        .filter(|d| d.revoked_at.is_none())
+        .ok_or(DelegationError::NotFound)?;
+    signatures::verify_ckb_address_signature(
+        &delegation.revoke_message_to_sign(),
+        creator_signature,
+        &event.creator_address,
+    )
+    .map_err(|_| DelegationError::InvalidSignature)
+}
+
+pub async fn revoke_delegation(cache: &Cache, event_id: &str, delegate_address: &str) -> Result<(), DelegationError> {
+    let lock_hash =
+        signatures::address_to_lock_hash(delegate_address).map_err(|_| DelegationError::InvalidAddress)?;
+    if !cache.revoke_delegation(event_id, &lock_hash, Utc::now()).await.map_err(DelegationError::Cache)? {
+        return Err(DelegationError::NotFound);
+    }
+    Ok(())
+}
+
+/// Whether `address` may exercise `capability` for `event` right now: the
+/// creator always may, a delegate only while unexpired and if its role
+/// allows it.
+pub async fn is_authorized(
+    cache: &Cache,
+    event: &ActiveEvent,
+    address: &str,
+    capability: Capability,
+) -> Result<bool, DelegationError> {
+    let Ok(lock_hash) = signatures::address_to_lock_hash(address) else {
+        return Ok(false);
+    };
+    if is_creator(event, &lock_hash) {
+        return Ok(true);
+    }
+
+    let delegation = cache
+        .get_delegation(&event.event_id, &lock_hash)
+        .await
+        .map_err(DelegationError::Cache)?;
+    Ok(delegation.is_some_and(|d| d.is_active_at(Utc::now().timestamp()) && d.role.allows(capability)))
+}
+
+fn is_creator(event: &ActiveEvent, lock_hash: &str) -> bool {
+    signatures::address_to_lock_hash(&event.creator_address).is_ok_and(|creator| creator == lock_hash)
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum DelegationError {
+    #[error("cache error: {0}")]
+    Cache(#[from] sqlx::Error),
+    #[error("invalid delegate address")]
+    InvalidAddress,
+    #[error("invalid creator signature")]
+    InvalidSignature,
+    #[error("delegation expiry is in the past")]
+    AlreadyExpired,
+    #[error("creator cannot delegate to themselves")]
+    SelfDelegation,
+    #[error("delegation not found")]
+    NotFound,
+    #[error("delegation version conflict")]
+    VersionConflict,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::crypto::signatures::test_wallet::TestWallet;
+    use crate::types::{EventMetadata, EventState};
+
+    fn test_event(creator: &TestWallet) -> ActiveEvent {
+        ActiveEvent {
+            event_id: "evt1".to_string(),
+            metadata: EventMetadata {
+                name: "Test".to_string(),
+                description: "Desc".to_string(),
+                image_url: None,
+                location: None,
+                start_time: None,
+                end_time: None,
+                max_attendees: None,
+                prerequisite_events: vec![],
+                min_prerequisites: None,
+            },
+            creator_address: creator.address.clone(),
+            payment_tx_hash: "0xtx".to_string(),
+            payment_block_number: 100,
+            activated_at: Utc::now(),
+            windows: vec![],
+            metadata_version: 1,
+            cancelled_at: None,
+            state: EventState::Active,
+            state_changed_at: Utc::now(),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_delegation_grants_role_capabilities() {
+        let cache = Cache::new("sqlite::memory:").await.unwrap();
+        let creator = TestWallet::new(1, "ckt");
+        let door = TestWallet::new(2, "ckt");
+        let event = test_event(&creator);
+        let expires_at = Utc::now().timestamp() + 3600;
+
+        let message = Delegation::message_to_sign("evt1", &door.address, DelegateRole::DoorStaff, expires_at, 1);
+        let signature = creator.sign(&message);
+
+        // Signed by someone other than the creator.
+        let result =
+            create_delegation(&cache, &event, &door.address, DelegateRole::DoorStaff, expires_at, 1, &door.sign(&message))
+                .await;
+        assert!(matches!(result, Err(DelegationError::InvalidSignature)));
+
+        // A signature for one role does not grant another.
+        let result = create_delegation(&cache, &event, &door.address, DelegateRole::CoHost, expires_at, 1, &signature).await;
+        assert!(matches!(result, Err(DelegationError::InvalidSignature)));
+
+        assert!(!is_authorized(&cache, &event, &door.address, Capability::CheckIn).await.unwrap());
+        let delegation =
+            create_delegation(&cache, &event, &door.address, DelegateRole::DoorStaff, expires_at, 1, &signature)
+                .await
+                .unwrap();
+        signatures::verify_ckb_address_signature(
+            &delegation.signed_message(),
+            &delegation.creator_signature,
+            &event.creator_address,
+        )
+        .unwrap();
+
+        assert!(is_authorized(&cache, &event, &door.address, Capability::CheckIn).await.unwrap());
+        assert!(!is_authorized(&cache, &event, &door.address, Capability::ManageWindows).await.unwrap());
+        assert!(is_authorized(&cache, &event, &creator.address, Capability::ManageWindows).await.unwrap());
+
+        let old_revoke = creator.sign(&delegation.revoke_message_to_sign());
+        verify_revocation(&cache, &event, &door.address, &old_revoke).await.unwrap();
+        revoke_delegation(&cache, "evt1", &door.address).await.unwrap();
+        assert!(!is_authorized(&cache, &event, &door.address, Capability::CheckIn).await.unwrap());
+        assert!(matches!(
+            revoke_delegation(&cache, "evt1", &door.address).await,
+            Err(DelegationError::NotFound)
+        ));
+
+        // The delegate cannot replay the revoked grant.
+        let result =
+            create_delegation(&cache, &event, &door.address, DelegateRole::DoorStaff, expires_at, 1, &signature).await;
+        assert!(matches!(result, Err(DelegationError::VersionConflict)));
+        assert!(!is_authorized(&cache, &event, &door.address, Capability::CheckIn).await.unwrap());
+
+        // Re-granting takes a new version and makes the earlier revoke signature useless.
+        let regrant = creator.sign(&Delegation::message_to_sign("evt1", &door.address, DelegateRole::DoorStaff, expires_at, 2));
+        create_delegation(&cache, &event, &door.address, DelegateRole::DoorStaff, expires_at, 2, &regrant).await.unwrap();
+        assert!(is_authorized(&cache, &event, &door.address, Capability::CheckIn).await.unwrap());
+        let result = verify_revocation(&cache, &event, &door.address, &old_revoke).await;
+        assert!(matches!(result, Err(DelegationError::InvalidSignature)));
+    }
+
+    #[tokio::test]
+    async fn test_expired_delegation_is_not_authorized() {
+        let cache = Cache::new("sqlite::memory:").await.unwrap();
+        let creator = TestWallet::new(1, "ckt");
+        let cohost = TestWallet::new(3, "ckt");
+        let event = test_event(&creator);
+
+        let past = Utc::now().timestamp() - 1;
+        let signature = creator.sign(&Delegation::message_to_sign("evt1", &cohost.address, DelegateRole::CoHost, past, 1));
+        let result = create_delegation(&cache, &event, &cohost.address, DelegateRole::CoHost, past, 1, &signature).await;
+        assert!(matches!(result, Err(DelegationError::AlreadyExpired)));
+
+        // A delegation that lapses after being stored stops applying.
+        cache
+            .store_delegation(&Delegation {
+                event_id: "evt1".to_string(),
+                delegate_address: cohost.address.clone(),
+                delegate_lock_hash: signatures::address_to_lock_hash(&cohost.address).unwrap(),
+                role: DelegateRole::CoHost,
+                expires_at: past,
+                version: 1,
+                creator_signature: signature,
+                created_at: Utc::now(),
+                revoked_at: None,
+            })
+            .await
+            .unwrap();
+        assert!(!is_authorized(&cache, &event, &cohost.address, Capability::ManageWindows).await.unwrap());
+    }
+}
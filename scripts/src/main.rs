@@ -1,9 +1,18 @@
+mod achievement;
+mod auth;
 mod cache;
 mod crypto;
+mod delegation;
 mod observe;
+mod ratelimit;
 mod relay;
+mod roles;
+mod rsvp;
 mod routes;
 mod rpc;
+mod schedule;
+mod series;
+mod sponsor;
 mod state;
 mod types;
 
@@ -14,6 +23,10 @@ use std::net::SocketAddr;
 use tower_http::cors::{Any, CorsLayer};
 use tracing_subscriber::{layer::SubscriberExt, util::SubscriberInitExt};
 
+use crate::crypto::signatures::AddressPolicy;
+use crate::crypto::sponsor::SponsorWallet;
+use crate::ratelimit::RateLimits;
+use crate::schedule::WindowScheduler;
 use crate::state::AppState;
 
 #[tokio::main]
@@ -40,7 +53,23 @@ async fn main() {
     let dob_code_hash = std::env::var("DOB_BADGE_CODE_HASH").ok();
     let address_hrp = if ckb_network == "mainnet" { "ckb" } else { "ckt" };
 
-    let state = AppState::new(&database_url, &ckb_rpc_url, dob_code_hash.clone(), address_hrp.to_string())
+    // Lock code hashes accepted for addresses; defaults to secp256k1-blake160 only.
+    let mut address_policy = AddressPolicy::new(address_hrp);
+    if let Ok(list) = std::env::var("ALLOWED_LOCK_CODE_HASHES") {
+        address_policy = address_policy
+            .with_lock_code_hashes(&list)
+            .expect("Invalid ALLOWED_LOCK_CODE_HASHES");
+    }
+
+    let scheduler = WindowScheduler::from_env();
+
+    // Backend fee-payer for sponsored minting; off unless a key is configured.
+    let sponsor = SponsorWallet::from_env(address_hrp);
+    if let Some(ref wallet) = sponsor {
+        tracing::info!("Sponsored minting enabled, sponsor wallet {}", wallet.address);
+    }
+
+    let state = AppState::new(&database_url, &ckb_rpc_url, dob_code_hash.clone(), address_policy, scheduler, sponsor)
         .await
         .expect("Failed to initialize app state");
 
@@ -51,7 +80,8 @@ async fn main() {
         observe::rehydrate_from_chain(&cache, &rpc, code_hash, address_hrp).await;
     }
 
-    // Spawn background task to confirm pending badges every 15 seconds.
+    // Spawn background task to confirm pending badges and advance event
+    // lifecycles (window open/close, archival) every 15 seconds.
     {
         let cache = Arc::clone(&state.cache);
         let rpc = Arc::clone(&state.rpc);
@@ -60,6 +90,34 @@ async fn main() {
             loop {
                 interval.tick().await;
                 observe::confirm_pending_badges(&cache, &rpc).await;
+                observe::advance_lifecycles(&cache).await;
+            }
+        });
+    }
+
+    // Announce window opens/closes and force-close overdue open-ended windows.
+    {
+        let cache = Arc::clone(&state.cache);
+        let scheduler = Arc::clone(&state.scheduler);
+        tokio::spawn(async move {
+            let mut interval = tokio::time::interval(std::time::Duration::from_secs(5));
+            loop {
+                interval.tick().await;
+                scheduler.tick(&cache).await;
+            }
+        });
+    }
+
+    let rate_limits = Arc::new(RateLimits::from_env());
+
+    // Periodically drop rate limiter keys whose quotas have replenished.
+    {
+        let rate_limits = Arc::clone(&rate_limits);
+        tokio::spawn(async move {
+            let mut interval = tokio::time::interval(std::time::Duration::from_secs(60));
+            loop {
+                interval.tick().await;
+                rate_limits.retain_recent();
             }
         });
     }
@@ -70,7 +128,8 @@ async fn main() {
         .allow_headers(Any);
 
     let app = Router::new()
-        .nest("/api", routes::router())
+        .nest("/api", routes::router(&rate_limits))
+        .layer(axum::middleware::from_fn_with_state(state.clone(), auth::resolve_session))
         .with_state(state)
         .layer(cors);
 
@@ -78,5 +137,7 @@ async fn main() {
     tracing::info!("Backend listening on {}", addr);
 
     let listener = tokio::net::TcpListener::bind(addr).await.unwrap();
-    axum::serve(listener, app).await.unwrap();
+    axum::serve(listener, app.into_make_service_with_connect_info::<SocketAddr>())
+        .await
+        .unwrap();
 }
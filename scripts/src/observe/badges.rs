@@ -1,4 +1,4 @@
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 
 use bech32::ToBase32;
 use chrono::Utc;
@@ -6,8 +6,9 @@ use serde::{Deserialize, Serialize};
 use sha2::Digest;
 
 use crate::cache::Cache;
-use crate::rpc::CkbRpcClient;
-use crate::types::BadgeObservation;
+use crate::crypto::signatures;
+use crate::rpc::{CkbRpcClient, RpcError};
+use crate::types::{BadgeObservation, BadgeRole};
 
 #[derive(Debug, Serialize, Deserialize)]
 pub struct BadgeListResponse {
@@ -30,8 +31,40 @@ pub async fn observe_badges_by_address(
         }
     }
 
+    let lock_hash = signatures::address_to_lock_hash(address)
+        .map_err(|_| BadgeObserveError::InvalidAddress)?;
+
+    let badges = cache
+        .get_badges_by_lock_hash(&lock_hash)
+        .await
+        .map_err(BadgeObserveError::Cache)?;
+
+    let verified_at_block = if verify {
+        rpc.get_tip_block_number().await.ok()
+    } else {
+        None
+    };
+
+    Ok(BadgeListResponse {
+        badges,
+        verified_at_block,
+        cached: !verify,
+    })
+}
+
+/// Look up badges by canonical lock script hash. Cache-only: the indexer
+/// needs the full lock script to search, which a hash cannot provide.
+pub async fn observe_badges_by_lock_hash(
+    cache: &Cache,
+    rpc: &CkbRpcClient,
+    lock_hash: &str,
+    verify: bool,
+) -> Result<BadgeListResponse, BadgeObserveError> {
+    let lock_hash = signatures::normalize_lock_hash(lock_hash)
+        .map_err(|_| BadgeObserveError::InvalidLockHash)?;
+
     let badges = cache
-        .get_badges_by_address(address)
+        .get_badges_by_lock_hash(&lock_hash)
         .await
         .map_err(BadgeObserveError::Cache)?;
 
@@ -117,6 +150,9 @@ pub async fn confirm_pending_badges(cache: &Cache, rpc: &CkbRpcClient) {
                     } else {
                         tracing::info!("Confirmed badge {} at block {block_number}", badge.mint_tx_hash);
                     }
+                    if let Err(e) = cache.confirm_airdrop_inclusions(&badge.mint_tx_hash, block_number).await {
+                        tracing::warn!("Failed to confirm airdrop inclusions for {}: {e}", badge.mint_tx_hash);
+                    }
                 }
             }
             Ok(_) => {
@@ -172,7 +208,7 @@ pub async fn rehydrate_from_chain(cache: &Cache, rpc: &CkbRpcClient, code_hash:
 ///
 /// Searches the indexer for badge cells owned by the address's lock script,
 /// matches event_id via SHA256 lookup, and stores any missing badges.
-async fn sync_from_chain_for_address(
+pub async fn sync_from_chain_for_address(
     cache: &Cache,
     rpc: &CkbRpcClient,
     address: &str,
@@ -180,19 +216,59 @@ async fn sync_from_chain_for_address(
     address_hrp: &str,
 ) -> Result<(), BadgeObserveError> {
     let (lock_code_hash, lock_hash_type, lock_args) =
-        crate::crypto::signatures::parse_ckb_address(address)
-            .map_err(|_| BadgeObserveError::InvalidAddress)?;
+        signatures::parse_ckb_address(address).map_err(|_| BadgeObserveError::InvalidAddress)?;
 
     let event_hash_map = build_event_hash_map(cache).await?;
     if event_hash_map.is_empty() {
         return Ok(());
     }
 
-    let search_key = serde_json::json!({
+    let search_key = holder_badge_search_key(&(lock_code_hash, lock_hash_type, lock_args), code_hash);
+    sync_cells_from_search(cache, rpc, &search_key, &event_hash_map, address_hrp).await;
+    Ok(())
+}
+
+/// Which of `event_ids` the holder of `lock` has a live badge cell for,
+/// straight from the indexer. The indexer only returns unspent cells, so a
+/// badge that was transferred away or burned does not count.
+pub async fn live_badge_event_ids(
+    rpc: &CkbRpcClient,
+    lock: &([u8; 32], u8, Vec<u8>),
+    code_hash: &str,
+    event_ids: &[String],
+) -> Result<HashSet<String>, RpcError> {
+    let event_hash_map: HashMap<String, String> = event_ids
+        .iter()
+        .map(|id| (hex::encode(sha2::Sha256::digest(id.as_bytes())), id.clone()))
+        .collect();
+    let search_key = holder_badge_search_key(lock, code_hash);
+
+    let mut live = HashSet::new();
+    let mut after_cursor: Option<String> = None;
+    loop {
+        let cells = rpc.search_cells(&search_key, after_cursor.as_deref(), 100).await?;
+        let objects = cells.get("objects").and_then(|o| o.as_array()).cloned().unwrap_or_default();
+        if objects.is_empty() {
+            break;
+        }
+        live.extend(objects.iter().filter_map(|cell| badge_cell_event_id(cell, &event_hash_map)));
+
+        after_cursor = cells.get("last_cursor").and_then(|c| c.as_str()).map(|s| s.to_string());
+        if after_cursor.is_none() {
+            break;
+        }
+    }
+    Ok(live)
+}
+
+/// Indexer search for the badge cells locked by `lock`.
+fn holder_badge_search_key(lock: &([u8; 32], u8, Vec<u8>), code_hash: &str) -> serde_json::Value {
+    let (lock_code_hash, lock_hash_type, lock_args) = lock;
+    serde_json::json!({
         "script": {
             "code_hash": format!("0x{}", hex::encode(lock_code_hash)),
-            "hash_type": hash_type_to_str(lock_hash_type),
-            "args": format!("0x{}", hex::encode(&lock_args))
+            "hash_type": hash_type_to_str(*lock_hash_type),
+            "args": format!("0x{}", hex::encode(lock_args))
         },
         "script_type": "lock",
         "filter": {
@@ -203,10 +279,7 @@ async fn sync_from_chain_for_address(
             }
         },
         "script_search_mode": "prefix"
-    });
-
-    sync_cells_from_search(cache, rpc, &search_key, &event_hash_map, address_hrp).await;
-    Ok(())
+    })
 }
 
 /// Sync badges from chain for a specific event.
@@ -298,30 +371,24 @@ async fn process_badge_cell(
     event_hash_map: &HashMap<String, String>,
     address_hrp: &str,
 ) -> bool {
-    let type_args = match cell
-        .get("output")
-        .and_then(|o| o.get("type"))
-        .and_then(|t| t.get("args"))
-        .and_then(|a| a.as_str())
-    {
-        Some(a) => a,
+    let event_id = match badge_cell_event_id(cell, event_hash_map) {
+        Some(id) => id,
         None => return false,
     };
 
-    let args_hex = type_args.trim_start_matches("0x");
-    if args_hex.len() < 64 {
-        return false;
-    }
-
-    let event_id = match event_hash_map.get(&args_hex[..64]) {
-        Some(id) => id.clone(),
+    let (lock_code_hash, lock_hash_type, lock_args) = match lock_script_from_cell(cell) {
+        Some(lock) => lock,
         None => return false,
     };
 
-    let holder_address = match encode_lock_as_address(cell, address_hrp) {
+    let holder_address = match encode_full_address(&lock_code_hash, lock_hash_type, &lock_args, address_hrp) {
         Some(addr) => addr,
         None => return false,
     };
+    let holder_lock_hash = format!(
+        "0x{}",
+        hex::encode(signatures::compute_script_hash(&lock_code_hash, lock_hash_type, &lock_args))
+    );
 
     let tx_hash = match cell
         .get("out_point")
@@ -340,15 +407,38 @@ async fn process_badge_cell(
     let badge = BadgeObservation {
         event_id,
         holder_address,
+        holder_lock_hash,
         mint_tx_hash: tx_hash,
         mint_block_number: block_number,
         verified_at_block: block_number,
         observed_at: Utc::now(),
+        flagged_at: None,
+        flag_reason: None,
+        role: role_from_cell(cell),
     };
 
     cache.store_badge_observation(&badge).await.is_ok()
 }
 
+/// The event a badge cell belongs to: its type args start with
+/// SHA256(event_id), looked up in `event_hash_map`.
+fn badge_cell_event_id(cell: &serde_json::Value, event_hash_map: &HashMap<String, String>) -> Option<String> {
+    let type_args = cell.get("output")?.get("type")?.get("args")?.as_str()?;
+    let args_hex = type_args.trim_start_matches("0x");
+    event_hash_map.get(args_hex.get(..64)?).cloned()
+}
+
+/// Read the optional `role` from a badge cell's metadata. Cells without
+/// one, or whose data is not metadata JSON, are attendee badges.
+fn role_from_cell(cell: &serde_json::Value) -> BadgeRole {
+    cell.get("output_data")
+        .and_then(|d| d.as_str())
+        .and_then(|d| hex::decode(d.trim_start_matches("0x")).ok())
+        .and_then(|bytes| serde_json::from_slice::<serde_json::Value>(&bytes).ok())
+        .and_then(|metadata| metadata.get("role")?.as_str().and_then(BadgeRole::parse))
+        .unwrap_or_default()
+}
+
 /// Convert hash_type byte to CKB RPC string representation.
 fn hash_type_to_str(hash_type: u8) -> &'static str {
     match hash_type {
@@ -374,17 +464,17 @@ async fn build_event_hash_map(cache: &Cache) -> Result<HashMap<String, String>,
     Ok(map)
 }
 
-/// Encode a cell's lock script (from search_cells JSON) as a full-format CKB address.
-fn encode_lock_as_address(cell: &serde_json::Value, hrp: &str) -> Option<String> {
+/// Extract a cell's lock script (from search_cells JSON) as (code_hash, hash_type, args).
+fn lock_script_from_cell(cell: &serde_json::Value) -> Option<([u8; 32], u8, Vec<u8>)> {
     let lock = cell.get("output")?.get("lock")?;
     let code_hash_hex = lock.get("code_hash")?.as_str()?;
     let hash_type_str = lock.get("hash_type")?.as_str()?;
     let args_hex = lock.get("args")?.as_str()?;
 
-    let code_hash = hex::decode(code_hash_hex.trim_start_matches("0x")).ok()?;
-    if code_hash.len() != 32 {
-        return None;
-    }
+    let code_hash: [u8; 32] = hex::decode(code_hash_hex.trim_start_matches("0x"))
+        .ok()?
+        .try_into()
+        .ok()?;
 
     let hash_type_byte: u8 = match hash_type_str {
         "data" => 0x00,
@@ -396,12 +486,17 @@ fn encode_lock_as_address(cell: &serde_json::Value, hrp: &str) -> Option<String>
 
     let args = hex::decode(args_hex.trim_start_matches("0x")).ok()?;
 
+    Some((code_hash, hash_type_byte, args))
+}
+
+/// Encode a lock script as a full-format (bech32m) CKB address.
+fn encode_full_address(code_hash: &[u8; 32], hash_type: u8, args: &[u8], hrp: &str) -> Option<String> {
     // Full-format CKB address payload: 0x00 | code_hash(32) | hash_type(1) | args
     let mut payload = Vec::with_capacity(34 + args.len());
     payload.push(0x00);
-    payload.extend_from_slice(&code_hash);
-    payload.push(hash_type_byte);
-    payload.extend_from_slice(&args);
+    payload.extend_from_slice(code_hash);
+    payload.push(hash_type);
+    payload.extend_from_slice(args);
 
     bech32::encode(hrp, payload.to_base32(), bech32::Variant::Bech32m).ok()
 }
@@ -412,6 +507,8 @@ pub enum BadgeObserveError {
     Cache(#[from] sqlx::Error),
     #[error("invalid CKB address")]
     InvalidAddress,
+    #[error("invalid lock hash")]
+    InvalidLockHash,
 }
 
 #[cfg(test)]
@@ -428,15 +525,32 @@ mod tests {
         CkbRpcClient::new("http://localhost:1")
     }
 
+    const FULL_ADDRESS: &str = "ckb1qzda0cr08m85hc8jlnfp3zer7xulejywt49kt2rr0vthywaa50xwsqdnnw7qkdnnclfkg59uzn8umtfd2kwxceqxwquc4";
+
+    /// Deprecated short-format encoding of the same lock as `FULL_ADDRESS`.
+    fn short_address() -> String {
+        let mut payload = vec![0x01, 0x00];
+        payload.extend_from_slice(&hex::decode("b39bbc0b3673c7d36450bc14cfcdad2d559c6c64").unwrap());
+        bech32::encode("ckb", payload.to_base32(), bech32::Variant::Bech32).unwrap()
+    }
+
     #[tokio::test]
     async fn test_observe_badges_by_address_empty() {
         let cache = test_cache().await;
         let rpc = test_rpc();
-        let result = observe_badges_by_address(&cache, &rpc, "addr1", false, None).await.unwrap();
+        let result = observe_badges_by_address(&cache, &rpc, FULL_ADDRESS, false, None).await.unwrap();
         assert!(result.badges.is_empty());
         assert!(result.cached);
     }
 
+    #[tokio::test]
+    async fn test_observe_badges_by_address_rejects_invalid() {
+        let cache = test_cache().await;
+        let rpc = test_rpc();
+        let result = observe_badges_by_address(&cache, &rpc, "addr1", false, None).await;
+        assert!(matches!(result, Err(BadgeObserveError::InvalidAddress)));
+    }
+
     #[tokio::test]
     async fn test_store_and_observe_badges_by_event() {
         let cache = test_cache().await;
@@ -445,16 +559,44 @@ mod tests {
         let badge = BadgeObservation {
             event_id: "evt1".to_string(),
             holder_address: "addr1".to_string(),
+            holder_lock_hash: "0xlock_addr1".to_string(),
             mint_tx_hash: "0xtx".to_string(),
             mint_block_number: 100,
             verified_at_block: 101,
             observed_at: Utc::now(),
+            flagged_at: None,
+            flag_reason: None,
+            role: BadgeRole::Speaker,
         };
         store_badge_observation(&cache, badge).await.unwrap();
 
         let result = observe_badges_by_event(&cache, &rpc, "evt1", false, None).await.unwrap();
         assert_eq!(result.badges.len(), 1);
         assert_eq!(result.badges[0].holder_address, "addr1");
+        assert_eq!(result.badges[0].role, BadgeRole::Speaker);
+    }
+
+    #[test]
+    fn test_role_from_cell_data() {
+        let data = |json: &str| serde_json::json!({ "output_data": format!("0x{}", hex::encode(json)) });
+
+        let cell = data(r#"{"protocol":"ckb-pop","version":"1","event_id":"evt1","role":"volunteer"}"#);
+        assert_eq!(role_from_cell(&cell), BadgeRole::Volunteer);
+        let cell = data(r#"{"protocol":"ckb-pop","version":"1","event_id":"evt1"}"#);
+        assert_eq!(role_from_cell(&cell), BadgeRole::Attendee);
+        assert_eq!(role_from_cell(&serde_json::json!({ "output_data": "0x00ff" })), BadgeRole::Attendee);
+    }
+
+    #[test]
+    fn test_badge_cell_event_id() {
+        let hash = hex::encode(sha2::Sha256::digest(b"evt1"));
+        let event_hash_map = HashMap::from([(hash.clone(), "evt1".to_string())]);
+        let cell = |args: String| serde_json::json!({ "output": { "type": { "args": args } } });
+
+        assert_eq!(badge_cell_event_id(&cell(format!("0x{hash}00")), &event_hash_map), Some("evt1".to_string()));
+        assert_eq!(badge_cell_event_id(&cell(format!("0x{}", "00".repeat(32))), &event_hash_map), None);
+        assert_eq!(badge_cell_event_id(&cell("0x1234".to_string()), &event_hash_map), None);
+        assert_eq!(badge_cell_event_id(&serde_json::json!({ "output": {} }), &event_hash_map), None);
     }
 
     #[tokio::test]
@@ -464,15 +606,49 @@ mod tests {
 
         let badge = BadgeObservation {
             event_id: "evt1".to_string(),
-            holder_address: "myaddr".to_string(),
+            holder_address: FULL_ADDRESS.to_string(),
+            holder_lock_hash: signatures::address_to_lock_hash(FULL_ADDRESS).unwrap(),
+            mint_tx_hash: "0xtx1".to_string(),
+            mint_block_number: 100,
+            verified_at_block: 101,
+            observed_at: Utc::now(),
+            flagged_at: None,
+            flag_reason: None,
+            role: BadgeRole::Attendee,
+        };
+        store_badge_observation(&cache, badge).await.unwrap();
+
+        // Querying with the short-format address finds the badge stored under the full one.
+        let result = observe_badges_by_address(&cache, &rpc, &short_address(), false, None).await.unwrap();
+        assert_eq!(result.badges.len(), 1);
+        assert_eq!(result.badges[0].holder_address, FULL_ADDRESS);
+    }
+
+    #[tokio::test]
+    async fn test_observe_badges_by_lock_hash() {
+        let cache = test_cache().await;
+        let rpc = test_rpc();
+        let lock_hash = signatures::address_to_lock_hash(FULL_ADDRESS).unwrap();
+
+        let badge = BadgeObservation {
+            event_id: "evt1".to_string(),
+            holder_address: FULL_ADDRESS.to_string(),
+            holder_lock_hash: lock_hash.clone(),
             mint_tx_hash: "0xtx1".to_string(),
             mint_block_number: 100,
             verified_at_block: 101,
             observed_at: Utc::now(),
+            flagged_at: None,
+            flag_reason: None,
+            role: BadgeRole::Attendee,
         };
         store_badge_observation(&cache, badge).await.unwrap();
 
-        let result = observe_badges_by_address(&cache, &rpc, "myaddr", false, None).await.unwrap();
+        let upper = lock_hash.trim_start_matches("0x").to_uppercase();
+        let result = observe_badges_by_lock_hash(&cache, &rpc, &upper, false).await.unwrap();
         assert_eq!(result.badges.len(), 1);
+
+        let result = observe_badges_by_lock_hash(&cache, &rpc, "0x1234", false).await;
+        assert!(matches!(result, Err(BadgeObserveError::InvalidLockHash)));
     }
 }
@@ -1,9 +1,16 @@
-use chrono::Utc;
+use chrono::{DateTime, Duration, Utc};
 use serde::{Deserialize, Serialize};
 
 use crate::cache::Cache;
+use crate::crypto::qr;
 use crate::rpc::CkbRpcClient;
-use crate::types::{ActiveEvent, PaymentIntent, WindowProof};
+use crate::types::{
+    ActiveEvent, Allowlist, CheckIn, CheckoutPolicy, ClaimPolicy, EventState, EventUpdate, MetadataRevision,
+    OfflinePolicy, PaymentIntent, StateTransition, WindowProof,
+};
+
+/// How long an event stays `Ended` before it is archived.
+const ARCHIVE_AFTER_DAYS: i64 = 30;
 
 #[derive(Debug, Serialize, Deserialize)]
 pub struct EventListResponse {
@@ -16,6 +23,9 @@ pub struct EventDetailResponse {
     pub event: ActiveEvent,
     pub verified_at_block: Option<u64>,
     pub cached: bool,
+    /// Check-in slots left under `max_attendees`; omitted for uncapped events.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub remaining_slots: Option<u32>,
 }
 
 pub async fn observe_events(
@@ -23,7 +33,11 @@ pub async fn observe_events(
     _rpc: &CkbRpcClient,
     _verify: bool,
 ) -> Result<EventListResponse, ObserveError> {
-    let events = cache.list_active_events().await.map_err(ObserveError::Cache)?;
+    let mut events = cache.list_active_events().await.map_err(ObserveError::Cache)?;
+    let now = Utc::now();
+    for event in events.iter_mut() {
+        advance_lifecycle(cache, event, now).await?;
+    }
     Ok(EventListResponse {
         events,
         cached: true,
@@ -37,7 +51,7 @@ pub async fn observe_event(
     verify: bool,
 ) -> Result<EventDetailResponse, ObserveError> {
     // Support prefix lookup for short IDs (< 64 hex chars)
-    let event = if event_id.len() < 64 {
+    let mut event = if event_id.len() < 64 {
         cache
             .get_active_event_by_prefix(event_id)
             .await
@@ -50,6 +64,7 @@ pub async fn observe_event(
             .map_err(ObserveError::Cache)?
             .ok_or(ObserveError::NotFound)?
     };
+    advance_lifecycle(cache, &mut event, Utc::now()).await?;
 
     let verified_at_block = if verify {
         rpc.get_tip_block_number().await.ok()
@@ -57,10 +72,19 @@ pub async fn observe_event(
         None
     };
 
+    let remaining_slots = match event.metadata.max_attendees {
+        Some(max) => {
+            let checked_in = cache.count_attendees(&event.event_id).await.map_err(ObserveError::Cache)?;
+            Some(max.saturating_sub(checked_in))
+        }
+        None => None,
+    };
+
     Ok(EventDetailResponse {
         event,
         verified_at_block,
         cached: !verify,
+        remaining_slots,
     })
 }
 
@@ -69,10 +93,28 @@ pub async fn submit_payment_intent(
     intent: PaymentIntent,
 ) -> Result<String, ObserveError> {
     let event_id = intent.event_id_preimage.compute_event_id();
+    let is_new = cache
+        .get_payment_intent(&event_id)
+        .await
+        .map_err(ObserveError::Cache)?
+        .is_none();
+
     cache
         .store_payment_intent(&intent)
         .await
         .map_err(ObserveError::Cache)?;
+
+    if is_new {
+        cache
+            .record_event_transition(&StateTransition {
+                event_id: event_id.clone(),
+                from_state: None,
+                to_state: EventState::Intent,
+                at: Utc::now(),
+            })
+            .await
+            .map_err(ObserveError::Cache)?;
+    }
     Ok(event_id)
 }
 
@@ -88,6 +130,13 @@ pub async fn activate_event_from_payment(
         .map_err(ObserveError::Cache)?
         .ok_or(ObserveError::NotFound)?;
 
+    // Either a bare intent or an event created ahead of payment.
+    let existing = cache.get_active_event(event_id).await.map_err(ObserveError::Cache)?;
+    let from = existing.as_ref().map_or(EventState::Intent, |event| event.state);
+    if !from.can_transition_to(EventState::Active) {
+        return Err(ObserveError::InvalidTransition { from, to: EventState::Active });
+    }
+
     let tx_info = rpc
         .get_transaction(tx_hash)
         .await
@@ -107,65 +156,504 @@ pub async fn activate_event_from_payment(
             other => ObserveError::Rpc(other.to_string()),
         })?;
 
-    let active_event = ActiveEvent {
-        event_id: event_id.to_string(),
-        metadata: intent.event_metadata,
-        creator_address: intent.creator_address,
-        payment_tx_hash: tx_hash.to_string(),
-        payment_block_number: block_number,
-        activated_at: Utc::now(),
-        window: None,
+    let now = Utc::now();
+    let active_event = match existing {
+        Some(event) => ActiveEvent {
+            payment_tx_hash: tx_hash.to_string(),
+            payment_block_number: block_number,
+            activated_at: now,
+            state: EventState::Active,
+            state_changed_at: now,
+            ..event
+        },
+        None => ActiveEvent {
+            event_id: event_id.to_string(),
+            metadata: intent.event_metadata,
+            creator_address: intent.creator_address,
+            payment_tx_hash: tx_hash.to_string(),
+            payment_block_number: block_number,
+            activated_at: now,
+            metadata_version: 1,
+            cancelled_at: None,
+            state: EventState::Active,
+            state_changed_at: now,
+            windows: vec![],
+        },
     };
 
     cache
         .store_active_event(&active_event)
         .await
         .map_err(ObserveError::Cache)?;
+    cache
+        .record_event_transition(&StateTransition {
+            event_id: event_id.to_string(),
+            from_state: Some(from),
+            to_state: EventState::Active,
+            at: now,
+        })
+        .await
+        .map_err(ObserveError::Cache)?;
 
     Ok(active_event)
 }
 
-pub async fn create_and_activate_event(
+/// Create an event record ahead of payment. It stays `PendingPayment`, and
+/// cannot open windows, until `activate_event_from_payment` observes the
+/// payment transaction.
+pub async fn create_pending_event(
     cache: &Cache,
     intent: PaymentIntent,
 ) -> Result<ActiveEvent, ObserveError> {
     let event_id = intent.event_id_preimage.compute_event_id();
 
-    cache
-        .store_payment_intent(&intent)
-        .await
-        .map_err(ObserveError::Cache)?;
+    if let Some(existing) = cache.get_active_event(&event_id).await.map_err(ObserveError::Cache)? {
+        return Err(ObserveError::InvalidTransition {
+            from: existing.state,
+            to: EventState::PendingPayment,
+        });
+    }
 
-    let active_event = ActiveEvent {
+    submit_payment_intent(cache, intent.clone()).await?;
+
+    let now = Utc::now();
+    let event = ActiveEvent {
         event_id,
         metadata: intent.event_metadata,
         creator_address: intent.creator_address,
         payment_tx_hash: String::new(),
         payment_block_number: 0,
-        activated_at: Utc::now(),
-        window: None,
+        activated_at: now,
+        metadata_version: 1,
+        cancelled_at: None,
+        state: EventState::PendingPayment,
+        state_changed_at: now,
+        windows: vec![],
     };
 
     cache
-        .store_active_event(&active_event)
+        .store_active_event(&event)
+        .await
+        .map_err(ObserveError::Cache)?;
+    cache
+        .record_event_transition(&StateTransition {
+            event_id: event.event_id.clone(),
+            from_state: Some(EventState::Intent),
+            to_state: EventState::PendingPayment,
+            at: now,
+        })
         .await
         .map_err(ObserveError::Cache)?;
 
-    Ok(active_event)
+    Ok(event)
 }
 
+/// Store an attendance window session and move the event into or out of
+/// `WindowOpen` to match. Other sessions of the event are left untouched.
+/// Replacing a session keeps its rotated secret, so replaying the request
+/// that first opened it cannot bring back a revoked secret.
 pub async fn update_window(
     cache: &Cache,
     event_id: &str,
-    window: WindowProof,
-) -> Result<(), ObserveError> {
+    mut window: WindowProof,
+) -> Result<ActiveEvent, ObserveError> {
+    let mut event = cache
+        .get_active_event(event_id)
+        .await
+        .map_err(ObserveError::Cache)?
+        .ok_or(ObserveError::NotFound)?;
+
+    match event.state {
+        EventState::Intent | EventState::PendingPayment => return Err(ObserveError::NotPaid),
+        EventState::Cancelled => return Err(ObserveError::EventCancelled),
+        EventState::Archived => return Err(ObserveError::EventArchived),
+        EventState::Active | EventState::WindowOpen | EventState::Ended => {}
+    }
+
+    if let Some(existing) = event.window(&window.session_id).filter(|w| w.secret_salt.is_some()) {
+        window.secret_salt = existing.secret_salt.clone();
+        window.rotated_at = existing.rotated_at;
+        window.window_secret_commitment = qr::secret_commitment(&qr::window_secret(&window));
+    }
+
+    cache.store_window(&window).await.map_err(ObserveError::Cache)?;
+    event.windows.retain(|w| w.session_id != window.session_id);
+    event.windows.push(window);
+    event.windows.sort_by_key(|w| w.window_start);
+
+    advance_lifecycle(cache, &mut event, Utc::now()).await?;
+    Ok(event)
+}
+
+/// Reason recorded on badges issued under a revoked window secret.
+pub const REVOKED_SECRET_FLAG: &str = "window secret revoked";
+
+/// Invalidate `window`'s current secret by rotating in a fresh one, keeping
+/// its start and end. Badges whose check-in QR was issued under the old
+/// secret are flagged for review. Returns the rotated window and the number
+/// of badges flagged.
+pub async fn revoke_window_secret(
+    cache: &Cache,
+    event: &ActiveEvent,
+    mut window: WindowProof,
+    now: i64,
+) -> Result<(WindowProof, u64), ObserveError> {
+    match event.state {
+        EventState::Cancelled => return Err(ObserveError::EventCancelled),
+        EventState::Archived => return Err(ObserveError::EventArchived),
+        _ => {}
+    }
+
+    let exposed_from = window.rotated_at.unwrap_or(window.window_start);
+    qr::rotate_window_secret(&mut window, now);
+    cache.store_window(&window).await.map_err(ObserveError::Cache)?;
+
+    let flagged = cache
+        .flag_badges_for_session(&event.event_id, &window.session_id, exposed_from, now, REVOKED_SECRET_FLAG)
+        .await
+        .map_err(ObserveError::Cache)?;
+    Ok((window, flagged))
+}
+
+/// Replace the event's allowlist. `merkle_root` must be the root of
+/// `lock_hashes`, and it and `version` already checked against the creator's
+/// signature. `version` must be the stored list's version + 1.
+pub async fn set_allowlist(
+    cache: &Cache,
+    event: &ActiveEvent,
+    mut lock_hashes: Vec<String>,
+    merkle_root: String,
+    version: u32,
+    creator_signature: String,
+) -> Result<Allowlist, ObserveError> {
+    match event.state {
+        EventState::Cancelled => return Err(ObserveError::EventCancelled),
+        EventState::Archived => return Err(ObserveError::EventArchived),
+        _ => {}
+    }
+    let current = cache.get_allowlist(&event.event_id).await.map_err(ObserveError::Cache)?;
+    if version != current.map_or(0, |a| a.version) + 1 {
+        return Err(ObserveError::PolicyVersionConflict);
+    }
+
+    lock_hashes.sort_unstable();
+    lock_hashes.dedup();
+    let allowlist = Allowlist {
+        event_id: event.event_id.clone(),
+        merkle_root,
+        entry_count: lock_hashes.len(),
+        version,
+        creator_signature,
+        updated_at: Utc::now(),
+    };
+    if !cache.store_allowlist(&allowlist, &lock_hashes).await.map_err(ObserveError::Cache)? {
+        return Err(ObserveError::PolicyVersionConflict);
+    }
+    Ok(allowlist)
+}
+
+/// The event's offline check-in policy, or a disabled one if it never set one.
+pub async fn get_offline_policy(cache: &Cache, event_id: &str) -> Result<OfflinePolicy, ObserveError> {
+    let event = cache
+        .get_active_event(event_id)
+        .await
+        .map_err(ObserveError::Cache)?
+        .ok_or(ObserveError::NotFound)?;
+
+    let policy = cache.get_offline_policy(&event.event_id).await.map_err(ObserveError::Cache)?;
+    Ok(policy.unwrap_or(OfflinePolicy {
+        event_id: event.event_id,
+        grace_secs: 0,
+        version: 0,
+        updated_at: event.activated_at,
+    }))
+}
+
+/// Replace the event's offline policy. `version` must be the stored
+/// policy's version + 1.
+pub async fn set_offline_policy(
+    cache: &Cache,
+    event: &ActiveEvent,
+    grace_secs: i64,
+    version: u32,
+) -> Result<OfflinePolicy, ObserveError> {
+    match event.state {
+        EventState::Cancelled => return Err(ObserveError::EventCancelled),
+        EventState::Archived => return Err(ObserveError::EventArchived),
+        _ => {}
+    }
+    let current = cache.get_offline_policy(&event.event_id).await.map_err(ObserveError::Cache)?;
+    if version != current.map_or(0, |p| p.version) + 1 {
+        return Err(ObserveError::PolicyVersionConflict);
+    }
+
+    let policy = OfflinePolicy {
+        event_id: event.event_id.clone(),
+        grace_secs,
+        version,
+        updated_at: Utc::now(),
+    };
+    if !cache.store_offline_policy(&policy).await.map_err(ObserveError::Cache)? {
+        return Err(ObserveError::PolicyVersionConflict);
+    }
+    Ok(policy)
+}
+
+pub async fn set_checkout_policy(
+    cache: &Cache,
+    event: &ActiveEvent,
+    checkout_session_id: &str,
+    min_duration_secs: i64,
+    version: u32,
+) -> Result<CheckoutPolicy, ObserveError> {
+    match event.state {
+        EventState::Cancelled => return Err(ObserveError::EventCancelled),
+        EventState::Archived => return Err(ObserveError::EventArchived),
+        _ => {}
+    }
+    let current = cache.get_checkout_policy(&event.event_id).await.map_err(ObserveError::Cache)?;
+    if version != current.map_or(0, |p| p.version) + 1 {
+        return Err(ObserveError::PolicyVersionConflict);
+    }
+
+    let policy = CheckoutPolicy {
+        event_id: event.event_id.clone(),
+        checkout_session_id: checkout_session_id.to_string(),
+        min_duration_secs,
+        version,
+        updated_at: Utc::now(),
+    };
+    if !cache.store_checkout_policy(&policy).await.map_err(ObserveError::Cache)? {
+        return Err(ObserveError::PolicyVersionConflict);
+    }
+    Ok(policy)
+}
+
+pub async fn set_claim_policy(
+    cache: &Cache,
+    event: &ActiveEvent,
+    claim_period_secs: i64,
+    version: u32,
+) -> Result<ClaimPolicy, ObserveError> {
+    match event.state {
+        EventState::Cancelled => return Err(ObserveError::EventCancelled),
+        EventState::Archived => return Err(ObserveError::EventArchived),
+        _ => {}
+    }
+    let current = cache.get_claim_policy(&event.event_id).await.map_err(ObserveError::Cache)?;
+    if version != current.map_or(0, |p| p.version) + 1 {
+        return Err(ObserveError::PolicyVersionConflict);
+    }
+
+    let policy = ClaimPolicy {
+        event_id: event.event_id.clone(),
+        claim_period_secs,
+        version,
+        updated_at: Utc::now(),
+    };
+    if !cache.store_claim_policy(&policy).await.map_err(ObserveError::Cache)? {
+        return Err(ObserveError::PolicyVersionConflict);
+    }
+    Ok(policy)
+}
+
+/// Check-ins for an event, grouped by window session in session order.
+pub async fn get_session_checkins(
+    cache: &Cache,
+    event: &ActiveEvent,
+) -> Result<Vec<(WindowProof, Vec<CheckIn>)>, ObserveError> {
+    let mut checkins = cache.get_checkins(&event.event_id).await.map_err(ObserveError::Cache)?;
+    Ok(event
+        .windows
+        .iter()
+        .map(|window| {
+            let (ours, rest) = checkins.drain(..).partition(|c| c.session_id == window.session_id);
+            checkins = rest;
+            (window.clone(), ours)
+        })
+        .collect())
+}
+
+pub async fn get_state_transitions(
+    cache: &Cache,
+    event_id: &str,
+) -> Result<Vec<StateTransition>, ObserveError> {
     cache
-        .update_event_window(event_id, &window)
+        .get_event_transitions(event_id)
+        .await
+        .map_err(ObserveError::Cache)
+}
+
+/// The time-driven transition `event` is due for at `now`, if any, and
+/// when it became due.
+fn scheduled_transition(event: &ActiveEvent, now: DateTime<Utc>) -> Option<(EventState, DateTime<Utc>)> {
+    let ts = |secs: i64| DateTime::from_timestamp(secs, 0).unwrap_or(now);
+    let now_ts = now.timestamp();
+    let earliest_open = event.windows.iter().filter(|w| w.is_open_at(now_ts)).map(|w| w.window_start).min();
+    let upcoming = event.windows.iter().any(|w| w.window_start > now_ts);
+    let last_end = event.windows.iter().filter_map(|w| w.end()).max();
+
+    let (to, due) = match (event.state, earliest_open) {
+        (EventState::Active | EventState::Ended, Some(start)) => (EventState::WindowOpen, ts(start)),
+        (EventState::Ended, None) if upcoming => (EventState::Active, now),
+        (EventState::Ended, None) => {
+            let due = event.state_changed_at + Duration::days(ARCHIVE_AFTER_DAYS);
+            if due > now {
+                return None;
+            }
+            (EventState::Archived, due)
+        }
+        // Every window elapsed between sweeps; pass through WindowOpen.
+        (EventState::Active, None) if !event.windows.is_empty() && !upcoming => {
+            let first_start = event.windows.iter().map(|w| w.window_start).min()?;
+            (EventState::WindowOpen, ts(first_start))
+        }
+        (EventState::WindowOpen, None) if upcoming => (EventState::Active, now),
+        (EventState::WindowOpen, None) => (EventState::Ended, last_end.map_or(now, ts)),
+        _ => return None,
+    };
+
+    // Never record a transition as happening before the previous one.
+    Some((to, due.max(event.state_changed_at)))
+}
+
+/// Apply any time-driven transitions (window opening, closing, archival)
+/// that are due, persisting each one.
+pub async fn advance_lifecycle(
+    cache: &Cache,
+    event: &mut ActiveEvent,
+    now: DateTime<Utc>,
+) -> Result<(), ObserveError> {
+    while let Some((to, at)) = scheduled_transition(event, now) {
+        match transition(cache, event, to, at).await {
+            Ok(()) => {}
+            // Someone else moved it first; their view wins.
+            Err(ObserveError::StateChanged) => break,
+            Err(e) => return Err(e),
+        }
+    }
+    Ok(())
+}
+
+/// Background sweep applying due transitions to every live event.
+pub async fn advance_lifecycles(cache: &Cache) {
+    let events = match cache.list_active_events().await {
+        Ok(events) => events,
+        Err(e) => {
+            tracing::warn!("Failed to list events for lifecycle sweep: {}", e);
+            return;
+        }
+    };
+
+    let now = Utc::now();
+    for mut event in events {
+        if let Err(e) = advance_lifecycle(cache, &mut event, now).await {
+            tracing::warn!("Failed to advance lifecycle of {}: {}", event.event_id, e);
+        }
+    }
+}
+
+/// Validate and persist a single transition, updating `event` in place.
+async fn transition(
+    cache: &Cache,
+    event: &mut ActiveEvent,
+    to: EventState,
+    at: DateTime<Utc>,
+) -> Result<(), ObserveError> {
+    if !event.state.can_transition_to(to) {
+        return Err(ObserveError::InvalidTransition { from: event.state, to });
+    }
+
+    let applied = cache
+        .transition_event_state(&event.event_id, event.state, to, at)
         .await
         .map_err(ObserveError::Cache)?;
+    if !applied {
+        return Err(ObserveError::StateChanged);
+    }
+
+    event.state = to;
+    event.state_changed_at = at;
+    if to == EventState::Cancelled {
+        event.cancelled_at = Some(at);
+    }
     Ok(())
 }
 
+/// Apply a creator-signed metadata update. The caller verifies the signature;
+/// this enforces the version sequence and that the event is still live.
+pub async fn update_event_metadata(
+    cache: &Cache,
+    event_id: &str,
+    update: &EventUpdate,
+) -> Result<ActiveEvent, ObserveError> {
+    let event = cache
+        .get_active_event(event_id)
+        .await
+        .map_err(ObserveError::Cache)?
+        .ok_or(ObserveError::NotFound)?;
+
+    match event.state {
+        EventState::Cancelled => return Err(ObserveError::EventCancelled),
+        EventState::Archived => return Err(ObserveError::EventArchived),
+        _ => {}
+    }
+    if update.version != event.metadata_version + 1 {
+        return Err(ObserveError::VersionConflict);
+    }
+
+    let applied = cache
+        .update_event_metadata(event_id, event.metadata_version, &update.metadata, &update.creator_signature)
+        .await
+        .map_err(ObserveError::Cache)?;
+    if !applied {
+        return Err(ObserveError::VersionConflict);
+    }
+
+    cache
+        .get_active_event(event_id)
+        .await
+        .map_err(ObserveError::Cache)?
+        .ok_or(ObserveError::NotFound)
+}
+
+pub async fn get_metadata_history(
+    cache: &Cache,
+    event_id: &str,
+) -> Result<Vec<MetadataRevision>, ObserveError> {
+    cache
+        .get_event_metadata_history(event_id)
+        .await
+        .map_err(ObserveError::Cache)
+}
+
+/// Cancel an event, closing all of its attendance windows immediately.
+pub async fn cancel_event(cache: &Cache, event_id: &str) -> Result<ActiveEvent, ObserveError> {
+    let mut event = cache
+        .get_active_event(event_id)
+        .await
+        .map_err(ObserveError::Cache)?
+        .ok_or(ObserveError::NotFound)?;
+
+    if event.state == EventState::Cancelled {
+        return Err(ObserveError::EventCancelled);
+    }
+    if !event.state.can_transition_to(EventState::Cancelled) {
+        return Err(ObserveError::InvalidTransition { from: event.state, to: EventState::Cancelled });
+    }
+
+    let now = Utc::now();
+    for window in event.windows.iter_mut() {
+        if window.end().is_none_or(|end| end > now.timestamp()) {
+            window.closed_at = Some(now.timestamp());
+            cache.store_window(window).await.map_err(ObserveError::Cache)?;
+        }
+    }
+
+    transition(cache, &mut event, EventState::Cancelled, now).await?;
+    Ok(event)
+}
+
 #[derive(Debug, thiserror::Error)]
 pub enum ObserveError {
     #[error("cache error: {0}")]
@@ -178,12 +666,26 @@ pub enum ObserveError {
     PaymentNotConfirmed,
     #[error("rpc error: {0}")]
     Rpc(String),
+    #[error("event has been cancelled")]
+    EventCancelled,
+    #[error("metadata version conflict")]
+    VersionConflict,
+    #[error("policy version conflict")]
+    PolicyVersionConflict,
+    #[error("event has been archived")]
+    EventArchived,
+    #[error("event payment has not been observed")]
+    NotPaid,
+    #[error("cannot move event from {from} to {to}")]
+    InvalidTransition { from: EventState, to: EventState },
+    #[error("event state changed concurrently")]
+    StateChanged,
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::types::EventMetadata;
+    use crate::types::{EventMetadata, DEFAULT_SESSION_ID};
     use chrono::Utc;
 
     async fn test_cache() -> Cache {
@@ -202,6 +704,9 @@ mod tests {
             location: None,
             start_time: None,
             end_time: None,
+            max_attendees: None,
+            prerequisite_events: vec![],
+            min_prerequisites: None,
         }
     }
 
@@ -226,7 +731,11 @@ mod tests {
             payment_tx_hash: "0xtx".to_string(),
             payment_block_number: 100,
             activated_at: Utc::now(),
-            window: None,
+            metadata_version: 1,
+            cancelled_at: None,
+            state: EventState::Active,
+            state_changed_at: Utc::now(),
+            windows: vec![],
         };
         cache.store_active_event(&event).await.unwrap();
 
@@ -255,7 +764,11 @@ mod tests {
             payment_tx_hash: "0xtx".to_string(),
             payment_block_number: 100,
             activated_at: Utc::now(),
-            window: None,
+            metadata_version: 1,
+            cancelled_at: None,
+            state: EventState::Active,
+            state_changed_at: Utc::now(),
+            windows: vec![],
         };
         cache.store_active_event(&event).await.unwrap();
 
@@ -265,10 +778,8 @@ mod tests {
         assert!(result.verified_at_block.is_none());
     }
 
-    #[tokio::test]
-    async fn test_submit_payment_intent() {
-        let cache = test_cache().await;
-        let intent = PaymentIntent {
+    fn test_intent() -> PaymentIntent {
+        PaymentIntent {
             event_id_preimage: crate::types::EventIdPreimage {
                 creator_address: "ckt1q".to_string(),
                 timestamp: 1700000000,
@@ -279,10 +790,198 @@ mod tests {
             event_metadata: test_metadata(),
             declared_at: Utc::now(),
             expires_at: Utc::now() + chrono::Duration::hours(24),
-        };
+        }
+    }
 
-        let event_id = submit_payment_intent(&cache, intent).await.unwrap();
+    #[tokio::test]
+    async fn test_submit_payment_intent() {
+        let cache = test_cache().await;
+        let event_id = submit_payment_intent(&cache, test_intent()).await.unwrap();
         assert_eq!(event_id.len(), 64); // SHA256 hex
+
+        let transitions = get_state_transitions(&cache, &event_id).await.unwrap();
+        assert_eq!(transitions.len(), 1);
+        assert_eq!(transitions[0].to_state, EventState::Intent);
+    }
+
+    #[tokio::test]
+    async fn test_pending_event_cannot_open_window() {
+        let cache = test_cache().await;
+        let event = create_pending_event(&cache, test_intent()).await.unwrap();
+        assert_eq!(event.state, EventState::PendingPayment);
+
+        let window = WindowProof {
+            event_id: event.event_id.clone(),
+            window_start: Utc::now().timestamp(),
+            window_end: None,
+            creator_signature: "sig".to_string(),
+            window_secret_commitment: "commit".to_string(),
+            secret_salt: None,
+            rotated_at: None,
+            closed_at: None,
+            authorized_by: None,
+            session_id: DEFAULT_SESSION_ID.to_string(),
+            label: None,
+        };
+        let result = update_window(&cache, &event.event_id, window).await;
+        assert!(matches!(result, Err(ObserveError::NotPaid)));
+
+        let result = create_pending_event(&cache, test_intent()).await;
+        assert!(matches!(
+            result,
+            Err(ObserveError::InvalidTransition { from: EventState::PendingPayment, .. })
+        ));
+
+        let states: Vec<_> = get_state_transitions(&cache, &event.event_id)
+            .await
+            .unwrap()
+            .into_iter()
+            .map(|t| t.to_state)
+            .collect();
+        assert_eq!(states, vec![EventState::Intent, EventState::PendingPayment]);
+    }
+
+    #[tokio::test]
+    async fn test_advance_lifecycle_ends_and_archives() {
+        let cache = test_cache().await;
+        let now = Utc::now();
+        let window_end = now.timestamp() - 60;
+        let mut event = test_event();
+        event.state = EventState::WindowOpen;
+        event.state_changed_at = now - chrono::Duration::hours(2);
+        event.windows = vec![WindowProof {
+            event_id: "evt1".to_string(),
+            window_start: now.timestamp() - 3600,
+            window_end: Some(window_end),
+            creator_signature: "sig".to_string(),
+            window_secret_commitment: "commit".to_string(),
+            secret_salt: None,
+            rotated_at: None,
+            closed_at: None,
+            authorized_by: None,
+            session_id: DEFAULT_SESSION_ID.to_string(),
+            label: None,
+        }];
+        cache.store_active_event(&event).await.unwrap();
+
+        advance_lifecycle(&cache, &mut event, now).await.unwrap();
+        assert_eq!(event.state, EventState::Ended);
+        assert_eq!(event.state_changed_at.timestamp(), window_end);
+
+        let later = now + chrono::Duration::days(ARCHIVE_AFTER_DAYS + 1);
+        advance_lifecycle(&cache, &mut event, later).await.unwrap();
+        assert_eq!(event.state, EventState::Archived);
+
+        let loaded = cache.get_active_event("evt1").await.unwrap().unwrap();
+        assert_eq!(loaded.state, EventState::Archived);
+        assert!(matches!(cancel_event(&cache, "evt1").await, Err(ObserveError::InvalidTransition { .. })));
+    }
+
+    fn test_event() -> ActiveEvent {
+        ActiveEvent {
+            event_id: "evt1".to_string(),
+            metadata: test_metadata(),
+            creator_address: "ckt1q".to_string(),
+            payment_tx_hash: "0xtx".to_string(),
+            payment_block_number: 100,
+            activated_at: Utc::now(),
+            windows: vec![],
+            metadata_version: 1,
+            cancelled_at: None,
+            state: EventState::Active,
+            state_changed_at: Utc::now(),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_update_event_metadata_requires_next_version() {
+        let cache = test_cache().await;
+        cache.store_active_event(&test_event()).await.unwrap();
+
+        let mut metadata = test_metadata();
+        metadata.name = "Updated".to_string();
+        let update = EventUpdate { metadata, version: 3, creator_signature: "sig".to_string() };
+        let result = update_event_metadata(&cache, "evt1", &update).await;
+        assert!(matches!(result, Err(ObserveError::VersionConflict)));
+
+        let update = EventUpdate { version: 2, ..update };
+        let event = update_event_metadata(&cache, "evt1", &update).await.unwrap();
+        assert_eq!(event.metadata.name, "Updated");
+        assert_eq!(event.metadata_version, 2);
+        assert_eq!(get_metadata_history(&cache, "evt1").await.unwrap().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_cancel_event_closes_window_and_blocks_updates() {
+        let cache = test_cache().await;
+        let mut event = test_event();
+        event.windows = vec![WindowProof {
+            event_id: "evt1".to_string(),
+            window_start: Utc::now().timestamp() - 60,
+            window_end: None,
+            creator_signature: "sig".to_string(),
+            window_secret_commitment: "commit".to_string(),
+            secret_salt: None,
+            rotated_at: None,
+            closed_at: None,
+            authorized_by: None,
+            session_id: DEFAULT_SESSION_ID.to_string(),
+            label: None,
+        }];
+        cache.store_active_event(&event).await.unwrap();
+
+        let cancelled = cancel_event(&cache, "evt1").await.unwrap();
+        assert!(cancelled.cancelled_at.is_some());
+        assert_eq!(cancelled.state, EventState::Cancelled);
+
+        let loaded = cache.get_active_event("evt1").await.unwrap().unwrap();
+        assert!(loaded.cancelled_at.is_some());
+        assert!(!loaded.windows[0].is_open());
+
+        let update = EventUpdate { metadata: test_metadata(), version: 2, creator_signature: "sig".to_string() };
+        let result = update_event_metadata(&cache, "evt1", &update).await;
+        assert!(matches!(result, Err(ObserveError::EventCancelled)));
+        assert!(matches!(cancel_event(&cache, "evt1").await, Err(ObserveError::EventCancelled)));
+    }
+
+    #[tokio::test]
+    async fn test_lifecycle_waits_for_upcoming_session() {
+        let cache = test_cache().await;
+        let now = Utc::now();
+        let day1 = WindowProof {
+            event_id: "evt1".to_string(),
+            session_id: "day1".to_string(),
+            label: None,
+            window_start: now.timestamp() - 7200,
+            window_end: Some(now.timestamp() - 3600),
+            creator_signature: "sig".to_string(),
+            window_secret_commitment: "commit".to_string(),
+            secret_salt: None,
+            rotated_at: None,
+            closed_at: None,
+            authorized_by: None,
+        };
+        let day2 = WindowProof {
+            session_id: "day2".to_string(),
+            window_start: now.timestamp() + 3600,
+            window_end: Some(now.timestamp() + 7200),
+            ..day1.clone()
+        };
+        let mut event = test_event();
+        event.state = EventState::WindowOpen;
+        event.state_changed_at = now - chrono::Duration::hours(2);
+        event.windows = vec![day1, day2];
+        cache.store_active_event(&event).await.unwrap();
+
+        // Day 1 is over but day 2 is scheduled, so the event is not Ended.
+        advance_lifecycle(&cache, &mut event, now).await.unwrap();
+        assert_eq!(event.state, EventState::Active);
+
+        advance_lifecycle(&cache, &mut event, now + chrono::Duration::minutes(90)).await.unwrap();
+        assert_eq!(event.state, EventState::WindowOpen);
+
+        advance_lifecycle(&cache, &mut event, now + chrono::Duration::hours(3)).await.unwrap();
+        assert_eq!(event.state, EventState::Ended);
     }
 
     #[tokio::test]
@@ -296,7 +995,11 @@ mod tests {
             payment_tx_hash: "0xtx".to_string(),
             payment_block_number: 100,
             activated_at: Utc::now(),
-            window: None,
+            metadata_version: 1,
+            cancelled_at: None,
+            state: EventState::Active,
+            state_changed_at: Utc::now(),
+            windows: vec![],
         };
         cache.store_active_event(&event).await.unwrap();
 
@@ -306,10 +1009,169 @@ mod tests {
             window_end: None,
             creator_signature: "sig".to_string(),
             window_secret_commitment: "commit".to_string(),
+            secret_salt: None,
+            rotated_at: None,
+            closed_at: None,
+            authorized_by: None,
+            session_id: DEFAULT_SESSION_ID.to_string(),
+            label: None,
         };
-        update_window(&cache, "evt1", window).await.unwrap();
+        let updated = update_window(&cache, "evt1", window).await.unwrap();
+        assert_eq!(updated.state, EventState::WindowOpen);
 
         let loaded = cache.get_active_event("evt1").await.unwrap().unwrap();
-        assert!(loaded.window.is_some());
+        assert_eq!(loaded.windows.len(), 1);
+        assert_eq!(loaded.state, EventState::WindowOpen);
+    }
+
+    #[tokio::test]
+    async fn test_revoke_window_secret_rotates_and_flags() {
+        let cache = test_cache().await;
+        let now = Utc::now().timestamp();
+        let window = WindowProof {
+            event_id: "evt1".to_string(),
+            window_start: now - 600,
+            window_end: None,
+            creator_signature: "sig".to_string(),
+            window_secret_commitment: "commit".to_string(),
+            secret_salt: None,
+            rotated_at: None,
+            closed_at: None,
+            authorized_by: None,
+            session_id: DEFAULT_SESSION_ID.to_string(),
+            label: None,
+        };
+        let mut event = test_event();
+        event.state = EventState::WindowOpen;
+        event.windows = vec![window.clone()];
+        cache.store_active_event(&event).await.unwrap();
+
+        cache
+            .record_checkin(
+                &CheckIn {
+                    event_id: "evt1".to_string(),
+                    session_id: DEFAULT_SESSION_ID.to_string(),
+                    attendee_address: "ckt1qattendee".to_string(),
+                    attendee_lock_hash: "0xlock".to_string(),
+                    qr_timestamp: now - 60,
+                    checked_in_at: Utc::now(),
+                },
+                None,
+            )
+            .await
+            .unwrap();
+        cache
+            .store_badge_observation(&crate::types::BadgeObservation {
+                event_id: "evt1".to_string(),
+                holder_address: "ckt1qattendee".to_string(),
+                holder_lock_hash: "0xlock".to_string(),
+                mint_tx_hash: "0xmint".to_string(),
+                mint_block_number: 0,
+                verified_at_block: 0,
+                observed_at: Utc::now(),
+                flagged_at: None,
+                flag_reason: None,
+                role: crate::types::BadgeRole::Attendee,
+            })
+            .await
+            .unwrap();
+
+        let (rotated, flagged) = revoke_window_secret(&cache, &event, window.clone(), now).await.unwrap();
+        assert_eq!(flagged, 1);
+        assert_eq!(rotated.window_start, window.window_start);
+        assert_ne!(rotated.window_secret_commitment, window.window_secret_commitment);
+
+        let stored = cache.get_event_windows("evt1").await.unwrap();
+        assert_eq!(stored[0].rotated_at, Some(now));
+        assert_eq!(stored[0].secret_salt, rotated.secret_salt);
+
+        let badges = cache.get_badges_by_event("evt1").await.unwrap();
+        assert_eq!(badges[0].flag_reason.as_deref(), Some(REVOKED_SECRET_FLAG));
+
+        event.state = EventState::Cancelled;
+        let result = revoke_window_secret(&cache, &event, rotated, now).await;
+        assert!(matches!(result, Err(ObserveError::EventCancelled)));
+    }
+
+    #[tokio::test]
+    async fn test_offline_policy_rejects_stale_versions() {
+        let cache = test_cache().await;
+        let event = test_event();
+        cache.store_active_event(&event).await.unwrap();
+
+        set_offline_policy(&cache, &event, 6 * 3600, 1).await.unwrap();
+        set_offline_policy(&cache, &event, 0, 2).await.unwrap();
+
+        // Re-posting the first, wider policy cannot bring it back.
+        let result = set_offline_policy(&cache, &event, 6 * 3600, 1).await;
+        assert!(matches!(result, Err(ObserveError::PolicyVersionConflict)));
+        let policy = get_offline_policy(&cache, "evt1").await.unwrap();
+        assert_eq!((policy.grace_secs, policy.version), (0, 2));
+    }
+
+    #[tokio::test]
+    async fn test_checkout_policy_rejects_stale_versions() {
+        let cache = test_cache().await;
+        let event = test_event();
+        cache.store_active_event(&event).await.unwrap();
+
+        set_checkout_policy(&cache, &event, "exit", 3600, 1).await.unwrap();
+        set_checkout_policy(&cache, &event, "exit", 7200, 2).await.unwrap();
+
+        // Re-posting the first, shorter stay cannot bring it back.
+        let result = set_checkout_policy(&cache, &event, "exit", 3600, 1).await;
+        assert!(matches!(result, Err(ObserveError::PolicyVersionConflict)));
+        let policy = cache.get_checkout_policy("evt1").await.unwrap().unwrap();
+        assert_eq!((policy.min_duration_secs, policy.version), (7200, 2));
+    }
+
+    #[tokio::test]
+    async fn test_claim_policy_rejects_stale_versions() {
+        let cache = test_cache().await;
+        let event = test_event();
+        cache.store_active_event(&event).await.unwrap();
+
+        set_claim_policy(&cache, &event, 86400, 1).await.unwrap();
+        set_claim_policy(&cache, &event, 0, 2).await.unwrap();
+
+        // Re-posting the first policy cannot turn vouchers back on.
+        let result = set_claim_policy(&cache, &event, 86400, 1).await;
+        assert!(matches!(result, Err(ObserveError::PolicyVersionConflict)));
+        let policy = cache.get_claim_policy("evt1").await.unwrap().unwrap();
+        assert_eq!((policy.claim_period_secs, policy.version), (0, 2));
+    }
+
+    #[tokio::test]
+    async fn test_resubmitted_window_keeps_rotated_secret() {
+        let cache = test_cache().await;
+        let now = Utc::now().timestamp();
+        let mut event = test_event();
+        event.state = EventState::Active;
+        cache.store_active_event(&event).await.unwrap();
+
+        let mut window = WindowProof {
+            event_id: "evt1".to_string(),
+            window_start: now - 600,
+            window_end: None,
+            creator_signature: "sig".to_string(),
+            window_secret_commitment: String::new(),
+            secret_salt: None,
+            rotated_at: None,
+            closed_at: None,
+            authorized_by: None,
+            session_id: DEFAULT_SESSION_ID.to_string(),
+            label: None,
+        };
+        window.window_secret_commitment = qr::secret_commitment(&qr::window_secret(&window));
+        let event = update_window(&cache, "evt1", window.clone()).await.unwrap();
+        let (rotated, _) = revoke_window_secret(&cache, &event, window.clone(), now).await.unwrap();
+
+        // Replaying the original creator-signed window leaves the rotation in place.
+        let replayed = update_window(&cache, "evt1", window.clone()).await.unwrap();
+        let stored = replayed.window(DEFAULT_SESSION_ID).unwrap();
+        assert_eq!(stored.secret_salt, rotated.secret_salt);
+        assert_eq!(stored.rotated_at, Some(now));
+        assert_eq!(stored.window_secret_commitment, rotated.window_secret_commitment);
+        assert_ne!(stored.window_secret_commitment, window.window_secret_commitment);
     }
 }
@@ -0,0 +1,364 @@
+use std::collections::HashMap;
+use std::net::{IpAddr, Ipv4Addr, SocketAddr};
+use std::num::NonZeroU32;
+use std::sync::{Arc, Mutex};
+
+use axum::{
+    extract::{ConnectInfo, Request, State},
+    http::{header, HeaderValue, StatusCode},
+    middleware::{self, Next},
+    response::{IntoResponse, Response},
+    routing::MethodRouter,
+    Json,
+};
+use governor::{
+    clock::{Clock, DefaultClock},
+    DefaultKeyedRateLimiter, Quota, RateLimiter,
+};
+
+use crate::types::AuthSession;
+
+/// Default quota family for a route.
+#[derive(Clone, Copy, Debug)]
+pub enum RouteClass {
+    /// Creates or mutates backend state.
+    Write,
+    /// Consumes a QR frame or builds a badge transaction.
+    CheckIn,
+    /// Triggers indexer or node queries against the CKB chain.
+    ChainSync,
+}
+
+impl RouteClass {
+    /// Default (per-IP, per-address) requests per minute.
+    fn default_quotas(self) -> (u32, u32) {
+        match self {
+            RouteClass::Write => (30, 10),
+            RouteClass::CheckIn => (60, 6),
+            RouteClass::ChainSync => (30, 10),
+        }
+    }
+}
+
+/// Per-route limiter with independent keyed quotas for client IP and CKB
+/// address. The address quota is keyed on a lock hash the caller has proven
+/// it controls: the signed-in session's, or one the handler spends through
+/// `AddressQuota` after checking the request's signature.
+pub struct RouteLimiter {
+    route: &'static str,
+    per_ip: DefaultKeyedRateLimiter<IpAddr>,
+    per_address: Option<DefaultKeyedRateLimiter<String>>,
+    trust_forwarded: bool,
+}
+
+impl RouteLimiter {
+    fn new(route: &'static str, per_ip: u32, per_address: u32, trust_forwarded: bool) -> Self {
+        Self {
+            route,
+            per_ip: RateLimiter::keyed(per_minute(per_ip)),
+            per_address: (per_address > 0).then(|| RateLimiter::keyed(per_minute(per_address))),
+            trust_forwarded,
+        }
+    }
+
+    fn client_ip(&self, req: &Request) -> IpAddr {
+        if self.trust_forwarded {
+            // The client controls every entry but the last, which the trusted
+            // proxy appended for the peer it saw.
+            let forwarded = req
+                .headers()
+                .get("x-forwarded-for")
+                .and_then(|v| v.to_str().ok())
+                .and_then(|v| v.rsplit(',').next())
+                .and_then(|ip| ip.trim().parse().ok());
+            if let Some(ip) = forwarded {
+                return ip;
+            }
+        }
+
+        req.extensions()
+            .get::<ConnectInfo<SocketAddr>>()
+            .map(|ConnectInfo(addr)| addr.ip())
+            .unwrap_or(IpAddr::V4(Ipv4Addr::UNSPECIFIED))
+    }
+
+    fn check_ip(&self, ip: IpAddr) -> Result<(), u64> {
+        let clock = DefaultClock::default();
+        self.per_ip
+            .check_key(&ip)
+            .map_err(|not_until| retry_after_secs(not_until.wait_time_from(clock.now())))
+    }
+
+    fn check_address(&self, key: &str) -> Result<(), u64> {
+        let Some(limiter) = &self.per_address else {
+            return Ok(());
+        };
+        let clock = DefaultClock::default();
+        limiter
+            .check_key(&key.to_string())
+            .map_err(|not_until| retry_after_secs(not_until.wait_time_from(clock.now())))
+    }
+
+    fn retain_recent(&self) {
+        self.per_ip.retain_recent();
+        if let Some(limiter) = &self.per_address {
+            limiter.retain_recent();
+        }
+    }
+}
+
+/// A route's per-address quota, handed to its handler as a request
+/// extension. Handlers spend it on the address a request signed for, or on
+/// the address a chain query names, once they have that address.
+#[derive(Clone)]
+pub struct AddressQuota(Arc<RouteLimiter>);
+
+impl AddressQuota {
+    /// Spend one request of `lock_hash`'s quota, or return the seconds until
+    /// it allows another.
+    pub fn check(&self, lock_hash: &str) -> Result<(), u64> {
+        self.0.check_address(lock_hash).inspect_err(|_| {
+            tracing::debug!("Rate limited address on {}", self.0.route);
+        })
+    }
+}
+
+/// Rate limit configuration, read once from the environment.
+///
+/// Each route's quota can be overridden with `RATE_LIMIT_<ROUTE>=<per_ip>,<per_address>`
+/// (requests per minute; the per-IP value must be positive, and a per-address
+/// value of 0 disables address keying).
+/// Set `RATE_LIMIT_TRUST_FORWARDED=true` behind a reverse proxy to key on the
+/// last `X-Forwarded-For` entry, the one the proxy appended, instead of the
+/// peer address.
+pub struct RateLimits {
+    overrides: HashMap<String, (u32, u32)>,
+    trust_forwarded: bool,
+    limiters: Mutex<Vec<Arc<RouteLimiter>>>,
+}
+
+impl RateLimits {
+    pub fn from_env() -> Self {
+        let overrides = std::env::vars()
+            .filter_map(|(key, value)| {
+                let route = key.strip_prefix("RATE_LIMIT_")?;
+                if route == "TRUST_FORWARDED" {
+                    return None;
+                }
+                let quotas = parse_override(&value).unwrap_or_else(|| panic!("Invalid {key}={value}"));
+                Some((route.to_ascii_lowercase(), quotas))
+            })
+            .collect();
+
+        let trust_forwarded = std::env::var("RATE_LIMIT_TRUST_FORWARDED")
+            .map(|v| v == "true" || v == "1")
+            .unwrap_or(false);
+
+        Self {
+            overrides,
+            trust_forwarded,
+            limiters: Mutex::new(Vec::new()),
+        }
+    }
+
+    /// Wrap one route's handlers in its own limiter. `route` names the
+    /// override variable, e.g. `badges_build` reads `RATE_LIMIT_BADGES_BUILD`.
+    pub fn apply<S>(&self, route: &'static str, class: RouteClass, handlers: MethodRouter<S>) -> MethodRouter<S>
+    where
+        S: Clone + Send + Sync + 'static,
+    {
+        let (per_ip, per_address) = self
+            .overrides
+            .get(route)
+            .copied()
+            .unwrap_or_else(|| class.default_quotas());
+        let limiter = Arc::new(RouteLimiter::new(route, per_ip, per_address, self.trust_forwarded));
+        self.limiters.lock().unwrap().push(Arc::clone(&limiter));
+        handlers.layer(middleware::from_fn_with_state(limiter, enforce))
+    }
+
+    /// Drop keys whose quota has fully replenished. Called periodically so
+    /// the keyed state does not grow without bound.
+    pub fn retain_recent(&self) {
+        for limiter in self.limiters.lock().unwrap().iter() {
+            limiter.retain_recent();
+        }
+    }
+}
+
+/// Check the per-IP quota, then the per-address quota if the request carries
+/// a session, and forward the request with the route's `AddressQuota` when
+/// both allow it.
+async fn enforce(State(limiter): State<Arc<RouteLimiter>>, mut req: Request, next: Next) -> Response {
+    let ip = limiter.client_ip(&req);
+    if let Err(retry_after) = limiter.check_ip(ip) {
+        tracing::debug!("Rate limited {} on {}", ip, limiter.route);
+        return too_many_requests(retry_after);
+    }
+
+    // Sessions are only opened for a signed challenge, so their lock hash is
+    // one the caller has proven it controls.
+    if let Some(session) = req.extensions().get::<AuthSession>() {
+        if let Err(retry_after) = limiter.check_address(&session.lock_hash) {
+            tracing::debug!("Rate limited address on {}", limiter.route);
+            return too_many_requests(retry_after);
+        }
+    }
+
+    req.extensions_mut().insert(AddressQuota(limiter));
+    next.run(req).await
+}
+
+pub fn too_many_requests(retry_after: u64) -> Response {
+    let body = serde_json::json!({ "error": "rate limit exceeded" });
+    let mut response = (StatusCode::TOO_MANY_REQUESTS, Json(body)).into_response();
+    response
+        .headers_mut()
+        .insert(header::RETRY_AFTER, HeaderValue::from(retry_after));
+    response
+}
+
+fn parse_override(value: &str) -> Option<(u32, u32)> {
+    let (per_ip, per_address) = value.split_once(',')?;
+    let per_ip = per_ip.trim().parse().ok().filter(|&n| n > 0)?;
+    Some((per_ip, per_address.trim().parse().ok()?))
+}
+
+/// Quotas reaching here are positive: overrides are validated at startup
+/// and a zero per-address quota never builds a limiter.
+fn per_minute(requests: u32) -> Quota {
+    Quota::per_minute(NonZeroU32::new(requests).expect("rate limit quota must be positive"))
+}
+
+fn retry_after_secs(wait: std::time::Duration) -> u64 {
+    wait.as_secs() + u64::from(wait.subsec_nanos() > 0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::body::Body;
+    use axum::routing::{get, post};
+    use axum::Router;
+    use chrono::Utc;
+    use tower::ServiceExt;
+
+    const ADDRESS: &str = "ckb1qzda0cr08m85hc8jlnfp3zer7xulejywt49kt2rr0vthywaa50xwsqdnnw7qkdnnclfkg59uzn8umtfd2kwxceqxwquc4";
+
+    fn test_limits(route: &str, quotas: (u32, u32)) -> RateLimits {
+        RateLimits {
+            overrides: HashMap::from([(route.to_string(), quotas)]),
+            trust_forwarded: true,
+            limiters: Mutex::new(Vec::new()),
+        }
+    }
+
+    fn request(method: &str, uri: &str, ip: &str, body: &str) -> Request {
+        Request::builder()
+            .method(method)
+            .uri(uri)
+            .header("x-forwarded-for", ip)
+            .header("content-type", "application/json")
+            .body(Body::from(body.to_string()))
+            .unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_per_ip_quota_returns_429_with_retry_after() {
+        let limits = test_limits("ping", (2, 0));
+        let app: Router = Router::new().route("/ping", limits.apply("ping", RouteClass::Write, get(|| async { "ok" })));
+
+        for _ in 0..2 {
+            let res = app.clone().oneshot(request("GET", "/ping", "10.0.0.1", "")).await.unwrap();
+            assert_eq!(res.status(), StatusCode::OK);
+        }
+
+        let res = app.clone().oneshot(request("GET", "/ping", "10.0.0.1", "")).await.unwrap();
+        assert_eq!(res.status(), StatusCode::TOO_MANY_REQUESTS);
+        let retry_after: u64 = res.headers()[header::RETRY_AFTER].to_str().unwrap().parse().unwrap();
+        assert!(retry_after > 0);
+
+        // A different client IP has its own quota.
+        let res = app.oneshot(request("GET", "/ping", "10.0.0.2", "")).await.unwrap();
+        assert_eq!(res.status(), StatusCode::OK);
+    }
+
+    #[tokio::test]
+    async fn test_forwarded_ip_is_the_proxy_appended_hop() {
+        let limits = test_limits("ping", (1, 0));
+        let app: Router = Router::new().route("/ping", limits.apply("ping", RouteClass::Write, get(|| async { "ok" })));
+
+        let res = app.clone().oneshot(request("GET", "/ping", "1.1.1.1, 10.0.0.1", "")).await.unwrap();
+        assert_eq!(res.status(), StatusCode::OK);
+        // Rotating the client-supplied entries does not buy a fresh quota.
+        let res = app.oneshot(request("GET", "/ping", "2.2.2.2, 10.0.0.1", "")).await.unwrap();
+        assert_eq!(res.status(), StatusCode::TOO_MANY_REQUESTS);
+    }
+
+    #[tokio::test]
+    async fn test_per_address_quota_keys_on_session() {
+        let limits = test_limits("build", (100, 1));
+        let app: Router = Router::new().route(
+            "/build",
+            limits.apply("build", RouteClass::CheckIn, post(|body: String| async move { body })),
+        );
+        let body = format!(r#"{{"address":"{}"}}"#, ADDRESS);
+        let with_session = |ip: &str| {
+            let mut req = request("POST", "/build", ip, &body);
+            req.extensions_mut().insert(AuthSession {
+                address: ADDRESS.to_string(),
+                lock_hash: "0xlock".to_string(),
+                created_at: Utc::now(),
+                expires_at: Utc::now(),
+            });
+            req
+        };
+
+        let res = app.clone().oneshot(with_session("10.0.0.1")).await.unwrap();
+        assert_eq!(res.status(), StatusCode::OK);
+        let res = app.clone().oneshot(with_session("10.0.0.2")).await.unwrap();
+        assert_eq!(res.status(), StatusCode::TOO_MANY_REQUESTS);
+
+        // An address named in the body alone cannot spend that quota.
+        for ip in ["10.0.0.3", "10.0.0.4"] {
+            let res = app.clone().oneshot(request("POST", "/build", ip, &body)).await.unwrap();
+            assert_eq!(res.status(), StatusCode::OK);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_handler_spends_address_quota() {
+        let limits = test_limits("checkin", (100, 1));
+        let handler = |axum::Extension(quota): axum::Extension<AddressQuota>, body: String| async move {
+            // Stands in for a handler that has verified `body` signed for this lock hash.
+            match quota.check(&body) {
+                Ok(()) => StatusCode::OK.into_response(),
+                Err(retry_after) => too_many_requests(retry_after),
+            }
+        };
+        let app: Router = Router::new().route("/checkin", limits.apply("checkin", RouteClass::CheckIn, post(handler)));
+
+        let res = app.clone().oneshot(request("POST", "/checkin", "10.0.0.1", "0xlock")).await.unwrap();
+        assert_eq!(res.status(), StatusCode::OK);
+        let res = app.clone().oneshot(request("POST", "/checkin", "10.0.0.2", "0xlock")).await.unwrap();
+        assert_eq!(res.status(), StatusCode::TOO_MANY_REQUESTS);
+        assert!(res.headers().contains_key(header::RETRY_AFTER));
+        let res = app.oneshot(request("POST", "/checkin", "10.0.0.2", "0xother")).await.unwrap();
+        assert_eq!(res.status(), StatusCode::OK);
+    }
+
+    #[test]
+    fn test_parse_override() {
+        assert_eq!(parse_override("20, 5"), Some((20, 5)));
+        assert_eq!(parse_override("20, 0"), Some((20, 0)));
+        assert_eq!(parse_override("0, 5"), None);
+        assert_eq!(parse_override("20"), None);
+        assert_eq!(parse_override("a,b"), None);
+    }
+
+    #[test]
+    fn test_retry_after_rounds_up() {
+        assert_eq!(retry_after_secs(std::time::Duration::from_millis(1500)), 2);
+        assert_eq!(retry_after_secs(std::time::Duration::from_secs(3)), 3);
+    }
+}
@@ -0,0 +1,140 @@
+use serde_json::Value;
+
+use crate::relay::RelayError;
+use crate::rpc::{CkbRpcClient, RpcError};
+
+/// A script that exited non-zero while the node verified a transaction.
+#[derive(Debug, PartialEq, Eq)]
+pub struct ScriptFailure {
+    /// Which script failed, as the node reports it, e.g. `Outputs[0].Type`.
+    pub script: String,
+    pub exit_code: i8,
+}
+
+/// What a dob-badge exit code means, per the contract's error codes.
+pub fn dob_badge_reason(exit_code: i8) -> Option<&'static str> {
+    match exit_code {
+        1 => Some("invalid args: badge type args must be the event id hash and the recipient hash"),
+        2 => Some("badge already minted in this tx"),
+        3 => Some("badge already exists for this event and recipient"),
+        _ => None,
+    }
+}
+
+/// Verify `tx` on the node without submitting it, returning the cycles it
+/// consumes. A failing dob-badge type script comes back as `ScriptFailed`
+/// with its exit code's reason.
+///
+/// Only `broadcast_tx` dry-runs. The badge, batch, airdrop and achievement
+/// builders return a placeholder in place of the unsigned transaction, so
+/// there is nothing for the node to verify until the wallet has built and
+/// signed one.
+pub async fn dry_run(rpc: &CkbRpcClient, tx: &Value, dob_code_hash: Option<&str>) -> Result<u64, RelayError> {
+    rpc.estimate_cycles(tx).await.map_err(|e| rejection(e, tx, dob_code_hash))
+}
+
+/// Submit `tx` to the node's pool, returning the hash the node computed.
+pub async fn submit(rpc: &CkbRpcClient, tx: &Value, dob_code_hash: Option<&str>) -> Result<String, RelayError> {
+    rpc.send_transaction(tx).await.map_err(|e| rejection(e, tx, dob_code_hash))
+}
+
+/// Map the node's rejection of `tx` to a relay error. Exit codes only carry
+/// a dob-badge reason when the failing script is the dob-badge type script;
+/// other scripts use the same small codes for unrelated errors.
+fn rejection(error: RpcError, tx: &Value, dob_code_hash: Option<&str>) -> RelayError {
+    let message = match error {
+        RpcError::Rpc(message) => message,
+        e => return RelayError::ChainUnavailable(e.to_string()),
+    };
+    let dob_failure = parse_script_failure(&message).and_then(|failure| {
+        let code_hash = script_code_hash(tx, &failure.script)?;
+        let dob_code_hash = dob_code_hash?;
+        if !code_hash.trim_start_matches("0x").eq_ignore_ascii_case(dob_code_hash.trim_start_matches("0x")) {
+            return None;
+        }
+        Some((dob_badge_reason(failure.exit_code)?, failure))
+    });
+    match dob_failure {
+        Some((reason, failure)) => RelayError::ScriptFailed {
+            script: failure.script,
+            exit_code: failure.exit_code,
+            reason: reason.to_string(),
+        },
+        None => RelayError::TxRejected(message),
+    }
+}
+
+/// Code hash of a script the node names, e.g. `Outputs[1].Type`. Only
+/// output type scripts resolve from the transaction itself; an input's
+/// scripts live in the cell it spends.
+pub fn script_code_hash<'a>(tx: &'a Value, script: &str) -> Option<&'a str> {
+    let index: usize = script.strip_prefix("Outputs[")?.strip_suffix("].Type")?.parse().ok()?;
+    tx.get("outputs")?.get(index)?.get("type")?.get("code_hash")?.as_str()
+}
+
+/// Pull the failing script and its exit code out of a verification error.
+/// Nodes report the code either as `ValidationFailure: see error code N`
+/// or, before 0.105, as `ValidationFailure(N)`.
+pub fn parse_script_failure(message: &str) -> Option<ScriptFailure> {
+    let cause = &message[message.find("ValidationFailure")? + "ValidationFailure".len()..];
+    let code = match cause.find("error code ") {
+        Some(at) => &cause[at + "error code ".len()..],
+        None => cause.strip_prefix('(')?,
+    };
+    let code_len = code.find(|c: char| c != '-' && !c.is_ascii_digit()).unwrap_or(code.len());
+    let exit_code = code[..code_len].parse().ok()?;
+
+    let source = &message[message.find("source: ")? + "source: ".len()..];
+    let source_len = source.find([',', ' ', '}']).unwrap_or(source.len());
+
+    Some(ScriptFailure { script: source[..source_len].to_string(), exit_code })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_script_failure_formats() {
+        let current = "TransactionFailedToVerify: Verification failed Script(TransactionScriptError { \
+                       source: Outputs[1].Type, cause: ValidationFailure: see error code 2 on page \
+                       https://nervosnetwork.github.io/ckb-script-error-codes/by-type-hash/0xab.html#2 })";
+        assert_eq!(
+            parse_script_failure(current),
+            Some(ScriptFailure { script: "Outputs[1].Type".to_string(), exit_code: 2 })
+        );
+
+        let legacy = "Script(TransactionScriptError { source: Inputs[0].Lock, cause: ValidationFailure(-31) })";
+        assert_eq!(
+            parse_script_failure(legacy),
+            Some(ScriptFailure { script: "Inputs[0].Lock".to_string(), exit_code: -31 })
+        );
+
+        assert_eq!(parse_script_failure("InsufficientCellCapacity"), None);
+        assert_eq!(dob_badge_reason(2), Some("badge already minted in this tx"));
+        assert_eq!(dob_badge_reason(4), None);
+    }
+
+    #[test]
+    fn test_reason_only_for_dob_badge_script() {
+        let tx = serde_json::json!({
+            "outputs": [
+                { "type": { "code_hash": "0xABCD", "hash_type": "data1", "args": "0x" } },
+                { "type": { "code_hash": "0x1234", "hash_type": "type", "args": "0x" } },
+            ]
+        });
+        assert_eq!(script_code_hash(&tx, "Outputs[1].Type"), Some("0x1234"));
+        assert_eq!(script_code_hash(&tx, "Inputs[0].Type"), None);
+
+        let failure = |index: usize| {
+            RpcError::Rpc(format!("Script(TransactionScriptError {{ source: Outputs[{index}].Type, cause: ValidationFailure(3) }})"))
+        };
+        assert!(matches!(
+            rejection(failure(0), &tx, Some("0xabcd")),
+            RelayError::ScriptFailed { exit_code: 3, .. }
+        ));
+        // Another type script exiting with the same code is not a dob-badge failure.
+        assert!(matches!(rejection(failure(1), &tx, Some("0xabcd")), RelayError::TxRejected(_)));
+        assert!(matches!(rejection(failure(0), &tx, None), RelayError::TxRejected(_)));
+    }
+}
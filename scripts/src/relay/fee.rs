@@ -0,0 +1,133 @@
+use serde::{Deserialize, Serialize};
+
+use crate::rpc::{CkbRpcClient, FeeRateStatistics};
+
+pub const SHANNONS_PER_CKB: u64 = 100_000_000;
+
+/// Lowest fee rate CKB nodes relay, in shannons per 1000 bytes.
+pub const MIN_FEE_RATE: u64 = 1_000;
+
+/// Size model for badge transactions, used until the relay serializes the
+/// transactions it builds. The fixed part covers cell deps for the lock and
+/// the dob-badge type script, the funding input and its witness, and the
+/// change output.
+pub const BASE_TX_BYTES: usize = 1_000;
+
+/// Serialized size each badge adds: a cell with the holder's lock, the DOB
+/// type script and the badge's cell data.
+pub const BADGE_OUTPUT_BYTES: usize = 400;
+
+/// Capacity a badge cell needs, in shannons. Covers the holder lock, the
+/// DOB type script and the badge's cell data.
+pub const BADGE_CELL_CAPACITY: u64 = 200 * SHANNONS_PER_CKB;
+
+/// How quickly the built transaction should be picked up by miners.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum FeePriority {
+    /// The recent median rate.
+    Low,
+    /// The recent mean rate, never below `Low`.
+    #[default]
+    Normal,
+    /// Twice `Normal`, to get ahead of a busy pool.
+    High,
+}
+
+impl FeePriority {
+    /// Fee rate in shannons per 1000 bytes for this priority, given recent
+    /// chain statistics.
+    pub fn fee_rate(self, stats: &FeeRateStatistics) -> u64 {
+        let low = stats.median.max(MIN_FEE_RATE);
+        let normal = stats.mean.max(low);
+        match self {
+            FeePriority::Low => low,
+            FeePriority::Normal => normal,
+            FeePriority::High => normal * 2,
+        }
+    }
+}
+
+/// What a built transaction costs whoever funds it. The size and fee come
+/// from the `BASE_TX_BYTES` / `BADGE_OUTPUT_BYTES` model, not the serialized
+/// transaction, so the wallet must recompute the fee from the transaction
+/// it signs.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct FeeEstimate {
+    pub fee_priority: FeePriority,
+    /// Shannons per 1000 bytes.
+    pub fee_rate: u64,
+    /// Modelled serialized size in bytes.
+    pub estimated_tx_size: usize,
+    /// Fee in shannons for the modelled size.
+    pub estimated_fee: u64,
+    /// Capacity the new badge cells need, in shannons, on top of the fee.
+    pub required_capacity: u64,
+}
+
+impl FeeEstimate {
+    /// Estimate for a transaction minting `badge_outputs` badges at `fee_rate`.
+    pub fn for_badges(fee_priority: FeePriority, fee_rate: u64, badge_outputs: usize) -> Self {
+        let estimated_tx_size = tx_size(badge_outputs);
+        Self {
+            fee_priority,
+            fee_rate,
+            estimated_tx_size,
+            estimated_fee: fee_for(estimated_tx_size, fee_rate),
+            required_capacity: badge_outputs as u64 * BADGE_CELL_CAPACITY,
+        }
+    }
+}
+
+/// Modelled serialized size of a transaction minting `badge_outputs` badges.
+pub fn tx_size(badge_outputs: usize) -> usize {
+    BASE_TX_BYTES + badge_outputs * BADGE_OUTPUT_BYTES
+}
+
+/// Fee in shannons for `tx_size` bytes at `fee_rate`, rounded up as nodes do.
+pub fn fee_for(tx_size: usize, fee_rate: u64) -> u64 {
+    (tx_size as u64 * fee_rate).div_ceil(1000)
+}
+
+/// Fee rate for `priority` from the node's recent statistics. Falls back to
+/// the minimum relay rate when the node has none or cannot be reached.
+pub async fn select_fee_rate(rpc: &CkbRpcClient, priority: FeePriority) -> u64 {
+    let stats = match rpc.get_fee_rate_statistics(None).await {
+        Ok(Some(stats)) => stats,
+        Ok(None) => FeeRateStatistics { mean: MIN_FEE_RATE, median: MIN_FEE_RATE },
+        Err(e) => {
+            tracing::warn!("Fee rate statistics unavailable, using the minimum rate: {e}");
+            FeeRateStatistics { mean: MIN_FEE_RATE, median: MIN_FEE_RATE }
+        }
+    };
+    priority.fee_rate(&stats)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_fee_rate_by_priority() {
+        let stats = FeeRateStatistics { mean: 3_000, median: 2_000 };
+        assert_eq!(FeePriority::Low.fee_rate(&stats), 2_000);
+        assert_eq!(FeePriority::Normal.fee_rate(&stats), 3_000);
+        assert_eq!(FeePriority::High.fee_rate(&stats), 6_000);
+
+        // Quiet chains still pay the minimum relay rate.
+        let quiet = FeeRateStatistics { mean: 10, median: 5 };
+        assert_eq!(FeePriority::Low.fee_rate(&quiet), MIN_FEE_RATE);
+        assert_eq!(FeePriority::Normal.fee_rate(&quiet), MIN_FEE_RATE);
+    }
+
+    #[test]
+    fn test_fee_estimate_rounds_up() {
+        assert_eq!(fee_for(1_401, 1_000), 1_401);
+        assert_eq!(fee_for(1_401, 1_500), 2_102);
+
+        let estimate = FeeEstimate::for_badges(FeePriority::Normal, 1_000, 2);
+        assert_eq!(estimate.estimated_tx_size, BASE_TX_BYTES + 2 * BADGE_OUTPUT_BYTES);
+        assert_eq!(estimate.estimated_fee, 1_800);
+        assert_eq!(estimate.required_capacity, 2 * BADGE_CELL_CAPACITY);
+    }
+}
@@ -1,2 +1,4 @@
+pub mod dry_run;
+pub mod fee;
 pub mod tx;
 pub use tx::*;
@@ -1,21 +1,157 @@
+use std::collections::HashSet;
+
 use serde::{Deserialize, Serialize};
 
-use crate::cache::Cache;
+use crate::achievement::AchievementEligibility;
+use crate::cache::{Cache, CheckInOutcome};
 use crate::crypto::{qr, signatures};
+use crate::relay::dry_run;
+use crate::relay::fee::{self, FeeEstimate, FeePriority};
+use crate::roles;
 use crate::rpc::CkbRpcClient;
-use crate::types::AttendanceProof;
+use crate::types::{
+    Achievement, ActiveEvent, AirdropInclusion, AttendanceProof, AttendanceTier, BadgeRole, CheckIn, CheckoutPolicy,
+    ClaimVoucher, DurationProof, EventState, PresenceRequest, QrPayload, RsvpStatus, Sponsorship, VoucherRecord,
+    WindowProof,
+};
 
 #[derive(Debug, Serialize, Deserialize)]
 pub struct BuildBadgeTxRequest {
     pub event_id: String,
     pub address: String,
     pub attendance_proof: AttendanceProof,
+    #[serde(default)]
+    pub fee_priority: FeePriority,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
 pub struct BuildBadgeTxResponse {
     pub unsigned_tx: String,
     pub tx_hash: String,
+    /// Window session the attendance proof was verified against.
+    pub session_id: String,
+    /// Role written into the badge's cell data.
+    pub role: BadgeRole,
+    #[serde(flatten)]
+    pub fee: FeeEstimate,
+    /// Set when the event's sponsor budget pays for the badge.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub sponsor: Option<Sponsorship>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct CheckOutResponse {
+    #[serde(flatten)]
+    pub badge: BuildBadgeTxResponse,
+    pub duration: DurationProof,
+    pub duration_secs: i64,
+    /// For the badge metadata; commits to both scans and the tier earned.
+    pub attendance_proof_hash: String,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct RedeemVoucherRequest {
+    pub event_id: String,
+    pub code: String,
+    /// Address the badge is minted to.
+    pub address: String,
+    /// Signature by `address` over the claim message.
+    pub attendee_signature: String,
+    #[serde(default)]
+    pub fee_priority: FeePriority,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct PresenceCheckInRequest {
+    /// Contents of the attendee's presence QR, as scanned.
+    pub presence_qr: String,
+    /// Window session to check in to; defaults to the current open window.
+    pub session_id: Option<String>,
+    /// Optional when the request carries an organizer's sign-in session.
+    pub organizer_signature: Option<String>,
+    /// Delegate who produced `organizer_signature`; the creator when omitted.
+    pub organizer_address: Option<String>,
+    #[serde(default)]
+    pub fee_priority: FeePriority,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct BuildAchievementTxRequest {
+    pub achievement_id: String,
+    pub address: String,
+    #[serde(default)]
+    pub fee_priority: FeePriority,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct BuildAchievementTxResponse {
+    pub unsigned_tx: String,
+    pub tx_hash: String,
+    /// Badge type script args: the achievement's namespaced id hash, then
+    /// `SHA256(address)`.
+    pub type_args: String,
+    #[serde(flatten)]
+    pub fee: FeeEstimate,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct AirdropRequest {
+    /// Checked-in attendees to mint badges to.
+    pub attendee_addresses: Vec<String>,
+    #[serde(default)]
+    pub fee_priority: FeePriority,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct AirdropRecipient {
+    pub address: String,
+    pub lock_hash: String,
+    pub role: BadgeRole,
+}
+
+/// One organizer-funded transaction minting a badge per recipient. The
+/// organizer reports the transaction's hash under `batch_id` once it is
+/// broadcast.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct AirdropBatch {
+    pub unsigned_tx: String,
+    pub batch_id: String,
+    pub recipients: Vec<AirdropRecipient>,
+    /// Modelled size and fee; the size stays within `MAX_TX_BYTES`.
+    #[serde(flatten)]
+    pub fee: FeeEstimate,
+    /// Modelled script cycles, within `MAX_AIRDROP_TX_CYCLES`.
+    pub estimated_cycles: u64,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct AirdropTxReport {
+    /// Hash of the broadcast batch transaction.
+    pub tx_hash: String,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum AirdropSkipReason {
+    InvalidAddress,
+    NotCheckedIn,
+    AlreadyMinted,
+    AlreadyIncluded,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct AirdropSkip {
+    pub address: String,
+    pub reason: AirdropSkipReason,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct AirdropResponse {
+    pub event_id: String,
+    /// Organizer whose cells fund every batch.
+    pub funder_address: String,
+    pub batches: Vec<AirdropBatch>,
+    pub skipped: Vec<AirdropSkip>,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -27,39 +163,51 @@ pub struct BroadcastRequest {
 pub struct BroadcastResponse {
     pub tx_hash: String,
     pub status: String,
+    /// Cycles the dry run consumed.
+    pub cycles: u64,
 }
 
+/// Verify an attendance proof, returning the event and the window session
+/// the proof belongs to.
 pub async fn verify_attendance_proof(
     cache: &Cache,
     proof: &AttendanceProof,
-) -> Result<(), RelayError> {
+) -> Result<(ActiveEvent, WindowProof), RelayError> {
     let event = cache
         .get_active_event(&proof.event_id)
         .await
         .map_err(RelayError::Cache)?
         .ok_or(RelayError::EventNotFound)?;
 
-    let window = event.window.as_ref().ok_or(RelayError::WindowNotOpen)?;
+    check_accepts_checkins(&event)?;
+    let window = resolve_window(&event, &proof.qr_payload)?;
 
-    if !window.is_open() {
+    // Offline proofs may arrive after the window closed; their timing is
+    // checked against the signed scan time below instead.
+    let offline_grace = match proof.scanned_at {
+        Some(_) => Some(offline_grace_secs(cache, &proof.event_id).await?),
+        None => None,
+    };
+
+    if offline_grace.is_none() && !window.is_open() {
         return Err(RelayError::WindowClosed);
     }
 
-    let window_secret = qr::derive_window_secret(
-        &proof.event_id,
-        window.window_start,
-        &window.creator_signature,
-    );
-
-    if !qr::verify_qr_hmac(&window_secret, proof.qr_payload.timestamp, &proof.qr_payload.hmac) {
+    if !qr::verify_qr_hmac(&qr::window_secret(window), proof.qr_payload.timestamp, &proof.qr_payload.hmac) {
         return Err(RelayError::InvalidQrHmac);
     }
 
-    if !qr::validate_qr_freshness(
-        proof.qr_payload.timestamp,
-        window.window_start,
-        window.window_end,
-    ) {
+    let fresh = match (proof.scanned_at, offline_grace) {
+        (Some(scanned_at), Some(grace_secs)) => qr::validate_offline_qr(
+            proof.qr_payload.timestamp,
+            scanned_at,
+            window.window_start,
+            window.end(),
+            grace_secs,
+        ),
+        _ => qr::validate_qr_freshness(proof.qr_payload.timestamp, window.window_start, window.end()),
+    };
+    if !fresh {
         return Err(RelayError::QrExpired);
     }
 
@@ -72,57 +220,652 @@ pub async fn verify_attendance_proof(
     )
     .map_err(|_| RelayError::InvalidSignature)?;
 
+    let attendee_lock_hash = check_allowlist(cache, &proof.event_id, &proof.attendee_address).await?;
+    check_rsvp(cache, &proof.event_id, &attendee_lock_hash).await?;
+    check_prerequisites(cache, &event, &attendee_lock_hash).await?;
+
     if cache
-        .check_qr_replay(&proof.event_id, proof.qr_payload.timestamp)
+        .check_qr_replay(&proof.event_id, &window.session_id, proof.qr_payload.timestamp)
         .await
         .map_err(RelayError::Cache)?
     {
         return Err(RelayError::ReplayDetected);
     }
 
+    let window = window.clone();
+    Ok((event, window))
+}
+
+fn check_accepts_checkins(event: &ActiveEvent) -> Result<(), RelayError> {
+    match event.state {
+        EventState::Cancelled => Err(RelayError::EventCancelled),
+        EventState::Archived => Err(RelayError::EventArchived),
+        EventState::Intent | EventState::PendingPayment => Err(RelayError::EventNotPaid),
+        EventState::Active | EventState::WindowOpen | EventState::Ended => Ok(()),
+    }
+}
+
+/// Reject attendees missing from the event's allowlist, if it has one.
+/// Returns the attendee's lock hash.
+async fn check_allowlist(cache: &Cache, event_id: &str, attendee_address: &str) -> Result<String, RelayError> {
+    let lock_hash =
+        signatures::address_to_lock_hash(attendee_address).map_err(|_| RelayError::InvalidSignature)?;
+    if !cache.allowlist_permits(event_id, &lock_hash).await.map_err(RelayError::Cache)? {
+        return Err(RelayError::NotOnAllowlist);
+    }
+    Ok(lock_hash)
+}
+
+/// Reject attendees without a confirmed RSVP, if the event requires one.
+async fn check_rsvp(cache: &Cache, event_id: &str, lock_hash: &str) -> Result<(), RelayError> {
+    let required = cache
+        .get_rsvp_policy(event_id)
+        .await
+        .map_err(RelayError::Cache)?
+        .is_some_and(|p| p.required);
+    if !required {
+        return Ok(());
+    }
+
+    let rsvp = cache.get_rsvp(event_id, lock_hash).await.map_err(RelayError::Cache)?;
+    if !rsvp.is_some_and(|r| r.status == RsvpStatus::Confirmed) {
+        return Err(RelayError::RsvpRequired);
+    }
+    Ok(())
+}
+
+/// Reject attendees who do not hold enough badges from the event's
+/// prerequisite events. Only observed, unflagged badges count; callers that
+/// can reach the indexer should sync the attendee's badges first.
+async fn check_prerequisites(cache: &Cache, event: &ActiveEvent, lock_hash: &str) -> Result<(), RelayError> {
+    let required = event.metadata.required_prerequisites();
+    if required == 0 {
+        return Ok(());
+    }
+
+    let badges = cache.get_badges_by_lock_hash(lock_hash).await.map_err(RelayError::Cache)?;
+    let held = event
+        .metadata
+        .prerequisite_events
+        .iter()
+        .filter(|id| badges.iter().any(|b| &b.event_id == *id && b.flagged_at.is_none()))
+        .count();
+    if held < required {
+        return Err(RelayError::MissingPrerequisites { held, required });
+    }
     Ok(())
 }
 
+/// The event's offline grace period, if it accepts offline check-ins at all.
+async fn offline_grace_secs(cache: &Cache, event_id: &str) -> Result<i64, RelayError> {
+    cache
+        .get_offline_policy(event_id)
+        .await
+        .map_err(RelayError::Cache)?
+        .filter(|policy| policy.allows_offline())
+        .map(|policy| policy.grace_secs)
+        .ok_or(RelayError::OfflineNotAllowed)
+}
+
+/// Find the window session a QR payload was generated for. QR codes from
+/// before sessions existed carry no session id; match them by HMAC.
+pub fn resolve_window<'a>(event: &'a ActiveEvent, payload: &QrPayload) -> Result<&'a WindowProof, RelayError> {
+    if event.windows.is_empty() {
+        return Err(RelayError::WindowNotOpen);
+    }
+
+    match payload.session_id.as_deref() {
+        Some(session_id) => event.window(session_id).ok_or(RelayError::UnknownSession),
+        None => event
+            .windows
+            .iter()
+            .find(|w| qr::verify_qr_hmac(&qr::window_secret(w), payload.timestamp, &payload.hmac))
+            .ok_or(RelayError::InvalidQrHmac),
+    }
+}
+
 pub async fn build_badge_tx(
     cache: &Cache,
-    _rpc: &CkbRpcClient,
+    rpc: &CkbRpcClient,
     request: BuildBadgeTxRequest,
 ) -> Result<BuildBadgeTxResponse, RelayError> {
-    verify_attendance_proof(cache, &request.attendance_proof).await?;
+    let proof = &request.attendance_proof;
+    let (event, window) = verify_attendance_proof(cache, proof).await?;
+    if checkout_policy(cache, &event.event_id).await?.is_some() {
+        return Err(RelayError::CheckoutRequired);
+    }
+
+    claim_checkin(cache, &event, &window, proof).await?;
+
+    Ok(BuildBadgeTxResponse {
+        unsigned_tx: "placeholder_unsigned_tx".to_string(),
+        tx_hash: badge_tx_hash(&request.event_id, &request.address),
+        session_id: window.session_id,
+        role: holder_role(cache, &event.event_id, &request.address).await?,
+        fee: badge_fee(rpc, request.fee_priority).await,
+        sponsor: None,
+    })
+}
+
+/// Check in to an event that has a check-out policy. Nothing is minted
+/// yet: the badge is built at check-out, once the stay is known.
+pub async fn record_checkin_scan(cache: &Cache, proof: &AttendanceProof) -> Result<CheckIn, RelayError> {
+    let (event, window) = verify_attendance_proof(cache, proof).await?;
+    let policy = checkout_policy(cache, &event.event_id).await?.ok_or(RelayError::NoCheckoutPolicy)?;
+    if window.session_id == policy.checkout_session_id {
+        return Err(RelayError::CheckoutWindow);
+    }
+
+    claim_checkin(cache, &event, &window, proof).await
+}
 
+/// Check out by scanning the check-out window's QR. Pairs the scan with the
+/// attendee's earliest check-in, records the resulting duration proof and
+/// builds the badge carrying its hash.
+pub async fn check_out(
+    cache: &Cache,
+    rpc: &CkbRpcClient,
+    request: BuildBadgeTxRequest,
+) -> Result<CheckOutResponse, RelayError> {
+    let proof = &request.attendance_proof;
+    let (event, window) = verify_attendance_proof(cache, proof).await?;
+    let policy = checkout_policy(cache, &event.event_id).await?.ok_or(RelayError::NoCheckoutPolicy)?;
+    if window.session_id != policy.checkout_session_id {
+        return Err(RelayError::NotCheckoutWindow);
+    }
+
+    let attendee_lock_hash = signatures::address_to_lock_hash(&proof.attendee_address)
+        .map_err(|_| RelayError::InvalidSignature)?;
+    let checkin = cache
+        .get_checkins(&event.event_id)
+        .await
+        .map_err(RelayError::Cache)?
+        .into_iter()
+        .filter(|c| c.attendee_lock_hash == attendee_lock_hash && c.session_id != policy.checkout_session_id)
+        .filter(|c| c.qr_timestamp <= proof.qr_payload.timestamp)
+        .min_by_key(|c| c.qr_timestamp)
+        .ok_or(RelayError::NotCheckedIn)?;
+
+    let stayed = proof.qr_payload.timestamp - checkin.qr_timestamp;
+    let duration = DurationProof {
+        event_id: event.event_id.clone(),
+        attendee_address: proof.attendee_address.clone(),
+        attendee_lock_hash,
+        checkin_session_id: checkin.session_id,
+        checkin_timestamp: checkin.qr_timestamp,
+        checkout_session_id: window.session_id.clone(),
+        checkout_timestamp: proof.qr_payload.timestamp,
+        tier: if stayed >= policy.min_duration_secs { AttendanceTier::FullDuration } else { AttendanceTier::Partial },
+        recorded_at: chrono::Utc::now(),
+    };
+    if !cache.record_duration_proof(&duration).await.map_err(RelayError::Cache)? {
+        return Err(RelayError::AlreadyCheckedOut);
+    }
     cache
-        .record_qr_usage(
-            &request.attendance_proof.event_id,
-            request.attendance_proof.qr_payload.timestamp,
-        )
+        .record_qr_usage(&proof.event_id, &window.session_id, proof.qr_payload.timestamp)
         .await
         .map_err(RelayError::Cache)?;
 
-    let tx_hash = format!(
-        "0x{}",
-        hex::encode(sha2::Sha256::digest(
-            format!("{}:{}", request.event_id, request.address).as_bytes()
-        ))
-    );
+    Ok(CheckOutResponse {
+        badge: BuildBadgeTxResponse {
+            unsigned_tx: "placeholder_unsigned_tx".to_string(),
+            tx_hash: badge_tx_hash(&request.event_id, &request.address),
+            session_id: window.session_id,
+            role: holder_role(cache, &event.event_id, &request.address).await?,
+            fee: badge_fee(rpc, request.fee_priority).await,
+            sponsor: None,
+        },
+        duration_secs: duration.duration_secs(),
+        attendance_proof_hash: duration.proof_hash(),
+        duration,
+    })
+}
+
+/// Issue a claim voucher for a scanned QR frame, for an attendee with no
+/// wallet at hand. The frame is verified and burned as a check-in would
+/// be, so each frame yields at most one voucher or one check-in.
+pub async fn issue_voucher(cache: &Cache, payload: &QrPayload) -> Result<ClaimVoucher, RelayError> {
+    let event = cache
+        .get_active_event(&payload.event_id)
+        .await
+        .map_err(RelayError::Cache)?
+        .ok_or(RelayError::EventNotFound)?;
+    check_accepts_checkins(&event)?;
+    let policy = cache
+        .get_claim_policy(&event.event_id)
+        .await
+        .map_err(RelayError::Cache)?
+        .filter(|policy| policy.allows_claims())
+        .ok_or(RelayError::VouchersNotAllowed)?;
+    if checkout_policy(cache, &event.event_id).await?.is_some() {
+        return Err(RelayError::CheckoutRequired);
+    }
+
+    let window = resolve_window(&event, payload)?;
+    if !window.is_open() {
+        return Err(RelayError::WindowClosed);
+    }
+    let secret = qr::window_secret(window);
+    if !qr::verify_qr_hmac(&secret, payload.timestamp, &payload.hmac) {
+        return Err(RelayError::InvalidQrHmac);
+    }
+    if !qr::validate_qr_freshness(payload.timestamp, window.window_start, window.end()) {
+        return Err(RelayError::QrExpired);
+    }
+    if cache
+        .check_qr_replay(&event.event_id, &window.session_id, payload.timestamp)
+        .await
+        .map_err(RelayError::Cache)?
+    {
+        return Err(RelayError::ReplayDetected);
+    }
+    cache
+        .record_qr_usage(&event.event_id, &window.session_id, payload.timestamp)
+        .await
+        .map_err(RelayError::Cache)?;
+
+    let now = chrono::Utc::now();
+    let code = hex::encode(rand::random::<[u8; 32]>());
+    let voucher_hash = ClaimVoucher::code_hash(&code);
+    let expires_at = now.timestamp() + policy.claim_period_secs;
+    let message =
+        ClaimVoucher::message_to_sign(&event.event_id, &window.session_id, payload.timestamp, &voucher_hash, expires_at);
+    let record = VoucherRecord {
+        voucher_hash,
+        event_id: event.event_id.clone(),
+        session_id: window.session_id.clone(),
+        qr_timestamp: payload.timestamp,
+        expires_at,
+        signature: qr::sign_voucher(&secret, &message),
+        issued_at: now,
+        redeemed_by: None,
+        redeemed_at: None,
+    };
+    cache.store_voucher(&record).await.map_err(RelayError::Cache)?;
+
+    Ok(ClaimVoucher {
+        event_id: record.event_id,
+        session_id: record.session_id,
+        qr_timestamp: record.qr_timestamp,
+        code,
+        expires_at: record.expires_at,
+        signature: record.signature,
+    })
+}
+
+/// Redeem a claim voucher to `request.address` and build its badge. The
+/// attendee's checks (allowlist, RSVP, prerequisites, capacity) apply to
+/// the redeeming address.
+pub async fn redeem_voucher(
+    cache: &Cache,
+    rpc: &CkbRpcClient,
+    request: RedeemVoucherRequest,
+) -> Result<BuildBadgeTxResponse, RelayError> {
+    let voucher_hash = ClaimVoucher::code_hash(&request.code);
+    let voucher = cache
+        .get_voucher(&voucher_hash)
+        .await
+        .map_err(RelayError::Cache)?
+        .filter(|v| v.event_id == request.event_id)
+        .ok_or(RelayError::VoucherNotFound)?;
+    if voucher.redeemed_at.is_some() {
+        return Err(RelayError::VoucherRedeemed);
+    }
+    let now = chrono::Utc::now();
+    if now.timestamp() > voucher.expires_at {
+        return Err(RelayError::VoucherExpired);
+    }
+
+    let event = cache
+        .get_active_event(&voucher.event_id)
+        .await
+        .map_err(RelayError::Cache)?
+        .ok_or(RelayError::EventNotFound)?;
+    check_accepts_checkins(&event)?;
+    let window = event.window(&voucher.session_id).ok_or(RelayError::UnknownSession)?;
+    if qr::sign_voucher(&qr::window_secret(window), &voucher.signed_message()) != voucher.signature {
+        return Err(RelayError::InvalidVoucher);
+    }
+
+    let message = ClaimVoucher::claim_message_to_sign(&event.event_id, &voucher_hash, &request.address);
+    signatures::verify_ckb_address_signature(&message, &request.attendee_signature, &request.address)
+        .map_err(|_| RelayError::InvalidSignature)?;
+    let attendee_lock_hash = check_allowlist(cache, &event.event_id, &request.address).await?;
+    check_rsvp(cache, &event.event_id, &attendee_lock_hash).await?;
+    check_prerequisites(cache, &event, &attendee_lock_hash).await?;
+
+    let checkin = CheckIn {
+        event_id: event.event_id.clone(),
+        session_id: window.session_id.clone(),
+        attendee_address: request.address.clone(),
+        attendee_lock_hash,
+        qr_timestamp: voucher.qr_timestamp,
+        checked_in_at: now,
+    };
+    match cache
+        .redeem_voucher(&voucher_hash, &checkin, event.metadata.max_attendees)
+        .await
+        .map_err(RelayError::Cache)?
+    {
+        None => return Err(RelayError::VoucherRedeemed),
+        Some(CheckInOutcome::AtCapacity) => return Err(RelayError::CapacityReached),
+        Some(CheckInOutcome::Recorded | CheckInOutcome::AlreadyCheckedIn) => {}
+    }
 
     Ok(BuildBadgeTxResponse {
         unsigned_tx: "placeholder_unsigned_tx".to_string(),
-        tx_hash,
+        tx_hash: badge_tx_hash(&event.event_id, &request.address),
+        session_id: window.session_id.clone(),
+        role: holder_role(cache, &event.event_id, &request.address).await?,
+        fee: badge_fee(rpc, request.fee_priority).await,
+        sponsor: None,
+    })
+}
+
+/// Record a verified proof's check-in and burn its QR. The capacity slot is
+/// claimed first, so a refused check-in can be retried once a slot frees
+/// up. Checking in again to the same session is allowed.
+async fn claim_checkin(
+    cache: &Cache,
+    event: &ActiveEvent,
+    window: &WindowProof,
+    proof: &AttendanceProof,
+) -> Result<CheckIn, RelayError> {
+    let attendee_lock_hash = signatures::address_to_lock_hash(&proof.attendee_address)
+        .map_err(|_| RelayError::InvalidSignature)?;
+    let checkin = CheckIn {
+        event_id: proof.event_id.clone(),
+        session_id: window.session_id.clone(),
+        attendee_address: proof.attendee_address.clone(),
+        attendee_lock_hash,
+        qr_timestamp: proof.qr_payload.timestamp,
+        checked_in_at: chrono::Utc::now(),
+    };
+    let outcome = cache
+        .record_checkin(&checkin, event.metadata.max_attendees)
+        .await
+        .map_err(RelayError::Cache)?;
+    if outcome == CheckInOutcome::AtCapacity {
+        return Err(RelayError::CapacityReached);
+    }
+
+    cache
+        .record_qr_usage(&proof.event_id, &window.session_id, proof.qr_payload.timestamp)
+        .await
+        .map_err(RelayError::Cache)?;
+    Ok(checkin)
+}
+
+async fn holder_role(cache: &Cache, event_id: &str, address: &str) -> Result<BadgeRole, RelayError> {
+    let lock_hash = signatures::address_to_lock_hash(address).map_err(|_| RelayError::InvalidSignature)?;
+    roles::role_for(cache, event_id, &lock_hash).await.map_err(RelayError::Cache)
+}
+
+async fn checkout_policy(cache: &Cache, event_id: &str) -> Result<Option<CheckoutPolicy>, RelayError> {
+    cache.get_checkout_policy(event_id).await.map_err(RelayError::Cache)
+}
+
+/// Check in an attendee from the presence QR their wallet displayed, as
+/// scanned by the organizer. The caller has already authenticated the
+/// organizer for `event` and picked the `window` to check in to.
+pub async fn check_in_presence(
+    cache: &Cache,
+    rpc: &CkbRpcClient,
+    event: &ActiveEvent,
+    window: &WindowProof,
+    presence: &PresenceRequest,
+    fee_priority: FeePriority,
+) -> Result<BuildBadgeTxResponse, RelayError> {
+    check_accepts_checkins(event)?;
+    if checkout_policy(cache, &event.event_id).await?.is_some() {
+        return Err(RelayError::CheckoutRequired);
+    }
+
+    if !window.is_open() {
+        return Err(RelayError::WindowClosed);
+    }
+
+    if !qr::validate_qr_freshness(presence.timestamp, window.window_start, window.end()) {
+        return Err(RelayError::QrExpired);
+    }
+
+    signatures::verify_ckb_address_signature(
+        &presence.signed_message(),
+        &presence.attendee_signature,
+        &presence.attendee_address,
+    )
+    .map_err(|_| RelayError::InvalidSignature)?;
+
+    let attendee_lock_hash = check_allowlist(cache, &event.event_id, &presence.attendee_address).await?;
+    check_rsvp(cache, &event.event_id, &attendee_lock_hash).await?;
+    check_prerequisites(cache, event, &attendee_lock_hash).await?;
+    let outcome = cache
+        .record_checkin(
+            &CheckIn {
+                event_id: event.event_id.clone(),
+                session_id: window.session_id.clone(),
+                attendee_address: presence.attendee_address.clone(),
+                attendee_lock_hash,
+                qr_timestamp: presence.timestamp,
+                checked_in_at: chrono::Utc::now(),
+            },
+            event.metadata.max_attendees,
+        )
+        .await
+        .map_err(RelayError::Cache)?;
+    match outcome {
+        CheckInOutcome::Recorded => {}
+        CheckInOutcome::AlreadyCheckedIn => return Err(RelayError::AlreadyCheckedIn),
+        CheckInOutcome::AtCapacity => return Err(RelayError::CapacityReached),
+    }
+
+    Ok(BuildBadgeTxResponse {
+        unsigned_tx: "placeholder_unsigned_tx".to_string(),
+        tx_hash: badge_tx_hash(&event.event_id, &presence.attendee_address),
+        session_id: window.session_id.clone(),
+        role: holder_role(cache, &event.event_id, &presence.attendee_address).await?,
+        fee: badge_fee(rpc, fee_priority).await,
+        sponsor: None,
+    })
+}
+
+/// Build the badge transaction for an achievement, given an eligibility
+/// check made just before.
+pub async fn build_achievement_tx(
+    rpc: &CkbRpcClient,
+    eligibility: &AchievementEligibility,
+    fee_priority: FeePriority,
+) -> Result<BuildAchievementTxResponse, RelayError> {
+    if !eligibility.eligible {
+        return Err(RelayError::NotEligible {
+            held: eligibility.held_event_ids.len(),
+            required: eligibility.min_count,
+        });
+    }
+
+    let mut type_args = Achievement::type_args_prefix(&eligibility.achievement_id).to_vec();
+    type_args.extend_from_slice(&sha2::Sha256::digest(eligibility.address.as_bytes()));
+
+    Ok(BuildAchievementTxResponse {
+        unsigned_tx: "placeholder_unsigned_tx".to_string(),
+        tx_hash: badge_tx_hash(&format!("achievement:{}", eligibility.achievement_id), &eligibility.address),
+        type_args: format!("0x{}", hex::encode(type_args)),
+        fee: badge_fee(rpc, fee_priority).await,
+    })
+}
+
+/// Fee estimate for a transaction minting a single badge.
+async fn badge_fee(rpc: &CkbRpcClient, fee_priority: FeePriority) -> FeeEstimate {
+    FeeEstimate::for_badges(fee_priority, fee::select_fee_rate(rpc, fee_priority).await, 1)
+}
+
+/// Largest serialized transaction CKB nodes accept into the pool.
+const MAX_TX_BYTES: usize = 512_000;
+
+/// Cycle budget per airdrop transaction, far below the block limit so a
+/// batch never crowds out a block.
+const MAX_AIRDROP_TX_CYCLES: u64 = 70_000_000;
+
+/// Cycles of the funder's secp256k1 lock, verified once per transaction.
+/// Like the fee size model, this stands in for measuring the batch until
+/// the relay builds transactions the node can verify.
+const FUNDER_LOCK_CYCLES: u64 = 1_500_000;
+
+/// Cycles of the dob-badge type script for one badge output.
+const BADGE_OUTPUT_CYCLES: u64 = 1_500_000;
+
+/// Most badges one airdrop transaction holds under both limits.
+fn airdrop_batch_size() -> usize {
+    let by_size = (MAX_TX_BYTES - fee::BASE_TX_BYTES) / fee::BADGE_OUTPUT_BYTES;
+    let by_cycles = ((MAX_AIRDROP_TX_CYCLES - FUNDER_LOCK_CYCLES) / BADGE_OUTPUT_CYCLES) as usize;
+    by_size.min(by_cycles)
+}
+
+/// Mint badges to checked-in attendees in as few organizer-funded
+/// transactions as the size and cycle limits allow. An attendee is included
+/// in at most one airdrop unless their batch is released before it is
+/// broadcast; anyone who cannot be included is reported in `skipped` with
+/// the reason.
+pub async fn build_airdrop(
+    cache: &Cache,
+    rpc: &CkbRpcClient,
+    event: &ActiveEvent,
+    funder_address: &str,
+    attendee_addresses: &[String],
+    fee_priority: FeePriority,
+) -> Result<AirdropResponse, RelayError> {
+    check_accepts_checkins(event)?;
+
+    let checkins = cache.get_checkins(&event.event_id).await.map_err(RelayError::Cache)?;
+    let checked_in: HashSet<&str> = checkins.iter().map(|c| c.attendee_lock_hash.as_str()).collect();
+    let badges = cache.get_badges_by_event(&event.event_id).await.map_err(RelayError::Cache)?;
+    let minted: HashSet<&str> = badges.iter().map(|b| b.holder_lock_hash.as_str()).collect();
+    let inclusions = cache.get_airdrop_inclusions(&event.event_id).await.map_err(RelayError::Cache)?;
+    let mut included: HashSet<String> = inclusions.into_iter().map(|i| i.holder_lock_hash).collect();
+
+    let mut candidates = Vec::new();
+    let mut skipped = Vec::new();
+    for address in attendee_addresses {
+        let reason = match signatures::address_to_lock_hash(address) {
+            Err(_) => AirdropSkipReason::InvalidAddress,
+            Ok(lock_hash) if !checked_in.contains(lock_hash.as_str()) => AirdropSkipReason::NotCheckedIn,
+            Ok(lock_hash) if included.contains(&lock_hash) => AirdropSkipReason::AlreadyIncluded,
+            Ok(lock_hash) if minted.contains(lock_hash.as_str()) => AirdropSkipReason::AlreadyMinted,
+            Ok(lock_hash) => {
+                included.insert(lock_hash.clone());
+                candidates.push((address, lock_hash));
+                continue;
+            }
+        };
+        skipped.push(AirdropSkip { address: address.clone(), reason });
+    }
+
+    let fee_rate = fee::select_fee_rate(rpc, fee_priority).await;
+    let mut batches = Vec::new();
+    for chunk in candidates.chunks(airdrop_batch_size()) {
+        let batch_id = hex::encode(rand::random::<[u8; 16]>());
+        let mut recipients = Vec::with_capacity(chunk.len());
+        for (address, lock_hash) in chunk {
+            let inclusion = AirdropInclusion {
+                event_id: event.event_id.clone(),
+                holder_address: address.to_string(),
+                holder_lock_hash: lock_hash.clone(),
+                batch_id: batch_id.clone(),
+                tx_hash: String::new(),
+                funder_address: funder_address.to_string(),
+                included_at: chrono::Utc::now(),
+                confirmed_block: None,
+            };
+            // Lost a race with a concurrent airdrop for the same attendee.
+            if !cache.record_airdrop_inclusion(&inclusion).await.map_err(RelayError::Cache)? {
+                skipped.push(AirdropSkip { address: address.to_string(), reason: AirdropSkipReason::AlreadyIncluded });
+                continue;
+            }
+            recipients.push(AirdropRecipient {
+                address: address.to_string(),
+                lock_hash: lock_hash.clone(),
+                role: holder_role(cache, &event.event_id, address).await?,
+            });
+        }
+        if recipients.is_empty() {
+            continue;
+        }
+
+        batches.push(AirdropBatch {
+            unsigned_tx: "placeholder_unsigned_tx".to_string(),
+            batch_id,
+            fee: FeeEstimate::for_badges(fee_priority, fee_rate, recipients.len()),
+            estimated_cycles: FUNDER_LOCK_CYCLES + recipients.len() as u64 * BADGE_OUTPUT_CYCLES,
+            recipients,
+        });
+    }
+
+    Ok(AirdropResponse {
+        event_id: event.event_id.clone(),
+        funder_address: funder_address.to_string(),
+        batches,
+        skipped,
     })
 }
 
+/// Record the hash of a broadcast airdrop batch, returning the batch's
+/// inclusions. Reporting the same hash again is a no-op; a batch cannot be
+/// moved to another transaction.
+pub async fn report_airdrop_tx(
+    cache: &Cache,
+    event: &ActiveEvent,
+    batch_id: &str,
+    tx_hash: &str,
+) -> Result<Vec<AirdropInclusion>, RelayError> {
+    cache.report_airdrop_tx(&event.event_id, batch_id, tx_hash).await?;
+    let batch = cache.get_airdrop_batch(&event.event_id, batch_id).await?;
+    match batch.first() {
+        None => Err(RelayError::AirdropBatchNotFound),
+        Some(inclusion) if inclusion.tx_hash != tx_hash => Err(RelayError::AirdropBatchReported),
+        Some(_) => Ok(batch),
+    }
+}
+
+/// Release an airdrop batch the organizer never broadcast, so its attendees
+/// can be included in a later airdrop. Once the batch's hash is reported it
+/// may be on chain, so it cannot be released.
+pub async fn release_airdrop(cache: &Cache, event: &ActiveEvent, batch_id: &str) -> Result<u64, RelayError> {
+    match cache.release_airdrop_batch(&event.event_id, batch_id).await? {
+        0 if cache.get_airdrop_batch(&event.event_id, batch_id).await?.is_empty() => {
+            Err(RelayError::AirdropBatchNotFound)
+        }
+        0 => Err(RelayError::AirdropBatchReported),
+        released => Ok(released),
+    }
+}
+
+fn badge_tx_hash(event_id: &str, address: &str) -> String {
+    format!(
+        "0x{}",
+        hex::encode(sha2::Sha256::digest(format!("{}:{}", event_id, address).as_bytes()))
+    )
+}
+
+/// Dry-run the signed transaction, then submit it, so contract failures
+/// come back as reasons rather than a rejected broadcast. The builders only
+/// hand out a placeholder for the wallet's transaction, so this is the only
+/// place a transaction is dry-run: the first point there is one to verify.
 pub async fn broadcast_tx(
-    _rpc: &CkbRpcClient,
+    rpc: &CkbRpcClient,
+    dob_code_hash: Option<&str>,
     request: BroadcastRequest,
 ) -> Result<BroadcastResponse, RelayError> {
-    let tx_hash = format!(
-        "0x{}",
-        hex::encode(&sha2::Sha256::digest(request.signed_tx.as_bytes())[..32])
-    );
+    let tx: serde_json::Value = serde_json::from_str(&request.signed_tx).map_err(|_| RelayError::InvalidTransaction)?;
+    if !tx.is_object() {
+        return Err(RelayError::InvalidTransaction);
+    }
+    let cycles = dry_run::dry_run(rpc, &tx, dob_code_hash).await?;
+    let tx_hash = dry_run::submit(rpc, &tx, dob_code_hash).await?;
 
     Ok(BroadcastResponse {
         tx_hash,
         status: "submitted".to_string(),
+        cycles,
     })
 }
 
@@ -134,8 +877,16 @@ pub enum RelayError {
     Cache(#[from] sqlx::Error),
     #[error("event not found")]
     EventNotFound,
+    #[error("event has been cancelled")]
+    EventCancelled,
+    #[error("event has been archived")]
+    EventArchived,
+    #[error("event payment has not been observed")]
+    EventNotPaid,
     #[error("attendance window not open")]
     WindowNotOpen,
+    #[error("unknown window session")]
+    UnknownSession,
     #[error("attendance window closed")]
     WindowClosed,
     #[error("invalid QR HMAC")]
@@ -146,6 +897,54 @@ pub enum RelayError {
     ReplayDetected,
     #[error("invalid signature")]
     InvalidSignature,
+    #[error("event does not accept offline check-ins")]
+    OfflineNotAllowed,
+    #[error("attendee already checked in to this session")]
+    AlreadyCheckedIn,
+    #[error("attendee is not on the event allowlist")]
+    NotOnAllowlist,
+    #[error("event capacity reached")]
+    CapacityReached,
+    #[error("attendee holds {held} of {required} prerequisite badges")]
+    MissingPrerequisites { held: usize, required: usize },
+    #[error("address holds {held} of {required} qualifying badges")]
+    NotEligible { held: usize, required: u32 },
+    #[error("event requires check-in and check-out scans")]
+    CheckoutRequired,
+    #[error("event has no check-out policy")]
+    NoCheckoutPolicy,
+    #[error("check-out window cannot be used to check in")]
+    CheckoutWindow,
+    #[error("not the event's check-out window")]
+    NotCheckoutWindow,
+    #[error("attendee has not checked in")]
+    NotCheckedIn,
+    #[error("attendee already checked out")]
+    AlreadyCheckedOut,
+    #[error("event requires a confirmed RSVP to check in")]
+    RsvpRequired,
+    #[error("event does not issue claim vouchers")]
+    VouchersNotAllowed,
+    #[error("claim voucher not found")]
+    VoucherNotFound,
+    #[error("claim voucher expired")]
+    VoucherExpired,
+    #[error("claim voucher already redeemed")]
+    VoucherRedeemed,
+    #[error("invalid claim voucher signature")]
+    InvalidVoucher,
+    #[error("airdrop batch not found")]
+    AirdropBatchNotFound,
+    #[error("airdrop batch already reported under another transaction")]
+    AirdropBatchReported,
+    #[error("signed_tx is not a JSON transaction")]
+    InvalidTransaction,
+    #[error("{script} failed with exit code {exit_code}: {reason}")]
+    ScriptFailed { script: String, exit_code: i8, reason: String },
+    #[error("transaction rejected by dry run: {0}")]
+    TxRejected(String),
+    #[error("chain unavailable: {0}")]
+    ChainUnavailable(String),
 }
 
 #[cfg(test)]
@@ -175,20 +974,33 @@ mod tests {
                 location: None,
                 start_time: None,
                 end_time: None,
+                max_attendees: None,
+                prerequisite_events: vec![],
+                min_prerequisites: None,
             },
             creator_address: "ckt1q".to_string(),
             payment_tx_hash: "0xtx".to_string(),
             payment_block_number: 100,
             activated_at: Utc::now(),
-            window: Some(WindowProof {
+            metadata_version: 1,
+            cancelled_at: None,
+            state: EventState::WindowOpen,
+            state_changed_at: Utc::now(),
+            windows: vec![WindowProof {
                 event_id: "evt1".to_string(),
                 window_start: now - 3600,
                 window_end: Some(now + 3600),
                 creator_signature: "0xcreator_sig".to_string(),
                 window_secret_commitment: "commit".to_string(),
-            }),
+                secret_salt: None,
+                rotated_at: None,
+                closed_at: None,
+                authorized_by: None,
+                session_id: DEFAULT_SESSION_ID.to_string(),
+                label: None,
+            }],
         };
-        let window = event.window.clone().unwrap();
+        let window = event.windows[0].clone();
         cache.store_active_event(&event).await.unwrap();
         ("evt1".to_string(), window)
     }
@@ -199,9 +1011,10 @@ mod tests {
         let proof = AttendanceProof {
             event_id: "nonexistent".to_string(),
             attendee_address: "addr".to_string(),
-            qr_payload: QrPayload { event_id: "nonexistent".to_string(), timestamp: 0, hmac: "".to_string() },
+            qr_payload: QrPayload { event_id: "nonexistent".to_string(), timestamp: 0, hmac: "".to_string(), session_id: None },
             attendee_signature: "sig".to_string(),
             created_at: 0,
+            scanned_at: None,
         };
         let result = verify_attendance_proof(&cache, &proof).await;
         assert!(matches!(result, Err(RelayError::EventNotFound)));
@@ -214,27 +1027,75 @@ mod tests {
             event_id: "evt1".to_string(),
             metadata: EventMetadata {
                 name: "T".to_string(), description: "D".to_string(),
-                image_url: None, location: None, start_time: None, end_time: None,
+                image_url: None, location: None, start_time: None, end_time: None, max_attendees: None,
+                prerequisite_events: vec![], min_prerequisites: None,
             },
             creator_address: "ckt1q".to_string(),
             payment_tx_hash: "0xtx".to_string(),
             payment_block_number: 100,
             activated_at: Utc::now(),
-            window: None, // no window
+            metadata_version: 1,
+            cancelled_at: None,
+            state: EventState::Active,
+            state_changed_at: Utc::now(),
+            windows: vec![],
         };
         cache.store_active_event(&event).await.unwrap();
 
         let proof = AttendanceProof {
             event_id: "evt1".to_string(),
             attendee_address: "addr".to_string(),
-            qr_payload: QrPayload { event_id: "evt1".to_string(), timestamp: 0, hmac: "".to_string() },
+            qr_payload: QrPayload { event_id: "evt1".to_string(), timestamp: 0, hmac: "".to_string(), session_id: None },
             attendee_signature: "sig".to_string(),
             created_at: 0,
+            scanned_at: None,
         };
         let result = verify_attendance_proof(&cache, &proof).await;
         assert!(matches!(result, Err(RelayError::WindowNotOpen)));
     }
 
+    #[tokio::test]
+    async fn test_verify_attendance_proof_event_cancelled() {
+        let cache = test_cache().await;
+        setup_event_with_window(&cache).await;
+        cache
+            .transition_event_state("evt1", EventState::WindowOpen, EventState::Cancelled, Utc::now())
+            .await
+            .unwrap();
+
+        let proof = AttendanceProof {
+            event_id: "evt1".to_string(),
+            attendee_address: "addr".to_string(),
+            qr_payload: QrPayload { event_id: "evt1".to_string(), timestamp: 0, hmac: "".to_string(), session_id: None },
+            attendee_signature: "sig".to_string(),
+            created_at: 0,
+            scanned_at: None,
+        };
+        let result = verify_attendance_proof(&cache, &proof).await;
+        assert!(matches!(result, Err(RelayError::EventCancelled)));
+    }
+
+    #[tokio::test]
+    async fn test_verify_attendance_proof_event_not_paid() {
+        let cache = test_cache().await;
+        setup_event_with_window(&cache).await;
+        let mut event = cache.get_active_event("evt1").await.unwrap().unwrap();
+        event.payment_tx_hash.clear();
+        event.state = EventState::PendingPayment;
+        cache.store_active_event(&event).await.unwrap();
+
+        let proof = AttendanceProof {
+            event_id: "evt1".to_string(),
+            attendee_address: "addr".to_string(),
+            qr_payload: QrPayload { event_id: "evt1".to_string(), timestamp: 0, hmac: "".to_string(), session_id: None },
+            attendee_signature: "sig".to_string(),
+            created_at: 0,
+            scanned_at: None,
+        };
+        let result = verify_attendance_proof(&cache, &proof).await;
+        assert!(matches!(result, Err(RelayError::EventNotPaid)));
+    }
+
     #[tokio::test]
     async fn test_verify_attendance_proof_invalid_hmac() {
         let cache = test_cache().await;
@@ -247,9 +1108,11 @@ mod tests {
                 event_id: "evt1".to_string(),
                 timestamp: Utc::now().timestamp(),
                 hmac: "badhmacinvalid00".to_string(),
+                session_id: None,
             },
             attendee_signature: "sig".to_string(),
             created_at: Utc::now().timestamp(),
+            scanned_at: None,
         };
         let result = verify_attendance_proof(&cache, &proof).await;
         assert!(matches!(result, Err(RelayError::InvalidQrHmac)));
@@ -271,29 +1134,586 @@ mod tests {
                 event_id: "evt1".to_string(),
                 timestamp: old_ts,
                 hmac,
+                session_id: None,
             },
             attendee_signature: "sig".to_string(),
             created_at: Utc::now().timestamp(),
+            scanned_at: None,
         };
         let result = verify_attendance_proof(&cache, &proof).await;
         assert!(matches!(result, Err(RelayError::QrExpired)));
     }
 
     #[tokio::test]
-    async fn test_broadcast_tx_returns_hash() {
+    async fn test_build_badge_tx_records_checkin_per_session() {
+        use crate::crypto::signatures::test_wallet::TestWallet;
+
+        let cache = test_cache().await;
+        let (_, default_window) = setup_event_with_window(&cache).await;
+        let day2 = WindowProof {
+            session_id: "day2".to_string(),
+            label: Some("Day 2".to_string()),
+            creator_signature: "0xday2_seed".to_string(),
+            ..default_window
+        };
+        cache.store_window(&day2).await.unwrap();
+
+        let wallet = TestWallet::new(7, "ckt");
+        let payload = qr::generate_qr_payload(&day2);
+        let proof = AttendanceProof {
+            event_id: "evt1".to_string(),
+            attendee_address: wallet.address.clone(),
+            attendee_signature: wallet.sign(&AttendanceProof::message_to_sign("evt1", payload.timestamp, &wallet.address)),
+            qr_payload: payload.clone(),
+            created_at: Utc::now().timestamp(),
+            scanned_at: None,
+        };
+
+        // The same QR presented as another session fails its HMAC.
+        let mut mislabeled = proof.clone();
+        mislabeled.qr_payload.session_id = Some(DEFAULT_SESSION_ID.to_string());
+        let result = verify_attendance_proof(&cache, &mislabeled).await;
+        assert!(matches!(result, Err(RelayError::InvalidQrHmac)));
+
+        let request = BuildBadgeTxRequest {
+            event_id: "evt1".to_string(),
+            address: wallet.address.clone(),
+            attendance_proof: proof,
+            fee_priority: FeePriority::High,
+        };
+        let response = build_badge_tx(&cache, &test_rpc(), request).await.unwrap();
+        assert_eq!(response.session_id, "day2");
+        // No node to ask for fee statistics: the minimum rate, doubled for high priority.
+        assert_eq!(response.fee.fee_rate, 2 * fee::MIN_FEE_RATE);
+        assert_eq!(response.fee.estimated_fee, fee::fee_for(fee::tx_size(1), 2 * fee::MIN_FEE_RATE));
+        assert_eq!(response.fee.required_capacity, fee::BADGE_CELL_CAPACITY);
+
+        let checkins = cache.get_checkins("evt1").await.unwrap();
+        assert_eq!(checkins.len(), 1);
+        assert_eq!(checkins[0].session_id, "day2");
+        assert_eq!(checkins[0].attendee_address, wallet.address);
+    }
+
+    #[tokio::test]
+    async fn test_check_out_records_duration_tier() {
+        use crate::crypto::signatures::test_wallet::TestWallet;
+
+        let cache = test_cache().await;
+        let (_, entry) = setup_event_with_window(&cache).await;
+        let exit = WindowProof {
+            session_id: "exit".to_string(),
+            creator_signature: "0xexit_seed".to_string(),
+            ..entry.clone()
+        };
+        cache.store_window(&exit).await.unwrap();
+        let policy = CheckoutPolicy {
+            event_id: "evt1".to_string(),
+            checkout_session_id: "exit".to_string(),
+            min_duration_secs: 3600,
+            version: 1,
+            updated_at: Utc::now(),
+        };
+        cache.store_checkout_policy(&policy).await.unwrap();
+
+        let wallet = TestWallet::new(7, "ckt");
+        let scan = |window: &WindowProof| {
+            let payload = qr::generate_qr_payload(window);
+            AttendanceProof {
+                event_id: "evt1".to_string(),
+                attendee_address: wallet.address.clone(),
+                attendee_signature: wallet.sign(&AttendanceProof::message_to_sign("evt1", payload.timestamp, &wallet.address)),
+                qr_payload: payload,
+                created_at: Utc::now().timestamp(),
+                scanned_at: None,
+            }
+        };
+        let request = |proof: AttendanceProof| BuildBadgeTxRequest {
+            event_id: "evt1".to_string(),
+            address: wallet.address.clone(),
+            attendance_proof: proof,
+            fee_priority: FeePriority::Normal,
+        };
+
+        let result = build_badge_tx(&cache, &test_rpc(), request(scan(&entry))).await;
+        assert!(matches!(result, Err(RelayError::CheckoutRequired)));
+        let result = record_checkin_scan(&cache, &scan(&exit)).await;
+        assert!(matches!(result, Err(RelayError::CheckoutWindow)));
+        let result = check_out(&cache, &test_rpc(), request(scan(&exit))).await;
+        assert!(matches!(result, Err(RelayError::NotCheckedIn)));
+
+        let result = check_out(&cache, &test_rpc(), request(scan(&entry))).await;
+        assert!(matches!(result, Err(RelayError::NotCheckoutWindow)));
+        record_checkin_scan(&cache, &scan(&entry)).await.unwrap();
+
+        let response = check_out(&cache, &test_rpc(), request(scan(&exit))).await.unwrap();
+        assert_eq!(response.duration.checkin_session_id, DEFAULT_SESSION_ID);
+        assert_eq!(response.duration.tier, AttendanceTier::Partial);
+        assert_eq!(response.attendance_proof_hash, response.duration.proof_hash());
+
+        let proofs = cache.get_duration_proofs("evt1").await.unwrap();
+        assert_eq!(proofs.len(), 1);
+        assert_eq!(proofs[0].proof_hash(), response.attendance_proof_hash);
+    }
+
+    #[tokio::test]
+    async fn test_verify_attendance_proof_requires_prerequisites() {
+        use crate::crypto::signatures::test_wallet::TestWallet;
+
+        let cache = test_cache().await;
+        let (_, window) = setup_event_with_window(&cache).await;
+        let mut event = cache.get_active_event("evt1").await.unwrap().unwrap();
+        event.metadata.prerequisite_events = vec!["kickoff".to_string(), "meetup".to_string()];
+        event.metadata.min_prerequisites = Some(1);
+        cache.store_active_event(&event).await.unwrap();
+
+        let wallet = TestWallet::new(9, "ckt");
+        let payload = qr::generate_qr_payload(&window);
+        let proof = AttendanceProof {
+            event_id: "evt1".to_string(),
+            attendee_address: wallet.address.clone(),
+            attendee_signature: wallet.sign(&AttendanceProof::message_to_sign("evt1", payload.timestamp, &wallet.address)),
+            qr_payload: payload,
+            created_at: Utc::now().timestamp(),
+            scanned_at: None,
+        };
+
+        let result = verify_attendance_proof(&cache, &proof).await;
+        assert!(matches!(result, Err(RelayError::MissingPrerequisites { held: 0, required: 1 })));
+
+        let badge = BadgeObservation {
+            event_id: "kickoff".to_string(),
+            holder_address: wallet.address.clone(),
+            holder_lock_hash: signatures::address_to_lock_hash(&wallet.address).unwrap(),
+            mint_tx_hash: "0xkickoff".to_string(),
+            mint_block_number: 10,
+            verified_at_block: 10,
+            observed_at: Utc::now(),
+            flagged_at: Some(Utc::now()),
+            flag_reason: Some("revoked".to_string()),
+            role: BadgeRole::Attendee,
+        };
+        // Flagged badges do not count.
+        cache.store_badge_observation(&badge).await.unwrap();
+        let result = verify_attendance_proof(&cache, &proof).await;
+        assert!(matches!(result, Err(RelayError::MissingPrerequisites { .. })));
+
+        let meetup = BadgeObservation {
+            event_id: "meetup".to_string(),
+            mint_tx_hash: "0xmeetup".to_string(),
+            flagged_at: None,
+            flag_reason: None,
+            role: BadgeRole::Attendee,
+            ..badge
+        };
+        cache.store_badge_observation(&meetup).await.unwrap();
+        verify_attendance_proof(&cache, &proof).await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_claim_voucher_redeems_once() {
+        use crate::crypto::signatures::test_wallet::TestWallet;
+
+        let cache = test_cache().await;
+        let (_, window) = setup_event_with_window(&cache).await;
+        let payload = qr::generate_qr_payload(&window);
+        assert!(matches!(issue_voucher(&cache, &payload).await, Err(RelayError::VouchersNotAllowed)));
+
+        let policy = ClaimPolicy {
+            event_id: "evt1".to_string(),
+            claim_period_secs: 86400,
+            version: 1,
+            updated_at: Utc::now(),
+        };
+        cache.store_claim_policy(&policy).await.unwrap();
+        let voucher = issue_voucher(&cache, &payload).await.unwrap();
+        assert!(matches!(issue_voucher(&cache, &payload).await, Err(RelayError::ReplayDetected)));
+
+        // Only the code's hash is stored.
+        let code_hash = ClaimVoucher::code_hash(&voucher.code);
+        assert!(cache.get_voucher(&voucher.code).await.unwrap().is_none());
+        assert!(cache.get_voucher(&code_hash).await.unwrap().is_some());
+
+        let wallet = TestWallet::new(5, "ckt");
+        let request = |signature: String| RedeemVoucherRequest {
+            event_id: "evt1".to_string(),
+            code: voucher.code.clone(),
+            address: wallet.address.clone(),
+            attendee_signature: signature,
+            fee_priority: FeePriority::Normal,
+        };
+        let result = redeem_voucher(&cache, &test_rpc(), request(wallet.sign("other"))).await;
+        assert!(matches!(result, Err(RelayError::InvalidSignature)));
+
+        let signature = wallet.sign(&ClaimVoucher::claim_message_to_sign("evt1", &code_hash, &wallet.address));
+        let response = redeem_voucher(&cache, &test_rpc(), request(signature.clone())).await.unwrap();
+        assert_eq!(response.session_id, DEFAULT_SESSION_ID);
+        let checkins = cache.get_checkins("evt1").await.unwrap();
+        assert_eq!(checkins[0].qr_timestamp, voucher.qr_timestamp);
+
+        let result = redeem_voucher(&cache, &test_rpc(), request(signature)).await;
+        assert!(matches!(result, Err(RelayError::VoucherRedeemed)));
+    }
+
+    #[tokio::test]
+    async fn test_airdrop_batches_checked_in_attendees_once() {
+        use crate::crypto::signatures::test_wallet::TestWallet;
+
+        let cache = test_cache().await;
+        setup_event_with_window(&cache).await;
+        let event = cache.get_active_event("evt1").await.unwrap().unwrap();
+
+        // One more attendee than fits in a batch, plus one who never checked in.
+        let wallets: Vec<TestWallet> =
+            (1..=airdrop_batch_size() as u8 + 2).map(|seed| TestWallet::new(seed, "ckt")).collect();
+        let (absent, attended) = wallets.split_last().unwrap();
+        for wallet in attended {
+            let checkin = CheckIn {
+                event_id: "evt1".to_string(),
+                session_id: DEFAULT_SESSION_ID.to_string(),
+                attendee_address: wallet.address.clone(),
+                attendee_lock_hash: signatures::address_to_lock_hash(&wallet.address).unwrap(),
+                qr_timestamp: 1000,
+                checked_in_at: Utc::now(),
+            };
+            cache.record_checkin(&checkin, None).await.unwrap();
+        }
+
+        let mut addresses: Vec<String> = wallets.iter().map(|w| w.address.clone()).collect();
+        addresses.push(attended[0].address.clone());
+        addresses.push("not-an-address".to_string());
         let rpc = test_rpc();
-        let result = broadcast_tx(&rpc, BroadcastRequest {
-            signed_tx: "some_signed_tx_data".to_string(),
-        }).await.unwrap();
-        assert!(result.tx_hash.starts_with("0x"));
-        assert_eq!(result.status, "submitted");
+        let response = build_airdrop(&cache, &rpc, &event, "ckt1qorganizer", &addresses, FeePriority::Low).await.unwrap();
+
+        let sizes: Vec<usize> = response.batches.iter().map(|b| b.recipients.len()).collect();
+        assert_eq!(sizes, vec![airdrop_batch_size(), 1]);
+        for batch in &response.batches {
+            assert!(batch.fee.estimated_tx_size <= MAX_TX_BYTES && batch.estimated_cycles <= MAX_AIRDROP_TX_CYCLES);
+        }
+        let reasons: Vec<(&str, AirdropSkipReason)> =
+            response.skipped.iter().map(|s| (s.address.as_str(), s.reason)).collect();
+        assert_eq!(
+            reasons,
+            vec![
+                (absent.address.as_str(), AirdropSkipReason::NotCheckedIn),
+                (attended[0].address.as_str(), AirdropSkipReason::AlreadyIncluded),
+                ("not-an-address", AirdropSkipReason::InvalidAddress),
+            ]
+        );
+        assert_eq!(cache.get_airdrop_inclusions("evt1").await.unwrap().len(), attended.len());
+
+        let again =
+            build_airdrop(&cache, &rpc, &event, "ckt1qorganizer", &addresses[..1], FeePriority::Low).await.unwrap();
+        assert!(again.batches.is_empty());
+        assert_eq!(again.skipped[0].reason, AirdropSkipReason::AlreadyIncluded);
+    }
+
+    #[tokio::test]
+    async fn test_only_unreported_airdrop_batches_can_be_released() {
+        use crate::crypto::signatures::test_wallet::TestWallet;
+
+        let cache = test_cache().await;
+        setup_event_with_window(&cache).await;
+        let event = cache.get_active_event("evt1").await.unwrap().unwrap();
+
+        let wallets = [TestWallet::new(1, "ckt"), TestWallet::new(2, "ckt")];
+        for wallet in &wallets {
+            let checkin = CheckIn {
+                event_id: "evt1".to_string(),
+                session_id: DEFAULT_SESSION_ID.to_string(),
+                attendee_address: wallet.address.clone(),
+                attendee_lock_hash: signatures::address_to_lock_hash(&wallet.address).unwrap(),
+                qr_timestamp: 1000,
+                checked_in_at: Utc::now(),
+            };
+            cache.record_checkin(&checkin, None).await.unwrap();
+        }
+        let rpc = test_rpc();
+        let airdrop = |address: &str| {
+            let addresses = vec![address.to_string()];
+            let (cache, rpc, event) = (&cache, &rpc, &event);
+            async move { build_airdrop(cache, rpc, event, "ckt1qorganizer", &addresses, FeePriority::Low).await.unwrap() }
+        };
+
+        // A broadcast batch is reported and stays; an abandoned one is released.
+        let broadcast = airdrop(&wallets[0].address).await.batches.remove(0);
+        let reported = report_airdrop_tx(&cache, &event, &broadcast.batch_id, "0xbatch").await.unwrap();
+        assert_eq!(reported[0].tx_hash, "0xbatch");
+        report_airdrop_tx(&cache, &event, &broadcast.batch_id, "0xbatch").await.unwrap();
+        assert!(matches!(
+            report_airdrop_tx(&cache, &event, &broadcast.batch_id, "0xother").await,
+            Err(RelayError::AirdropBatchReported)
+        ));
+        assert!(matches!(
+            release_airdrop(&cache, &event, &broadcast.batch_id).await,
+            Err(RelayError::AirdropBatchReported)
+        ));
+
+        let abandoned = airdrop(&wallets[1].address).await.batches.remove(0);
+        assert_eq!(release_airdrop(&cache, &event, &abandoned.batch_id).await.unwrap(), 1);
+        assert!(matches!(
+            release_airdrop(&cache, &event, &abandoned.batch_id).await,
+            Err(RelayError::AirdropBatchNotFound)
+        ));
+
+        cache.confirm_airdrop_inclusions("0xbatch", 42).await.unwrap();
+        let inclusions = cache.get_airdrop_inclusions("evt1").await.unwrap();
+        assert_eq!(inclusions.len(), 1);
+        assert_eq!(inclusions[0].confirmed_block, Some(42));
+        assert_eq!(airdrop(&wallets[1].address).await.batches.len(), 1);
+        assert_eq!(airdrop(&wallets[0].address).await.skipped[0].reason, AirdropSkipReason::AlreadyIncluded);
+    }
+
+    #[tokio::test]
+    async fn test_verify_attendance_proof_requires_confirmed_rsvp() {
+        use crate::crypto::signatures::test_wallet::TestWallet;
+
+        let cache = test_cache().await;
+        let (_, window) = setup_event_with_window(&cache).await;
+        let policy = RsvpPolicy {
+            event_id: "evt1".to_string(),
+            capacity: Some(1),
+            required: true,
+            version: 1,
+            updated_at: Utc::now(),
+        };
+        cache.store_rsvp_policy(&policy).await.unwrap();
+
+        let wallet = TestWallet::new(9, "ckt");
+        let payload = qr::generate_qr_payload(&window);
+        let proof = AttendanceProof {
+            event_id: "evt1".to_string(),
+            attendee_address: wallet.address.clone(),
+            attendee_signature: wallet.sign(&AttendanceProof::message_to_sign("evt1", payload.timestamp, &wallet.address)),
+            qr_payload: payload,
+            created_at: Utc::now().timestamp(),
+            scanned_at: None,
+        };
+        let result = verify_attendance_proof(&cache, &proof).await;
+        assert!(matches!(result, Err(RelayError::RsvpRequired)));
+
+        let rsvp = |address: &str, lock_hash: String| Rsvp {
+            event_id: "evt1".to_string(),
+            attendee_address: address.to_string(),
+            attendee_lock_hash: lock_hash,
+            status: RsvpStatus::Confirmed,
+            attendee_signature: "0xsig".to_string(),
+            created_at: Utc::now(),
+        };
+        // A waitlisted RSVP does not count.
+        cache.record_rsvp(&rsvp("someone", "0xlock_someone".to_string()), Some(1)).await.unwrap();
+        let lock_hash = signatures::address_to_lock_hash(&wallet.address).unwrap();
+        let stored = cache.record_rsvp(&rsvp(&wallet.address, lock_hash), Some(1)).await.unwrap();
+        assert_eq!(stored.status, RsvpStatus::Waitlisted);
+        let result = verify_attendance_proof(&cache, &proof).await;
+        assert!(matches!(result, Err(RelayError::RsvpRequired)));
+
+        cache.delete_rsvp("evt1", "0xlock_someone").await.unwrap();
+        cache.promote_waitlist("evt1", Some(1)).await.unwrap();
+        verify_attendance_proof(&cache, &proof).await.unwrap();
     }
 
     #[tokio::test]
-    async fn test_broadcast_tx_deterministic() {
+    async fn test_broadcast_tx_requires_dry_run() {
         let rpc = test_rpc();
-        let r1 = broadcast_tx(&rpc, BroadcastRequest { signed_tx: "tx_data".to_string() }).await.unwrap();
-        let r2 = broadcast_tx(&rpc, BroadcastRequest { signed_tx: "tx_data".to_string() }).await.unwrap();
-        assert_eq!(r1.tx_hash, r2.tx_hash);
+        let result = broadcast_tx(&rpc, None, BroadcastRequest {
+            signed_tx: "some_signed_tx_data".to_string(),
+        }).await;
+        assert!(matches!(result, Err(RelayError::InvalidTransaction)));
+
+        // Nothing is submitted when the node cannot dry-run the transaction.
+        let result = broadcast_tx(&rpc, None, BroadcastRequest { signed_tx: r#"{"version":"0x0"}"#.to_string() }).await;
+        assert!(matches!(result, Err(RelayError::ChainUnavailable(_))));
+    }
+
+    #[tokio::test]
+    async fn test_offline_proof_accepted_after_window_closes() {
+        use crate::crypto::signatures::test_wallet::TestWallet;
+
+        let cache = test_cache().await;
+        let (_, mut window) = setup_event_with_window(&cache).await;
+        let now = Utc::now().timestamp();
+        let qr_ts = now - 2 * 3600;
+        window.window_start = qr_ts - 600;
+        window.window_end = Some(qr_ts + 600);
+        cache.store_window(&window).await.unwrap();
+
+        let secret = qr::window_secret(&window);
+        let wallet = TestWallet::new(9, "ckt");
+        let scanned_at = qr_ts + 5;
+        let proof = AttendanceProof {
+            event_id: "evt1".to_string(),
+            attendee_address: wallet.address.clone(),
+            qr_payload: QrPayload {
+                event_id: "evt1".to_string(),
+                timestamp: qr_ts,
+                hmac: qr::generate_qr_hmac(&secret, qr_ts),
+                session_id: Some(DEFAULT_SESSION_ID.to_string()),
+            },
+            attendee_signature: wallet.sign(&AttendanceProof::offline_message_to_sign(
+                "evt1",
+                qr_ts,
+                &wallet.address,
+                scanned_at,
+            )),
+            created_at: scanned_at,
+            scanned_at: Some(scanned_at),
+        };
+
+        let result = verify_attendance_proof(&cache, &proof).await;
+        assert!(matches!(result, Err(RelayError::OfflineNotAllowed)));
+
+        let mut policy = OfflinePolicy {
+            event_id: "evt1".to_string(),
+            grace_secs: 3600,
+            version: 1,
+            updated_at: Utc::now(),
+        };
+        cache.store_offline_policy(&policy).await.unwrap();
+        let result = verify_attendance_proof(&cache, &proof).await;
+        assert!(matches!(result, Err(RelayError::QrExpired)));
+
+        policy.grace_secs = 4 * 3600;
+        policy.version = 2;
+        cache.store_offline_policy(&policy).await.unwrap();
+        let (_, window) = verify_attendance_proof(&cache, &proof).await.unwrap();
+        assert_eq!(window.session_id, DEFAULT_SESSION_ID);
+
+        // The scan time is part of what the attendee signed.
+        let tampered = AttendanceProof { scanned_at: Some(scanned_at + 1), ..proof.clone() };
+        let result = verify_attendance_proof(&cache, &tampered).await;
+        assert!(matches!(result, Err(RelayError::InvalidSignature)));
+
+        // Without a scan time the closed window still rejects the proof.
+        let online = AttendanceProof { scanned_at: None, ..proof };
+        let result = verify_attendance_proof(&cache, &online).await;
+        assert!(matches!(result, Err(RelayError::WindowClosed)));
+    }
+
+    #[tokio::test]
+    async fn test_offline_replay_outlives_replay_log_cleanup() {
+        use crate::crypto::signatures::test_wallet::TestWallet;
+
+        let cache = test_cache().await;
+        let (_, mut window) = setup_event_with_window(&cache).await;
+        let now = Utc::now().timestamp();
+        let qr_ts = now - 3 * 24 * 3600;
+        window.window_start = qr_ts - 600;
+        window.window_end = Some(qr_ts + 600);
+        cache.store_window(&window).await.unwrap();
+        let policy = OfflinePolicy {
+            event_id: "evt1".to_string(),
+            grace_secs: qr::MAX_OFFLINE_GRACE_SECS,
+            version: 1,
+            updated_at: Utc::now(),
+        };
+        cache.store_offline_policy(&policy).await.unwrap();
+
+        let wallet = TestWallet::new(10, "ckt");
+        let scanned_at = qr_ts + 5;
+        let proof = AttendanceProof {
+            event_id: "evt1".to_string(),
+            attendee_address: wallet.address.clone(),
+            qr_payload: QrPayload {
+                event_id: "evt1".to_string(),
+                timestamp: qr_ts,
+                hmac: qr::generate_qr_hmac(&qr::window_secret(&window), qr_ts),
+                session_id: Some(DEFAULT_SESSION_ID.to_string()),
+            },
+            attendee_signature: wallet.sign(&AttendanceProof::offline_message_to_sign(
+                "evt1",
+                qr_ts,
+                &wallet.address,
+                scanned_at,
+            )),
+            created_at: scanned_at,
+            scanned_at: Some(scanned_at),
+        };
+        verify_attendance_proof(&cache, &proof).await.unwrap();
+        cache.record_qr_usage("evt1", DEFAULT_SESSION_ID, qr_ts).await.unwrap();
+
+        // The frame is still within the grace period, so cleanup keeps it.
+        cache.cleanup_expired_replay_log(now - qr::REPLAY_LOG_RETENTION_SECS).await.unwrap();
+        let result = verify_attendance_proof(&cache, &proof).await;
+        assert!(matches!(result, Err(RelayError::ReplayDetected)));
+    }
+
+    #[tokio::test]
+    async fn test_check_in_presence() {
+        use crate::crypto::signatures::test_wallet::TestWallet;
+
+        let cache = test_cache().await;
+        let (_, window) = setup_event_with_window(&cache).await;
+        let event = cache.get_active_event("evt1").await.unwrap().unwrap();
+
+        let wallet = TestWallet::new(11, "ckt");
+        let timestamp = Utc::now().timestamp();
+        let presence = PresenceRequest {
+            event_id: "evt1".to_string(),
+            attendee_address: wallet.address.clone(),
+            timestamp,
+            attendee_signature: wallet.sign(&PresenceRequest::message_to_sign("evt1", timestamp, &wallet.address)),
+        };
+
+        let forged = PresenceRequest { timestamp: timestamp - 1, ..presence.clone() };
+        let result = check_in_presence(&cache, &test_rpc(), &event, &window, &forged, FeePriority::Normal).await;
+        assert!(matches!(result, Err(RelayError::InvalidSignature)));
+
+        let response =
+            check_in_presence(&cache, &test_rpc(), &event, &window, &presence, FeePriority::Normal).await.unwrap();
+        assert_eq!(response.session_id, DEFAULT_SESSION_ID);
+        let checkins = cache.get_checkins("evt1").await.unwrap();
+        assert_eq!(checkins.len(), 1);
+        assert_eq!(checkins[0].attendee_address, wallet.address);
+
+        let result = check_in_presence(&cache, &test_rpc(), &event, &window, &presence, FeePriority::Normal).await;
+        assert!(matches!(result, Err(RelayError::AlreadyCheckedIn)));
+
+        // Invite-only: another attendee not on the allowlist is turned away.
+        let allowlist = Allowlist {
+            event_id: "evt1".to_string(),
+            merkle_root: "0xroot".to_string(),
+            entry_count: 1,
+            version: 1,
+            creator_signature: "0xsig".to_string(),
+            updated_at: Utc::now(),
+        };
+        let listed = signatures::address_to_lock_hash(&wallet.address).unwrap();
+        cache.store_allowlist(&allowlist, &[listed]).await.unwrap();
+        let outsider = TestWallet::new(12, "ckt");
+        let uninvited = PresenceRequest {
+            event_id: "evt1".to_string(),
+            attendee_address: outsider.address.clone(),
+            timestamp,
+            attendee_signature: outsider.sign(&PresenceRequest::message_to_sign("evt1", timestamp, &outsider.address)),
+        };
+        let result = check_in_presence(&cache, &test_rpc(), &event, &window, &uninvited, FeePriority::Normal).await;
+        assert!(matches!(result, Err(RelayError::NotOnAllowlist)));
+
+        let stale = PresenceRequest { timestamp: timestamp - 120, ..presence };
+        let result = check_in_presence(&cache, &test_rpc(), &event, &window, &stale, FeePriority::Normal).await;
+        assert!(matches!(result, Err(RelayError::QrExpired)));
+    }
+
+    #[tokio::test]
+    async fn test_build_achievement_tx_requires_eligibility() {
+        let mut eligibility = AchievementEligibility {
+            achievement_id: "ach1".to_string(),
+            address: "ckt1qholder".to_string(),
+            held_event_ids: vec!["w1".to_string()],
+            min_count: 2,
+            eligible: false,
+            verified_at_block: None,
+        };
+        let result = build_achievement_tx(&test_rpc(), &eligibility, FeePriority::Normal).await;
+        assert!(matches!(result, Err(RelayError::NotEligible { held: 1, required: 2 })));
+
+        eligibility.held_event_ids.push("w2".to_string());
+        eligibility.eligible = true;
+        let response = build_achievement_tx(&test_rpc(), &eligibility, FeePriority::Normal).await.unwrap();
+        let args = hex::decode(response.type_args.trim_start_matches("0x")).unwrap();
+        assert_eq!(args.len(), 64);
+        // The namespace keeps achievement args apart from any plain event badge's.
+        assert_ne!(args[..32], sha2::Sha256::digest(b"ach1")[..]);
+        assert_ne!(response.tx_hash, badge_tx_hash("ach1", "ckt1qholder"));
     }
 }
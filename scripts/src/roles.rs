@@ -0,0 +1,125 @@
+use chrono::Utc;
+
+use crate::cache::Cache;
+use crate::crypto::signatures;
+use crate::types::{ActiveEvent, BadgeRole, RoleAssignment};
+
+/// Record a creator-signed role for `holder_address`. The role is written
+/// into the badge when it is built, so it must be assigned before then:
+/// badge cell data is immutable once minted.
+pub async fn assign_role(
+    cache: &Cache,
+    event: &ActiveEvent,
+    holder_address: &str,
+    role: BadgeRole,
+    creator_signature: &str,
+) -> Result<RoleAssignment, RoleError> {
+    let holder_lock_hash = signatures::address_to_lock_hash(holder_address).map_err(|_| RoleError::InvalidAddress)?;
+
+    let assignment = RoleAssignment {
+        event_id: event.event_id.clone(),
+        holder_address: holder_address.to_string(),
+        holder_lock_hash,
+        role,
+        creator_signature: creator_signature.to_string(),
+        assigned_at: Utc::now(),
+    };
+    signatures::verify_ckb_address_signature(&assignment.signed_message(), creator_signature, &event.creator_address)
+        .map_err(|_| RoleError::InvalidSignature)?;
+
+    let badges = cache.get_badges_by_lock_hash(&assignment.holder_lock_hash).await.map_err(RoleError::Cache)?;
+    if badges.iter().any(|b| b.event_id == event.event_id) {
+        return Err(RoleError::AlreadyMinted);
+    }
+
+    cache.store_role_assignment(&assignment).await.map_err(RoleError::Cache)?;
+    Ok(assignment)
+}
+
+/// The role to write into a badge for `holder_lock_hash`; unassigned
+/// holders are attendees.
+pub async fn role_for(cache: &Cache, event_id: &str, holder_lock_hash: &str) -> Result<BadgeRole, sqlx::Error> {
+    let assignment = cache.get_role_assignment(event_id, holder_lock_hash).await?;
+    Ok(assignment.map_or(BadgeRole::Attendee, |a| a.role))
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum RoleError {
+    #[error("cache error: {0}")]
+    Cache(#[from] sqlx::Error),
+    #[error("invalid holder address")]
+    InvalidAddress,
+    #[error("invalid creator signature")]
+    InvalidSignature,
+    #[error("badge already built for this holder")]
+    AlreadyMinted,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::crypto::signatures::test_wallet::TestWallet;
+    use crate::types::{BadgeObservation, EventMetadata, EventState};
+
+    fn test_event(creator: &TestWallet) -> ActiveEvent {
+        ActiveEvent {
+            event_id: "evt1".to_string(),
+            metadata: EventMetadata {
+                name: "Test".to_string(),
+                description: "Desc".to_string(),
+                image_url: None,
+                location: None,
+                start_time: None,
+                end_time: None,
+                max_attendees: None,
+                prerequisite_events: vec![],
+                min_prerequisites: None,
+            },
+            creator_address: creator.address.clone(),
+            payment_tx_hash: "0xtx".to_string(),
+            payment_block_number: 100,
+            activated_at: Utc::now(),
+            windows: vec![],
+            metadata_version: 1,
+            cancelled_at: None,
+            state: EventState::Active,
+            state_changed_at: Utc::now(),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_assign_role_before_mint() {
+        let cache = Cache::new("sqlite::memory:").await.unwrap();
+        let creator = TestWallet::new(1, "ckt");
+        let speaker = TestWallet::new(2, "ckt");
+        let event = test_event(&creator);
+        let message = RoleAssignment::message_to_sign("evt1", &speaker.address, BadgeRole::Speaker);
+
+        let result = assign_role(&cache, &event, &speaker.address, BadgeRole::Speaker, &speaker.sign(&message)).await;
+        assert!(matches!(result, Err(RoleError::InvalidSignature)));
+        let result = assign_role(&cache, &event, &speaker.address, BadgeRole::Organizer, &creator.sign(&message)).await;
+        assert!(matches!(result, Err(RoleError::InvalidSignature)));
+
+        let lock_hash = signatures::address_to_lock_hash(&speaker.address).unwrap();
+        assert_eq!(role_for(&cache, "evt1", &lock_hash).await.unwrap(), BadgeRole::Attendee);
+        assign_role(&cache, &event, &speaker.address, BadgeRole::Speaker, &creator.sign(&message)).await.unwrap();
+        assert_eq!(role_for(&cache, "evt1", &lock_hash).await.unwrap(), BadgeRole::Speaker);
+
+        let badge = BadgeObservation {
+            event_id: "evt1".to_string(),
+            holder_address: speaker.address.clone(),
+            holder_lock_hash: lock_hash,
+            mint_tx_hash: "0xmint".to_string(),
+            mint_block_number: 0,
+            verified_at_block: 0,
+            observed_at: Utc::now(),
+            flagged_at: None,
+            flag_reason: None,
+            role: BadgeRole::Speaker,
+        };
+        cache.store_badge_observation(&badge).await.unwrap();
+        let message = RoleAssignment::message_to_sign("evt1", &speaker.address, BadgeRole::Volunteer);
+        let result = assign_role(&cache, &event, &speaker.address, BadgeRole::Volunteer, &creator.sign(&message)).await;
+        assert!(matches!(result, Err(RoleError::AlreadyMinted)));
+    }
+}
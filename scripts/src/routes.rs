@@ -1,41 +1,160 @@
+use std::collections::BTreeMap;
+use std::convert::Infallible;
+
 use axum::{
     extract::{Path, Query, State},
-    http::StatusCode,
-    response::{IntoResponse, Response},
-    routing::{get, post},
-    Json, Router,
+    http::{header, HeaderMap, StatusCode},
+    response::{
+        sse::{Event as SseEvent, KeepAlive, Sse},
+        IntoResponse, Response,
+    },
+    routing::{get, patch, post},
+    Extension, Json, Router,
 };
 use chrono::{Duration, Utc};
 use serde::{Deserialize, Serialize};
-use sha2::Digest;
+use tokio_stream::{wrappers::BroadcastStream, Stream, StreamExt};
 
-use crate::crypto::{qr, signatures};
+use crate::achievement::{self, AchievementError};
+use crate::auth::{self, AuthError};
+use crate::crypto::signatures::{self, SignatureError};
+use crate::crypto::{merkle, qr};
+use crate::delegation::{self, Capability, DelegationError};
 use crate::observe::{self, ObserveError, PaymentObserveError};
+use crate::ratelimit::{self, AddressQuota, RateLimits, RouteClass};
 use crate::relay::{self, RelayError};
+use crate::roles::{self, RoleError};
+use crate::rsvp::{self, RsvpError};
+use crate::schedule::WindowNoticeKind;
+use crate::series::{self, SeriesError};
+use crate::sponsor::{self, SponsorError};
 use crate::state::AppState;
 use crate::types::{
-    ActiveEvent, BadgeObservation, EventIdPreimage, EventMetadata, HealthResponse, PaymentIntent,
-    QrPayload, QrResponse, WindowProof,
+    cancel_message_to_sign, revoke_message_to_sign, Achievement, AchievementDefinition, ActiveEvent, AirdropInclusion,
+    Allowlist, AttendanceProof, AuthSession, BadgeObservation, BadgeRole, CheckIn, CheckoutPolicy, ClaimPolicy,
+    ClaimVoucher, DelegateRole, Delegation, DurationProof, EventIdPreimage, EventMetadata, EventSeries, EventState,
+    EventUpdate, HealthResponse, MetadataRevision, OfflinePolicy, PaymentIntent, PresenceRequest, QrPayload, QrResponse,
+    Recurrence, RoleAssignment, Rsvp, RsvpPolicy, SponsorDeposit, Sponsorship, StateTransition, WindowProof,
 };
 
-pub fn router() -> Router<AppState> {
+pub fn router(limits: &RateLimits) -> Router<AppState> {
     Router::new()
         .route("/health", get(health))
-        .route("/events/intent", post(submit_intent))
-        .route("/events/create", post(create_event))
+        .route("/auth/challenge", limits.apply("auth_challenge", RouteClass::Write, post(create_challenge)))
+        .route(
+            "/auth/session",
+            limits.apply("auth_session", RouteClass::Write, post(create_session))
+                .get(get_session)
+                .delete(delete_session),
+        )
+        .route("/events/intent", limits.apply("events_intent", RouteClass::Write, post(submit_intent)))
+        .route("/events/create", limits.apply("events_create", RouteClass::Write, post(create_event)))
         .route("/events", get(list_events))
-        .route("/events/:id", get(get_event))
-        .route("/events/:id/window", post(submit_window))
+        .route(
+            "/events/:id",
+            get(get_event).merge(limits.apply("events_update", RouteClass::Write, patch(update_event))),
+        )
+        .route("/events/:id/cancel", limits.apply("events_cancel", RouteClass::Write, post(cancel_event)))
+        .route("/events/:id/history", get(get_event_history))
+        .route("/events/:id/lifecycle", get(get_event_lifecycle))
+        .route("/events/:id/window", limits.apply("events_window", RouteClass::Write, post(submit_window)))
+        .route(
+            "/events/:id/window/revoke",
+            limits.apply("events_window_revoke", RouteClass::Write, post(revoke_window)),
+        )
+        .route("/events/:id/windows", get(list_windows))
+        .route("/events/:id/windows/stream", get(stream_window_notices))
         .route("/events/:id/qr", get(get_qr))
-        .route("/events/:id/activate", post(activate_event))
-        .route("/events/:id/badge-holders", get(get_badge_holders))
-        .route("/badges/observe", get(observe_badges))
-        .route("/badges/build", post(build_badge))
-        .route("/badges/broadcast", post(broadcast_badge))
-        .route("/badges/record", post(record_badge))
-        .route("/tx/:hash", get(get_tx_status))
-        .route("/payments/:tx_hash", get(get_payment))
-        .route("/qr/parse", get(parse_qr))
+        .route("/events/:id/windows/:session_id/qr", get(get_session_qr))
+        .route("/events/:id/checkins", get(get_checkins))
+        .route(
+            "/events/:id/allowlist",
+            get(get_allowlist).merge(limits.apply("events_allowlist", RouteClass::Write, post(set_allowlist))),
+        )
+        .route(
+            "/events/:id/delegates",
+            get(list_delegates).merge(limits.apply("events_delegates", RouteClass::Write, post(create_delegate))),
+        )
+        .route(
+            "/events/:id/delegates/:address/revoke",
+            limits.apply("events_delegates_revoke", RouteClass::Write, post(revoke_delegate)),
+        )
+        .route(
+            "/events/:id/roles",
+            get(list_roles).merge(limits.apply("events_roles", RouteClass::Write, post(assign_role))),
+        )
+        .route(
+            "/events/:id/offline-policy",
+            get(get_offline_policy).merge(limits.apply("events_offline_policy", RouteClass::Write, post(set_offline_policy))),
+        )
+        .route(
+            "/events/:id/airdrop",
+            get(list_airdrop).merge(limits.apply("events_airdrop", RouteClass::Write, post(build_airdrop))),
+        )
+        .route(
+            "/events/:id/airdrop/:batch_id/tx",
+            limits.apply("events_airdrop_tx", RouteClass::Write, post(report_airdrop_tx)),
+        )
+        .route(
+            "/events/:id/airdrop/:batch_id/release",
+            limits.apply("events_airdrop_release", RouteClass::Write, post(release_airdrop)),
+        )
+        .route("/events/:id/rsvp", limits.apply("events_rsvp", RouteClass::Write, post(submit_rsvp)))
+        .route("/events/:id/rsvp/cancel", limits.apply("events_rsvp_cancel", RouteClass::Write, post(cancel_rsvp)))
+        .route("/events/:id/rsvps", get(get_rsvp_report))
+        .route(
+            "/events/:id/rsvp-policy",
+            get(get_rsvp_policy).merge(limits.apply("events_rsvp_policy", RouteClass::Write, post(set_rsvp_policy))),
+        )
+        .route("/events/:id/sponsor", get(get_sponsor_budget))
+        .route(
+            "/events/:id/sponsor/deposits",
+            limits.apply("events_sponsor_deposit", RouteClass::ChainSync, post(credit_sponsor_deposit)),
+        )
+        .route(
+            "/events/:id/sponsor/sign",
+            limits.apply("events_sponsor_sign", RouteClass::ChainSync, post(cosign_sponsored_badge)),
+        )
+        .route(
+            "/events/:id/claim-policy",
+            get(get_claim_policy).merge(limits.apply("events_claim_policy", RouteClass::Write, post(set_claim_policy))),
+        )
+        .route(
+            "/events/:id/checkout-policy",
+            get(get_checkout_policy)
+                .merge(limits.apply("events_checkout_policy", RouteClass::Write, post(set_checkout_policy))),
+        )
+        .route("/events/:id/checkouts", get(get_checkouts))
+        .route("/events/:id/activate", limits.apply("events_activate", RouteClass::ChainSync, post(activate_event)))
+        .route("/events/:id/badge-holders", limits.apply("events_badge_holders", RouteClass::ChainSync, get(get_badge_holders)))
+        .route("/series", limits.apply("series_create", RouteClass::Write, post(create_series)))
+        .route("/series/:id", get(get_series))
+        .route("/series/:id/events", get(list_series_events))
+        .route("/series/:id/attendance", get(get_series_attendance))
+        .route("/achievements", limits.apply("achievements_create", RouteClass::Write, post(define_achievement)))
+        .route("/achievements/:id", get(get_achievement))
+        .route(
+            "/achievements/:id/eligibility",
+            limits.apply("achievements_eligibility", RouteClass::ChainSync, get(get_achievement_eligibility)),
+        )
+        .route("/badges/observe", limits.apply("badges_observe", RouteClass::ChainSync, get(observe_badges)))
+        .route("/locks/:lock_hash/badges", get(get_lock_badges))
+        .route("/badges/build", limits.apply("badges_build", RouteClass::CheckIn, post(build_badge)))
+        .route("/badges/build/batch", limits.apply("badges_build_batch", RouteClass::CheckIn, post(build_badge_batch)))
+        .route("/badges/checkin", limits.apply("badges_checkin", RouteClass::CheckIn, post(check_in_scan)))
+        .route("/badges/checkout", limits.apply("badges_checkout", RouteClass::CheckIn, post(check_out)))
+        .route("/badges/voucher", limits.apply("badges_voucher", RouteClass::CheckIn, post(issue_voucher)))
+        .route("/badges/claim", limits.apply("badges_claim", RouteClass::CheckIn, post(redeem_voucher)))
+        .route("/badges/presence", limits.apply("badges_presence", RouteClass::CheckIn, post(presence_check_in)))
+        .route(
+            "/badges/achievement/build",
+            limits.apply("badges_achievement_build", RouteClass::ChainSync, post(build_achievement_badge)),
+        )
+        .route("/badges/broadcast", limits.apply("badges_broadcast", RouteClass::Write, post(broadcast_badge)))
+        .route("/badges/record", limits.apply("badges_record", RouteClass::Write, post(record_badge)))
+        .route("/tx/:hash", limits.apply("tx_status", RouteClass::ChainSync, get(get_tx_status)))
+        .route("/payments/:tx_hash", limits.apply("payments", RouteClass::ChainSync, get(get_payment)))
+        .route("/qr/parse", limits.apply("qr_parse", RouteClass::CheckIn, get(parse_qr)))
 }
 
 async fn health(State(state): State<AppState>) -> Json<HealthResponse> {
@@ -51,8 +170,9 @@ async fn health(State(state): State<AppState>) -> Json<HealthResponse> {
         "unavailable"
     };
 
-    // Opportunistically clean up expired replay log entries.
-    let _ = state.cache.cleanup_expired_replay_log(Utc::now() - Duration::hours(24)).await;
+    // Opportunistically clean up expired replay log entries and sessions.
+    let _ = state.cache.cleanup_expired_replay_log(Utc::now().timestamp() - qr::REPLAY_LOG_RETENTION_SECS).await;
+    let _ = state.cache.cleanup_expired_sessions(Utc::now()).await;
 
     Json(HealthResponse {
         status: "operational".to_string(),
@@ -63,6 +183,83 @@ async fn health(State(state): State<AppState>) -> Json<HealthResponse> {
     })
 }
 
+#[derive(Deserialize)]
+pub struct ChallengeRequest {
+    pub address: String,
+}
+
+#[derive(Serialize)]
+pub struct ChallengeResponse {
+    pub challenge_id: String,
+    /// Exact message the wallet must sign.
+    pub message: String,
+    pub expires_at: i64,
+}
+
+async fn create_challenge(
+    State(state): State<AppState>,
+    Json(req): Json<ChallengeRequest>,
+) -> Result<Json<ChallengeResponse>, AppError> {
+    check_signer(&state, &req.address)?;
+
+    let challenge = auth::issue_challenge(&state.cache, &req.address)
+        .await
+        .map_err(AppError::Auth)?;
+
+    Ok(Json(ChallengeResponse {
+        message: challenge.signed_message(),
+        challenge_id: challenge.challenge_id,
+        expires_at: challenge.expires_at.timestamp(),
+    }))
+}
+
+#[derive(Deserialize)]
+pub struct SessionRequest {
+    pub challenge_id: String,
+    pub signature: String,
+}
+
+#[derive(Serialize)]
+pub struct SessionResponse {
+    /// Bearer token for the `Authorization` header.
+    pub token: String,
+    pub address: String,
+    pub expires_at: i64,
+}
+
+async fn create_session(
+    State(state): State<AppState>,
+    Json(req): Json<SessionRequest>,
+) -> Result<Json<SessionResponse>, AppError> {
+    let (token, session) = auth::open_session(&state.cache, &req.challenge_id, &req.signature)
+        .await
+        .map_err(AppError::Auth)?;
+
+    Ok(Json(SessionResponse {
+        token,
+        address: session.address,
+        expires_at: session.expires_at.timestamp(),
+    }))
+}
+
+async fn get_session(session: Option<Extension<AuthSession>>) -> Result<Json<AuthSession>, AppError> {
+    let Extension(session) = session.ok_or(AppError::SessionRequired)?;
+    Ok(Json(session))
+}
+
+async fn delete_session(State(state): State<AppState>, headers: HeaderMap) -> Result<StatusCode, AppError> {
+    let token = headers
+        .get(header::AUTHORIZATION)
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| v.strip_prefix("Bearer "))
+        .ok_or(AppError::SessionRequired)?;
+
+    auth::close_session(&state.cache, token.trim())
+        .await
+        .map_err(AppError::Auth)?;
+    Ok(StatusCode::NO_CONTENT)
+}
+
 #[derive(Deserialize)]
 pub struct IntentRequest {
     pub creator_address: String,
@@ -81,6 +278,8 @@ async fn submit_intent(
     State(state): State<AppState>,
     Json(req): Json<IntentRequest>,
 ) -> Result<Json<IntentResponse>, AppError> {
+    check_signer(&state, &req.creator_address)?;
+
     let now = Utc::now();
     let preimage = EventIdPreimage {
         creator_address: req.creator_address.clone(),
@@ -111,6 +310,8 @@ async fn create_event(
     State(state): State<AppState>,
     Json(req): Json<IntentRequest>,
 ) -> Result<Json<ActiveEvent>, AppError> {
+    check_signer(&state, &req.creator_address)?;
+
     let now = Utc::now();
     let preimage = EventIdPreimage {
         creator_address: req.creator_address.clone(),
@@ -127,11 +328,11 @@ async fn create_event(
         expires_at: now + Duration::hours(24),
     };
 
-    let active_event = observe::create_and_activate_event(&state.cache, intent)
+    let event = observe::create_pending_event(&state.cache, intent)
         .await
         .map_err(AppError::Observe)?;
 
-    Ok(Json(active_event))
+    Ok(Json(event))
 }
 
 #[derive(Deserialize)]
@@ -142,99 +343,1253 @@ pub struct VerifyQuery {
 
 async fn list_events(
     State(state): State<AppState>,
+    session: Option<Extension<AuthSession>>,
     Query(query): Query<VerifyQuery>,
 ) -> Result<Json<observe::EventListResponse>, AppError> {
-    let response = observe::observe_events(&state.cache, &state.rpc, query.verify)
+    let mut response = observe::observe_events(&state.cache, &state.rpc, query.verify)
         .await
         .map_err(AppError::Observe)?;
+    for event in &mut response.events {
+        redact_window_secret(event, session.as_deref());
+    }
     Ok(Json(response))
 }
 
 async fn get_event(
     State(state): State<AppState>,
+    session: Option<Extension<AuthSession>>,
     Path(event_id): Path<String>,
     Query(query): Query<VerifyQuery>,
 ) -> Result<Json<observe::EventDetailResponse>, AppError> {
-    let response = observe::observe_event(&state.cache, &state.rpc, &event_id, query.verify)
+    let mut response = observe::observe_event(&state.cache, &state.rpc, &event_id, query.verify)
         .await
         .map_err(AppError::Observe)?;
+    redact_window_secret(&mut response.event, session.as_deref());
     Ok(Json(response))
 }
 
-#[derive(Deserialize)]
-pub struct WindowRequest {
-    pub window_start: i64,
-    pub window_end: Option<i64>,
-    pub creator_signature: String,
+/// Apply a creator-signed metadata update (`version` must be current + 1).
+async fn update_event(
+    State(state): State<AppState>,
+    Path(event_id): Path<String>,
+    Json(update): Json<EventUpdate>,
+) -> Result<Json<ActiveEvent>, AppError> {
+    let event = state
+        .cache
+        .get_active_event(&event_id)
+        .await
+        .map_err(|e| AppError::Observe(ObserveError::Cache(e)))?
+        .ok_or(AppError::Observe(ObserveError::NotFound))?;
+
+    signatures::verify_ckb_address_signature(
+        &update.signed_message(&event_id),
+        &update.creator_signature,
+        &event.creator_address,
+    )
+    .map_err(|_| AppError::InvalidSignature)?;
+
+    let mut event = observe::update_event_metadata(&state.cache, &event_id, &update)
+        .await
+        .map_err(AppError::Observe)?;
+    redact_window_secret(&mut event, None);
+    Ok(Json(event))
+}
+
+#[derive(Deserialize)]
+pub struct CancelRequest {
+    pub creator_signature: String,
+}
+
+/// Cancel an event on the creator's signature, closing any open window.
+async fn cancel_event(
+    State(state): State<AppState>,
+    Path(event_id): Path<String>,
+    Json(req): Json<CancelRequest>,
+) -> Result<Json<ActiveEvent>, AppError> {
+    let event = state
+        .cache
+        .get_active_event(&event_id)
+        .await
+        .map_err(|e| AppError::Observe(ObserveError::Cache(e)))?
+        .ok_or(AppError::Observe(ObserveError::NotFound))?;
+
+    signatures::verify_ckb_address_signature(
+        &cancel_message_to_sign(&event_id),
+        &req.creator_signature,
+        &event.creator_address,
+    )
+    .map_err(|_| AppError::InvalidSignature)?;
+
+    let mut event = observe::cancel_event(&state.cache, &event_id)
+        .await
+        .map_err(AppError::Observe)?;
+
+    let closed_at = event.cancelled_at.map(|at| at.timestamp());
+    for window in event.windows.iter().filter(|w| w.closed_at.is_some() && w.closed_at == closed_at) {
+        state.scheduler.notify(window, WindowNoticeKind::Closed, window.closed_at.unwrap_or_default());
+    }
+    redact_window_secret(&mut event, None);
+    Ok(Json(event))
+}
+
+#[derive(Serialize)]
+pub struct EventHistoryResponse {
+    pub event_id: String,
+    pub revisions: Vec<MetadataRevision>,
+}
+
+async fn get_event_history(
+    State(state): State<AppState>,
+    Path(event_id): Path<String>,
+) -> Result<Json<EventHistoryResponse>, AppError> {
+    let revisions = observe::get_metadata_history(&state.cache, &event_id)
+        .await
+        .map_err(AppError::Observe)?;
+    Ok(Json(EventHistoryResponse { event_id, revisions }))
+}
+
+#[derive(Serialize)]
+pub struct EventLifecycleResponse {
+    pub event_id: String,
+    pub state: EventState,
+    pub transitions: Vec<StateTransition>,
+}
+
+async fn get_event_lifecycle(
+    State(state): State<AppState>,
+    Path(event_id): Path<String>,
+) -> Result<Json<EventLifecycleResponse>, AppError> {
+    let event = observe::observe_event(&state.cache, &state.rpc, &event_id, false)
+        .await
+        .map_err(AppError::Observe)?
+        .event;
+    let transitions = observe::get_state_transitions(&state.cache, &event.event_id)
+        .await
+        .map_err(AppError::Observe)?;
+    Ok(Json(EventLifecycleResponse {
+        event_id: event.event_id,
+        state: event.state,
+        transitions,
+    }))
+}
+
+/// Each window's `creator_signature` is the QR HMAC secret material; only the
+/// event creator's own session may read it.
+fn redact_window_secret(event: &mut ActiveEvent, session: Option<&AuthSession>) {
+    if session.is_some_and(|s| auth::session_is_for(s, &event.creator_address)) {
+        return;
+    }
+    for window in event.windows.iter_mut() {
+        window.creator_signature.clear();
+        window.secret_salt = None;
+    }
+}
+
+/// Require a sign-in session belonging to the event's creator.
+fn require_creator(session: Option<&AuthSession>, event: &ActiveEvent) -> Result<(), AppError> {
+    let session = session.ok_or(AppError::SessionRequired)?;
+    if !auth::session_is_for(session, &event.creator_address) {
+        return Err(AppError::NotEventCreator);
+    }
+    Ok(())
+}
+
+/// Require a sign-in session belonging to the creator or to a delegate whose
+/// role allows `capability`. Returns the session's address.
+async fn require_organizer(
+    state: &AppState,
+    session: Option<&AuthSession>,
+    event: &ActiveEvent,
+    capability: Capability,
+) -> Result<String, AppError> {
+    let session = session.ok_or(AppError::SessionRequired)?;
+    authorize_signer(state, event, &session.address, capability).await?;
+    Ok(session.address.clone())
+}
+
+/// Check that `signer` (the creator when `None`) may act for the event, and
+/// return the signer's address for signature verification.
+async fn resolve_signer(
+    state: &AppState,
+    event: &ActiveEvent,
+    signer: Option<String>,
+    capability: Capability,
+) -> Result<String, AppError> {
+    let Some(signer) = signer else {
+        return Ok(event.creator_address.clone());
+    };
+    authorize_signer(state, event, &signer, capability).await?;
+    Ok(signer)
+}
+
+async fn authorize_signer(
+    state: &AppState,
+    event: &ActiveEvent,
+    address: &str,
+    capability: Capability,
+) -> Result<(), AppError> {
+    let authorized = delegation::is_authorized(&state.cache, event, address, capability)
+        .await
+        .map_err(AppError::Delegation)?;
+    if !authorized {
+        return Err(AppError::NotEventOrganizer);
+    }
+    Ok(())
+}
+
+#[derive(Deserialize)]
+pub struct WindowRequest {
+    /// Session to create or replace. When omitted a new session is created,
+    /// leaving the event's other windows untouched.
+    pub session_id: Option<String>,
+    pub label: Option<String>,
+    /// Required unless `use_event_times` is set.
+    pub window_start: Option<i64>,
+    pub window_end: Option<i64>,
+    /// Take the window times from the event's `start_time`/`end_time`, so
+    /// organizers can pre-sign the window ahead of the event.
+    #[serde(default)]
+    pub use_event_times: bool,
+    /// Optional when the request carries an organizer's sign-in session.
+    pub creator_signature: Option<String>,
+    /// Delegate who produced `creator_signature`; the creator when omitted.
+    pub signer_address: Option<String>,
+}
+
+async fn submit_window(
+    State(state): State<AppState>,
+    session: Option<Extension<AuthSession>>,
+    Path(event_id): Path<String>,
+    Json(req): Json<WindowRequest>,
+) -> Result<Json<WindowProof>, AppError> {
+    let event = state
+        .cache
+        .get_active_event(&event_id)
+        .await
+        .map_err(|e| AppError::Observe(ObserveError::Cache(e)))?
+        .ok_or(AppError::Observe(ObserveError::NotFound))?;
+
+    check_signer(&state, &event.creator_address)?;
+
+    if let Some(session_id) = req.session_id.as_deref() {
+        if !is_valid_session_id(session_id) {
+            return Err(AppError::InvalidSessionId);
+        }
+    }
+
+    let (window_start, window_end) = if req.use_event_times {
+        let start = event.metadata.start_time.ok_or(AppError::NoEventSchedule)?;
+        (start.timestamp(), event.metadata.end_time.map(|end| end.timestamp()))
+    } else {
+        (req.window_start.ok_or(AppError::MissingWindowStart)?, req.window_end)
+    };
+
+    // Either the creator or a co-host signs the window parameters, or their
+    // sign-in session vouches for them and the server generates the seed.
+    let (creator_signature, authorized_by) = match req.creator_signature {
+        Some(signature) => {
+            let signer = resolve_signer(&state, &event, req.signer_address, Capability::ManageWindows).await?;
+            let message =
+                WindowProof::message_to_sign(&event_id, req.session_id.as_deref(), window_start, window_end);
+            signatures::verify_ckb_address_signature(&message, &signature, &signer)
+                .map_err(|_| AppError::InvalidSignature)?;
+            let delegate = (signer != event.creator_address).then_some(signer);
+            (signature, delegate)
+        }
+        None => {
+            let address =
+                require_organizer(&state, session.as_deref(), &event, Capability::ManageWindows).await?;
+            let seed = format!("0x{}", hex::encode(rand::random::<[u8; 32]>()));
+            (seed, Some(address))
+        }
+    };
+
+    let mut window = WindowProof {
+        event_id: event_id.clone(),
+        session_id: req.session_id.unwrap_or_else(|| hex::encode(rand::random::<[u8; 8]>())),
+        label: req.label,
+        window_start,
+        window_end,
+        creator_signature,
+        window_secret_commitment: String::new(),
+        secret_salt: None,
+        rotated_at: None,
+        closed_at: None,
+        authorized_by,
+    };
+    window.window_secret_commitment = qr::secret_commitment(&qr::window_secret(&window));
+
+    let event = observe::update_window(&state.cache, &event_id, window.clone())
+        .await
+        .map_err(AppError::Observe)?;
+    // Replacing a rotated session keeps its current secret.
+    if let Some(stored) = event.window(&window.session_id) {
+        window = stored.clone();
+    }
+
+    // Windows starting later are announced by the scheduler when they open.
+    if window.is_open() {
+        state.scheduler.notify(&window, WindowNoticeKind::Opened, window.window_start);
+    }
+
+    Ok(Json(window))
+}
+
+#[derive(Deserialize)]
+pub struct RevokeWindowRequest {
+    /// Session whose secret to revoke; defaults to the current open window.
+    pub session_id: Option<String>,
+    /// Signature over the revoke message, which names the window's current
+    /// commitment. Optional when the request carries an organizer's session.
+    pub creator_signature: Option<String>,
+    /// Delegate who produced `creator_signature`; the creator when omitted.
+    pub signer_address: Option<String>,
+}
+
+#[derive(Serialize)]
+pub struct RevokeWindowResponse {
+    pub window: WindowProof,
+    /// Badges issued under the revoked secret, now flagged for review.
+    pub flagged_badges: u64,
+}
+
+/// Immediately invalidate a window's QR secret, e.g. after its display was
+/// photographed and shared. The window keeps its times; QR codes issued
+/// from now on use a fresh secret.
+async fn revoke_window(
+    State(state): State<AppState>,
+    session: Option<Extension<AuthSession>>,
+    Path(event_id): Path<String>,
+    Json(req): Json<RevokeWindowRequest>,
+) -> Result<Json<RevokeWindowResponse>, AppError> {
+    let event = state
+        .cache
+        .get_active_event(&event_id)
+        .await
+        .map_err(|e| AppError::Observe(ObserveError::Cache(e)))?
+        .ok_or(AppError::Observe(ObserveError::NotFound))?;
+
+    let window = match req.session_id.as_deref() {
+        Some(session_id) => event.window(session_id).ok_or(AppError::Relay(RelayError::UnknownSession))?,
+        None => event.current_window().ok_or(AppError::WindowNotOpen)?,
+    }
+    .clone();
+
+    match req.creator_signature {
+        Some(signature) => {
+            let signer = resolve_signer(&state, &event, req.signer_address, Capability::ManageWindows).await?;
+            let message =
+                revoke_message_to_sign(&event_id, &window.session_id, &window.window_secret_commitment);
+            signatures::verify_ckb_address_signature(&message, &signature, &signer)
+                .map_err(|_| AppError::InvalidSignature)?;
+        }
+        None => {
+            require_organizer(&state, session.as_deref(), &event, Capability::ManageWindows).await?;
+        }
+    }
+
+    let now = Utc::now().timestamp();
+    let (window, flagged_badges) = observe::revoke_window_secret(&state.cache, &event, window, now)
+        .await
+        .map_err(AppError::Observe)?;
+    tracing::warn!(
+        "Revoked window secret {}/{}; flagged {} badges",
+        window.event_id,
+        window.session_id,
+        flagged_badges
+    );
+
+    state.scheduler.notify(&window, WindowNoticeKind::Rotated, now);
+    Ok(Json(RevokeWindowResponse { window, flagged_badges }))
+}
+
+/// Windows of an event, in start order, without their secrets.
+async fn list_windows(
+    State(state): State<AppState>,
+    session: Option<Extension<AuthSession>>,
+    Path(event_id): Path<String>,
+) -> Result<Json<Vec<WindowProof>>, AppError> {
+    let mut event = observe::observe_event(&state.cache, &state.rpc, &event_id, false)
+        .await
+        .map_err(AppError::Observe)?
+        .event;
+    redact_window_secret(&mut event, session.as_deref());
+    Ok(Json(event.windows))
+}
+
+/// Server-sent events announcing when the event's windows open and close,
+/// so QR displays know when to stop. Each SSE event is named after its kind.
+async fn stream_window_notices(
+    State(state): State<AppState>,
+    Path(event_id): Path<String>,
+) -> Result<Sse<impl Stream<Item = Result<SseEvent, Infallible>>>, AppError> {
+    let event = observe::observe_event(&state.cache, &state.rpc, &event_id, false)
+        .await
+        .map_err(AppError::Observe)?
+        .event;
+
+    let notices = BroadcastStream::new(state.scheduler.subscribe()).filter_map(move |notice| match notice {
+        Ok(notice) if notice.event_id == event.event_id => {
+            let sse = SseEvent::default().event(notice.kind.as_str()).json_data(&notice).ok()?;
+            Some(Ok(sse))
+        }
+        // Notices for other events, or ones dropped for a lagging client.
+        _ => None,
+    });
+    Ok(Sse::new(notices).keep_alive(KeepAlive::default()))
+}
+
+/// Organizers only: the QR payload is what attendees scan to check in.
+/// Serves the most recently started open window.
+async fn get_qr(
+    State(state): State<AppState>,
+    session: Option<Extension<AuthSession>>,
+    Path(event_id): Path<String>,
+) -> Result<Json<QrResponse>, AppError> {
+    let event = organizer_event(&state, session.as_deref(), &event_id).await?;
+    let window = event.current_window().ok_or_else(|| {
+        if event.windows.is_empty() {
+            AppError::WindowNotOpen
+        } else {
+            AppError::WindowClosed
+        }
+    })?;
+    Ok(Json(qr_response(window)))
+}
+
+/// Organizers only: QR payload for one window session.
+async fn get_session_qr(
+    State(state): State<AppState>,
+    session: Option<Extension<AuthSession>>,
+    Path((event_id, session_id)): Path<(String, String)>,
+) -> Result<Json<QrResponse>, AppError> {
+    let event = organizer_event(&state, session.as_deref(), &event_id).await?;
+    let window = event.window(&session_id).ok_or(AppError::Relay(RelayError::UnknownSession))?;
+    if !window.is_open() {
+        return Err(AppError::WindowClosed);
+    }
+    Ok(Json(qr_response(window)))
+}
+
+/// Load an event for an organizer who may display its QR codes, rejecting
+/// cancelled events.
+async fn organizer_event(
+    state: &AppState,
+    session: Option<&AuthSession>,
+    event_id: &str,
+) -> Result<ActiveEvent, AppError> {
+    let event = state
+        .cache
+        .get_active_event(event_id)
+        .await
+        .map_err(|e| AppError::Observe(ObserveError::Cache(e)))?
+        .ok_or(AppError::Observe(ObserveError::NotFound))?;
+
+    require_organizer(state, session, &event, Capability::CheckIn).await?;
+
+    if event.state == EventState::Cancelled {
+        return Err(AppError::Observe(ObserveError::EventCancelled));
+    }
+    Ok(event)
+}
+
+fn qr_response(window: &WindowProof) -> QrResponse {
+    let payload = qr::generate_qr_payload(window);
+    let ttl = qr::qr_ttl_seconds();
+
+    QrResponse {
+        qr_data: payload.encode(),
+        ttl_seconds: ttl,
+        expires_at: payload.timestamp + ttl as i64,
+        window_end: window.end(),
+    }
+}
+
+/// Session ids appear in QR payloads, so they cannot contain the `|` separator.
+fn is_valid_session_id(session_id: &str) -> bool {
+    (1..=64).contains(&session_id.len())
+        && session_id.chars().all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+}
+
+#[derive(Deserialize)]
+pub struct SeriesRequest {
+    pub creator_address: String,
+    pub creator_signature: String,
+    pub nonce: String,
+    /// Metadata of the first occurrence; later ones shift its times.
+    pub template: EventMetadata,
+    pub recurrence: Recurrence,
+}
+
+#[derive(Serialize)]
+pub struct SeriesResponse {
+    pub series: EventSeries,
+    pub event_ids: Vec<String>,
+}
+
+/// Create a series and a pending event for each of its occurrences.
+async fn create_series(
+    State(state): State<AppState>,
+    Json(req): Json<SeriesRequest>,
+) -> Result<Json<SeriesResponse>, AppError> {
+    check_signer(&state, &req.creator_address)?;
+
+    let (series, events) = series::create_series(
+        &state.cache,
+        &req.creator_address,
+        &req.nonce,
+        req.template,
+        req.recurrence,
+        &req.creator_signature,
+    )
+    .await
+    .map_err(AppError::Series)?;
+
+    Ok(Json(SeriesResponse {
+        series,
+        event_ids: events.into_iter().map(|e| e.event_id).collect(),
+    }))
+}
+
+async fn get_series(
+    State(state): State<AppState>,
+    Path(series_id): Path<String>,
+) -> Result<Json<SeriesResponse>, AppError> {
+    let series = series::get_series(&state.cache, &series_id).await.map_err(AppError::Series)?;
+    let event_ids = state
+        .cache
+        .get_series_event_ids(&series_id)
+        .await
+        .map_err(|e| AppError::Series(SeriesError::Cache(e)))?;
+    Ok(Json(SeriesResponse { series, event_ids }))
+}
+
+async fn list_series_events(
+    State(state): State<AppState>,
+    session: Option<Extension<AuthSession>>,
+    Path(series_id): Path<String>,
+) -> Result<Json<Vec<ActiveEvent>>, AppError> {
+    let mut events = series::series_events(&state.cache, &series_id).await.map_err(AppError::Series)?;
+    for event in &mut events {
+        redact_window_secret(event, session.as_deref());
+    }
+    Ok(Json(events))
+}
+
+async fn get_series_attendance(
+    State(state): State<AppState>,
+    Path(series_id): Path<String>,
+) -> Result<Json<series::SeriesAttendance>, AppError> {
+    let attendance = series::series_attendance(&state.cache, &series_id).await.map_err(AppError::Series)?;
+    Ok(Json(attendance))
+}
+
+#[derive(Deserialize)]
+pub struct AchievementRequest {
+    pub creator_address: String,
+    pub creator_signature: String,
+    pub definition: AchievementDefinition,
+}
+
+async fn define_achievement(
+    State(state): State<AppState>,
+    Json(req): Json<AchievementRequest>,
+) -> Result<Json<Achievement>, AppError> {
+    check_signer(&state, &req.creator_address)?;
+    let achievement =
+        achievement::define_achievement(&state.cache, &req.creator_address, req.definition, &req.creator_signature)
+            .await
+            .map_err(AppError::Achievement)?;
+    Ok(Json(achievement))
+}
+
+async fn get_achievement(
+    State(state): State<AppState>,
+    Path(achievement_id): Path<String>,
+) -> Result<Json<Achievement>, AppError> {
+    let achievement = achievement::get_achievement(&state.cache, &achievement_id)
+        .await
+        .map_err(AppError::Achievement)?;
+    Ok(Json(achievement))
+}
+
+#[derive(Deserialize)]
+pub struct EligibilityQuery {
+    pub address: String,
+}
+
+async fn get_achievement_eligibility(
+    State(state): State<AppState>,
+    Extension(quota): Extension<AddressQuota>,
+    Path(achievement_id): Path<String>,
+    Query(query): Query<EligibilityQuery>,
+) -> Result<Json<achievement::AchievementEligibility>, AppError> {
+    check_address(&state, &query.address)?;
+    limit_address(&quota, &query.address)?;
+    let eligibility = achievement_eligibility(&state, &achievement_id, &query.address).await?;
+    Ok(Json(eligibility))
+}
+
+/// Mint an achievement badge. Eligibility is re-checked on chain here, not
+/// taken from an earlier eligibility query.
+async fn build_achievement_badge(
+    State(state): State<AppState>,
+    Extension(quota): Extension<AddressQuota>,
+    Json(req): Json<relay::BuildAchievementTxRequest>,
+) -> Result<Json<relay::BuildAchievementTxResponse>, AppError> {
+    check_address(&state, &req.address)?;
+    limit_address(&quota, &req.address)?;
+    let eligibility = achievement_eligibility(&state, &req.achievement_id, &req.address).await?;
+    let response = relay::build_achievement_tx(&state.rpc, &eligibility, req.fee_priority)
+        .await
+        .map_err(AppError::Relay)?;
+    Ok(Json(response))
+}
+
+async fn achievement_eligibility(
+    state: &AppState,
+    achievement_id: &str,
+    address: &str,
+) -> Result<achievement::AchievementEligibility, AppError> {
+    let achievement = achievement::get_achievement(&state.cache, achievement_id)
+        .await
+        .map_err(AppError::Achievement)?;
+    let chain_config = state.dob_code_hash.as_deref().map(|ch| (ch, state.address_policy.hrp.as_str()));
+    achievement::check_eligibility(&state.cache, &state.rpc, &achievement, address, chain_config)
+        .await
+        .map_err(AppError::Achievement)
+}
+
+#[derive(Deserialize)]
+pub struct DelegateRequest {
+    pub delegate_address: String,
+    pub role: DelegateRole,
+    /// Unix seconds after which the delegate loses access.
+    pub expires_at: i64,
+    /// One more than the delegate's last grant; 1 for a first grant.
+    pub version: u32,
+    /// Creator's signature over the delegation message; always required so
+    /// the stored record can be verified independently.
+    pub creator_signature: String,
+}
+
+/// Creator-only: delegations for an event, including expired ones, with the
+/// creator signatures that authorize them.
+async fn list_delegates(
+    State(state): State<AppState>,
+    session: Option<Extension<AuthSession>>,
+    Path(event_id): Path<String>,
+) -> Result<Json<Vec<Delegation>>, AppError> {
+    let event = load_event(&state, &event_id).await?;
+    require_creator(session.as_deref(), &event)?;
+
+    let delegations = state
+        .cache
+        .get_delegations(&event_id)
+        .await
+        .map_err(|e| AppError::Delegation(DelegationError::Cache(e)))?;
+    Ok(Json(delegations))
+}
+
+async fn create_delegate(
+    State(state): State<AppState>,
+    Path(event_id): Path<String>,
+    Json(req): Json<DelegateRequest>,
+) -> Result<Json<Delegation>, AppError> {
+    check_signer(&state, &req.delegate_address)?;
+    let event = load_event(&state, &event_id).await?;
+
+    let delegation = delegation::create_delegation(
+        &state.cache,
+        &event,
+        &req.delegate_address,
+        req.role,
+        req.expires_at,
+        req.version,
+        &req.creator_signature,
+    )
+    .await
+    .map_err(AppError::Delegation)?;
+    Ok(Json(delegation))
+}
+
+#[derive(Deserialize)]
+pub struct RevokeDelegateRequest {
+    /// Optional when the request carries the creator's sign-in session.
+    pub creator_signature: Option<String>,
+}
+
+/// Withdraw a delegation before it expires. Only the creator may do this.
+async fn revoke_delegate(
+    State(state): State<AppState>,
+    session: Option<Extension<AuthSession>>,
+    Path((event_id, address)): Path<(String, String)>,
+    Json(req): Json<RevokeDelegateRequest>,
+) -> Result<Json<Vec<Delegation>>, AppError> {
+    let event = load_event(&state, &event_id).await?;
+
+    match req.creator_signature {
+        Some(signature) => delegation::verify_revocation(&state.cache, &event, &address, &signature)
+            .await
+            .map_err(AppError::Delegation)?,
+        None => require_creator(session.as_deref(), &event)?,
+    }
+
+    delegation::revoke_delegation(&state.cache, &event_id, &address)
+        .await
+        .map_err(AppError::Delegation)?;
+    let delegations = state
+        .cache
+        .get_delegations(&event_id)
+        .await
+        .map_err(|e| AppError::Delegation(DelegationError::Cache(e)))?;
+    Ok(Json(delegations))
+}
+
+async fn load_event(state: &AppState, event_id: &str) -> Result<ActiveEvent, AppError> {
+    state
+        .cache
+        .get_active_event(event_id)
+        .await
+        .map_err(|e| AppError::Observe(ObserveError::Cache(e)))?
+        .ok_or(AppError::Observe(ObserveError::NotFound))
+}
+
+/// Creator-only: roles assigned for an event, with the creator signatures
+/// behind them.
+async fn list_roles(
+    State(state): State<AppState>,
+    session: Option<Extension<AuthSession>>,
+    Path(event_id): Path<String>,
+) -> Result<Json<Vec<RoleAssignment>>, AppError> {
+    let event = load_event(&state, &event_id).await?;
+    require_creator(session.as_deref(), &event)?;
+
+    let assignments = state
+        .cache
+        .get_role_assignments(&event_id)
+        .await
+        .map_err(|e| AppError::Role(RoleError::Cache(e)))?;
+    Ok(Json(assignments))
+}
+
+#[derive(Deserialize)]
+pub struct RoleRequest {
+    pub holder_address: String,
+    pub role: BadgeRole,
+    /// Creator's signature over the role message; always required so the
+    /// stored record can be verified independently.
+    pub creator_signature: String,
+}
+
+async fn assign_role(
+    State(state): State<AppState>,
+    Path(event_id): Path<String>,
+    Json(req): Json<RoleRequest>,
+) -> Result<Json<RoleAssignment>, AppError> {
+    check_address(&state, &req.holder_address)?;
+    let event = load_event(&state, &event_id).await?;
+
+    let assignment = roles::assign_role(&state.cache, &event, &req.holder_address, req.role, &req.creator_signature)
+        .await
+        .map_err(AppError::Role)?;
+    Ok(Json(assignment))
+}
+
+/// Most entries accepted in one allowlist upload.
+const MAX_ALLOWLIST_ENTRIES: usize = 10_000;
+
+/// The allowlist's Merkle root and signature. Entries are not published.
+async fn get_allowlist(
+    State(state): State<AppState>,
+    Path(event_id): Path<String>,
+) -> Result<Json<Allowlist>, AppError> {
+    let allowlist = state
+        .cache
+        .get_allowlist(&event_id)
+        .await
+        .map_err(|e| AppError::Observe(ObserveError::Cache(e)))?
+        .ok_or(AppError::NoAllowlist)?;
+    Ok(Json(allowlist))
+}
+
+#[derive(Deserialize)]
+pub struct AllowlistRequest {
+    /// Permitted attendees, as CKB addresses or `0x` lock hashes.
+    pub entries: Vec<String>,
+    /// Version the allowlist will have once applied (current + 1).
+    pub version: u32,
+    /// Creator's signature over the allowlist message for the entries'
+    /// Merkle root and `version`.
+    pub creator_signature: String,
+}
+
+/// Make the event invite-only, replacing any earlier allowlist.
+async fn set_allowlist(
+    State(state): State<AppState>,
+    Path(event_id): Path<String>,
+    Json(req): Json<AllowlistRequest>,
+) -> Result<Json<Allowlist>, AppError> {
+    if req.entries.is_empty() || req.entries.len() > MAX_ALLOWLIST_ENTRIES {
+        return Err(AppError::InvalidAllowlist);
+    }
+    let lock_hashes = req
+        .entries
+        .iter()
+        .map(|entry| allowlist_lock_hash(&state, entry))
+        .collect::<Result<Vec<_>, _>>()?;
+    let merkle_root = merkle::allowlist_root(&lock_hashes).ok_or(AppError::InvalidAllowlist)?;
+
+    let event = load_event(&state, &event_id).await?;
+    signatures::verify_ckb_address_signature(
+        &Allowlist::message_to_sign(&event_id, &merkle_root, req.version),
+        &req.creator_signature,
+        &event.creator_address,
+    )
+    .map_err(|_| AppError::InvalidSignature)?;
+
+    let allowlist =
+        observe::set_allowlist(&state.cache, &event, lock_hashes, merkle_root, req.version, req.creator_signature)
+            .await
+            .map_err(AppError::Observe)?;
+    Ok(Json(allowlist))
+}
+
+/// Allowlist entries are lock hashes; addresses are converted.
+fn allowlist_lock_hash(state: &AppState, entry: &str) -> Result<String, AppError> {
+    if entry.starts_with("0x") {
+        return signatures::normalize_lock_hash(entry).map_err(|_| AppError::InvalidAllowlist);
+    }
+    check_address(state, entry)?;
+    signatures::address_to_lock_hash(entry).map_err(|_| AppError::InvalidAddress)
+}
+
+/// Most queued check-ins accepted in one batch submission.
+const MAX_CHECKIN_BATCH: usize = 100;
+
+/// The event's offline check-in policy; events without one reject offline
+/// proofs.
+async fn get_offline_policy(
+    State(state): State<AppState>,
+    Path(event_id): Path<String>,
+) -> Result<Json<OfflinePolicy>, AppError> {
+    let policy = observe::get_offline_policy(&state.cache, &event_id)
+        .await
+        .map_err(AppError::Observe)?;
+    Ok(Json(policy))
+}
+
+#[derive(Deserialize)]
+pub struct OfflinePolicyRequest {
+    /// How long after a QR's timestamp an offline proof is still accepted;
+    /// zero disables offline check-in.
+    pub grace_secs: i64,
+    /// Version the policy will have once applied (current + 1).
+    pub version: u32,
+    /// Optional when the request carries the creator's sign-in session.
+    pub creator_signature: Option<String>,
+}
+
+async fn set_offline_policy(
+    State(state): State<AppState>,
+    session: Option<Extension<AuthSession>>,
+    Path(event_id): Path<String>,
+    Json(req): Json<OfflinePolicyRequest>,
+) -> Result<Json<OfflinePolicy>, AppError> {
+    if !(0..=qr::MAX_OFFLINE_GRACE_SECS).contains(&req.grace_secs) {
+        return Err(AppError::InvalidGracePeriod);
+    }
+
+    let event = state
+        .cache
+        .get_active_event(&event_id)
+        .await
+        .map_err(|e| AppError::Observe(ObserveError::Cache(e)))?
+        .ok_or(AppError::Observe(ObserveError::NotFound))?;
+
+    match req.creator_signature {
+        Some(signature) => {
+            let message = OfflinePolicy::message_to_sign(&event_id, req.grace_secs, req.version);
+            signatures::verify_ckb_address_signature(&message, &signature, &event.creator_address)
+                .map_err(|_| AppError::InvalidSignature)?;
+        }
+        None => require_creator(session.as_deref(), &event)?,
+    }
+
+    let policy = observe::set_offline_policy(&state.cache, &event, req.grace_secs, req.version)
+        .await
+        .map_err(AppError::Observe)?;
+    Ok(Json(policy))
+}
+
+#[derive(Deserialize)]
+pub struct RsvpRequest {
+    pub attendee_address: String,
+    /// Attendee's signature over the RSVP (or, to cancel, RSVP-cancel) message.
+    pub attendee_signature: String,
+}
+
+async fn submit_rsvp(
+    State(state): State<AppState>,
+    Path(event_id): Path<String>,
+    Json(req): Json<RsvpRequest>,
+) -> Result<Json<Rsvp>, AppError> {
+    check_signer(&state, &req.attendee_address)?;
+    let event = load_event(&state, &event_id).await?;
+
+    let rsvp = rsvp::submit_rsvp(&state.cache, &event, &req.attendee_address, &req.attendee_signature)
+        .await
+        .map_err(AppError::Rsvp)?;
+    Ok(Json(rsvp))
+}
+
+async fn cancel_rsvp(
+    State(state): State<AppState>,
+    Path(event_id): Path<String>,
+    Json(req): Json<RsvpRequest>,
+) -> Result<StatusCode, AppError> {
+    let event = load_event(&state, &event_id).await?;
+
+    rsvp::cancel_rsvp(&state.cache, &event, &req.attendee_address, &req.attendee_signature)
+        .await
+        .map_err(AppError::Rsvp)?;
+    Ok(StatusCode::NO_CONTENT)
+}
+
+/// Organizer-only: RSVPs against actual check-ins.
+async fn get_rsvp_report(
+    State(state): State<AppState>,
+    session: Option<Extension<AuthSession>>,
+    Path(event_id): Path<String>,
+) -> Result<Json<rsvp::RsvpReport>, AppError> {
+    let event = load_event(&state, &event_id).await?;
+    require_organizer(&state, session.as_deref(), &event, Capability::CheckIn).await?;
+
+    let report = rsvp::rsvp_report(&state.cache, &event).await.map_err(AppError::Rsvp)?;
+    Ok(Json(report))
+}
+
+/// The event's RSVP policy; events without one take RSVPs uncapped and do
+/// not require them.
+async fn get_rsvp_policy(
+    State(state): State<AppState>,
+    Path(event_id): Path<String>,
+) -> Result<Json<RsvpPolicy>, AppError> {
+    let event = load_event(&state, &event_id).await?;
+    let policy = rsvp::get_rsvp_policy(&state.cache, &event.event_id)
+        .await
+        .map_err(AppError::Rsvp)?;
+    Ok(Json(policy))
+}
+
+#[derive(Deserialize)]
+pub struct RsvpPolicyRequest {
+    /// Confirmed RSVPs allowed before later ones are waitlisted.
+    pub capacity: Option<u32>,
+    /// Only attendees with a confirmed RSVP may check in.
+    #[serde(default)]
+    pub required: bool,
+    /// Version the policy will have once applied (current + 1).
+    pub version: u32,
+    /// Optional when the request carries the creator's sign-in session.
+    pub creator_signature: Option<String>,
+}
+
+async fn set_rsvp_policy(
+    State(state): State<AppState>,
+    session: Option<Extension<AuthSession>>,
+    Path(event_id): Path<String>,
+    Json(req): Json<RsvpPolicyRequest>,
+) -> Result<Json<RsvpPolicy>, AppError> {
+    if req.capacity == Some(0) {
+        return Err(AppError::InvalidRsvpCapacity);
+    }
+    let event = load_event(&state, &event_id).await?;
+
+    match req.creator_signature {
+        Some(signature) => {
+            let message = RsvpPolicy::message_to_sign(&event_id, req.capacity, req.required, req.version);
+            signatures::verify_ckb_address_signature(&message, &signature, &event.creator_address)
+                .map_err(|_| AppError::InvalidSignature)?;
+        }
+        None => require_creator(session.as_deref(), &event)?,
+    }
+
+    let policy = rsvp::set_rsvp_policy(&state.cache, &event, req.capacity, req.required, req.version)
+        .await
+        .map_err(AppError::Rsvp)?;
+    Ok(Json(policy))
+}
+
+/// Organizer-only: the event's sponsor budget and what it has paid for.
+async fn get_sponsor_budget(
+    State(state): State<AppState>,
+    session: Option<Extension<AuthSession>>,
+    Path(event_id): Path<String>,
+) -> Result<Json<sponsor::SponsorBudget>, AppError> {
+    let wallet = state.sponsor.as_deref().ok_or(AppError::SponsorDisabled)?;
+    let event = load_event(&state, &event_id).await?;
+    require_organizer(&state, session.as_deref(), &event, Capability::CheckIn).await?;
+
+    let budget = sponsor::get_budget(&state.cache, wallet, &event.event_id)
+        .await
+        .map_err(AppError::Sponsor)?;
+    Ok(Json(budget))
+}
+
+#[derive(Deserialize)]
+pub struct SponsorDepositRequest {
+    /// Confirmed transaction paying the sponsor wallet, with the event id
+    /// as the data of each output to credit.
+    pub tx_hash: String,
+    /// Optional when the request carries the creator's sign-in session.
+    pub creator_signature: Option<String>,
+}
+
+/// Credit a payment to the sponsor wallet to the event's budget.
+async fn credit_sponsor_deposit(
+    State(state): State<AppState>,
+    session: Option<Extension<AuthSession>>,
+    Path(event_id): Path<String>,
+    Json(req): Json<SponsorDepositRequest>,
+) -> Result<Json<SponsorDeposit>, AppError> {
+    let wallet = state.sponsor.as_deref().ok_or(AppError::SponsorDisabled)?;
+    let event = load_event(&state, &event_id).await?;
+
+    match req.creator_signature {
+        Some(signature) => {
+            let message = SponsorDeposit::message_to_sign(&event_id, &req.tx_hash);
+            signatures::verify_ckb_address_signature(&message, &signature, &event.creator_address)
+                .map_err(|_| AppError::InvalidSignature)?;
+        }
+        None => require_creator(session.as_deref(), &event)?,
+    }
+
+    let deposit = sponsor::credit_deposit(&state.cache, &state.rpc, wallet, &event, &req.tx_hash)
+        .await
+        .map_err(AppError::Sponsor)?;
+    Ok(Json(deposit))
+}
+
+#[derive(Deserialize)]
+pub struct SponsorSignRequest {
+    pub holder_address: String,
+    /// The badge transaction with sponsor cells added as inputs and any
+    /// change paid back to the sponsor address.
+    pub transaction: ckb_jsonrpc_types::Transaction,
+    /// Holder's signature over `Sponsorship::message_to_sign` for the
+    /// transaction's hash.
+    pub holder_signature: String,
+}
+
+/// Co-sign the sponsor inputs of a holder's sponsored badge transaction.
+async fn cosign_sponsored_badge(
+    State(state): State<AppState>,
+    Extension(quota): Extension<AddressQuota>,
+    Path(event_id): Path<String>,
+    Json(req): Json<SponsorSignRequest>,
+) -> Result<Json<sponsor::SponsorWitness>, AppError> {
+    let wallet = state.sponsor.as_deref().ok_or(AppError::SponsorDisabled)?;
+    let event = load_event(&state, &event_id).await?;
+
+    let tx = ckb_types::packed::Transaction::from(req.transaction);
+    let message = Sponsorship::message_to_sign(&event.event_id, &sponsor::tx_hash(&tx));
+    signatures::verify_ckb_address_signature(&message, &req.holder_signature, &req.holder_address)
+        .map_err(|_| AppError::InvalidSignature)?;
+    limit_address(&quota, &req.holder_address)?;
+
+    let witness = sponsor::cosign_badge_tx(&state.cache, &state.rpc, wallet, &event.event_id, &req.holder_address, &tx)
+        .await
+        .map_err(AppError::Sponsor)?;
+    Ok(Json(witness))
+}
+
+/// Longest claim period an organizer may set; events are archived 30 days
+/// after they end.
+const MAX_CLAIM_PERIOD_SECS: i64 = 30 * 24 * 3600;
+
+/// The event's claim voucher policy; events without one issue no vouchers.
+async fn get_claim_policy(
+    State(state): State<AppState>,
+    Path(event_id): Path<String>,
+) -> Result<Json<ClaimPolicy>, AppError> {
+    let event = load_event(&state, &event_id).await?;
+    let policy = state
+        .cache
+        .get_claim_policy(&event.event_id)
+        .await
+        .map_err(|e| AppError::Observe(ObserveError::Cache(e)))?;
+    Ok(Json(policy.unwrap_or(ClaimPolicy {
+        event_id: event.event_id,
+        claim_period_secs: 0,
+        version: 0,
+        updated_at: event.activated_at,
+    })))
+}
+
+#[derive(Deserialize)]
+pub struct ClaimPolicyRequest {
+    /// How long after issue a voucher may be redeemed; zero disables
+    /// vouchers.
+    pub claim_period_secs: i64,
+    /// Version the policy will have once applied (current + 1).
+    pub version: u32,
+    /// Optional when the request carries the creator's sign-in session.
+    pub creator_signature: Option<String>,
+}
+
+async fn set_claim_policy(
+    State(state): State<AppState>,
+    session: Option<Extension<AuthSession>>,
+    Path(event_id): Path<String>,
+    Json(req): Json<ClaimPolicyRequest>,
+) -> Result<Json<ClaimPolicy>, AppError> {
+    if !(0..=MAX_CLAIM_PERIOD_SECS).contains(&req.claim_period_secs) {
+        return Err(AppError::InvalidClaimPeriod);
+    }
+    let event = load_event(&state, &event_id).await?;
+
+    match req.creator_signature {
+        Some(signature) => {
+            let message = ClaimPolicy::message_to_sign(&event_id, req.claim_period_secs, req.version);
+            signatures::verify_ckb_address_signature(&message, &signature, &event.creator_address)
+                .map_err(|_| AppError::InvalidSignature)?;
+        }
+        None => require_creator(session.as_deref(), &event)?,
+    }
+
+    let policy = observe::set_claim_policy(&state.cache, &event, req.claim_period_secs, req.version)
+        .await
+        .map_err(AppError::Observe)?;
+    Ok(Json(policy))
+}
+
+/// Longest minimum stay a check-out policy may require.
+const MAX_MIN_DURATION_SECS: i64 = 7 * 24 * 3600;
+
+/// The event's check-out policy. Events without one mint at check-in.
+async fn get_checkout_policy(
+    State(state): State<AppState>,
+    Path(event_id): Path<String>,
+) -> Result<Json<CheckoutPolicy>, AppError> {
+    let policy = state
+        .cache
+        .get_checkout_policy(&event_id)
+        .await
+        .map_err(|e| AppError::Observe(ObserveError::Cache(e)))?
+        .ok_or(AppError::Relay(RelayError::NoCheckoutPolicy))?;
+    Ok(Json(policy))
+}
+
+#[derive(Deserialize)]
+pub struct CheckoutPolicyRequest {
+    /// Window session whose QR is scanned to check out.
+    pub checkout_session_id: String,
+    /// Stay needed for the full-duration tier; shorter stays earn partial.
+    pub min_duration_secs: i64,
+    /// Version the policy will have once applied (current + 1).
+    pub version: u32,
+    /// Optional when the request carries the creator's sign-in session.
+    pub creator_signature: Option<String>,
+}
+
+async fn set_checkout_policy(
+    State(state): State<AppState>,
+    session: Option<Extension<AuthSession>>,
+    Path(event_id): Path<String>,
+    Json(req): Json<CheckoutPolicyRequest>,
+) -> Result<Json<CheckoutPolicy>, AppError> {
+    if !is_valid_session_id(&req.checkout_session_id) {
+        return Err(AppError::InvalidSessionId);
+    }
+    if !(0..=MAX_MIN_DURATION_SECS).contains(&req.min_duration_secs) {
+        return Err(AppError::InvalidCheckoutPolicy);
+    }
+
+    let event = state
+        .cache
+        .get_active_event(&event_id)
+        .await
+        .map_err(|e| AppError::Observe(ObserveError::Cache(e)))?
+        .ok_or(AppError::Observe(ObserveError::NotFound))?;
+
+    match req.creator_signature {
+        Some(signature) => {
+            let message =
+                CheckoutPolicy::message_to_sign(&event_id, &req.checkout_session_id, req.min_duration_secs, req.version);
+            signatures::verify_ckb_address_signature(&message, &signature, &event.creator_address)
+                .map_err(|_| AppError::InvalidSignature)?;
+        }
+        None => require_creator(session.as_deref(), &event)?,
+    }
+
+    let policy =
+        observe::set_checkout_policy(&state.cache, &event, &req.checkout_session_id, req.min_duration_secs, req.version)
+            .await
+            .map_err(AppError::Observe)?;
+    Ok(Json(policy))
 }
 
-async fn submit_window(
+/// Organizer-only: duration proofs recorded at check-out.
+async fn get_checkouts(
     State(state): State<AppState>,
+    session: Option<Extension<AuthSession>>,
     Path(event_id): Path<String>,
-    Json(req): Json<WindowRequest>,
-) -> Result<Json<WindowProof>, AppError> {
-    // Verify the creator's signature over the window parameters.
+) -> Result<Json<Vec<DurationProof>>, AppError> {
     let event = state
         .cache
         .get_active_event(&event_id)
         .await
         .map_err(|e| AppError::Observe(ObserveError::Cache(e)))?
         .ok_or(AppError::Observe(ObserveError::NotFound))?;
+    require_creator(session.as_deref(), &event)?;
 
-    let message = WindowProof::message_to_sign(&event_id, req.window_start, req.window_end);
-    signatures::verify_ckb_address_signature(&message, &req.creator_signature, &event.creator_address)
-        .map_err(|_| AppError::InvalidSignature)?;
-
-    let window_secret = qr::derive_window_secret(&event_id, req.window_start, &req.creator_signature);
-    let commitment = hex::encode(sha2::Sha256::digest(window_secret));
-
-    let window = WindowProof {
-        event_id: event_id.clone(),
-        window_start: req.window_start,
-        window_end: req.window_end,
-        creator_signature: req.creator_signature,
-        window_secret_commitment: commitment,
-    };
-
-    observe::update_window(&state.cache, &event_id, window.clone())
+    let proofs = state
+        .cache
+        .get_duration_proofs(&event.event_id)
         .await
-        .map_err(AppError::Observe)?;
+        .map_err(|e| AppError::Observe(ObserveError::Cache(e)))?;
+    Ok(Json(proofs))
+}
 
-    Ok(Json(window))
+#[derive(Serialize)]
+pub struct SessionCheckIns {
+    pub session_id: String,
+    pub label: Option<String>,
+    pub window_start: i64,
+    pub window_end: Option<i64>,
+    pub checkins: Vec<CheckIn>,
 }
 
-async fn get_qr(
+/// Organizer-only: who checked in to each window session.
+async fn get_checkins(
     State(state): State<AppState>,
+    session: Option<Extension<AuthSession>>,
     Path(event_id): Path<String>,
-) -> Result<Json<QrResponse>, AppError> {
+) -> Result<Json<Vec<SessionCheckIns>>, AppError> {
     let event = state
         .cache
         .get_active_event(&event_id)
         .await
         .map_err(|e| AppError::Observe(ObserveError::Cache(e)))?
         .ok_or(AppError::Observe(ObserveError::NotFound))?;
+    require_creator(session.as_deref(), &event)?;
 
-    let window = event.window.as_ref().ok_or(AppError::WindowNotOpen)?;
-
-    if !window.is_open() {
-        return Err(AppError::WindowClosed);
-    }
-
-    let window_secret = qr::derive_window_secret(
-        &event_id,
-        window.window_start,
-        &window.creator_signature,
-    );
-
-    let payload = qr::generate_qr_payload(&event_id, &window_secret);
-    let ttl = qr::qr_ttl_seconds();
-
-    Ok(Json(QrResponse {
-        qr_data: payload.encode(),
-        ttl_seconds: ttl,
-        expires_at: payload.timestamp + ttl as i64,
-        window_end: window.window_end,
-    }))
+    let sessions = observe::get_session_checkins(&state.cache, &event)
+        .await
+        .map_err(AppError::Observe)?;
+    Ok(Json(
+        sessions
+            .into_iter()
+            .map(|(window, checkins)| SessionCheckIns {
+                window_end: window.end(),
+                session_id: window.session_id,
+                label: window.label,
+                window_start: window.window_start,
+                checkins,
+            })
+            .collect(),
+    ))
 }
 
 #[derive(Deserialize)]
@@ -264,16 +1619,46 @@ async fn activate_event(
     Ok(Json(event))
 }
 
+#[derive(Deserialize)]
+pub struct BadgeHoldersQuery {
+    #[serde(default)]
+    pub verify: bool,
+    /// Only list holders with this role.
+    pub role: Option<BadgeRole>,
+    #[serde(default)]
+    pub group_by_role: bool,
+}
+
+#[derive(Serialize)]
+pub struct BadgeHoldersResponse {
+    #[serde(flatten)]
+    pub holders: observe::BadgeListResponse,
+    /// Holder addresses keyed by role, when `group_by_role` is set.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub by_role: Option<BTreeMap<&'static str, Vec<String>>>,
+}
+
 async fn get_badge_holders(
     State(state): State<AppState>,
     Path(event_id): Path<String>,
-    Query(query): Query<VerifyQuery>,
-) -> Result<Json<observe::BadgeListResponse>, AppError> {
-    let chain_config = state.dob_code_hash.as_deref().map(|ch| (ch, state.address_hrp.as_str()));
-    let response = observe::observe_badges_by_event(&state.cache, &state.rpc, &event_id, query.verify, chain_config)
+    Query(query): Query<BadgeHoldersQuery>,
+) -> Result<Json<BadgeHoldersResponse>, AppError> {
+    let chain_config = state.dob_code_hash.as_deref().map(|ch| (ch, state.address_policy.hrp.as_str()));
+    let mut holders = observe::observe_badges_by_event(&state.cache, &state.rpc, &event_id, query.verify, chain_config)
         .await
         .map_err(AppError::BadgeObserve)?;
-    Ok(Json(response))
+
+    if let Some(role) = query.role {
+        holders.badges.retain(|b| b.role == role);
+    }
+    let by_role = query.group_by_role.then(|| {
+        let mut groups: BTreeMap<&'static str, Vec<String>> = BTreeMap::new();
+        for badge in &holders.badges {
+            groups.entry(badge.role.as_str()).or_default().push(badge.holder_address.clone());
+        }
+        groups
+    });
+    Ok(Json(BadgeHoldersResponse { holders, by_role }))
 }
 
 #[derive(Deserialize)]
@@ -285,46 +1670,399 @@ pub struct BadgeQuery {
 
 async fn observe_badges(
     State(state): State<AppState>,
+    Extension(quota): Extension<AddressQuota>,
     Query(query): Query<BadgeQuery>,
 ) -> Result<Json<observe::BadgeListResponse>, AppError> {
-    let chain_config = state.dob_code_hash.as_deref().map(|ch| (ch, state.address_hrp.as_str()));
+    check_address(&state, &query.address)?;
+    limit_address(&quota, &query.address)?;
+
+    let chain_config = state.dob_code_hash.as_deref().map(|ch| (ch, state.address_policy.hrp.as_str()));
     let response = observe::observe_badges_by_address(&state.cache, &state.rpc, &query.address, query.verify, chain_config)
         .await
         .map_err(AppError::BadgeObserve)?;
     Ok(Json(response))
 }
 
+async fn get_lock_badges(
+    State(state): State<AppState>,
+    Path(lock_hash): Path<String>,
+    Query(query): Query<VerifyQuery>,
+) -> Result<Json<observe::BadgeListResponse>, AppError> {
+    let response = observe::observe_badges_by_lock_hash(&state.cache, &state.rpc, &lock_hash, query.verify)
+        .await
+        .map_err(AppError::BadgeObserve)?;
+    Ok(Json(response))
+}
+
 async fn build_badge(
     State(state): State<AppState>,
+    Extension(quota): Extension<AddressQuota>,
+    Json(req): Json<relay::BuildBadgeTxRequest>,
+) -> Result<Json<relay::BuildBadgeTxResponse>, AppError> {
+    build_and_record_badge(&state, &quota, req).await.map(Json)
+}
+
+#[derive(Serialize)]
+pub struct BatchBuildResult {
+    pub event_id: String,
+    pub address: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub response: Option<relay::BuildBadgeTxResponse>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+/// Submit check-ins queued on a device while offline. Each proof is
+/// verified on its own; one bad proof does not fail the rest.
+async fn build_badge_batch(
+    State(state): State<AppState>,
+    Extension(quota): Extension<AddressQuota>,
+    Json(requests): Json<Vec<relay::BuildBadgeTxRequest>>,
+) -> Result<Json<Vec<BatchBuildResult>>, AppError> {
+    if requests.len() > MAX_CHECKIN_BATCH {
+        return Err(AppError::BatchTooLarge);
+    }
+
+    let mut results = Vec::with_capacity(requests.len());
+    for req in requests {
+        let (event_id, address) = (req.event_id.clone(), req.address.clone());
+        let (response, error) = match build_and_record_badge(&state, &quota, req).await {
+            Ok(response) => (Some(response), None),
+            Err(e) => (None, Some(e.status_and_message().1)),
+        };
+        results.push(BatchBuildResult { event_id, address, response, error });
+    }
+    Ok(Json(results))
+}
+
+async fn build_and_record_badge(
+    state: &AppState,
+    quota: &AddressQuota,
+    req: relay::BuildBadgeTxRequest,
+) -> Result<relay::BuildBadgeTxResponse, AppError> {
+    check_address(state, &req.address)?;
+    check_signer(state, &req.attendance_proof.attendee_address)?;
+    limit_proof_address(quota, &req.attendance_proof)?;
+
+    let event_id = req.event_id.clone();
+    let holder_address = req.address.clone();
+    let holder_lock_hash = signatures::address_to_lock_hash(&holder_address)
+        .map_err(|_| AppError::InvalidAddress)?;
+
+    if let Ok(Some(event)) = state.cache.get_active_event(&event_id).await {
+        sync_prerequisite_badges(state, &event, &req.attendance_proof.attendee_address).await;
+    }
+
+    let mut response = relay::build_badge_tx(&state.cache, &state.rpc, req)
+        .await
+        .map_err(AppError::Relay)?;
+
+    sponsor_badge(state, &event_id, &holder_address, &mut response).await?;
+    record_pending_badge(state, event_id, holder_address, holder_lock_hash, &response).await;
+    Ok(response)
+}
+
+/// Most attendees one airdrop request may list.
+const MAX_AIRDROP_RECIPIENTS: usize = 10_000;
+
+/// Organizer-only: mint badges to checked-in attendees in batched
+/// transactions funded by the signed-in organizer.
+async fn build_airdrop(
+    State(state): State<AppState>,
+    session: Option<Extension<AuthSession>>,
+    Path(event_id): Path<String>,
+    Json(req): Json<relay::AirdropRequest>,
+) -> Result<Json<relay::AirdropResponse>, AppError> {
+    if req.attendee_addresses.len() > MAX_AIRDROP_RECIPIENTS {
+        return Err(AppError::BatchTooLarge);
+    }
+    let event = load_event(&state, &event_id).await?;
+    let funder_address = require_organizer(&state, session.as_deref(), &event, Capability::CheckIn).await?;
+
+    let response = relay::build_airdrop(
+        &state.cache,
+        &state.rpc,
+        &event,
+        &funder_address,
+        &req.attendee_addresses,
+        req.fee_priority,
+    )
+        .await
+        .map_err(AppError::Relay)?;
+    Ok(Json(response))
+}
+
+/// Organizer-only: report the transaction an airdrop batch was broadcast
+/// in. Its badges are recorded as pending until the transaction confirms.
+async fn report_airdrop_tx(
+    State(state): State<AppState>,
+    session: Option<Extension<AuthSession>>,
+    Path((event_id, batch_id)): Path<(String, String)>,
+    Json(req): Json<relay::AirdropTxReport>,
+) -> Result<Json<Vec<AirdropInclusion>>, AppError> {
+    let event = load_event(&state, &event_id).await?;
+    require_organizer(&state, session.as_deref(), &event, Capability::CheckIn).await?;
+
+    let batch = relay::report_airdrop_tx(&state.cache, &event, &batch_id, &req.tx_hash)
+        .await
+        .map_err(AppError::Relay)?;
+    for inclusion in &batch {
+        let role = roles::role_for(&state.cache, &event.event_id, &inclusion.holder_lock_hash)
+            .await
+            .map_err(|e| AppError::Observe(ObserveError::Cache(e)))?;
+        let badge = BadgeObservation {
+            event_id: event.event_id.clone(),
+            holder_address: inclusion.holder_address.clone(),
+            holder_lock_hash: inclusion.holder_lock_hash.clone(),
+            mint_tx_hash: inclusion.tx_hash.clone(),
+            mint_block_number: 0,
+            verified_at_block: 0,
+            observed_at: Utc::now(),
+            flagged_at: None,
+            flag_reason: None,
+            role,
+        };
+        let _ = observe::store_badge_observation(&state.cache, badge).await;
+    }
+    Ok(Json(batch))
+}
+
+/// Organizer-only: attendees included in the event's airdrops so far.
+async fn list_airdrop(
+    State(state): State<AppState>,
+    session: Option<Extension<AuthSession>>,
+    Path(event_id): Path<String>,
+) -> Result<Json<Vec<AirdropInclusion>>, AppError> {
+    let event = load_event(&state, &event_id).await?;
+    require_organizer(&state, session.as_deref(), &event, Capability::CheckIn).await?;
+
+    let inclusions = state
+        .cache
+        .get_airdrop_inclusions(&event.event_id)
+        .await
+        .map_err(|e| AppError::Observe(ObserveError::Cache(e)))?;
+    Ok(Json(inclusions))
+}
+
+/// Organizer-only: release an airdrop batch that was never broadcast, so
+/// its attendees can be airdropped again.
+async fn release_airdrop(
+    State(state): State<AppState>,
+    session: Option<Extension<AuthSession>>,
+    Path((event_id, batch_id)): Path<(String, String)>,
+) -> Result<Json<Vec<AirdropInclusion>>, AppError> {
+    let event = load_event(&state, &event_id).await?;
+    require_organizer(&state, session.as_deref(), &event, Capability::CheckIn).await?;
+
+    relay::release_airdrop(&state.cache, &event, &batch_id)
+        .await
+        .map_err(AppError::Relay)?;
+    let inclusions = state
+        .cache
+        .get_airdrop_inclusions(&event.event_id)
+        .await
+        .map_err(|e| AppError::Observe(ObserveError::Cache(e)))?;
+    Ok(Json(inclusions))
+}
+
+/// First scan at an event with a check-out policy. Records the check-in;
+/// the badge is built at check-out.
+async fn check_in_scan(
+    State(state): State<AppState>,
+    Extension(quota): Extension<AddressQuota>,
+    Json(proof): Json<AttendanceProof>,
+) -> Result<Json<CheckIn>, AppError> {
+    check_signer(&state, &proof.attendee_address)?;
+    limit_proof_address(&quota, &proof)?;
+
+    if let Ok(Some(event)) = state.cache.get_active_event(&proof.event_id).await {
+        sync_prerequisite_badges(&state, &event, &proof.attendee_address).await;
+    }
+
+    let checkin = relay::record_checkin_scan(&state.cache, &proof)
+        .await
+        .map_err(AppError::Relay)?;
+    Ok(Json(checkin))
+}
+
+/// Second scan: pairs with the check-in and builds the badge with the
+/// attendance duration committed in its proof hash.
+async fn check_out(
+    State(state): State<AppState>,
+    Extension(quota): Extension<AddressQuota>,
     Json(req): Json<relay::BuildBadgeTxRequest>,
+) -> Result<Json<relay::CheckOutResponse>, AppError> {
+    check_address(&state, &req.address)?;
+    check_signer(&state, &req.attendance_proof.attendee_address)?;
+    limit_proof_address(&quota, &req.attendance_proof)?;
+
+    let event_id = req.event_id.clone();
+    let holder_address = req.address.clone();
+    let holder_lock_hash = signatures::address_to_lock_hash(&holder_address)
+        .map_err(|_| AppError::InvalidAddress)?;
+
+    let mut response = relay::check_out(&state.cache, &state.rpc, req)
+        .await
+        .map_err(AppError::Relay)?;
+
+    sponsor_badge(&state, &event_id, &holder_address, &mut response.badge).await?;
+    record_pending_badge(&state, event_id, holder_address, holder_lock_hash, &response.badge).await;
+    Ok(Json(response))
+}
+
+#[derive(Deserialize)]
+pub struct IssueVoucherRequest {
+    pub qr_payload: QrPayload,
+}
+
+/// Check-in for attendees without a wallet: trade the scanned QR frame for
+/// a claim voucher to redeem later.
+async fn issue_voucher(
+    State(state): State<AppState>,
+    Json(req): Json<IssueVoucherRequest>,
+) -> Result<Json<ClaimVoucher>, AppError> {
+    let voucher = relay::issue_voucher(&state.cache, &req.qr_payload)
+        .await
+        .map_err(AppError::Relay)?;
+    Ok(Json(voucher))
+}
+
+async fn redeem_voucher(
+    State(state): State<AppState>,
+    Extension(quota): Extension<AddressQuota>,
+    Json(req): Json<relay::RedeemVoucherRequest>,
 ) -> Result<Json<relay::BuildBadgeTxResponse>, AppError> {
+    check_signer(&state, &req.address)?;
+    let claim = ClaimVoucher::claim_message_to_sign(&req.event_id, &ClaimVoucher::code_hash(&req.code), &req.address);
+    limit_signed_address(&quota, &claim, &req.attendee_signature, &req.address)?;
     let event_id = req.event_id.clone();
     let holder_address = req.address.clone();
+    let holder_lock_hash = signatures::address_to_lock_hash(&holder_address)
+        .map_err(|_| AppError::InvalidAddress)?;
+
+    if let Ok(Some(event)) = state.cache.get_active_event(&event_id).await {
+        sync_prerequisite_badges(&state, &event, &holder_address).await;
+    }
+
+    let mut response = relay::redeem_voucher(&state.cache, &state.rpc, req)
+        .await
+        .map_err(AppError::Relay)?;
+
+    sponsor_badge(&state, &event_id, &holder_address, &mut response).await?;
+    record_pending_badge(&state, event_id, holder_address, holder_lock_hash, &response).await;
+    Ok(Json(response))
+}
+
+/// Organizer-side check-in: the organizer scans the presence QR shown by
+/// the attendee's wallet and submits it here.
+async fn presence_check_in(
+    State(state): State<AppState>,
+    session: Option<Extension<AuthSession>>,
+    Extension(quota): Extension<AddressQuota>,
+    Json(req): Json<relay::PresenceCheckInRequest>,
+) -> Result<Json<relay::BuildBadgeTxResponse>, AppError> {
+    let presence = PresenceRequest::parse(&req.presence_qr).ok_or(AppError::InvalidQrData)?;
+    check_signer(&state, &presence.attendee_address)?;
+    limit_signed_address(&quota, &presence.signed_message(), &presence.attendee_signature, &presence.attendee_address)?;
+    let holder_lock_hash = signatures::address_to_lock_hash(&presence.attendee_address)
+        .map_err(|_| AppError::InvalidAddress)?;
+
+    let event = state
+        .cache
+        .get_active_event(&presence.event_id)
+        .await
+        .map_err(|e| AppError::Observe(ObserveError::Cache(e)))?
+        .ok_or(AppError::Observe(ObserveError::NotFound))?;
+
+    let window = match req.session_id.as_deref() {
+        Some(session_id) => event.window(session_id).ok_or(AppError::Relay(RelayError::UnknownSession))?,
+        None => event.current_window().ok_or(AppError::WindowNotOpen)?,
+    };
+
+    match req.organizer_signature {
+        Some(signature) => {
+            let signer = resolve_signer(&state, &event, req.organizer_address, Capability::CheckIn).await?;
+            let message = presence.ack_message_to_sign(&window.session_id);
+            signatures::verify_ckb_address_signature(&message, &signature, &signer)
+                .map_err(|_| AppError::InvalidSignature)?;
+        }
+        None => {
+            require_organizer(&state, session.as_deref(), &event, Capability::CheckIn).await?;
+        }
+    }
 
-    let response = relay::build_badge_tx(&state.cache, &state.rpc, req)
+    sync_prerequisite_badges(&state, &event, &presence.attendee_address).await;
+    let mut response = relay::check_in_presence(&state.cache, &state.rpc, &event, window, &presence, req.fee_priority)
         .await
         .map_err(AppError::Relay)?;
 
-    // Record a pending badge observation so badge-holders queries reflect
-    // the mint intent immediately, before on-chain confirmation.
+    sponsor_badge(&state, &event.event_id, &presence.attendee_address, &mut response).await?;
+    record_pending_badge(&state, event.event_id, presence.attendee_address, holder_lock_hash, &response).await;
+    Ok(Json(response))
+}
+
+/// Pull the attendee's badges from the indexer before a gated check-in, so
+/// prerequisites minted elsewhere are seen. Best effort: on failure the
+/// check falls back to whatever the cache already holds.
+async fn sync_prerequisite_badges(state: &AppState, event: &ActiveEvent, attendee_address: &str) {
+    if event.metadata.prerequisite_events.is_empty() {
+        return;
+    }
+    let Some(code_hash) = state.dob_code_hash.as_deref() else {
+        return;
+    };
+    let hrp = state.address_policy.hrp.as_str();
+    if let Err(e) = observe::sync_from_chain_for_address(&state.cache, &state.rpc, attendee_address, code_hash, hrp).await {
+        tracing::warn!("Prerequisite badge sync for {attendee_address} failed: {e}");
+    }
+}
+
+/// Have the event's sponsor budget pay for a freshly built badge, when
+/// sponsored minting is configured and the budget still covers one.
+async fn sponsor_badge(
+    state: &AppState,
+    event_id: &str,
+    holder_address: &str,
+    response: &mut relay::BuildBadgeTxResponse,
+) -> Result<(), AppError> {
+    let Some(wallet) = state.sponsor.as_deref() else {
+        return Ok(());
+    };
+    response.sponsor = sponsor::sponsor_badge(&state.cache, wallet, event_id, holder_address, &response.fee)
+        .await
+        .map_err(AppError::Sponsor)?;
+    Ok(())
+}
+
+/// Record a pending badge observation so badge-holders queries reflect the
+/// mint intent immediately, before on-chain confirmation.
+async fn record_pending_badge(
+    state: &AppState,
+    event_id: String,
+    holder_address: String,
+    holder_lock_hash: String,
+    response: &relay::BuildBadgeTxResponse,
+) {
     let badge = BadgeObservation {
         event_id,
         holder_address,
+        holder_lock_hash,
         mint_tx_hash: response.tx_hash.clone(),
         mint_block_number: 0,
         verified_at_block: 0,
         observed_at: Utc::now(),
+        flagged_at: None,
+        flag_reason: None,
+        role: response.role,
     };
     let _ = observe::store_badge_observation(&state.cache, badge).await;
-
-    Ok(Json(response))
 }
 
 async fn broadcast_badge(
     State(state): State<AppState>,
     Json(req): Json<relay::BroadcastRequest>,
 ) -> Result<Json<relay::BroadcastResponse>, AppError> {
-    let response = relay::broadcast_tx(&state.rpc, req)
+    let response = relay::broadcast_tx(&state.rpc, state.dob_code_hash.as_deref(), req)
         .await
         .map_err(AppError::Relay)?;
     Ok(Json(response))
@@ -355,13 +2093,24 @@ async fn record_badge(
         Ok(Some(_)) => {}
     }
 
+    check_address(&state, &req.holder_address)?;
+    let holder_lock_hash = signatures::address_to_lock_hash(&req.holder_address)
+        .map_err(|_| AppError::InvalidAddress)?;
+    let role = roles::role_for(&state.cache, &req.event_id, &holder_lock_hash)
+        .await
+        .map_err(|e| AppError::Observe(ObserveError::Cache(e)))?;
+
     let badge = BadgeObservation {
         event_id: req.event_id,
         holder_address: req.holder_address,
+        holder_lock_hash,
         mint_tx_hash: req.tx_hash,
         mint_block_number: 0, // Pending confirmation — background task will resolve.
         verified_at_block: 0,
         observed_at: Utc::now(),
+        flagged_at: None,
+        flag_reason: None,
+        role,
     };
 
     observe::store_badge_observation(&state.cache, badge.clone())
@@ -423,6 +2172,8 @@ pub struct QrParseQuery {
 pub struct QrParseResponse {
     pub event_id: String,
     pub timestamp: i64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub session_id: Option<String>,
     pub valid: bool,
 }
 
@@ -433,31 +2184,71 @@ async fn parse_qr(
     let payload = QrPayload::parse(&query.data)
         .ok_or(AppError::InvalidQrData)?;
 
-    // Validate HMAC if the event exists and has an open window.
-    let valid = if let Some(event) = state.cache.get_active_event(&payload.event_id)
+    // Validate HMAC if the event exists and has a matching window session.
+    let valid = state
+        .cache
+        .get_active_event(&payload.event_id)
         .await
-        .map_err(|e| AppError::Observe(ObserveError::Cache(e)))? {
-        if let Some(window) = event.window.as_ref() {
-            let window_secret = qr::derive_window_secret(
-                &payload.event_id,
-                window.window_start,
-                &window.creator_signature,
-            );
-            qr::verify_qr_hmac(&window_secret, payload.timestamp, &payload.hmac)
-        } else {
-            false
-        }
-    } else {
-        false
-    };
+        .map_err(|e| AppError::Observe(ObserveError::Cache(e)))?
+        .is_some_and(|event| {
+            relay::resolve_window(&event, &payload).is_ok_and(|window| {
+                qr::verify_qr_hmac(&qr::window_secret(window), payload.timestamp, &payload.hmac)
+            })
+        });
 
     Ok(Json(QrParseResponse {
         event_id: payload.event_id,
         timestamp: payload.timestamp,
+        session_id: payload.session_id,
         valid,
     }))
 }
 
+/// Reject addresses that are malformed, encoded for another network, or use a
+/// lock script this deployment does not accept.
+fn check_address(state: &AppState, address: &str) -> Result<(), AppError> {
+    state.address_policy.validate(address).map_err(address_error)
+}
+
+/// `check_address` for an address that signs the request or will sign
+/// later ones, which the extra lock code hashes cannot.
+fn check_signer(state: &AppState, address: &str) -> Result<(), AppError> {
+    state.address_policy.validate_signer(address).map_err(address_error)
+}
+
+fn address_error(e: SignatureError) -> AppError {
+    match e {
+        SignatureError::WrongNetwork => AppError::WrongNetwork,
+        SignatureError::UnsupportedLockScript => AppError::UnsupportedLockScript,
+        SignatureError::LockCannotSign => AppError::LockCannotSign,
+        _ => AppError::InvalidAddress,
+    }
+}
+
+/// Spend the route's per-address quota on `address` once `signature` shows
+/// the caller controls it. A bad signature only counts against the per-IP
+/// quota; the handler rejects the request anyway.
+fn limit_signed_address(quota: &AddressQuota, message: &str, signature: &str, address: &str) -> Result<(), AppError> {
+    if signatures::verify_ckb_address_signature(message, signature, address).is_err() {
+        return Ok(());
+    }
+    limit_address(quota, address)
+}
+
+/// Spend the route's per-address quota on the attendee an attendance proof
+/// is signed by.
+fn limit_proof_address(quota: &AddressQuota, proof: &AttendanceProof) -> Result<(), AppError> {
+    limit_signed_address(quota, &proof.signed_message(), &proof.attendee_signature, &proof.attendee_address)
+}
+
+/// Spend the route's per-address quota on `address`.
+fn limit_address(quota: &AddressQuota, address: &str) -> Result<(), AppError> {
+    match signatures::address_to_lock_hash(address) {
+        Ok(lock_hash) => quota.check(&lock_hash).map_err(AppError::RateLimited),
+        Err(_) => Ok(()),
+    }
+}
+
 #[derive(Debug)]
 pub enum AppError {
     Observe(ObserveError),
@@ -468,15 +2259,55 @@ pub enum AppError {
     WindowClosed,
     InvalidSignature,
     InvalidQrData,
+    InvalidAddress,
+    WrongNetwork,
+    UnsupportedLockScript,
+    LockCannotSign,
+    Auth(AuthError),
+    SessionRequired,
+    NotEventCreator,
+    InvalidSessionId,
+    MissingWindowStart,
+    NoEventSchedule,
+    InvalidGracePeriod,
+    InvalidCheckoutPolicy,
+    InvalidRsvpCapacity,
+    InvalidClaimPeriod,
+    BatchTooLarge,
+    /// Seconds until the address may try again.
+    RateLimited(u64),
+    Delegation(DelegationError),
+    NotEventOrganizer,
+    InvalidAllowlist,
+    NoAllowlist,
+    Series(SeriesError),
+    Achievement(AchievementError),
+    Role(RoleError),
+    Rsvp(RsvpError),
+    Sponsor(SponsorError),
+    SponsorDisabled,
 }
 
-impl IntoResponse for AppError {
-    fn into_response(self) -> Response {
-        let (status, message) = match &self {
+impl AppError {
+    fn status_and_message(&self) -> (StatusCode, String) {
+        let detail;
+        let (status, message) = match self {
             AppError::Observe(ObserveError::NotFound) => (StatusCode::NOT_FOUND, "event not found"),
             AppError::Observe(ObserveError::PaymentNotFound) => (StatusCode::NOT_FOUND, "payment not found"),
             AppError::Observe(ObserveError::PaymentNotConfirmed) => (StatusCode::BAD_REQUEST, "payment not confirmed"),
+            AppError::Observe(ObserveError::EventCancelled) => (StatusCode::CONFLICT, "event cancelled"),
+            AppError::Observe(ObserveError::VersionConflict) => (StatusCode::CONFLICT, "metadata version conflict"),
+            AppError::Observe(ObserveError::PolicyVersionConflict) => (StatusCode::CONFLICT, "policy version conflict"),
+            AppError::Observe(ObserveError::EventArchived) => (StatusCode::GONE, "event archived"),
+            AppError::Observe(ObserveError::NotPaid) => (StatusCode::PAYMENT_REQUIRED, "event payment has not been observed"),
+            AppError::Observe(ObserveError::StateChanged) => (StatusCode::CONFLICT, "event state changed, retry"),
+            AppError::Observe(e @ ObserveError::InvalidTransition { .. }) => {
+                detail = e.to_string();
+                (StatusCode::CONFLICT, detail.as_str())
+            }
             AppError::Observe(_) => (StatusCode::INTERNAL_SERVER_ERROR, "internal error"),
+            AppError::BadgeObserve(observe::BadgeObserveError::InvalidAddress) => (StatusCode::BAD_REQUEST, "invalid CKB address"),
+            AppError::BadgeObserve(observe::BadgeObserveError::InvalidLockHash) => (StatusCode::BAD_REQUEST, "invalid lock hash"),
             AppError::BadgeObserve(e) => {
                 tracing::error!("Badge observe error: {e}");
                 (StatusCode::INTERNAL_SERVER_ERROR, "internal error")
@@ -484,19 +2315,194 @@ impl IntoResponse for AppError {
             AppError::PaymentObserve(PaymentObserveError::NotFound) => (StatusCode::NOT_FOUND, "payment not found"),
             AppError::PaymentObserve(_) => (StatusCode::INTERNAL_SERVER_ERROR, "internal error"),
             AppError::Relay(RelayError::EventNotFound) => (StatusCode::NOT_FOUND, "event not found"),
+            AppError::Relay(RelayError::EventCancelled) => (StatusCode::GONE, "event cancelled"),
+            AppError::Relay(RelayError::EventArchived) => (StatusCode::GONE, "event archived"),
+            AppError::Relay(RelayError::EventNotPaid) => (StatusCode::PAYMENT_REQUIRED, "event payment has not been observed"),
             AppError::Relay(RelayError::WindowNotOpen) => (StatusCode::FORBIDDEN, "window not open"),
+            AppError::Relay(RelayError::UnknownSession) => (StatusCode::NOT_FOUND, "window session not found"),
             AppError::Relay(RelayError::WindowClosed) => (StatusCode::FORBIDDEN, "window closed"),
             AppError::Relay(RelayError::ReplayDetected) => (StatusCode::CONFLICT, "replay detected"),
             AppError::Relay(RelayError::InvalidQrHmac) => (StatusCode::UNAUTHORIZED, "invalid qr"),
             AppError::Relay(RelayError::QrExpired) => (StatusCode::GONE, "qr expired"),
+            AppError::Relay(RelayError::InvalidSignature) => (StatusCode::UNAUTHORIZED, "invalid attendee signature"),
+            AppError::Relay(RelayError::CapacityReached) => (StatusCode::CONFLICT, "capacity reached"),
+            AppError::Relay(RelayError::NotOnAllowlist) => (StatusCode::FORBIDDEN, "attendee is not on the event allowlist"),
+            AppError::Relay(e @ RelayError::MissingPrerequisites { .. }) => {
+                detail = e.to_string();
+                (StatusCode::FORBIDDEN, detail.as_str())
+            }
+            AppError::Relay(e @ RelayError::NotEligible { .. }) => {
+                detail = e.to_string();
+                (StatusCode::FORBIDDEN, detail.as_str())
+            }
+            AppError::Relay(RelayError::AlreadyCheckedIn) => (StatusCode::CONFLICT, "attendee already checked in"),
+            AppError::Relay(RelayError::OfflineNotAllowed) => (StatusCode::FORBIDDEN, "event does not accept offline check-ins"),
+            AppError::Relay(RelayError::CheckoutRequired) => {
+                (StatusCode::CONFLICT, "event requires check-in and check-out scans")
+            }
+            AppError::Relay(RelayError::NoCheckoutPolicy) => (StatusCode::NOT_FOUND, "event has no check-out policy"),
+            AppError::Relay(RelayError::CheckoutWindow) => {
+                (StatusCode::BAD_REQUEST, "check-out window cannot be used to check in")
+            }
+            AppError::Relay(RelayError::NotCheckoutWindow) => (StatusCode::BAD_REQUEST, "not the event's check-out window"),
+            AppError::Relay(RelayError::NotCheckedIn) => (StatusCode::CONFLICT, "attendee has not checked in"),
+            AppError::Relay(RelayError::AlreadyCheckedOut) => (StatusCode::CONFLICT, "attendee already checked out"),
+            AppError::Relay(RelayError::VouchersNotAllowed) => (StatusCode::FORBIDDEN, "event does not issue claim vouchers"),
+            AppError::Relay(RelayError::VoucherNotFound) => (StatusCode::NOT_FOUND, "claim voucher not found"),
+            AppError::Relay(RelayError::VoucherExpired) => (StatusCode::GONE, "claim voucher expired"),
+            AppError::Relay(RelayError::VoucherRedeemed) => (StatusCode::CONFLICT, "claim voucher already redeemed"),
+            AppError::Relay(RelayError::InvalidVoucher) => (StatusCode::UNAUTHORIZED, "invalid claim voucher"),
+            AppError::Relay(RelayError::RsvpRequired) => {
+                (StatusCode::FORBIDDEN, "event requires a confirmed RSVP to check in")
+            }
+            AppError::Relay(RelayError::AirdropBatchNotFound) => (StatusCode::NOT_FOUND, "airdrop batch not found"),
+            AppError::Relay(RelayError::AirdropBatchReported) => {
+                (StatusCode::CONFLICT, "airdrop batch already reported under another transaction")
+            }
+            AppError::Relay(RelayError::InvalidTransaction) => {
+                (StatusCode::BAD_REQUEST, "signed_tx is not a JSON transaction")
+            }
+            AppError::Relay(RelayError::ScriptFailed { reason, .. }) => (StatusCode::UNPROCESSABLE_ENTITY, reason.as_str()),
+            AppError::Relay(e @ RelayError::TxRejected(_)) => {
+                detail = e.to_string();
+                (StatusCode::UNPROCESSABLE_ENTITY, detail.as_str())
+            }
+            AppError::Relay(RelayError::ChainUnavailable(e)) => {
+                tracing::warn!("Node could not verify or submit the transaction: {e}");
+                (StatusCode::SERVICE_UNAVAILABLE, "chain unavailable")
+            }
             AppError::Relay(_) => (StatusCode::INTERNAL_SERVER_ERROR, "internal error"),
             AppError::WindowNotOpen => (StatusCode::FORBIDDEN, "window not open"),
             AppError::WindowClosed => (StatusCode::FORBIDDEN, "window closed"),
             AppError::InvalidSignature => (StatusCode::UNAUTHORIZED, "invalid creator signature"),
             AppError::InvalidQrData => (StatusCode::BAD_REQUEST, "invalid QR data format"),
+            AppError::InvalidAddress => (StatusCode::BAD_REQUEST, "invalid CKB address"),
+            AppError::WrongNetwork => (StatusCode::BAD_REQUEST, "address belongs to a different CKB network"),
+            AppError::UnsupportedLockScript => (StatusCode::BAD_REQUEST, "unsupported lock script"),
+            AppError::LockCannotSign => {
+                (StatusCode::BAD_REQUEST, "lock script cannot sign; use a secp256k1-blake160 address")
+            }
+            AppError::Auth(AuthError::ChallengeNotFound) => (StatusCode::NOT_FOUND, "challenge not found"),
+            AppError::Auth(AuthError::ChallengeExpired) => (StatusCode::GONE, "challenge expired"),
+            AppError::Auth(AuthError::InvalidSignature) => (StatusCode::UNAUTHORIZED, "invalid signature"),
+            AppError::Auth(_) => (StatusCode::INTERNAL_SERVER_ERROR, "internal error"),
+            AppError::SessionRequired => (StatusCode::UNAUTHORIZED, "sign-in session required"),
+            AppError::NotEventCreator => (StatusCode::FORBIDDEN, "session is not the event creator"),
+            AppError::MissingWindowStart => (StatusCode::BAD_REQUEST, "window_start is required unless use_event_times is set"),
+            AppError::NoEventSchedule => (StatusCode::BAD_REQUEST, "event metadata has no start_time"),
+            AppError::InvalidSessionId => (StatusCode::BAD_REQUEST, "session_id must be 1-64 letters, digits, '-' or '_'"),
+            AppError::InvalidGracePeriod => (StatusCode::BAD_REQUEST, "grace_secs must be between 0 and 7 days"),
+            AppError::InvalidCheckoutPolicy => {
+                (StatusCode::BAD_REQUEST, "min_duration_secs must be between 0 and 7 days")
+            }
+            AppError::InvalidRsvpCapacity => (StatusCode::BAD_REQUEST, "capacity must be at least 1"),
+            AppError::InvalidClaimPeriod => (StatusCode::BAD_REQUEST, "claim_period_secs must be between 0 and 30 days"),
+            AppError::BatchTooLarge => (StatusCode::PAYLOAD_TOO_LARGE, "too many check-ins in one batch"),
+            AppError::RateLimited(_) => (StatusCode::TOO_MANY_REQUESTS, "rate limit exceeded"),
+            AppError::Delegation(DelegationError::InvalidAddress) => (StatusCode::BAD_REQUEST, "invalid delegate address"),
+            AppError::Delegation(DelegationError::InvalidSignature) => (StatusCode::UNAUTHORIZED, "invalid creator signature"),
+            AppError::Delegation(DelegationError::AlreadyExpired) => (StatusCode::BAD_REQUEST, "delegation expiry is in the past"),
+            AppError::Delegation(DelegationError::SelfDelegation) => (StatusCode::BAD_REQUEST, "creator cannot delegate to themselves"),
+            AppError::Delegation(DelegationError::NotFound) => (StatusCode::NOT_FOUND, "delegation not found"),
+            AppError::Delegation(DelegationError::VersionConflict) => (StatusCode::CONFLICT, "delegation version conflict"),
+            AppError::Delegation(_) => (StatusCode::INTERNAL_SERVER_ERROR, "internal error"),
+            AppError::NotEventOrganizer => (StatusCode::FORBIDDEN, "not an organizer of this event"),
+            AppError::InvalidAllowlist => (StatusCode::BAD_REQUEST, "allowlist must have 1-10000 addresses or lock hashes"),
+            AppError::NoAllowlist => (StatusCode::NOT_FOUND, "event has no allowlist"),
+            AppError::Series(SeriesError::NotFound) => (StatusCode::NOT_FOUND, "series not found"),
+            AppError::Series(SeriesError::InvalidSignature) => (StatusCode::UNAUTHORIZED, "invalid creator signature"),
+            AppError::Series(SeriesError::MissingStartTime) => (StatusCode::BAD_REQUEST, "series template needs a start_time"),
+            AppError::Series(SeriesError::InvalidRecurrence) => {
+                (StatusCode::BAD_REQUEST, "recurrence needs interval >= 1 and 1-104 occurrences")
+            }
+            AppError::Series(SeriesError::Observe(ObserveError::InvalidTransition { .. })) => {
+                (StatusCode::CONFLICT, "series occurrence already exists")
+            }
+            AppError::Achievement(AchievementError::NotFound) => (StatusCode::NOT_FOUND, "achievement not found"),
+            AppError::Achievement(AchievementError::InvalidSignature) => (StatusCode::UNAUTHORIZED, "invalid creator signature"),
+            AppError::Achievement(AchievementError::InvalidAddress) => (StatusCode::BAD_REQUEST, "invalid CKB address"),
+            AppError::Achievement(AchievementError::InvalidDefinition) => {
+                (StatusCode::BAD_REQUEST, "achievement needs 1-500 distinct event ids and 1 <= min_count <= their number")
+            }
+            AppError::Achievement(AchievementError::Chain(e)) => {
+                tracing::warn!("Achievement chain check failed: {e}");
+                (StatusCode::SERVICE_UNAVAILABLE, "chain unavailable")
+            }
+            AppError::Achievement(e) => {
+                tracing::error!("Achievement error: {e}");
+                (StatusCode::INTERNAL_SERVER_ERROR, "internal error")
+            }
+            AppError::Series(e) => {
+                tracing::error!("Series error: {e}");
+                (StatusCode::INTERNAL_SERVER_ERROR, "internal error")
+            }
+            AppError::Role(RoleError::InvalidAddress) => (StatusCode::BAD_REQUEST, "invalid holder address"),
+            AppError::Role(RoleError::InvalidSignature) => (StatusCode::UNAUTHORIZED, "invalid creator signature"),
+            AppError::Role(RoleError::AlreadyMinted) => (StatusCode::CONFLICT, "badge already built for this holder"),
+            AppError::Role(e) => {
+                tracing::error!("Role error: {e}");
+                (StatusCode::INTERNAL_SERVER_ERROR, "internal error")
+            }
+            AppError::Rsvp(RsvpError::InvalidAddress) => (StatusCode::BAD_REQUEST, "invalid attendee address"),
+            AppError::Rsvp(RsvpError::InvalidSignature) => (StatusCode::UNAUTHORIZED, "invalid attendee signature"),
+            AppError::Rsvp(RsvpError::EventClosed) => (StatusCode::CONFLICT, "event is not taking RSVPs"),
+            AppError::Rsvp(RsvpError::NotFound) => (StatusCode::NOT_FOUND, "RSVP not found"),
+            AppError::Rsvp(RsvpError::PolicyVersionConflict) => (StatusCode::CONFLICT, "policy version conflict"),
+            AppError::Rsvp(e) => {
+                tracing::error!("RSVP error: {e}");
+                (StatusCode::INTERNAL_SERVER_ERROR, "internal error")
+            }
+            AppError::Sponsor(SponsorError::EventClosed) => (StatusCode::CONFLICT, "event is cancelled or archived"),
+            AppError::Sponsor(SponsorError::DepositNotFound) => (StatusCode::NOT_FOUND, "deposit transaction not found"),
+            AppError::Sponsor(SponsorError::DepositNotConfirmed) => {
+                (StatusCode::BAD_REQUEST, "deposit transaction not confirmed")
+            }
+            AppError::Sponsor(SponsorError::NotPaidToSponsor) => {
+                (StatusCode::BAD_REQUEST, "deposit pays nothing to the sponsor wallet for this event")
+            }
+            AppError::Sponsor(SponsorError::AlreadyCredited) => (StatusCode::CONFLICT, "deposit already credited"),
+            AppError::Sponsor(SponsorError::DrawNotFound) => (StatusCode::NOT_FOUND, "no sponsor draw for this holder"),
+            AppError::Sponsor(SponsorError::InputNotFound) => {
+                (StatusCode::BAD_REQUEST, "transaction input cell not found")
+            }
+            AppError::Sponsor(SponsorError::NoSponsorInputs) => {
+                (StatusCode::BAD_REQUEST, "transaction spends no sponsor cells")
+            }
+            AppError::Sponsor(SponsorError::ExceedsDraw) => {
+                (StatusCode::FORBIDDEN, "transaction takes more than the sponsor draw")
+            }
+            AppError::Sponsor(SponsorError::HolderNotPaid) => {
+                (StatusCode::BAD_REQUEST, "transaction does not pay the holder the sponsored capacity")
+            }
+            AppError::Sponsor(SponsorError::MalformedWitness) => (StatusCode::BAD_REQUEST, "malformed sponsor witness"),
+            AppError::Sponsor(SponsorError::DrawAlreadySigned) => {
+                (StatusCode::CONFLICT, "sponsor draw already signed for another transaction")
+            }
+            AppError::Sponsor(SponsorError::Rpc(e)) => {
+                tracing::warn!("Sponsor chain lookup failed: {e}");
+                (StatusCode::SERVICE_UNAVAILABLE, "chain unavailable")
+            }
+            AppError::Sponsor(e) => {
+                tracing::error!("Sponsor error: {e}");
+                (StatusCode::INTERNAL_SERVER_ERROR, "internal error")
+            }
+            AppError::SponsorDisabled => (StatusCode::NOT_FOUND, "sponsored minting is not configured"),
         };
+        (status, message.to_string())
+    }
+}
 
-        let body = serde_json::json!({ "error": message });
+impl IntoResponse for AppError {
+    fn into_response(self) -> Response {
+        if let AppError::RateLimited(retry_after) = self {
+            return ratelimit::too_many_requests(retry_after);
+        }
+        let (status, message) = self.status_and_message();
+        let mut body = serde_json::json!({ "error": message });
+        if let AppError::Relay(RelayError::ScriptFailed { script, exit_code, .. }) = &self {
+            body["script"] = script.as_str().into();
+            body["exit_code"] = (*exit_code).into();
+        }
         (status, Json(body)).into_response()
     }
 }
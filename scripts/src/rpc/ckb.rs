@@ -17,6 +17,25 @@ pub struct TransactionInfo {
     pub block_number: Option<u64>,
     pub block_hash: Option<String>,
     pub confirmed: bool,
+    pub outputs: Vec<TxOutput>,
+}
+
+/// A transaction output's capacity, lock script and cell data.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct TxOutput {
+    /// Capacity in shannons.
+    pub capacity: u64,
+    pub lock_code_hash: String,
+    pub lock_args: String,
+    /// Cell data as 0x-prefixed hex.
+    pub data: String,
+}
+
+/// Recent fee rates in shannons per 1000 bytes.
+#[derive(Clone, Copy, Debug, Serialize, Deserialize)]
+pub struct FeeRateStatistics {
+    pub mean: u64,
+    pub median: u64,
 }
 
 #[derive(Debug, thiserror::Error)]
@@ -91,6 +110,25 @@ impl CkbRpcClient {
 
         let confirmed = status == "committed";
 
+        let transaction = result.get("transaction");
+        let outputs_data = transaction
+            .and_then(|tx| tx.get("outputs_data"))
+            .and_then(|data| data.as_array());
+        let outputs = transaction
+            .and_then(|tx| tx.get("outputs"))
+            .and_then(|outputs| outputs.as_array())
+            .map(|outputs| {
+                outputs
+                    .iter()
+                    .enumerate()
+                    .filter_map(|(i, output)| {
+                        let data = outputs_data.and_then(|d| d.get(i)).and_then(|d| d.as_str()).unwrap_or("0x");
+                        parse_output(output, data)
+                    })
+                    .collect()
+            })
+            .unwrap_or_default();
+
         let block_number = if let Some(ref bh) = block_hash {
             self.get_block_number_by_hash(bh).await.ok().flatten()
         } else {
@@ -102,6 +140,7 @@ impl CkbRpcClient {
             block_number,
             block_hash,
             confirmed,
+            outputs,
         }))
     }
 
@@ -127,6 +166,56 @@ impl CkbRpcClient {
         }
     }
 
+    /// Fee rates of transactions committed over the last `target` blocks
+    /// (the node's default when `None`). `None` when there are none to
+    /// measure.
+    pub async fn get_fee_rate_statistics(&self, target: Option<u64>) -> Result<Option<FeeRateStatistics>, RpcError> {
+        let params = match target {
+            Some(target) => json!([format!("0x{:x}", target)]),
+            None => json!([]),
+        };
+        let result = self.call("get_fee_rate_statistics", params).await?;
+        if result.is_null() {
+            return Ok(None);
+        }
+
+        let field = |name: &str| -> Result<u64, RpcError> {
+            let hex = result
+                .get(name)
+                .and_then(|v| v.as_str())
+                .ok_or_else(|| RpcError::Parse(format!("missing {name}")))?;
+            u64::from_str_radix(hex.trim_start_matches("0x"), 16).map_err(|e| RpcError::Parse(e.to_string()))
+        };
+        Ok(Some(FeeRateStatistics { mean: field("mean")?, median: field("median")? }))
+    }
+
+    /// Cycles the node would spend verifying `tx`, without submitting it.
+    /// Falls back to `dry_run_transaction` on nodes without `estimate_cycles`.
+    pub async fn estimate_cycles(&self, tx: &Value) -> Result<u64, RpcError> {
+        let result = match self.call("estimate_cycles", json!([tx])).await {
+            // -32601: method not found.
+            Err(RpcError::Rpc(e)) if e.contains("-32601") => self.call("dry_run_transaction", json!([tx])).await?,
+            other => other?,
+        };
+
+        let hex = result
+            .get("cycles")
+            .and_then(|v| v.as_str())
+            .ok_or_else(|| RpcError::Parse("missing cycles".into()))?;
+        u64::from_str_radix(hex.trim_start_matches("0x"), 16).map_err(|e| RpcError::Parse(e.to_string()))
+    }
+
+    /// Submit a signed transaction to the pool, returning its hash. Outputs
+    /// pass through unvalidated since the dob-badge type script is not one
+    /// the node knows.
+    pub async fn send_transaction(&self, tx: &Value) -> Result<String, RpcError> {
+        let result = self.call("send_transaction", json!([tx, "passthrough"])).await?;
+        result
+            .as_str()
+            .map(str::to_string)
+            .ok_or_else(|| RpcError::Parse("missing tx hash".into()))
+    }
+
     pub async fn is_connected(&self) -> bool {
         self.get_tip_block_number().await.is_ok()
     }
@@ -154,3 +243,14 @@ impl CkbRpcClient {
         *self.last_block.read().await
     }
 }
+
+fn parse_output(output: &Value, data: &str) -> Option<TxOutput> {
+    let capacity = output.get("capacity")?.as_str()?;
+    let lock = output.get("lock")?;
+    Some(TxOutput {
+        capacity: u64::from_str_radix(capacity.trim_start_matches("0x"), 16).ok()?,
+        lock_code_hash: lock.get("code_hash")?.as_str()?.to_string(),
+        lock_args: lock.get("args")?.as_str()?.to_string(),
+        data: data.to_string(),
+    })
+}
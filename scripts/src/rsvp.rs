@@ -0,0 +1,285 @@
+use std::collections::HashSet;
+
+use chrono::Utc;
+use serde::Serialize;
+
+use crate::cache::Cache;
+use crate::crypto::signatures;
+use crate::types::{ActiveEvent, EventState, Rsvp, RsvpPolicy, RsvpStatus};
+
+#[derive(Debug, Serialize)]
+pub struct RsvpAttendance {
+    #[serde(flatten)]
+    pub rsvp: Rsvp,
+    pub checked_in: bool,
+}
+
+/// RSVPs set against actual check-ins, for organizers.
+#[derive(Debug, Serialize)]
+pub struct RsvpReport {
+    pub event_id: String,
+    pub capacity: Option<u32>,
+    pub confirmed: u32,
+    pub waitlisted: u32,
+    /// Confirmed RSVPs that checked in.
+    pub attended: u32,
+    /// Confirmed RSVPs that did not check in.
+    pub no_shows: u32,
+    /// Addresses that checked in without a confirmed RSVP.
+    pub walk_ins: Vec<String>,
+    pub rsvps: Vec<RsvpAttendance>,
+}
+
+/// The event's RSVP policy, or an open, uncapped one if it never set one.
+pub async fn get_rsvp_policy(cache: &Cache, event_id: &str) -> Result<RsvpPolicy, RsvpError> {
+    let policy = cache.get_rsvp_policy(event_id).await.map_err(RsvpError::Cache)?;
+    Ok(policy.unwrap_or(RsvpPolicy {
+        event_id: event_id.to_string(),
+        capacity: None,
+        required: false,
+        version: 0,
+        updated_at: Utc::now(),
+    }))
+}
+
+/// Store the policy and confirm any waitlisted RSVPs a raised capacity now
+/// has room for. `version` must be the current policy's plus one.
+pub async fn set_rsvp_policy(
+    cache: &Cache,
+    event: &ActiveEvent,
+    capacity: Option<u32>,
+    required: bool,
+    version: u32,
+) -> Result<RsvpPolicy, RsvpError> {
+    check_open(event)?;
+    let current = cache.get_rsvp_policy(&event.event_id).await.map_err(RsvpError::Cache)?;
+    if version != current.map_or(0, |p| p.version) + 1 {
+        return Err(RsvpError::PolicyVersionConflict);
+    }
+
+    let policy = RsvpPolicy {
+        event_id: event.event_id.clone(),
+        capacity,
+        required,
+        version,
+        updated_at: Utc::now(),
+    };
+    if !cache.store_rsvp_policy(&policy).await.map_err(RsvpError::Cache)? {
+        return Err(RsvpError::PolicyVersionConflict);
+    }
+    cache.promote_waitlist(&event.event_id, capacity).await.map_err(RsvpError::Cache)?;
+    Ok(policy)
+}
+
+/// Record an attendee-signed RSVP. Past the policy's capacity the attendee
+/// is waitlisted. RSVPing again returns the existing RSVP unchanged.
+pub async fn submit_rsvp(
+    cache: &Cache,
+    event: &ActiveEvent,
+    attendee_address: &str,
+    attendee_signature: &str,
+) -> Result<Rsvp, RsvpError> {
+    check_open(event)?;
+    let attendee_lock_hash =
+        signatures::address_to_lock_hash(attendee_address).map_err(|_| RsvpError::InvalidAddress)?;
+
+    let rsvp = Rsvp {
+        event_id: event.event_id.clone(),
+        attendee_address: attendee_address.to_string(),
+        attendee_lock_hash,
+        status: RsvpStatus::Confirmed,
+        attendee_signature: attendee_signature.to_string(),
+        created_at: Utc::now(),
+    };
+    signatures::verify_ckb_address_signature(&rsvp.signed_message(), attendee_signature, attendee_address)
+        .map_err(|_| RsvpError::InvalidSignature)?;
+
+    let policy = get_rsvp_policy(cache, &event.event_id).await?;
+    cache.record_rsvp(&rsvp, policy.capacity).await.map_err(RsvpError::Cache)
+}
+
+/// Withdraw an RSVP with the attendee's signature, freeing its place for
+/// the waitlist.
+pub async fn cancel_rsvp(
+    cache: &Cache,
+    event: &ActiveEvent,
+    attendee_address: &str,
+    attendee_signature: &str,
+) -> Result<(), RsvpError> {
+    let attendee_lock_hash =
+        signatures::address_to_lock_hash(attendee_address).map_err(|_| RsvpError::InvalidAddress)?;
+    let message = Rsvp::cancel_message_to_sign(&event.event_id, attendee_address);
+    signatures::verify_ckb_address_signature(&message, attendee_signature, attendee_address)
+        .map_err(|_| RsvpError::InvalidSignature)?;
+
+    if !cache.delete_rsvp(&event.event_id, &attendee_lock_hash).await.map_err(RsvpError::Cache)? {
+        return Err(RsvpError::NotFound);
+    }
+    let policy = get_rsvp_policy(cache, &event.event_id).await?;
+    cache.promote_waitlist(&event.event_id, policy.capacity).await.map_err(RsvpError::Cache)?;
+    Ok(())
+}
+
+pub async fn rsvp_report(cache: &Cache, event: &ActiveEvent) -> Result<RsvpReport, RsvpError> {
+    let policy = get_rsvp_policy(cache, &event.event_id).await?;
+    let rsvps = cache.get_rsvps(&event.event_id).await.map_err(RsvpError::Cache)?;
+    let checkins = cache.get_checkins(&event.event_id).await.map_err(RsvpError::Cache)?;
+    let checked_in: HashSet<&str> = checkins.iter().map(|c| c.attendee_lock_hash.as_str()).collect();
+
+    let confirmed: HashSet<&str> = rsvps
+        .iter()
+        .filter(|r| r.status == RsvpStatus::Confirmed)
+        .map(|r| r.attendee_lock_hash.as_str())
+        .collect();
+    let attended = confirmed.iter().filter(|lock_hash| checked_in.contains(*lock_hash)).count() as u32;
+
+    let mut walk_ins: Vec<String> = Vec::new();
+    for checkin in &checkins {
+        if !confirmed.contains(checkin.attendee_lock_hash.as_str()) && !walk_ins.contains(&checkin.attendee_address) {
+            walk_ins.push(checkin.attendee_address.clone());
+        }
+    }
+
+    Ok(RsvpReport {
+        event_id: event.event_id.clone(),
+        capacity: policy.capacity,
+        confirmed: confirmed.len() as u32,
+        waitlisted: (rsvps.len() - confirmed.len()) as u32,
+        attended,
+        no_shows: confirmed.len() as u32 - attended,
+        walk_ins,
+        rsvps: rsvps
+            .iter()
+            .map(|rsvp| RsvpAttendance {
+                checked_in: checked_in.contains(rsvp.attendee_lock_hash.as_str()),
+                rsvp: rsvp.clone(),
+            })
+            .collect(),
+    })
+}
+
+fn check_open(event: &ActiveEvent) -> Result<(), RsvpError> {
+    match event.state {
+        EventState::Ended | EventState::Cancelled | EventState::Archived => Err(RsvpError::EventClosed),
+        _ => Ok(()),
+    }
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum RsvpError {
+    #[error("cache error: {0}")]
+    Cache(#[from] sqlx::Error),
+    #[error("invalid attendee address")]
+    InvalidAddress,
+    #[error("invalid attendee signature")]
+    InvalidSignature,
+    #[error("event is not taking RSVPs")]
+    EventClosed,
+    #[error("RSVP not found")]
+    NotFound,
+    #[error("RSVP policy version conflict")]
+    PolicyVersionConflict,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::crypto::signatures::test_wallet::TestWallet;
+    use crate::types::{CheckIn, EventMetadata};
+
+    fn test_event() -> ActiveEvent {
+        ActiveEvent {
+            event_id: "evt1".to_string(),
+            metadata: EventMetadata {
+                name: "Test".to_string(),
+                description: "Desc".to_string(),
+                image_url: None,
+                location: None,
+                start_time: None,
+                end_time: None,
+                max_attendees: None,
+                prerequisite_events: vec![],
+                min_prerequisites: None,
+            },
+            creator_address: "ckt1qcreator".to_string(),
+            payment_tx_hash: "0xtx".to_string(),
+            payment_block_number: 100,
+            activated_at: Utc::now(),
+            windows: vec![],
+            metadata_version: 1,
+            cancelled_at: None,
+            state: EventState::Active,
+            state_changed_at: Utc::now(),
+        }
+    }
+
+    async fn rsvp(cache: &Cache, wallet: &TestWallet) -> Result<Rsvp, RsvpError> {
+        let signature = wallet.sign(&Rsvp::message_to_sign("evt1", &wallet.address));
+        submit_rsvp(cache, &test_event(), &wallet.address, &signature).await
+    }
+
+    #[tokio::test]
+    async fn test_rsvp_waitlist_promotes_on_cancel() {
+        let cache = Cache::new("sqlite::memory:").await.unwrap();
+        let event = test_event();
+        set_rsvp_policy(&cache, &event, Some(1), false, 1).await.unwrap();
+        let (first, second) = (TestWallet::new(1, "ckt"), TestWallet::new(2, "ckt"));
+
+        let result = submit_rsvp(&cache, &event, &first.address, &second.sign("other")).await;
+        assert!(matches!(result, Err(RsvpError::InvalidSignature)));
+
+        assert_eq!(rsvp(&cache, &first).await.unwrap().status, RsvpStatus::Confirmed);
+        assert_eq!(rsvp(&cache, &second).await.unwrap().status, RsvpStatus::Waitlisted);
+        assert_eq!(rsvp(&cache, &first).await.unwrap().status, RsvpStatus::Confirmed);
+
+        let signature = first.sign(&Rsvp::cancel_message_to_sign("evt1", &first.address));
+        cancel_rsvp(&cache, &event, &first.address, &signature).await.unwrap();
+        let lock_hash = signatures::address_to_lock_hash(&second.address).unwrap();
+        let promoted = cache.get_rsvp("evt1", &lock_hash).await.unwrap().unwrap();
+        assert_eq!(promoted.status, RsvpStatus::Confirmed);
+
+        let result = cancel_rsvp(&cache, &event, &first.address, &signature).await;
+        assert!(matches!(result, Err(RsvpError::NotFound)));
+    }
+
+    #[tokio::test]
+    async fn test_rsvp_policy_rejects_stale_versions() {
+        let cache = Cache::new("sqlite::memory:").await.unwrap();
+        let event = test_event();
+        set_rsvp_policy(&cache, &event, Some(50), true, 1).await.unwrap();
+        set_rsvp_policy(&cache, &event, None, false, 2).await.unwrap();
+
+        // Re-posting the first, capped policy cannot bring it back.
+        let result = set_rsvp_policy(&cache, &event, Some(50), true, 1).await;
+        assert!(matches!(result, Err(RsvpError::PolicyVersionConflict)));
+        let policy = get_rsvp_policy(&cache, "evt1").await.unwrap();
+        assert_eq!((policy.capacity, policy.required, policy.version), (None, false, 2));
+    }
+
+    #[tokio::test]
+    async fn test_rsvp_report_matches_checkins() {
+        let cache = Cache::new("sqlite::memory:").await.unwrap();
+        let event = test_event();
+        let (came, stayed_home, walk_in) = (TestWallet::new(1, "ckt"), TestWallet::new(2, "ckt"), TestWallet::new(3, "ckt"));
+        rsvp(&cache, &came).await.unwrap();
+        rsvp(&cache, &stayed_home).await.unwrap();
+
+        for wallet in [&came, &walk_in] {
+            let checkin = CheckIn {
+                event_id: "evt1".to_string(),
+                session_id: "default".to_string(),
+                attendee_address: wallet.address.clone(),
+                attendee_lock_hash: signatures::address_to_lock_hash(&wallet.address).unwrap(),
+                qr_timestamp: 1000,
+                checked_in_at: Utc::now(),
+            };
+            cache.record_checkin(&checkin, None).await.unwrap();
+        }
+
+        let report = rsvp_report(&cache, &event).await.unwrap();
+        assert_eq!((report.confirmed, report.attended, report.no_shows), (2, 1, 1));
+        assert_eq!(report.walk_ins, vec![walk_in.address.clone()]);
+        assert!(report.rsvps[0].checked_in);
+        assert!(!report.rsvps[1].checked_in);
+    }
+}
@@ -0,0 +1,226 @@
+use std::sync::atomic::{AtomicI64, Ordering};
+
+use chrono::Utc;
+use serde::Serialize;
+use tokio::sync::broadcast;
+
+use crate::cache::Cache;
+use crate::types::{ActiveEvent, WindowProof};
+
+/// Default cap on how long a window without an end may stay open.
+const DEFAULT_MAX_OPEN_HOURS: i64 = 24;
+
+/// Notices buffered per subscriber before slow clients start missing some.
+const NOTICE_CAPACITY: usize = 256;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum WindowNoticeKind {
+    Opened,
+    Closed,
+    /// An open-ended window hit the configured maximum and was closed.
+    ForceClosed,
+    /// The window's secret was revoked; displays must fetch a fresh QR.
+    Rotated,
+}
+
+impl WindowNoticeKind {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            WindowNoticeKind::Opened => "opened",
+            WindowNoticeKind::Closed => "closed",
+            WindowNoticeKind::ForceClosed => "force_closed",
+            WindowNoticeKind::Rotated => "rotated",
+        }
+    }
+}
+
+/// A window session opening or closing, pushed to QR display clients.
+#[derive(Clone, Debug, Serialize)]
+pub struct WindowNotice {
+    pub event_id: String,
+    pub session_id: String,
+    pub kind: WindowNoticeKind,
+    /// Unix seconds at which the window opened or closed.
+    pub at: i64,
+}
+
+/// Watches stored windows as time passes: announces scheduled windows as
+/// they open and expire, and closes open-ended windows that have run past
+/// the configured maximum.
+pub struct WindowScheduler {
+    notices: broadcast::Sender<WindowNotice>,
+    max_open_secs: i64,
+    last_tick: AtomicI64,
+}
+
+impl WindowScheduler {
+    pub fn new(max_open_hours: i64) -> Self {
+        let (notices, _) = broadcast::channel(NOTICE_CAPACITY);
+        Self {
+            notices,
+            max_open_secs: max_open_hours * 3600,
+            last_tick: AtomicI64::new(Utc::now().timestamp()),
+        }
+    }
+
+    /// Reads `MAX_OPEN_WINDOW_HOURS`, defaulting to 24.
+    pub fn from_env() -> Self {
+        let hours = match std::env::var("MAX_OPEN_WINDOW_HOURS") {
+            Ok(value) => value.parse().expect("Invalid MAX_OPEN_WINDOW_HOURS"),
+            Err(_) => DEFAULT_MAX_OPEN_HOURS,
+        };
+        Self::new(hours)
+    }
+
+    pub fn subscribe(&self) -> broadcast::Receiver<WindowNotice> {
+        self.notices.subscribe()
+    }
+
+    /// Publish a notice. Having no subscribers is not an error.
+    pub fn notify(&self, window: &WindowProof, kind: WindowNoticeKind, at: i64) {
+        let _ = self.notices.send(WindowNotice {
+            event_id: window.event_id.clone(),
+            session_id: window.session_id.clone(),
+            kind,
+            at,
+        });
+    }
+
+    pub async fn tick(&self, cache: &Cache) {
+        self.tick_at(cache, Utc::now().timestamp()).await;
+    }
+
+    async fn tick_at(&self, cache: &Cache, now: i64) {
+        let since = self.last_tick.swap(now, Ordering::SeqCst);
+
+        let events = match cache.list_active_events().await {
+            Ok(events) => events,
+            Err(e) => {
+                tracing::warn!("Failed to list events for window schedule: {}", e);
+                return;
+            }
+        };
+
+        for event in events {
+            self.check_event(cache, event, since, now).await;
+        }
+    }
+
+    async fn check_event(&self, cache: &Cache, event: ActiveEvent, since: i64, now: i64) {
+        for mut window in event.windows {
+            let overdue = window.window_start + self.max_open_secs;
+            if window.end().is_none() && overdue <= now {
+                window.closed_at = Some(overdue);
+                if let Err(e) = cache.store_window(&window).await {
+                    tracing::warn!("Failed to force-close window {}/{}: {}", window.event_id, window.session_id, e);
+                    continue;
+                }
+                tracing::info!("Force-closed open-ended window {}/{}", window.event_id, window.session_id);
+                self.notify(&window, WindowNoticeKind::ForceClosed, overdue);
+                continue;
+            }
+
+            if since < window.window_start && window.window_start <= now {
+                self.notify(&window, WindowNoticeKind::Opened, window.window_start);
+            }
+            if let Some(end) = window.end().filter(|&end| since < end && end <= now) {
+                self.notify(&window, WindowNoticeKind::Closed, end);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::{EventMetadata, EventState};
+
+    async fn cache_with_windows(windows: Vec<WindowProof>) -> Cache {
+        let cache = Cache::new("sqlite::memory:").await.unwrap();
+        let event = ActiveEvent {
+            event_id: "evt1".to_string(),
+            metadata: EventMetadata {
+                name: "Test".to_string(),
+                description: "Desc".to_string(),
+                image_url: None,
+                location: None,
+                start_time: None,
+                end_time: None,
+                max_attendees: None,
+                prerequisite_events: vec![],
+                min_prerequisites: None,
+            },
+            creator_address: "ckt1q".to_string(),
+            payment_tx_hash: "0xtx".to_string(),
+            payment_block_number: 100,
+            activated_at: Utc::now(),
+            windows,
+            metadata_version: 1,
+            cancelled_at: None,
+            state: EventState::Active,
+            state_changed_at: Utc::now(),
+        };
+        cache.store_active_event(&event).await.unwrap();
+        cache
+    }
+
+    fn window(session_id: &str, start: i64, end: Option<i64>) -> WindowProof {
+        WindowProof {
+            event_id: "evt1".to_string(),
+            session_id: session_id.to_string(),
+            label: None,
+            window_start: start,
+            window_end: end,
+            creator_signature: "sig".to_string(),
+            window_secret_commitment: "commit".to_string(),
+            secret_salt: None,
+            rotated_at: None,
+            closed_at: None,
+            authorized_by: None,
+        }
+    }
+
+    fn drain(rx: &mut broadcast::Receiver<WindowNotice>) -> Vec<(String, WindowNoticeKind)> {
+        std::iter::from_fn(|| rx.try_recv().ok()).map(|n| (n.session_id, n.kind)).collect()
+    }
+
+    #[tokio::test]
+    async fn test_tick_announces_scheduled_open_and_close() {
+        let cache = cache_with_windows(vec![window("talk", 1_000, Some(2_000))]).await;
+        let scheduler = WindowScheduler::new(24);
+        scheduler.last_tick.store(500, Ordering::SeqCst);
+        let mut rx = scheduler.subscribe();
+
+        scheduler.tick_at(&cache, 900).await;
+        assert!(drain(&mut rx).is_empty());
+
+        scheduler.tick_at(&cache, 1_005).await;
+        assert_eq!(drain(&mut rx), vec![("talk".to_string(), WindowNoticeKind::Opened)]);
+
+        scheduler.tick_at(&cache, 1_500).await;
+        assert!(drain(&mut rx).is_empty());
+
+        scheduler.tick_at(&cache, 2_010).await;
+        assert_eq!(drain(&mut rx), vec![("talk".to_string(), WindowNoticeKind::Closed)]);
+    }
+
+    #[tokio::test]
+    async fn test_tick_force_closes_open_ended_window() {
+        let cache = cache_with_windows(vec![window("door", 1_000, None)]).await;
+        let scheduler = WindowScheduler::new(1);
+        scheduler.last_tick.store(1_000, Ordering::SeqCst);
+        let mut rx = scheduler.subscribe();
+
+        scheduler.tick_at(&cache, 1_000 + 3_599).await;
+        assert!(drain(&mut rx).is_empty());
+
+        scheduler.tick_at(&cache, 1_000 + 3_700).await;
+        assert_eq!(drain(&mut rx), vec![("door".to_string(), WindowNoticeKind::ForceClosed)]);
+
+        // The signed window_end is left as the creator signed it.
+        let stored = cache.get_event_windows("evt1").await.unwrap();
+        assert_eq!((stored[0].window_end, stored[0].closed_at), (None, Some(1_000 + 3_600)));
+        assert!(!stored[0].is_open_at(1_000 + 3_600));
+    }
+}
@@ -0,0 +1,261 @@
+use std::collections::HashMap;
+
+use chrono::{DateTime, Duration, Utc};
+use serde::Serialize;
+
+use crate::cache::Cache;
+use crate::crypto::signatures;
+use crate::observe::{self, ObserveError};
+use crate::types::{ActiveEvent, EventIdPreimage, EventMetadata, EventSeries, PaymentIntent, Recurrence};
+
+/// Most occurrences a single series may generate.
+const MAX_SERIES_OCCURRENCES: u32 = 104;
+
+/// Attendance for one occurrence of a series.
+#[derive(Debug, Serialize)]
+pub struct OccurrenceAttendance {
+    pub event_id: String,
+    pub start_time: Option<DateTime<Utc>>,
+    pub attendee_count: u32,
+}
+
+/// An attendee's record across a series.
+#[derive(Debug, Serialize)]
+pub struct SeriesAttendee {
+    pub attendee_address: String,
+    pub attendee_lock_hash: String,
+    pub events_attended: u32,
+}
+
+#[derive(Debug, Serialize)]
+pub struct SeriesAttendance {
+    pub series_id: String,
+    pub events: Vec<OccurrenceAttendance>,
+    /// Most regular attendees first.
+    pub attendees: Vec<SeriesAttendee>,
+}
+
+/// Record a creator-signed series and generate a pending event for each
+/// occurrence. Occurrences then go through the usual payment and
+/// activation flow one by one.
+pub async fn create_series(
+    cache: &Cache,
+    creator_address: &str,
+    nonce: &str,
+    template: EventMetadata,
+    recurrence: Recurrence,
+    creator_signature: &str,
+) -> Result<(EventSeries, Vec<ActiveEvent>), SeriesError> {
+    if template.start_time.is_none() {
+        return Err(SeriesError::MissingStartTime);
+    }
+    if recurrence.interval == 0 || recurrence.count == 0 || recurrence.count > MAX_SERIES_OCCURRENCES {
+        return Err(SeriesError::InvalidRecurrence);
+    }
+
+    let now = Utc::now();
+    let preimage = EventIdPreimage {
+        creator_address: creator_address.to_string(),
+        timestamp: now.timestamp(),
+        nonce: nonce.to_string(),
+    };
+    let series = EventSeries {
+        series_id: preimage.compute_event_id(),
+        creator_address: creator_address.to_string(),
+        nonce: nonce.to_string(),
+        template,
+        recurrence,
+        creator_signature: creator_signature.to_string(),
+        created_at: now,
+    };
+    signatures::verify_ckb_address_signature(&series.signed_message(), creator_signature, creator_address)
+        .map_err(|_| SeriesError::InvalidSignature)?;
+
+    let metadata = (0..series.recurrence.count)
+        .map(|index| series.occurrence_metadata(index))
+        .collect::<Option<Vec<_>>>()
+        .ok_or(SeriesError::InvalidRecurrence)?;
+
+    cache.store_series(&series).await.map_err(SeriesError::Cache)?;
+
+    let mut events = Vec::with_capacity(metadata.len());
+    for (index, event_metadata) in (0..).zip(metadata) {
+        let intent = PaymentIntent {
+            event_id_preimage: series.occurrence_preimage(index),
+            creator_address: series.creator_address.clone(),
+            creator_signature: series.creator_signature.clone(),
+            event_metadata,
+            declared_at: now,
+            expires_at: now + Duration::hours(24),
+        };
+        let event = observe::create_pending_event(cache, intent).await.map_err(SeriesError::Observe)?;
+        cache
+            .link_series_event(&series.series_id, &event.event_id, index)
+            .await
+            .map_err(SeriesError::Cache)?;
+        events.push(event);
+    }
+
+    Ok((series, events))
+}
+
+pub async fn get_series(cache: &Cache, series_id: &str) -> Result<EventSeries, SeriesError> {
+    cache
+        .get_series(series_id)
+        .await
+        .map_err(SeriesError::Cache)?
+        .ok_or(SeriesError::NotFound)
+}
+
+/// The series' events in occurrence order.
+pub async fn series_events(cache: &Cache, series_id: &str) -> Result<Vec<ActiveEvent>, SeriesError> {
+    get_series(cache, series_id).await?;
+
+    let mut events = Vec::new();
+    for event_id in cache.get_series_event_ids(series_id).await.map_err(SeriesError::Cache)? {
+        if let Some(event) = cache.get_active_event(&event_id).await.map_err(SeriesError::Cache)? {
+            events.push(event);
+        }
+    }
+    Ok(events)
+}
+
+/// Check-ins across every occurrence. An attendee counts once per event,
+/// however many of its window sessions they attended.
+pub async fn series_attendance(cache: &Cache, series_id: &str) -> Result<SeriesAttendance, SeriesError> {
+    let events = series_events(cache, series_id).await?;
+
+    let mut occurrences = Vec::with_capacity(events.len());
+    let mut attendees: HashMap<String, SeriesAttendee> = HashMap::new();
+    for event in events {
+        let mut checkins = cache.get_checkins(&event.event_id).await.map_err(SeriesError::Cache)?;
+        checkins.sort_by(|a, b| a.attendee_lock_hash.cmp(&b.attendee_lock_hash));
+        checkins.dedup_by(|a, b| a.attendee_lock_hash == b.attendee_lock_hash);
+
+        for checkin in &checkins {
+            attendees
+                .entry(checkin.attendee_lock_hash.clone())
+                .or_insert_with(|| SeriesAttendee {
+                    attendee_address: checkin.attendee_address.clone(),
+                    attendee_lock_hash: checkin.attendee_lock_hash.clone(),
+                    events_attended: 0,
+                })
+                .events_attended += 1;
+        }
+        occurrences.push(OccurrenceAttendance {
+            event_id: event.event_id,
+            start_time: event.metadata.start_time,
+            attendee_count: checkins.len() as u32,
+        });
+    }
+
+    let mut attendees: Vec<_> = attendees.into_values().collect();
+    attendees.sort_by(|a, b| {
+        b.events_attended
+            .cmp(&a.events_attended)
+            .then_with(|| a.attendee_address.cmp(&b.attendee_address))
+    });
+
+    Ok(SeriesAttendance {
+        series_id: series_id.to_string(),
+        events: occurrences,
+        attendees,
+    })
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum SeriesError {
+    #[error("cache error: {0}")]
+    Cache(#[from] sqlx::Error),
+    #[error("event error: {0}")]
+    Observe(ObserveError),
+    #[error("series template needs a start_time")]
+    MissingStartTime,
+    #[error("invalid recurrence rule")]
+    InvalidRecurrence,
+    #[error("invalid creator signature")]
+    InvalidSignature,
+    #[error("series not found")]
+    NotFound,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::crypto::signatures::test_wallet::TestWallet;
+    use crate::types::{CheckIn, Frequency};
+
+    fn template() -> EventMetadata {
+        EventMetadata {
+            name: "Meetup".to_string(),
+            description: "Weekly".to_string(),
+            image_url: None,
+            location: None,
+            start_time: Some(DateTime::parse_from_rfc3339("2026-01-06T18:00:00Z").unwrap().with_timezone(&Utc)),
+            end_time: Some(DateTime::parse_from_rfc3339("2026-01-06T20:00:00Z").unwrap().with_timezone(&Utc)),
+            max_attendees: None,
+            prerequisite_events: vec![],
+            min_prerequisites: None,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_create_series_generates_linked_occurrences() {
+        let cache = Cache::new("sqlite::memory:").await.unwrap();
+        let creator = TestWallet::new(1, "ckt");
+        let recurrence = Recurrence { frequency: Frequency::Weekly, interval: 2, count: 3 };
+        let message = EventSeries::message_to_sign("n1", &template().metadata_hash(), &recurrence);
+
+        let result =
+            create_series(&cache, &creator.address, "n1", template(), recurrence.clone(), &creator.sign("other")).await;
+        assert!(matches!(result, Err(SeriesError::InvalidSignature)));
+
+        let (series, events) =
+            create_series(&cache, &creator.address, "n1", template(), recurrence, &creator.sign(&message)).await.unwrap();
+        assert_eq!(events.len(), 3);
+        let third = events[2].metadata.start_time.unwrap();
+        assert_eq!(third, template().start_time.unwrap() + Duration::weeks(4));
+        assert_eq!(events[2].metadata.end_time.unwrap() - third, Duration::hours(2));
+
+        let listed = series_events(&cache, &series.series_id).await.unwrap();
+        let ids: Vec<_> = listed.iter().map(|e| e.event_id.clone()).collect();
+        assert_eq!(ids, events.iter().map(|e| e.event_id.clone()).collect::<Vec<_>>());
+        assert_eq!(ids[1], series.occurrence_preimage(1).compute_event_id());
+    }
+
+    #[tokio::test]
+    async fn test_series_attendance_counts_each_event_once() {
+        let cache = Cache::new("sqlite::memory:").await.unwrap();
+        let creator = TestWallet::new(1, "ckt");
+        let recurrence = Recurrence { frequency: Frequency::Monthly, interval: 1, count: 2 };
+        let signature = creator.sign(&EventSeries::message_to_sign("n2", &template().metadata_hash(), &recurrence));
+        let (series, events) =
+            create_series(&cache, &creator.address, "n2", template(), recurrence, &signature).await.unwrap();
+
+        let checkins = [
+            (&events[0].event_id, "day1", "regular"),
+            (&events[0].event_id, "day2", "regular"),
+            (&events[1].event_id, "day1", "regular"),
+            (&events[1].event_id, "day1", "newcomer"),
+        ];
+        for (event_id, session, who) in checkins {
+            let checkin = CheckIn {
+                event_id: event_id.clone(),
+                session_id: session.to_string(),
+                attendee_address: who.to_string(),
+                attendee_lock_hash: format!("0xlock_{who}"),
+                qr_timestamp: 1000,
+                checked_in_at: Utc::now(),
+            };
+            cache.record_checkin(&checkin, None).await.unwrap();
+        }
+
+        let attendance = series_attendance(&cache, &series.series_id).await.unwrap();
+        assert_eq!(attendance.events.iter().map(|e| e.attendee_count).collect::<Vec<_>>(), vec![1, 2]);
+        assert_eq!(attendance.attendees[0].attendee_address, "regular");
+        assert_eq!(attendance.attendees[0].events_attended, 2);
+        assert_eq!(attendance.attendees[1].events_attended, 1);
+
+        assert!(matches!(series_attendance(&cache, "missing").await, Err(SeriesError::NotFound)));
+    }
+}
@@ -0,0 +1,472 @@
+use chrono::Utc;
+use ckb_types::packed::{self, WitnessArgs};
+use ckb_types::prelude::*;
+use serde::Serialize;
+
+use crate::cache::Cache;
+use crate::crypto::signatures;
+use crate::crypto::sponsor::SponsorWallet;
+use crate::relay::fee::{self, FeeEstimate, BADGE_CELL_CAPACITY, MIN_FEE_RATE};
+use crate::rpc::{CkbRpcClient, TransactionInfo, TxOutput};
+use crate::types::{ActiveEvent, EventState, SponsorDeposit, SponsorDraw, Sponsorship};
+
+/// An event's sponsor budget, in shannons, with the deposits and draws it
+/// is made of.
+#[derive(Debug, Serialize)]
+pub struct SponsorBudget {
+    pub event_id: String,
+    /// Where organizers send deposits. Each deposit output carries the
+    /// event id as its cell data, so it can only be credited to this event.
+    pub sponsor_address: String,
+    pub deposited: u64,
+    pub drawn: u64,
+    pub remaining: u64,
+    /// Badges the remaining budget can still pay for at the minimum fee
+    /// rate.
+    pub badges_remaining: u64,
+    pub deposits: Vec<SponsorDeposit>,
+    pub draws: Vec<SponsorDraw>,
+}
+
+pub async fn get_budget(cache: &Cache, wallet: &SponsorWallet, event_id: &str) -> Result<SponsorBudget, SponsorError> {
+    let deposits = cache.get_sponsor_deposits(event_id).await.map_err(SponsorError::Cache)?;
+    let draws = cache.get_sponsor_draws(event_id).await.map_err(SponsorError::Cache)?;
+    let deposited: u64 = deposits.iter().map(|d| d.capacity).sum();
+    let drawn: u64 = draws.iter().map(|d| d.capacity + d.fee).sum();
+    let remaining = deposited.saturating_sub(drawn);
+
+    Ok(SponsorBudget {
+        event_id: event_id.to_string(),
+        sponsor_address: wallet.address.clone(),
+        deposited,
+        drawn,
+        remaining,
+        badges_remaining: remaining / (BADGE_CELL_CAPACITY + fee::fee_for(fee::tx_size(1), MIN_FEE_RATE)),
+        deposits,
+        draws,
+    })
+}
+
+/// Credit a confirmed payment to the sponsor wallet to the event's budget.
+pub async fn credit_deposit(
+    cache: &Cache,
+    rpc: &CkbRpcClient,
+    wallet: &SponsorWallet,
+    event: &ActiveEvent,
+    tx_hash: &str,
+) -> Result<SponsorDeposit, SponsorError> {
+    if matches!(event.state, EventState::Cancelled | EventState::Archived) {
+        return Err(SponsorError::EventClosed);
+    }
+    let tx_info = rpc
+        .get_transaction(tx_hash)
+        .await
+        .map_err(|e| SponsorError::Rpc(e.to_string()))?
+        .ok_or(SponsorError::DepositNotFound)?;
+
+    record_deposit(cache, wallet, &event.event_id, &tx_info).await
+}
+
+/// Credit the outputs of `tx_info` locked to the sponsor wallet and tagged
+/// with `event_id` in their data. Untagged payments are not credited, so a
+/// deposit meant for one event cannot be claimed by another.
+async fn record_deposit(
+    cache: &Cache,
+    wallet: &SponsorWallet,
+    event_id: &str,
+    tx_info: &TransactionInfo,
+) -> Result<SponsorDeposit, SponsorError> {
+    let block_number = tx_info
+        .block_number
+        .filter(|_| tx_info.confirmed)
+        .ok_or(SponsorError::DepositNotConfirmed)?;
+    let capacity: u64 = tx_info
+        .outputs
+        .iter()
+        .filter(|output| wallet.owns_lock(&output.lock_code_hash, &output.lock_args))
+        .filter(|output| is_tagged_for(&output.data, event_id))
+        .map(|output| output.capacity)
+        .sum();
+    if capacity == 0 {
+        return Err(SponsorError::NotPaidToSponsor);
+    }
+
+    let deposit = SponsorDeposit {
+        event_id: event_id.to_string(),
+        tx_hash: tx_info.tx_hash.clone(),
+        capacity,
+        block_number,
+        recorded_at: Utc::now(),
+    };
+    if !cache.record_sponsor_deposit(&deposit).await.map_err(SponsorError::Cache)? {
+        return Err(SponsorError::AlreadyCredited);
+    }
+    Ok(deposit)
+}
+
+fn is_tagged_for(data: &str, event_id: &str) -> bool {
+    let data = data.strip_prefix("0x").unwrap_or(data);
+    let event_id = event_id.strip_prefix("0x").unwrap_or(event_id);
+    !data.is_empty() && data.eq_ignore_ascii_case(event_id)
+}
+
+/// Reserve a badge's capacity and fee from the event's budget. Returns
+/// `None` when the budget cannot cover it, leaving the attendee to fund it.
+/// A holder's draw is made once: rebuilding their badge reuses it. The
+/// sponsor wallet signs nothing until `cosign_badge_tx`.
+pub async fn sponsor_badge(
+    cache: &Cache,
+    wallet: &SponsorWallet,
+    event_id: &str,
+    holder_address: &str,
+    estimate: &FeeEstimate,
+) -> Result<Option<Sponsorship>, SponsorError> {
+    let holder_lock_hash =
+        signatures::address_to_lock_hash(holder_address).map_err(|_| SponsorError::InvalidAddress)?;
+
+    let draw = match cache.get_sponsor_draw(event_id, &holder_lock_hash).await.map_err(SponsorError::Cache)? {
+        Some(draw) => draw,
+        None => {
+            let draw = SponsorDraw {
+                event_id: event_id.to_string(),
+                holder_address: holder_address.to_string(),
+                holder_lock_hash,
+                tx_hash: String::new(),
+                capacity: estimate.required_capacity,
+                fee: estimate.estimated_fee,
+                drawn_at: Utc::now(),
+            };
+            if !cache.draw_sponsor_budget(&draw).await.map_err(SponsorError::Cache)? {
+                return Ok(None);
+            }
+            draw
+        }
+    };
+
+    Ok(Some(Sponsorship {
+        sponsor_address: wallet.address.clone(),
+        capacity: draw.capacity,
+        fee: draw.fee,
+    }))
+}
+
+/// The sponsor wallet's witness for a sponsored badge transaction.
+#[derive(Debug, Serialize)]
+pub struct SponsorWitness {
+    pub tx_hash: String,
+    /// Index of the witness to replace: the first sponsor input's.
+    pub witness_index: usize,
+    /// `WitnessArgs` whose lock holds the sponsor's sighash-all signature.
+    pub witness: String,
+}
+
+/// Hash of a transaction as the chain computes it.
+pub fn tx_hash(tx: &packed::Transaction) -> String {
+    format!("0x{}", hex::encode(signatures::ckb_blake2b(tx.raw().as_slice())))
+}
+
+/// Co-sign the sponsor inputs of a holder's badge transaction. The
+/// transaction must pay the holder the drawn capacity and take no more than
+/// the draw's capacity and fee from the sponsor wallet. A draw is signed
+/// for one transaction only.
+pub async fn cosign_badge_tx(
+    cache: &Cache,
+    rpc: &CkbRpcClient,
+    wallet: &SponsorWallet,
+    event_id: &str,
+    holder_address: &str,
+    tx: &packed::Transaction,
+) -> Result<SponsorWitness, SponsorError> {
+    let holder_lock_hash =
+        signatures::address_to_lock_hash(holder_address).map_err(|_| SponsorError::InvalidAddress)?;
+    let draw = cache
+        .get_sponsor_draw(event_id, &holder_lock_hash)
+        .await
+        .map_err(SponsorError::Cache)?
+        .ok_or(SponsorError::DrawNotFound)?;
+
+    let mut inputs = Vec::new();
+    for input in tx.raw().inputs() {
+        let out_point = input.previous_output();
+        let prev_hash = format!("0x{}", hex::encode(out_point.tx_hash().raw_data()));
+        let index: u32 = out_point.index().unpack();
+        let prev = rpc
+            .get_transaction(&prev_hash)
+            .await
+            .map_err(|e| SponsorError::Rpc(e.to_string()))?
+            .ok_or(SponsorError::InputNotFound)?;
+        inputs.push(prev.outputs.into_iter().nth(index as usize).ok_or(SponsorError::InputNotFound)?);
+    }
+
+    let witness = sponsor_witness(wallet, &draw, tx, &inputs)?;
+    if !cache
+        .bind_sponsor_draw(event_id, &holder_lock_hash, &witness.tx_hash)
+        .await
+        .map_err(SponsorError::Cache)?
+    {
+        return Err(SponsorError::DrawAlreadySigned);
+    }
+    Ok(witness)
+}
+
+/// Check `tx` against the draw and sign the sponsor lock group, given the
+/// cells its inputs spend.
+fn sponsor_witness(
+    wallet: &SponsorWallet,
+    draw: &SponsorDraw,
+    tx: &packed::Transaction,
+    inputs: &[TxOutput],
+) -> Result<SponsorWitness, SponsorError> {
+    let group: Vec<usize> = (0..inputs.len())
+        .filter(|&i| wallet.owns_lock(&inputs[i].lock_code_hash, &inputs[i].lock_args))
+        .collect();
+    let Some(&first) = group.first() else {
+        return Err(SponsorError::NoSponsorInputs);
+    };
+
+    let lock_hash = |output: &packed::CellOutput| {
+        format!("0x{}", hex::encode(signatures::ckb_blake2b(output.lock().as_slice())))
+    };
+    let outputs: Vec<packed::CellOutput> = tx.raw().outputs().into_iter().collect();
+    let spent: u64 = group.iter().map(|&i| inputs[i].capacity).sum();
+    let change: u64 = outputs
+        .iter()
+        .filter(|output| lock_hash(output) == wallet.lock_hash)
+        .map(|output| Unpack::<u64>::unpack(&output.capacity()))
+        .sum();
+    if spent.saturating_sub(change) > draw.capacity + draw.fee {
+        return Err(SponsorError::ExceedsDraw);
+    }
+    let pays_holder = outputs.iter().any(|output| {
+        lock_hash(output) == draw.holder_lock_hash && Unpack::<u64>::unpack(&output.capacity()) >= draw.capacity
+    });
+    if !pays_holder {
+        return Err(SponsorError::HolderNotPaid);
+    }
+
+    // Sighash-all over the sponsor lock group: the first witness with a
+    // zeroed 65-byte lock, then the group's other witnesses and any
+    // witnesses past the inputs, each prefixed with its length.
+    let witnesses: Vec<Vec<u8>> = tx.witnesses().into_iter().map(|w| w.raw_data().to_vec()).collect();
+    let first_witness = match witnesses.get(first).filter(|w| !w.is_empty()) {
+        Some(bytes) => WitnessArgs::from_slice(bytes).map_err(|_| SponsorError::MalformedWitness)?,
+        None => WitnessArgs::default(),
+    };
+    let with_lock = |lock: &[u8]| {
+        let lock = packed::BytesOpt::new_builder().set(Some(lock.pack())).build();
+        first_witness.clone().as_builder().lock(lock).build()
+    };
+    let placeholder = with_lock(&[0; 65]);
+
+    let tx_hash = signatures::ckb_blake2b(tx.raw().as_slice());
+    let mut message = tx_hash.to_vec();
+    message.extend_from_slice(&(placeholder.as_slice().len() as u64).to_le_bytes());
+    message.extend_from_slice(placeholder.as_slice());
+    for i in group[1..].iter().copied().chain(inputs.len()..witnesses.len()) {
+        let witness = witnesses.get(i).map(Vec::as_slice).unwrap_or_default();
+        message.extend_from_slice(&(witness.len() as u64).to_le_bytes());
+        message.extend_from_slice(witness);
+    }
+    let signature = wallet.sign_digest(signatures::ckb_blake2b(&message));
+
+    Ok(SponsorWitness {
+        tx_hash: format!("0x{}", hex::encode(tx_hash)),
+        witness_index: first,
+        witness: format!("0x{}", hex::encode(with_lock(&signature).as_slice())),
+    })
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum SponsorError {
+    #[error("cache error: {0}")]
+    Cache(#[from] sqlx::Error),
+    #[error("rpc error: {0}")]
+    Rpc(String),
+    #[error("invalid holder address")]
+    InvalidAddress,
+    #[error("event is cancelled or archived")]
+    EventClosed,
+    #[error("deposit transaction not found")]
+    DepositNotFound,
+    #[error("deposit transaction not confirmed")]
+    DepositNotConfirmed,
+    #[error("deposit pays nothing to the sponsor wallet for this event")]
+    NotPaidToSponsor,
+    #[error("deposit already credited")]
+    AlreadyCredited,
+    #[error("no sponsor draw for this holder")]
+    DrawNotFound,
+    #[error("transaction input cell not found")]
+    InputNotFound,
+    #[error("transaction spends no sponsor cells")]
+    NoSponsorInputs,
+    #[error("transaction takes more than the sponsor draw")]
+    ExceedsDraw,
+    #[error("transaction does not pay the holder the sponsored capacity")]
+    HolderNotPaid,
+    #[error("malformed sponsor witness")]
+    MalformedWitness,
+    #[error("sponsor draw already signed for another transaction")]
+    DrawAlreadySigned,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::crypto::signatures::test_wallet::TestWallet;
+    use crate::relay::fee::{FeePriority, SHANNONS_PER_CKB};
+    use secp256k1::{ecdsa, Message, Secp256k1};
+
+    fn deposit_tx(tx_hash: &str, wallet: &SponsorWallet, event_id: &str, ckb: u64) -> TransactionInfo {
+        TransactionInfo {
+            tx_hash: tx_hash.to_string(),
+            block_number: Some(100),
+            block_hash: Some("0xblock".to_string()),
+            confirmed: true,
+            outputs: vec![
+                TxOutput {
+                    capacity: ckb * SHANNONS_PER_CKB,
+                    lock_code_hash: format!("0x{}", hex::encode(signatures::SECP256K1_BLAKE160_CODE_HASH)),
+                    lock_args: wallet.lock_args.clone(),
+                    data: format!("0x{event_id}"),
+                },
+                TxOutput {
+                    capacity: 1_000 * SHANNONS_PER_CKB,
+                    lock_code_hash: format!("0x{}", hex::encode(signatures::SECP256K1_BLAKE160_CODE_HASH)),
+                    lock_args: "0x00".to_string(),
+                    data: "0x".to_string(),
+                },
+            ],
+        }
+    }
+
+    #[tokio::test]
+    async fn test_deposit_funds_sponsored_badges() {
+        let cache = Cache::new("sqlite::memory:").await.unwrap();
+        let wallet = SponsorWallet::from_hex(&"22".repeat(32), "ckt").unwrap();
+        let (first, second) = (TestWallet::new(1, "ckt"), TestWallet::new(2, "ckt"));
+
+        // Enough for one badge, not two.
+        let deposit = record_deposit(&cache, &wallet, "evt1", &deposit_tx("0xdep", &wallet, "evt1", 300)).await.unwrap();
+        assert_eq!(deposit.capacity, 300 * SHANNONS_PER_CKB);
+        let result = record_deposit(&cache, &wallet, "evt1", &deposit_tx("0xdep", &wallet, "evt1", 300)).await;
+        assert!(matches!(result, Err(SponsorError::AlreadyCredited)));
+
+        let estimate = FeeEstimate::for_badges(FeePriority::Normal, MIN_FEE_RATE, 1);
+        let sponsorship = sponsor_badge(&cache, &wallet, "evt1", &first.address, &estimate).await.unwrap().unwrap();
+        assert_eq!(
+            (sponsorship.capacity, sponsorship.fee),
+            (BADGE_CELL_CAPACITY, estimate.estimated_fee)
+        );
+        let again = sponsor_badge(&cache, &wallet, "evt1", &first.address, &estimate).await.unwrap();
+        assert!(again.is_some());
+        let over = sponsor_badge(&cache, &wallet, "evt1", &second.address, &estimate).await.unwrap();
+        assert!(over.is_none());
+
+        let budget = get_budget(&cache, &wallet, "evt1").await.unwrap();
+        assert_eq!(budget.drawn, BADGE_CELL_CAPACITY + estimate.estimated_fee);
+        assert_eq!(budget.remaining, 300 * SHANNONS_PER_CKB - budget.drawn);
+        assert_eq!((budget.draws.len(), budget.badges_remaining), (1, 0));
+    }
+
+    #[tokio::test]
+    async fn test_deposit_is_only_credited_to_its_tagged_event() {
+        let cache = Cache::new("sqlite::memory:").await.unwrap();
+        let wallet = SponsorWallet::from_hex(&"22".repeat(32), "ckt").unwrap();
+        let event_id = "ab".repeat(32);
+
+        let result = record_deposit(&cache, &wallet, "evt2", &deposit_tx("0xdep", &wallet, &event_id, 300)).await;
+        assert!(matches!(result, Err(SponsorError::NotPaidToSponsor)));
+        let deposit =
+            record_deposit(&cache, &wallet, &event_id, &deposit_tx("0xdep", &wallet, &event_id.to_uppercase(), 300))
+                .await
+                .unwrap();
+        assert_eq!(deposit.capacity, 300 * SHANNONS_PER_CKB);
+    }
+
+    fn lock_script(address: &str) -> packed::Script {
+        let (code_hash, hash_type, args) = signatures::parse_ckb_address(address).unwrap();
+        packed::Script::new_builder()
+            .code_hash(code_hash.pack())
+            .hash_type(packed::Byte::new(hash_type))
+            .args(args.pack())
+            .build()
+    }
+
+    /// A badge transaction spending one holder cell and one sponsor cell,
+    /// with the sponsor inputs' cells as `cosign_badge_tx` resolves them.
+    fn badge_tx(
+        wallet: &SponsorWallet,
+        holder: &TestWallet,
+        badge: u64,
+        change: u64,
+    ) -> (packed::Transaction, Vec<TxOutput>) {
+        let output = |address: &str, capacity: u64| {
+            packed::CellOutput::new_builder().capacity(capacity.pack()).lock(lock_script(address)).build()
+        };
+        let input = |n: u8| {
+            let out_point = packed::OutPoint::new_builder().tx_hash([n; 32].pack()).build();
+            packed::CellInput::new_builder().previous_output(out_point).build()
+        };
+        let raw = packed::RawTransaction::new_builder()
+            .inputs(vec![input(1), input(2)].pack())
+            .outputs(vec![output(&holder.address, badge), output(&wallet.address, change)].pack())
+            .outputs_data(vec![packed::Bytes::default(); 2].pack())
+            .build();
+        let cell = |address: &str| {
+            let (code_hash, _, args) = signatures::parse_ckb_address(address).unwrap();
+            TxOutput {
+                capacity: 1_000 * SHANNONS_PER_CKB,
+                lock_code_hash: format!("0x{}", hex::encode(code_hash)),
+                lock_args: format!("0x{}", hex::encode(args)),
+                data: "0x".to_string(),
+            }
+        };
+        (packed::Transaction::new_builder().raw(raw).build(), vec![cell(&holder.address), cell(&wallet.address)])
+    }
+
+    #[tokio::test]
+    async fn test_sponsor_signs_only_transactions_within_its_draw() {
+        let cache = Cache::new("sqlite::memory:").await.unwrap();
+        let wallet = SponsorWallet::from_hex(&"22".repeat(32), "ckt").unwrap();
+        let holder = TestWallet::new(1, "ckt");
+        let event_id = "ab".repeat(32);
+        let deposit = deposit_tx("0xdep", &wallet, &event_id, 300);
+        record_deposit(&cache, &wallet, &event_id, &deposit).await.unwrap();
+        let estimate = FeeEstimate::for_badges(FeePriority::Normal, MIN_FEE_RATE, 1);
+        sponsor_badge(&cache, &wallet, &event_id, &holder.address, &estimate).await.unwrap().unwrap();
+        let draw = cache.get_sponsor_draw(&event_id, &signatures::address_to_lock_hash(&holder.address).unwrap()).await;
+        let draw = draw.unwrap().unwrap();
+        let change = 1_000 * SHANNONS_PER_CKB - draw.capacity - draw.fee;
+
+        let (tx, inputs) = badge_tx(&wallet, &holder, draw.capacity, change - 1);
+        assert!(matches!(sponsor_witness(&wallet, &draw, &tx, &inputs), Err(SponsorError::ExceedsDraw)));
+        let (tx, inputs) = badge_tx(&wallet, &holder, draw.capacity - 1, change);
+        assert!(matches!(sponsor_witness(&wallet, &draw, &tx, &inputs), Err(SponsorError::HolderNotPaid)));
+        assert!(matches!(sponsor_witness(&wallet, &draw, &tx, &inputs[..1]), Err(SponsorError::NoSponsorInputs)));
+
+        // The signature covers the sponsor group's sighash-all digest.
+        let (tx, inputs) = badge_tx(&wallet, &holder, draw.capacity, change);
+        let witness = sponsor_witness(&wallet, &draw, &tx, &inputs).unwrap();
+        assert_eq!((witness.tx_hash.clone(), witness.witness_index), (tx_hash(&tx), 1));
+        let witness_args = WitnessArgs::from_slice(&hex::decode(&witness.witness[2..]).unwrap()).unwrap();
+        let signature = witness_args.lock().to_opt().unwrap().raw_data();
+        let placeholder = WitnessArgs::new_builder()
+            .lock(packed::BytesOpt::new_builder().set(Some([0u8; 65].pack())).build())
+            .build();
+        let mut message = signatures::ckb_blake2b(tx.raw().as_slice()).to_vec();
+        message.extend_from_slice(&(placeholder.as_slice().len() as u64).to_le_bytes());
+        message.extend_from_slice(placeholder.as_slice());
+        let recovery_id = ecdsa::RecoveryId::from_i32(signature[64] as i32).unwrap();
+        let recoverable = ecdsa::RecoverableSignature::from_compact(&signature[..64], recovery_id).unwrap();
+        let digest = Message::from_digest(signatures::ckb_blake2b(&message));
+        let pubkey = Secp256k1::new().recover_ecdsa(&digest, &recoverable).unwrap();
+        assert_eq!(format!("0x{}", hex::encode(signatures::blake160(&pubkey.serialize()))), wallet.lock_args);
+
+        // A draw is signed for one transaction only.
+        let lock_hash = draw.holder_lock_hash.as_str();
+        assert!(cache.bind_sponsor_draw(&event_id, lock_hash, &witness.tx_hash).await.unwrap());
+        assert!(cache.bind_sponsor_draw(&event_id, lock_hash, &witness.tx_hash).await.unwrap());
+        assert!(!cache.bind_sponsor_draw(&event_id, lock_hash, "0xother").await.unwrap());
+    }
+}
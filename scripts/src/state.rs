@@ -1,7 +1,10 @@
 use std::sync::Arc;
 
 use crate::cache::Cache;
+use crate::crypto::signatures::AddressPolicy;
+use crate::crypto::sponsor::SponsorWallet;
 use crate::rpc::CkbRpcClient;
+use crate::schedule::WindowScheduler;
 
 #[derive(Clone)]
 pub struct AppState {
@@ -9,8 +12,12 @@ pub struct AppState {
     pub rpc: Arc<CkbRpcClient>,
     /// DOB badge type script code_hash, if configured.
     pub dob_code_hash: Option<String>,
-    /// CKB address human-readable prefix ("ckt" for testnet, "ckb" for mainnet).
-    pub address_hrp: String,
+    /// Network prefix and lock scripts accepted at API boundaries.
+    pub address_policy: AddressPolicy,
+    /// Opens, expires and announces attendance windows.
+    pub scheduler: Arc<WindowScheduler>,
+    /// Pays for badges out of event budgets, if configured.
+    pub sponsor: Option<Arc<SponsorWallet>>,
 }
 
 impl AppState {
@@ -18,7 +25,9 @@ impl AppState {
         database_url: &str,
         ckb_rpc_url: &str,
         dob_code_hash: Option<String>,
-        address_hrp: String,
+        address_policy: AddressPolicy,
+        scheduler: WindowScheduler,
+        sponsor: Option<SponsorWallet>,
     ) -> Result<Self, Box<dyn std::error::Error>> {
         let cache = Cache::new(database_url).await?;
         let rpc = CkbRpcClient::new(ckb_rpc_url);
@@ -27,7 +36,9 @@ impl AppState {
             cache: Arc::new(cache),
             rpc: Arc::new(rpc),
             dob_code_hash,
-            address_hrp,
+            address_policy,
+            scheduler: Arc::new(scheduler),
+            sponsor: sponsor.map(Arc::new),
         })
     }
 }
@@ -7,23 +7,31 @@ pub struct QrPayload {
     pub event_id: String,
     pub timestamp: i64,
     pub hmac: String,
+    /// Window session the QR was generated for. Older QR codes omit it.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub session_id: Option<String>,
 }
 
 impl QrPayload {
+    /// Parses `event|timestamp|hmac` with an optional trailing `|session`.
     pub fn parse(data: &str) -> Option<Self> {
         let parts: Vec<&str> = data.split('|').collect();
-        if parts.len() != 3 {
+        if parts.len() != 3 && parts.len() != 4 {
             return None;
         }
         Some(Self {
             event_id: parts[0].to_string(),
             timestamp: parts[1].parse().ok()?,
             hmac: parts[2].to_string(),
+            session_id: parts.get(3).map(|s| s.to_string()),
         })
     }
 
     pub fn encode(&self) -> String {
-        format!("{}|{}|{}", self.event_id, self.timestamp, self.hmac)
+        match &self.session_id {
+            Some(session_id) => format!("{}|{}|{}|{}", self.event_id, self.timestamp, self.hmac, session_id),
+            None => format!("{}|{}|{}", self.event_id, self.timestamp, self.hmac),
+        }
     }
 }
 
@@ -34,6 +42,10 @@ pub struct AttendanceProof {
     pub qr_payload: QrPayload,
     pub attendee_signature: String,
     pub created_at: i64,
+    /// Device clock when the QR was scanned, for proofs signed offline and
+    /// submitted later. Covered by `attendee_signature` when present.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub scanned_at: Option<i64>,
 }
 
 impl AttendanceProof {
@@ -41,34 +53,819 @@ impl AttendanceProof {
         format!("CKB-PoP|{}|{}|{}", event_id, qr_timestamp, attendee_address)
     }
 
+    pub fn offline_message_to_sign(event_id: &str, qr_timestamp: i64, attendee_address: &str, scanned_at: i64) -> String {
+        format!("CKB-PoP-Offline|{}|{}|{}|{}", event_id, qr_timestamp, attendee_address, scanned_at)
+    }
+
+    pub fn signed_message(&self) -> String {
+        match self.scanned_at {
+            Some(scanned_at) => Self::offline_message_to_sign(
+                &self.event_id,
+                self.qr_payload.timestamp,
+                &self.attendee_address,
+                scanned_at,
+            ),
+            None => Self::message_to_sign(&self.event_id, self.qr_payload.timestamp, &self.attendee_address),
+        }
+    }
+}
+
+/// QR shown by an attendee's wallet for the organizer to scan: the reverse
+/// of the usual flow, where the attendee scans the organizer's display.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct PresenceRequest {
+    pub event_id: String,
+    pub attendee_address: String,
+    pub timestamp: i64,
+    pub attendee_signature: String,
+}
+
+impl PresenceRequest {
+    const QR_PREFIX: &'static str = "presence";
+
+    pub fn message_to_sign(event_id: &str, timestamp: i64, attendee_address: &str) -> String {
+        format!("CKB-PoP-Presence|{}|{}|{}", event_id, timestamp, attendee_address)
+    }
+
+    /// Message the organizer signs to accept a presence request into a
+    /// window session.
+    pub fn ack_message_to_sign(&self, session_id: &str) -> String {
+        format!(
+            "CKB-PoP-PresenceAck|{}|{}|{}|{}",
+            self.event_id, session_id, self.timestamp, self.attendee_address
+        )
+    }
+
+    pub fn signed_message(&self) -> String {
+        Self::message_to_sign(&self.event_id, self.timestamp, &self.attendee_address)
+    }
+
+    /// Parses `presence|event|timestamp|address|signature`.
+    pub fn parse(data: &str) -> Option<Self> {
+        let parts: Vec<&str> = data.split('|').collect();
+        if parts.len() != 5 || parts[0] != Self::QR_PREFIX {
+            return None;
+        }
+        Some(Self {
+            event_id: parts[1].to_string(),
+            timestamp: parts[2].parse().ok()?,
+            attendee_address: parts[3].to_string(),
+            attendee_signature: parts[4].to_string(),
+        })
+    }
+}
+
+/// Addresses allowed to check in to an invite-only event, identified by lock
+/// hash. The creator signs the Merkle root, which commits to the whole set
+/// and can also be placed in the event's anchor cell.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Allowlist {
+    pub event_id: String,
+    pub merkle_root: String,
+    pub entry_count: usize,
+    /// Bumped on every replacement and bound into the creator's signature,
+    /// so an old signature cannot restore a superseded list.
+    #[serde(default)]
+    pub version: u32,
+    pub creator_signature: String,
+    pub updated_at: DateTime<Utc>,
+}
+
+impl Allowlist {
+    pub fn message_to_sign(event_id: &str, merkle_root: &str, version: u32) -> String {
+        format!("CKB-PoP-Allowlist|{}|{}|{}", event_id, merkle_root, version)
+    }
+}
+
+/// How late an event accepts attendance proofs scanned offline. A proof is
+/// accepted up to `grace_secs` after its QR timestamp; zero disables offline
+/// check-in.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct OfflinePolicy {
+    pub event_id: String,
+    pub grace_secs: i64,
+    /// Bumped on every change and bound into the creator's signature, so an
+    /// old signature cannot restore a superseded policy. Zero until set.
+    #[serde(default)]
+    pub version: u32,
+    pub updated_at: DateTime<Utc>,
+}
+
+impl OfflinePolicy {
+    pub fn message_to_sign(event_id: &str, grace_secs: i64, version: u32) -> String {
+        format!("CKB-PoP-OfflinePolicy|{}|{}|{}", event_id, grace_secs, version)
+    }
+
+    pub fn allows_offline(&self) -> bool {
+        self.grace_secs > 0
+    }
+}
+
+/// Events that need attendees to stay, such as CPE-credit workshops.
+/// Attendees check in on any other window and check out by scanning the
+/// QR of `checkout_session_id`; the badge is minted at check-out.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct CheckoutPolicy {
+    pub event_id: String,
+    pub checkout_session_id: String,
+    /// Stay needed between the two scans for the full-duration tier.
+    pub min_duration_secs: i64,
+    /// Bumped on every change and bound into the creator's signature, so an
+    /// old signature cannot restore a superseded policy. Zero until set.
+    #[serde(default)]
+    pub version: u32,
+    pub updated_at: DateTime<Utc>,
+}
+
+impl CheckoutPolicy {
+    pub fn message_to_sign(event_id: &str, checkout_session_id: &str, min_duration_secs: i64, version: u32) -> String {
+        format!("CKB-PoP-CheckoutPolicy|{}|{}|{}|{}", event_id, checkout_session_id, min_duration_secs, version)
+    }
+}
+
+/// Lets attendees without a wallet at the door take a claim voucher instead
+/// of checking in, and redeem it from any address within
+/// `claim_period_secs`. Zero disables vouchers.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct ClaimPolicy {
+    pub event_id: String,
+    pub claim_period_secs: i64,
+    /// Bumped on every change and bound into the creator's signature, so an
+    /// old signature cannot restore a superseded policy. Zero until set.
+    #[serde(default)]
+    pub version: u32,
+    pub updated_at: DateTime<Utc>,
+}
+
+impl ClaimPolicy {
+    pub fn message_to_sign(event_id: &str, claim_period_secs: i64, version: u32) -> String {
+        format!("CKB-PoP-ClaimPolicy|{}|{}|{}", event_id, claim_period_secs, version)
+    }
+
+    pub fn allows_claims(&self) -> bool {
+        self.claim_period_secs > 0
+    }
+}
+
+/// One-time claim voucher, bound to the QR frame it was issued for. Only
+/// the attendee holds `code`; the backend keeps its hash.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct ClaimVoucher {
+    pub event_id: String,
+    pub session_id: String,
+    pub qr_timestamp: i64,
+    pub code: String,
+    /// Unix seconds after which the voucher can no longer be redeemed.
+    pub expires_at: i64,
+    /// Window-secret HMAC over `message_to_sign`.
+    pub signature: String,
+}
+
+impl ClaimVoucher {
+    pub fn code_hash(code: &str) -> String {
+        format!("0x{}", hex::encode(Sha256::digest(code.as_bytes())))
+    }
+
+    pub fn message_to_sign(
+        event_id: &str,
+        session_id: &str,
+        qr_timestamp: i64,
+        code_hash: &str,
+        expires_at: i64,
+    ) -> String {
+        format!("CKB-PoP-Voucher|{}|{}|{}|{}|{}", event_id, session_id, qr_timestamp, code_hash, expires_at)
+    }
+
+    /// Message the redeeming address signs.
+    pub fn claim_message_to_sign(event_id: &str, code_hash: &str, address: &str) -> String {
+        format!("CKB-PoP-Claim|{}|{}|{}", event_id, code_hash, address)
+    }
+}
+
+/// Stored form of a claim voucher.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct VoucherRecord {
+    pub voucher_hash: String,
+    pub event_id: String,
+    pub session_id: String,
+    pub qr_timestamp: i64,
+    pub expires_at: i64,
+    pub signature: String,
+    pub issued_at: DateTime<Utc>,
+    pub redeemed_by: Option<String>,
+    pub redeemed_at: Option<DateTime<Utc>>,
+}
+
+impl VoucherRecord {
+    pub fn signed_message(&self) -> String {
+        ClaimVoucher::message_to_sign(
+            &self.event_id,
+            &self.session_id,
+            self.qr_timestamp,
+            &self.voucher_hash,
+            self.expires_at,
+        )
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum AttendanceTier {
+    /// Checked in and out, but left before the minimum duration.
+    Partial,
+    /// Stayed at least the policy's minimum duration.
+    FullDuration,
+}
+
+impl AttendanceTier {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            AttendanceTier::Partial => "partial",
+            AttendanceTier::FullDuration => "full_duration",
+        }
+    }
+
+    pub fn parse(value: &str) -> Option<Self> {
+        match value {
+            "partial" => Some(AttendanceTier::Partial),
+            "full_duration" => Some(AttendanceTier::FullDuration),
+            _ => None,
+        }
+    }
+}
+
+/// A check-in scan paired with the attendee's later check-out scan. Times
+/// are the QR timestamps, so both ends are bound to window secrets.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct DurationProof {
+    pub event_id: String,
+    pub attendee_address: String,
+    pub attendee_lock_hash: String,
+    pub checkin_session_id: String,
+    pub checkin_timestamp: i64,
+    pub checkout_session_id: String,
+    pub checkout_timestamp: i64,
+    pub tier: AttendanceTier,
+    pub recorded_at: DateTime<Utc>,
+}
+
+impl DurationProof {
+    pub fn duration_secs(&self) -> i64 {
+        self.checkout_timestamp - self.checkin_timestamp
+    }
+
+    /// The badge's `attendance_proof_hash`: commits to both scans and the
+    /// tier they earned, `0x`-prefixed.
+    pub fn proof_hash(&self) -> String {
+        let preimage = format!(
+            "CKB-PoP-Duration|{}|{}|{}|{}|{}|{}|{}",
+            self.event_id,
+            self.attendee_address,
+            self.checkin_session_id,
+            self.checkin_timestamp,
+            self.checkout_session_id,
+            self.checkout_timestamp,
+            self.tier.as_str()
+        );
+        format!("0x{}", hex::encode(Sha256::digest(preimage.as_bytes())))
+    }
+}
+
+/// Pre-registration rules for an event. With a `capacity`, RSVPs beyond it
+/// are waitlisted; with `required`, only confirmed RSVPs may check in.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct RsvpPolicy {
+    pub event_id: String,
+    pub capacity: Option<u32>,
+    pub required: bool,
+    /// Bumped on every change and bound into the creator's signature, so an
+    /// old signature cannot restore a superseded policy. Zero until set.
+    #[serde(default)]
+    pub version: u32,
+    pub updated_at: DateTime<Utc>,
+}
+
+impl RsvpPolicy {
+    pub fn message_to_sign(event_id: &str, capacity: Option<u32>, required: bool, version: u32) -> String {
+        let capacity = capacity.map(|c| c.to_string()).unwrap_or_default();
+        format!("CKB-PoP-RsvpPolicy|{}|{}|{}|{}", event_id, capacity, required, version)
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum RsvpStatus {
+    Confirmed,
+    /// Over capacity; promoted in RSVP order as confirmed places free up.
+    Waitlisted,
+}
+
+impl RsvpStatus {
+    pub fn parse(value: &str) -> Option<Self> {
+        match value {
+            "confirmed" => Some(RsvpStatus::Confirmed),
+            "waitlisted" => Some(RsvpStatus::Waitlisted),
+            _ => None,
+        }
+    }
+}
+
+/// An attendee's signed intent to attend an event.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Rsvp {
+    pub event_id: String,
+    pub attendee_address: String,
+    pub attendee_lock_hash: String,
+    pub status: RsvpStatus,
+    pub attendee_signature: String,
+    pub created_at: DateTime<Utc>,
+}
+
+impl Rsvp {
+    pub fn message_to_sign(event_id: &str, attendee_address: &str) -> String {
+        format!("CKB-PoP-RSVP|{}|{}", event_id, attendee_address)
+    }
+
     pub fn signed_message(&self) -> String {
-        Self::message_to_sign(&self.event_id, self.qr_payload.timestamp, &self.attendee_address)
+        Self::message_to_sign(&self.event_id, &self.attendee_address)
     }
+
+    pub fn cancel_message_to_sign(event_id: &str, attendee_address: &str) -> String {
+        format!("CKB-PoP-RSVP-Cancel|{}|{}", event_id, attendee_address)
+    }
+}
+
+/// Session id given to windows that predate multiple sessions per event.
+pub const DEFAULT_SESSION_ID: &str = "default";
+
+fn default_session_id() -> String {
+    DEFAULT_SESSION_ID.to_string()
 }
 
+/// One attendance window (check-in session) of an event. Each session has
+/// its own QR secret, so opening another never disturbs one in progress.
 #[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct WindowProof {
     pub event_id: String,
+    #[serde(default = "default_session_id")]
+    pub session_id: String,
+    /// Organizer-facing name, e.g. "Day 2" or "Track B".
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub label: Option<String>,
     pub window_start: i64,
     pub window_end: Option<i64>,
+    /// Creator's signature over the window, or a server-generated seed when
+    /// the window was opened under a sign-in session (see `authorized_by`).
+    /// Secret material for the QR HMAC; never exposed publicly.
     pub creator_signature: String,
     pub window_secret_commitment: String,
+    /// Random salt mixed into the QR secret, replaced each time the secret is
+    /// revoked. Secret material like `creator_signature`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub secret_salt: Option<String>,
+    /// When the current secret took effect, if it has been rotated.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub rotated_at: Option<i64>,
+    /// When the backend closed the window ahead of `window_end`, because it
+    /// ran past the open-ended maximum or the event was cancelled. Kept apart
+    /// from `window_end` so the creator's signature still matches.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub closed_at: Option<i64>,
+    /// Session address that opened the window without a fresh signature.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub authorized_by: Option<String>,
 }
 
 impl WindowProof {
     pub fn is_open(&self) -> bool {
-        let now = Utc::now().timestamp();
-        now >= self.window_start && self.window_end.is_none_or(|end| now < end)
+        self.is_open_at(Utc::now().timestamp())
+    }
+
+    pub fn is_open_at(&self, now: i64) -> bool {
+        now >= self.window_start && self.end().is_none_or(|end| now < end)
+    }
+
+    /// When the window actually stops accepting check-ins: the signed
+    /// `window_end` or an earlier `closed_at`.
+    pub fn end(&self) -> Option<i64> {
+        match (self.window_end, self.closed_at) {
+            (Some(end), Some(closed)) => Some(end.min(closed)),
+            (end, closed) => end.or(closed),
+        }
+    }
+
+    /// Message the creator signs for a window. A session id chosen by the
+    /// client is bound into the signature; without one the original format
+    /// is used and the server assigns the id.
+    pub fn message_to_sign(
+        event_id: &str,
+        session_id: Option<&str>,
+        window_start: i64,
+        window_end: Option<i64>,
+    ) -> String {
+        let end = window_end.map_or_else(|| "open".to_string(), |end| end.to_string());
+        match session_id {
+            Some(session_id) => format!("CKB-PoP-Window|{}|{}|{}|{}", event_id, session_id, window_start, end),
+            None => format!("CKB-PoP-Window|{}|{}|{}", event_id, window_start, end),
+        }
+    }
+}
+
+/// Message the creator signs to revoke a window's current secret. Binding the
+/// current commitment makes each signature good for one rotation only.
+pub fn revoke_message_to_sign(event_id: &str, session_id: &str, commitment: &str) -> String {
+    format!("CKB-PoP-Revoke|{}|{}|{}", event_id, session_id, commitment)
+}
+
+/// Server-issued nonce a wallet signs to start a sign-in session.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct SessionChallenge {
+    pub challenge_id: String,
+    pub address: String,
+    pub nonce: String,
+    pub created_at: DateTime<Utc>,
+    pub expires_at: DateTime<Utc>,
+}
+
+impl SessionChallenge {
+    pub fn message_to_sign(address: &str, nonce: &str) -> String {
+        format!("CKB-PoP-Login|{}|{}", address, nonce)
+    }
+
+    pub fn signed_message(&self) -> String {
+        Self::message_to_sign(&self.address, &self.nonce)
+    }
+}
+
+/// An authenticated sign-in session, resolved from a bearer token.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct AuthSession {
+    pub address: String,
+    /// Canonical identity of `address`; compare on this, not the string.
+    pub lock_hash: String,
+    pub created_at: DateTime<Utc>,
+    pub expires_at: DateTime<Utc>,
+}
+
+/// What a delegate may do on the creator's behalf.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum DelegateRole {
+    /// Opens, closes and revokes windows, and checks attendees in.
+    CoHost,
+    /// Displays QR codes and checks attendees in at the door.
+    DoorStaff,
+}
+
+impl DelegateRole {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            DelegateRole::CoHost => "co_host",
+            DelegateRole::DoorStaff => "door_staff",
+        }
+    }
+
+    pub fn parse(value: &str) -> Option<Self> {
+        match value {
+            "co_host" => Some(DelegateRole::CoHost),
+            "door_staff" => Some(DelegateRole::DoorStaff),
+            _ => None,
+        }
+    }
+
+    pub fn can_manage_windows(self) -> bool {
+        self == DelegateRole::CoHost
+    }
+}
+
+/// Creator-signed authorization for another address (a co-host's wallet or
+/// a door device's key) to act for an event until `expires_at`.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Delegation {
+    pub event_id: String,
+    pub delegate_address: String,
+    pub delegate_lock_hash: String,
+    pub role: DelegateRole,
+    /// Unix seconds after which the delegation no longer applies.
+    pub expires_at: i64,
+    /// Grant number for this delegate, one more than the last grant to
+    /// them, so an old grant cannot be replayed after a revoke.
+    #[serde(default)]
+    pub version: u32,
+    /// Creator's signature over `message_to_sign`, verifiable by anyone.
+    pub creator_signature: String,
+    pub created_at: DateTime<Utc>,
+    /// When the creator withdrew the grant. Revoked grants are kept so
+    /// their version stays spent.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub revoked_at: Option<DateTime<Utc>>,
+}
+
+impl Delegation {
+    pub fn message_to_sign(
+        event_id: &str,
+        delegate_address: &str,
+        role: DelegateRole,
+        expires_at: i64,
+        version: u32,
+    ) -> String {
+        format!("CKB-PoP-Delegate|{}|{}|{}|{}|{}", event_id, delegate_address, role.as_str(), expires_at, version)
+    }
+
+    pub fn signed_message(&self) -> String {
+        Self::message_to_sign(&self.event_id, &self.delegate_address, self.role, self.expires_at, self.version)
+    }
+
+    pub fn is_active_at(&self, now: i64) -> bool {
+        self.revoked_at.is_none() && now < self.expires_at
+    }
+
+    /// Message a creator signs to withdraw this delegation early. It names
+    /// the grant's version, so it cannot revoke a later re-grant.
+    pub fn revoke_message_to_sign(&self) -> String {
+        format!("CKB-PoP-Undelegate|{}|{}|{}", self.event_id, self.delegate_address, self.version)
+    }
+}
+
+/// What part a badge holder played at an event. Stored in the badge's cell
+/// data as the optional `role` field of the metadata schema.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum BadgeRole {
+    #[default]
+    Attendee,
+    Speaker,
+    Volunteer,
+    Organizer,
+}
+
+impl BadgeRole {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            BadgeRole::Attendee => "attendee",
+            BadgeRole::Speaker => "speaker",
+            BadgeRole::Volunteer => "volunteer",
+            BadgeRole::Organizer => "organizer",
+        }
+    }
+
+    pub fn parse(value: &str) -> Option<Self> {
+        match value {
+            "attendee" => Some(BadgeRole::Attendee),
+            "speaker" => Some(BadgeRole::Speaker),
+            "volunteer" => Some(BadgeRole::Volunteer),
+            "organizer" => Some(BadgeRole::Organizer),
+            _ => None,
+        }
+    }
+}
+
+/// Creator-signed role for one address at an event, applied to the badge
+/// built for that address.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct RoleAssignment {
+    pub event_id: String,
+    pub holder_address: String,
+    pub holder_lock_hash: String,
+    pub role: BadgeRole,
+    /// Creator's signature over `message_to_sign`, verifiable by anyone.
+    pub creator_signature: String,
+    pub assigned_at: DateTime<Utc>,
+}
+
+impl RoleAssignment {
+    pub fn message_to_sign(event_id: &str, holder_address: &str, role: BadgeRole) -> String {
+        format!("CKB-PoP-Role|{}|{}|{}", event_id, holder_address, role.as_str())
     }
 
-    pub fn message_to_sign(event_id: &str, window_start: i64, window_end: Option<i64>) -> String {
-        match window_end {
-            Some(end) => format!("CKB-PoP-Window|{}|{}|{}", event_id, window_start, end),
-            None => format!("CKB-PoP-Window|{}|{}|open", event_id, window_start),
+    pub fn signed_message(&self) -> String {
+        Self::message_to_sign(&self.event_id, &self.holder_address, self.role)
+    }
+}
+
+/// CKB an organizer paid to the sponsor wallet to fund an event's badges.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct SponsorDeposit {
+    pub event_id: String,
+    pub tx_hash: String,
+    /// Shannons paid to the sponsor lock.
+    pub capacity: u64,
+    pub block_number: u64,
+    pub recorded_at: DateTime<Utc>,
+}
+
+impl SponsorDeposit {
+    /// Credits `tx_hash` to the event; signed by the creator so nobody else
+    /// can claim their deposit for another event.
+    pub fn message_to_sign(event_id: &str, tx_hash: &str) -> String {
+        format!("CKB-PoP-SponsorDeposit|{}|{}", event_id, tx_hash)
+    }
+}
+
+/// What one sponsored badge drew from its event's budget, in shannons.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct SponsorDraw {
+    pub event_id: String,
+    pub holder_address: String,
+    pub holder_lock_hash: String,
+    /// Transaction the sponsor wallet co-signed for this draw; empty until
+    /// it has signed one.
+    pub tx_hash: String,
+    pub capacity: u64,
+    pub fee: u64,
+    pub drawn_at: DateTime<Utc>,
+}
+
+/// The sponsor side of a badge transaction: the sponsor wallet funds the
+/// badge cell's capacity and the fee. The attendee's wallet adds sponsor
+/// cells as inputs, with any change back to `sponsor_address`, and has the
+/// backend co-sign them.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Sponsorship {
+    pub sponsor_address: String,
+    pub capacity: u64,
+    pub fee: u64,
+}
+
+impl Sponsorship {
+    /// Signed by the holder to have the sponsor wallet co-sign `tx_hash`.
+    pub fn message_to_sign(event_id: &str, tx_hash: &str) -> String {
+        format!("CKB-PoP-Sponsor|{}|{}", event_id, tx_hash)
+    }
+}
+
+/// An attendee included in an organizer's airdrop batch, and the batch
+/// transaction that mints their badge once the organizer reports it.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct AirdropInclusion {
+    pub event_id: String,
+    pub holder_address: String,
+    pub holder_lock_hash: String,
+    pub batch_id: String,
+    /// Empty until the organizer reports the broadcast transaction.
+    pub tx_hash: String,
+    pub funder_address: String,
+    pub included_at: DateTime<Utc>,
+    /// Block the batch was committed in; `None` until it is confirmed.
+    #[serde(default)]
+    pub confirmed_block: Option<u64>,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Frequency {
+    Daily,
+    Weekly,
+    Monthly,
+}
+
+impl Frequency {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Frequency::Daily => "daily",
+            Frequency::Weekly => "weekly",
+            Frequency::Monthly => "monthly",
+        }
+    }
+}
+
+/// `count` occurrences, one every `interval` periods of `frequency`,
+/// starting at the series template's `start_time`.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Recurrence {
+    pub frequency: Frequency,
+    #[serde(default = "default_interval")]
+    pub interval: u32,
+    pub count: u32,
+}
+
+fn default_interval() -> u32 {
+    1
+}
+
+impl Recurrence {
+    /// Start of occurrence `index` (0-based) for a series first starting at `first`.
+    pub fn occurrence_start(&self, first: DateTime<Utc>, index: u32) -> Option<DateTime<Utc>> {
+        let periods = self.interval.checked_mul(index)?;
+        match self.frequency {
+            Frequency::Daily => first.checked_add_signed(chrono::Duration::days(periods.into())),
+            Frequency::Weekly => first.checked_add_signed(chrono::Duration::weeks(periods.into())),
+            Frequency::Monthly => first.checked_add_months(chrono::Months::new(periods)),
         }
     }
 }
 
+/// A creator's recurring event. Each occurrence is an ordinary event,
+/// generated from `template` with its times shifted by the recurrence rule.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct EventSeries {
+    pub series_id: String,
+    pub creator_address: String,
+    /// Client-chosen nonce, signed so the series cannot be replayed.
+    pub nonce: String,
+    pub template: EventMetadata,
+    pub recurrence: Recurrence,
+    pub creator_signature: String,
+    pub created_at: DateTime<Utc>,
+}
+
+impl EventSeries {
+    pub fn message_to_sign(nonce: &str, template_hash: &str, recurrence: &Recurrence) -> String {
+        format!(
+            "CKB-PoP-Series|{}|{}|{}|{}|{}",
+            nonce,
+            template_hash,
+            recurrence.frequency.as_str(),
+            recurrence.interval,
+            recurrence.count
+        )
+    }
+
+    pub fn signed_message(&self) -> String {
+        Self::message_to_sign(&self.nonce, &self.template.metadata_hash(), &self.recurrence)
+    }
+
+    /// Metadata for occurrence `index`: the template, moved to the
+    /// occurrence's start and keeping the template's duration.
+    pub fn occurrence_metadata(&self, index: u32) -> Option<EventMetadata> {
+        let first = self.template.start_time?;
+        let start = self.recurrence.occurrence_start(first, index)?;
+        Some(EventMetadata {
+            start_time: Some(start),
+            end_time: self.template.end_time.map(|end| start + (end - first)),
+            ..self.template.clone()
+        })
+    }
+
+    /// Event id preimage for occurrence `index`. Derived from the series so
+    /// occurrence ids can be recomputed from the series record alone.
+    pub fn occurrence_preimage(&self, index: u32) -> EventIdPreimage {
+        EventIdPreimage {
+            creator_address: self.creator_address.clone(),
+            timestamp: self.created_at.timestamp(),
+            nonce: format!("{}#{}", self.series_id, index),
+        }
+    }
+}
+
+/// What an achievement rewards: holding badges from at least `min_count`
+/// of `event_ids`, e.g. "attended 8 of the 10 spring meetups".
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct AchievementDefinition {
+    pub name: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
+    pub event_ids: Vec<String>,
+    pub min_count: u32,
+}
+
+impl AchievementDefinition {
+    /// SHA256 over the canonical JSON encoding, `0x`-prefixed; what the creator signs.
+    pub fn definition_hash(&self) -> String {
+        let json = serde_json::to_vec(self).expect("definition serializes");
+        format!("0x{}", hex::encode(Sha256::digest(json)))
+    }
+}
+
+/// A creator-signed achievement. Its badges are minted under their own type
+/// args namespace, so they never collide with plain event badges.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Achievement {
+    pub achievement_id: String,
+    pub creator_address: String,
+    pub definition: AchievementDefinition,
+    pub creator_signature: String,
+    pub created_at: DateTime<Utc>,
+}
+
+impl Achievement {
+    pub fn message_to_sign(definition_hash: &str) -> String {
+        format!("CKB-PoP-Achievement|{}", definition_hash)
+    }
+
+    pub fn signed_message(&self) -> String {
+        Self::message_to_sign(&self.definition.definition_hash())
+    }
+
+    /// Deterministic id: one creator defining the same achievement twice
+    /// gets the same id back.
+    pub fn compute_id(creator_address: &str, definition_hash: &str) -> String {
+        let mut hasher = Sha256::new();
+        hasher.update(creator_address.as_bytes());
+        hasher.update(definition_hash.as_bytes());
+        hex::encode(hasher.finalize())
+    }
+
+    /// First half of the badge type args. Plain event badges use
+    /// `SHA256(event_id)`; the domain prefix keeps the two apart.
+    pub fn type_args_prefix(achievement_id: &str) -> [u8; 32] {
+        let mut hasher = Sha256::new();
+        hasher.update(b"ckb-pop/achievement|");
+        hasher.update(achievement_id.as_bytes());
+        hasher.finalize().into()
+    }
+}
+
 #[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct EventMetadata {
     pub name: String,
@@ -77,6 +874,67 @@ pub struct EventMetadata {
     pub location: Option<String>,
     pub start_time: Option<DateTime<Utc>>,
     pub end_time: Option<DateTime<Utc>>,
+    /// Cap on distinct attendees. Omitted from the JSON when unset, so
+    /// metadata hashes of events without a cap are unchanged.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_attendees: Option<u32>,
+    /// Events whose badges gate check-in to this one.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub prerequisite_events: Vec<String>,
+    /// How many of `prerequisite_events` an attendee must hold badges for;
+    /// all of them when unset.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub min_prerequisites: Option<u32>,
+}
+
+impl EventMetadata {
+    /// Number of prerequisite badges an attendee needs, never more than are listed.
+    pub fn required_prerequisites(&self) -> usize {
+        let listed = self.prerequisite_events.len();
+        self.min_prerequisites.map_or(listed, |min| (min as usize).min(listed))
+    }
+
+    /// SHA256 over the canonical JSON encoding (struct field order), `0x`-prefixed.
+    /// This is the value signed for updates and committed in the event anchor.
+    pub fn metadata_hash(&self) -> String {
+        let json = serde_json::to_vec(self).expect("metadata serializes");
+        format!("0x{}", hex::encode(Sha256::digest(json)))
+    }
+}
+
+/// A creator-signed replacement of an event's metadata.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct EventUpdate {
+    pub metadata: EventMetadata,
+    /// Version the metadata will have once applied (current + 1).
+    pub version: u32,
+    pub creator_signature: String,
+}
+
+impl EventUpdate {
+    pub fn message_to_sign(event_id: &str, version: u32, metadata_hash: &str) -> String {
+        format!("CKB-PoP-Update|{}|{}|{}", event_id, version, metadata_hash)
+    }
+
+    pub fn signed_message(&self, event_id: &str) -> String {
+        Self::message_to_sign(event_id, self.version, &self.metadata.metadata_hash())
+    }
+}
+
+/// A prior version of an event's metadata, kept when an update replaces it.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct MetadataRevision {
+    pub event_id: String,
+    pub version: u32,
+    pub metadata: EventMetadata,
+    pub superseded_at: DateTime<Utc>,
+    /// Creator signature on the update that replaced this version.
+    pub superseded_by_signature: String,
+}
+
+/// Message a creator signs to cancel an event.
+pub fn cancel_message_to_sign(event_id: &str) -> String {
+    format!("CKB-PoP-Cancel|{}", event_id)
 }
 
 #[derive(Clone, Debug, Serialize, Deserialize)]
@@ -114,6 +972,91 @@ pub struct PaymentObservation {
     pub observed_at: DateTime<Utc>,
 }
 
+/// Lifecycle of an event, from declared intent to a terminal state.
+///
+/// ```text
+/// Intent ─┬─> PendingPayment ─> Active <─> WindowOpen ─> Ended ─> Archived
+///         └─────────────────────^   (any live state) ─> Cancelled
+/// ```
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum EventState {
+    /// Payment intent declared; no event record yet.
+    Intent,
+    /// Event record exists but no payment has been observed on chain.
+    PendingPayment,
+    /// Paid; no attendance window currently open.
+    #[default]
+    Active,
+    /// An attendance window is open for check-ins.
+    WindowOpen,
+    /// The last window has closed; scheduling another session reopens it.
+    Ended,
+    /// Retired after the retention period; read-only.
+    Archived,
+    /// Cancelled by the creator.
+    Cancelled,
+}
+
+impl EventState {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            EventState::Intent => "intent",
+            EventState::PendingPayment => "pending_payment",
+            EventState::Active => "active",
+            EventState::WindowOpen => "window_open",
+            EventState::Ended => "ended",
+            EventState::Archived => "archived",
+            EventState::Cancelled => "cancelled",
+        }
+    }
+
+    pub fn parse(s: &str) -> Option<Self> {
+        Some(match s {
+            "intent" => EventState::Intent,
+            "pending_payment" => EventState::PendingPayment,
+            "active" => EventState::Active,
+            "window_open" => EventState::WindowOpen,
+            "ended" => EventState::Ended,
+            "archived" => EventState::Archived,
+            "cancelled" => EventState::Cancelled,
+            _ => return None,
+        })
+    }
+
+    pub fn can_transition_to(self, next: EventState) -> bool {
+        use EventState::*;
+        matches!(
+            (self, next),
+            (Intent, PendingPayment)
+                | (Intent, Active)
+                | (PendingPayment, Active)
+                | (Active, WindowOpen)
+                | (WindowOpen, Active)
+                | (WindowOpen, Ended)
+                | (Ended, Active)
+                | (Ended, WindowOpen)
+                | (Ended, Archived)
+                | (Intent | PendingPayment | Active | WindowOpen, Cancelled)
+        )
+    }
+}
+
+impl std::fmt::Display for EventState {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// A recorded lifecycle transition. `from_state` is `None` for the first one.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct StateTransition {
+    pub event_id: String,
+    pub from_state: Option<EventState>,
+    pub to_state: EventState,
+    pub at: DateTime<Utc>,
+}
+
 #[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct ActiveEvent {
     pub event_id: String,
@@ -122,17 +1065,69 @@ pub struct ActiveEvent {
     pub payment_tx_hash: String,
     pub payment_block_number: u64,
     pub activated_at: DateTime<Utc>,
-    pub window: Option<WindowProof>,
+    /// Attendance windows, one per check-in session, ordered by start time.
+    #[serde(default)]
+    pub windows: Vec<WindowProof>,
+    /// Incremented by each signed metadata update; starts at 1.
+    #[serde(default = "initial_metadata_version")]
+    pub metadata_version: u32,
+    /// Set once the creator cancels the event; cancelled events reject check-ins.
+    #[serde(default)]
+    pub cancelled_at: Option<DateTime<Utc>>,
+    #[serde(default)]
+    pub state: EventState,
+    /// When the event entered `state`.
+    pub state_changed_at: DateTime<Utc>,
+}
+
+impl ActiveEvent {
+    pub fn window(&self, session_id: &str) -> Option<&WindowProof> {
+        self.windows.iter().find(|w| w.session_id == session_id)
+    }
+
+    /// The window to show by default: the most recently started open one.
+    pub fn current_window(&self) -> Option<&WindowProof> {
+        let now = Utc::now().timestamp();
+        self.windows.iter().filter(|w| w.is_open_at(now)).max_by_key(|w| w.window_start)
+    }
+}
+
+/// An attendee checked in to one window session of an event.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct CheckIn {
+    pub event_id: String,
+    pub session_id: String,
+    pub attendee_address: String,
+    pub attendee_lock_hash: String,
+    pub qr_timestamp: i64,
+    pub checked_in_at: DateTime<Utc>,
+}
+
+fn initial_metadata_version() -> u32 {
+    1
 }
 
 #[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct BadgeObservation {
     pub event_id: String,
+    /// Address as originally submitted or derived from the chain.
     pub holder_address: String,
+    /// Canonical holder identity: `0x`-prefixed lock script hash.
+    pub holder_lock_hash: String,
     pub mint_tx_hash: String,
     pub mint_block_number: u64,
     pub verified_at_block: u64,
     pub observed_at: DateTime<Utc>,
+    /// Set when the badge needs organizer review, e.g. it was issued under a
+    /// window secret that was later revoked.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub flagged_at: Option<DateTime<Utc>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub flag_reason: Option<String>,
+    /// Role written into the badge's cell data; badges minted before roles
+    /// existed read as `attendee`.
+    #[serde(default)]
+    pub role: BadgeRole,
 }
 
 #[derive(Clone, Debug, Serialize, Deserialize)]
@@ -199,6 +1194,7 @@ mod tests {
             event_id: "TEST".to_string(),
             timestamp: 12345,
             hmac: "abcd".to_string(),
+            session_id: None,
         };
         let encoded = original.encode();
         assert_eq!(encoded, "TEST|12345|abcd");
@@ -206,6 +1202,22 @@ mod tests {
         assert_eq!(parsed.event_id, original.event_id);
         assert_eq!(parsed.timestamp, original.timestamp);
         assert_eq!(parsed.hmac, original.hmac);
+        assert!(parsed.session_id.is_none());
+    }
+
+    #[test]
+    fn test_qr_payload_with_session_roundtrip() {
+        let original = QrPayload {
+            event_id: "TEST".to_string(),
+            timestamp: 12345,
+            hmac: "abcd".to_string(),
+            session_id: Some("day2".to_string()),
+        };
+        let encoded = original.encode();
+        assert_eq!(encoded, "TEST|12345|abcd|day2");
+        let parsed = QrPayload::parse(&encoded).unwrap();
+        assert_eq!(parsed.session_id.as_deref(), Some("day2"));
+        assert!(QrPayload::parse("TEST|12345|abcd|day2|extra").is_none());
     }
 
     // --- AttendanceProof ---
@@ -225,11 +1237,41 @@ mod tests {
                 event_id: "EVT001".to_string(),
                 timestamp: 1700000000,
                 hmac: "hmac".to_string(),
+                session_id: None,
             },
             attendee_signature: "sig".to_string(),
             created_at: 1700000005,
+            scanned_at: None,
         };
         assert_eq!(proof.signed_message(), "CKB-PoP|EVT001|1700000000|ckt1qaddr");
+
+        let offline = AttendanceProof { scanned_at: Some(1700000003), ..proof };
+        assert_eq!(offline.signed_message(), "CKB-PoP-Offline|EVT001|1700000000|ckt1qaddr|1700000003");
+    }
+
+    #[test]
+    fn test_presence_request_parse() {
+        let request = PresenceRequest {
+            event_id: "EVT001".to_string(),
+            attendee_address: "ckt1qaddr".to_string(),
+            timestamp: 1700000000,
+            attendee_signature: "0xsig".to_string(),
+        };
+        let parsed = PresenceRequest::parse("presence|EVT001|1700000000|ckt1qaddr|0xsig");
+        assert_eq!(parsed, Some(request.clone()));
+        assert_eq!(request.signed_message(), "CKB-PoP-Presence|EVT001|1700000000|ckt1qaddr");
+
+        assert!(PresenceRequest::parse("EVT001|1700000000|hmac").is_none());
+        assert!(PresenceRequest::parse("presence|EVT001|notanumber|ckt1qaddr|0xsig").is_none());
+    }
+
+    #[test]
+    fn test_delegation_message_format() {
+        let msg = Delegation::message_to_sign("EVT001", "ckt1qdoor", DelegateRole::DoorStaff, 1700003600, 2);
+        assert_eq!(msg, "CKB-PoP-Delegate|EVT001|ckt1qdoor|door_staff|1700003600|2");
+        assert_eq!(DelegateRole::parse("co_host"), Some(DelegateRole::CoHost));
+        assert!(DelegateRole::CoHost.can_manage_windows());
+        assert!(!DelegateRole::DoorStaff.can_manage_windows());
     }
 
     // --- WindowProof ---
@@ -243,6 +1285,12 @@ mod tests {
             window_end: Some(now + 100),
             creator_signature: "sig".to_string(),
             window_secret_commitment: "commit".to_string(),
+            secret_salt: None,
+            rotated_at: None,
+            closed_at: None,
+            authorized_by: None,
+            session_id: DEFAULT_SESSION_ID.to_string(),
+            label: None,
         };
         assert!(window.is_open());
     }
@@ -256,6 +1304,12 @@ mod tests {
             window_end: None,
             creator_signature: "sig".to_string(),
             window_secret_commitment: "commit".to_string(),
+            secret_salt: None,
+            rotated_at: None,
+            closed_at: None,
+            authorized_by: None,
+            session_id: DEFAULT_SESSION_ID.to_string(),
+            label: None,
         };
         assert!(window.is_open());
     }
@@ -269,6 +1323,12 @@ mod tests {
             window_end: Some(now - 100),
             creator_signature: "sig".to_string(),
             window_secret_commitment: "commit".to_string(),
+            secret_salt: None,
+            rotated_at: None,
+            closed_at: None,
+            authorized_by: None,
+            session_id: DEFAULT_SESSION_ID.to_string(),
+            label: None,
         };
         assert!(!window.is_open());
     }
@@ -282,22 +1342,126 @@ mod tests {
             window_end: Some(now + 200),
             creator_signature: "sig".to_string(),
             window_secret_commitment: "commit".to_string(),
+            secret_salt: None,
+            rotated_at: None,
+            closed_at: None,
+            authorized_by: None,
+            session_id: DEFAULT_SESSION_ID.to_string(),
+            label: None,
         };
         assert!(!window.is_open());
     }
 
     #[test]
     fn test_window_proof_message_to_sign_with_end() {
-        let msg = WindowProof::message_to_sign("EVT001", 1000, Some(2000));
+        let msg = WindowProof::message_to_sign("EVT001", None, 1000, Some(2000));
         assert_eq!(msg, "CKB-PoP-Window|EVT001|1000|2000");
     }
 
     #[test]
     fn test_window_proof_message_to_sign_open_end() {
-        let msg = WindowProof::message_to_sign("EVT001", 1000, None);
+        let msg = WindowProof::message_to_sign("EVT001", None, 1000, None);
         assert_eq!(msg, "CKB-PoP-Window|EVT001|1000|open");
     }
 
+    #[test]
+    fn test_window_proof_message_to_sign_binds_session() {
+        let msg = WindowProof::message_to_sign("EVT001", Some("day2"), 1000, None);
+        assert_eq!(msg, "CKB-PoP-Window|EVT001|day2|1000|open");
+    }
+
+    // --- SessionChallenge ---
+
+    #[test]
+    fn test_session_challenge_message_format() {
+        let msg = SessionChallenge::message_to_sign("ckt1qaddr", "abc123");
+        assert_eq!(msg, "CKB-PoP-Login|ckt1qaddr|abc123");
+    }
+
+    // --- EventUpdate ---
+
+    #[test]
+    fn test_event_update_message_format() {
+        let msg = EventUpdate::message_to_sign("EVT001", 2, "0xabc");
+        assert_eq!(msg, "CKB-PoP-Update|EVT001|2|0xabc");
+        assert_eq!(cancel_message_to_sign("EVT001"), "CKB-PoP-Cancel|EVT001");
+    }
+
+    #[test]
+    fn test_metadata_hash_changes_with_content() {
+        let mut meta = EventMetadata {
+            name: "A".to_string(),
+            description: "B".to_string(),
+            image_url: None,
+            location: None,
+            start_time: None,
+            end_time: None,
+            max_attendees: None,
+            prerequisite_events: vec![],
+            min_prerequisites: None,
+        };
+        let h1 = meta.metadata_hash();
+        assert_eq!(h1.len(), 66);
+        assert_eq!(h1, meta.metadata_hash());
+        meta.name = "C".to_string();
+        assert_ne!(h1, meta.metadata_hash());
+    }
+
+    #[test]
+    fn test_metadata_hash_commits_to_capacity() {
+        let mut meta = EventMetadata {
+            name: "A".to_string(),
+            description: "B".to_string(),
+            image_url: None,
+            location: None,
+            start_time: None,
+            end_time: None,
+            max_attendees: None,
+            prerequisite_events: vec![],
+            min_prerequisites: None,
+        };
+        // Events without a cap hash exactly as they did before caps existed.
+        let legacy = r#"{"name":"A","description":"B","image_url":null,"location":null,"start_time":null,"end_time":null}"#;
+        assert_eq!(meta.metadata_hash(), format!("0x{}", hex::encode(Sha256::digest(legacy))));
+
+        let uncapped = meta.metadata_hash();
+        meta.max_attendees = Some(50);
+        assert_ne!(uncapped, meta.metadata_hash());
+    }
+
+    // --- EventState ---
+
+    #[test]
+    fn test_event_state_string_roundtrip() {
+        for state in [
+            EventState::Intent,
+            EventState::PendingPayment,
+            EventState::Active,
+            EventState::WindowOpen,
+            EventState::Ended,
+            EventState::Archived,
+            EventState::Cancelled,
+        ] {
+            assert_eq!(EventState::parse(state.as_str()), Some(state));
+            assert_eq!(serde_json::to_value(state).unwrap(), state.as_str());
+        }
+        assert_eq!(EventState::parse("bogus"), None);
+    }
+
+    #[test]
+    fn test_event_state_transitions() {
+        assert!(EventState::PendingPayment.can_transition_to(EventState::Active));
+        assert!(EventState::Active.can_transition_to(EventState::WindowOpen));
+        assert!(EventState::WindowOpen.can_transition_to(EventState::Ended));
+        assert!(EventState::Ended.can_transition_to(EventState::Archived));
+        assert!(EventState::WindowOpen.can_transition_to(EventState::Cancelled));
+
+        assert!(!EventState::PendingPayment.can_transition_to(EventState::WindowOpen));
+        assert!(!EventState::Cancelled.can_transition_to(EventState::Active));
+        assert!(!EventState::Archived.can_transition_to(EventState::WindowOpen));
+        assert!(!EventState::Ended.can_transition_to(EventState::Cancelled));
+    }
+
     // --- EventIdPreimage ---
 
     #[test]
@@ -354,6 +1518,9 @@ mod tests {
             location: Some("NYC".to_string()),
             start_time: Some(Utc::now()),
             end_time: None,
+            max_attendees: None,
+            prerequisite_events: vec![],
+            min_prerequisites: None,
         };
         let json = serde_json::to_string(&meta).unwrap();
         let parsed: EventMetadata = serde_json::from_str(&json).unwrap();
@@ -367,10 +1534,14 @@ mod tests {
         let badge = BadgeObservation {
             event_id: "EVT001".to_string(),
             holder_address: "ckt1qaddr".to_string(),
+            holder_lock_hash: "0xlock".to_string(),
             mint_tx_hash: "0xabc".to_string(),
             mint_block_number: 12345,
             verified_at_block: 12346,
             observed_at: Utc::now(),
+            flagged_at: None,
+            flag_reason: None,
+            role: BadgeRole::Attendee,
         };
         let json = serde_json::to_string(&badge).unwrap();
         let parsed: BadgeObservation = serde_json::from_str(&json).unwrap();